@@ -0,0 +1,29 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use gitnu::storage::Storage;
+use std::fs;
+
+/// Snapshot a vault of 1000 notes spread over 10 domains. The content never
+/// changes, so after the first run the tarball is reused and this measures
+/// reading and hashing the files.
+fn snapshot_1000_files(c: &mut Criterion) {
+    let vault = tempfile::tempdir().unwrap();
+    let storage = Storage::new(vault.path().to_path_buf());
+    storage.init("bench").unwrap();
+    for i in 0..1000 {
+        let dir = vault.path().join(format!("domains/domain{}", i % 10));
+        fs::create_dir_all(&dir).unwrap();
+        let body = format!("# Note {}\n\n", i) + &"Some knowledge worth keeping.\n".repeat(40);
+        fs::write(dir.join(format!("note{}.md", i)), body).unwrap();
+    }
+
+    let mut n = 0;
+    c.bench_function("create_snapshot 1000 files", |b| {
+        b.iter(|| {
+            n += 1;
+            storage.create_snapshot(&format!("{:064x}", n)).unwrap()
+        })
+    });
+}
+
+criterion_group!(benches, snapshot_1000_files);
+criterion_main!(benches);
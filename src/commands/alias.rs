@@ -0,0 +1,243 @@
+use crate::errors::*;
+use crate::storage::Storage;
+use crate::utils::*;
+use colored::Colorize;
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::path::PathBuf;
+
+/// List aliases, show one, define one or (with `unset`) remove one.
+/// `builtins` are the command names an alias can't shadow.
+pub fn alias(name: Option<String>, command: Vec<String>, unset: bool, builtins: &[String]) -> Result<()> {
+    let storage = Storage::new(find_vault_root()?);
+
+    let name = match name {
+        Some(name) => name,
+        None => {
+            let config = storage.load_config()?;
+            if config.alias.is_empty() {
+                println!("{}", "No aliases defined".dimmed());
+                println!("  Define one: gnu alias ci \"commit --author human\"");
+            }
+            for (name, expansion) in &config.alias {
+                println!("{} = {}", name.cyan(), expansion);
+            }
+            return Ok(());
+        }
+    };
+
+    if command.is_empty() && !unset {
+        let config = storage.load_config()?;
+        return match config.alias.get(&name) {
+            Some(expansion) => {
+                println!("{}", expansion);
+                Ok(())
+            }
+            None => Err(GitnuError::Other(format!("No alias named '{}'", name))),
+        };
+    }
+
+    let _lock = storage.lock()?;
+    let mut config = storage.load_config()?;
+    if unset {
+        if config.alias.remove(&name).is_none() {
+            return Err(GitnuError::Other(format!("No alias named '{}'", name)));
+        }
+        storage.save_config(&config)?;
+        println!("{} alias '{}'", "Removed".green(), name);
+        return Ok(());
+    }
+
+    if builtins.contains(&name) {
+        return Err(GitnuError::Other(format!(
+            "'{}' is a gnu command and can't be redefined\n  Pick another name for the alias",
+            name
+        )));
+    }
+    if name.starts_with('-') || name.chars().any(char::is_whitespace) {
+        return Err(GitnuError::Other(format!("Invalid alias name '{}'", name)));
+    }
+    let expansion = command.join(" ");
+    if split_command(&expansion)?.is_empty() {
+        return Err(GitnuError::Other(format!("Alias '{}' needs a command to expand to", name)));
+    }
+
+    config.alias.insert(name.clone(), expansion.clone());
+    storage.save_config(&config)?;
+    println!("{} {} = {}", "Aliased".green(), name.cyan(), expansion);
+    Ok(())
+}
+
+/// Replace an alias in the command position of `args` (program name first)
+/// with the command it stands for, following aliases of aliases. Arguments
+/// are returned unchanged when there is no alias to expand or no vault to
+/// read aliases from, leaving clap to report unknown commands.
+pub fn expand_alias(args: Vec<OsString>, builtins: &[String]) -> Result<Vec<OsString>> {
+    let (position, vault) = match command_position(&args) {
+        Some(found) => found,
+        None => return Ok(args),
+    };
+    if args[position].to_str().is_some_and(|name| builtins.iter().any(|b| b == name)) {
+        return Ok(args);
+    }
+
+    let root = match vault {
+        Some(root) => Some(root),
+        None => find_vault_root().ok(),
+    };
+    let aliases = match root.and_then(|root| Storage::new(root).load_config().ok()) {
+        Some(config) => config.alias,
+        None => return Ok(args),
+    };
+    expand_with(args, position, &aliases, builtins)
+}
+
+/// Index of the subcommand in `args`, skipping global flags, and the vault
+/// named by `--vault` if it came first. A command that isn't valid UTF-8
+/// can't be an alias, so it is left for clap to report.
+fn command_position(args: &[OsString]) -> Option<(usize, Option<PathBuf>)> {
+    let mut vault = None;
+    let mut i = 1;
+    while i < args.len() {
+        let arg = args[i].to_str()?;
+        match arg {
+            "--json" | "--no-color" => i += 1,
+            "--vault" => {
+                vault = args.get(i + 1).map(PathBuf::from);
+                i += 2;
+            }
+            _ if arg.starts_with("--vault=") => {
+                vault = Some(PathBuf::from(&arg["--vault=".len()..]));
+                i += 1;
+            }
+            // Help, version and unknown flags are clap's business
+            _ if arg.starts_with('-') => return None,
+            _ => return Some((i, vault)),
+        }
+    }
+    None
+}
+
+fn expand_with(
+    mut args: Vec<OsString>,
+    position: usize,
+    aliases: &BTreeMap<String, String>,
+    builtins: &[String],
+) -> Result<Vec<OsString>> {
+    let mut chain: Vec<String> = Vec::new();
+    while let Some((name, expansion)) = args[position].to_str()
+        .and_then(|name| aliases.get_key_value(name))
+    {
+        if builtins.contains(name) {
+            break;
+        }
+        let name = name.clone();
+        if chain.contains(&name) {
+            chain.push(name);
+            return Err(GitnuError::Other(format!(
+                "Alias '{}' expands to itself: {}\n  Fix the [alias] section of .gitnu/config.toml",
+                chain[0],
+                chain.join(" -> ")
+            )));
+        }
+        chain.push(name.clone());
+
+        let words = split_command(expansion)?;
+        if words.is_empty() {
+            return Err(GitnuError::Other(format!("Alias '{}' is empty", name)));
+        }
+        args.splice(position..=position, words.into_iter().map(OsString::from));
+    }
+    Ok(args)
+}
+
+/// Split an alias into words the way a shell would, honoring single and
+/// double quotes and backslash escapes
+fn split_command(command: &str) -> Result<Vec<String>> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut quote = None;
+    let mut chars = command.chars();
+
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some('"'), '\\') | (None, '\\') => {
+                if let Some(next) = chars.next() {
+                    word.push(next);
+                }
+                in_word = true;
+            }
+            (Some(_), c) => word.push(c),
+            (None, '"') | (None, '\'') => {
+                quote = Some(c);
+                in_word = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            (None, c) => {
+                word.push(c);
+                in_word = true;
+            }
+        }
+    }
+
+    if quote.is_some() {
+        return Err(GitnuError::Other(format!("Unterminated quote in alias: {}", command)));
+    }
+    if in_word {
+        words.push(word);
+    }
+    Ok(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(words: &[&str]) -> Vec<String> {
+        words.iter().map(|w| w.to_string()).collect()
+    }
+
+    fn os_args(words: &[&str]) -> Vec<OsString> {
+        words.iter().map(OsString::from).collect()
+    }
+
+    #[test]
+    fn test_split_command_honors_quotes() {
+        assert_eq!(
+            split_command(r#"commit --author human -m "two words" 'it''s' a\ b"#).unwrap(),
+            args(&["commit", "--author", "human", "-m", "two words", "its", "a b"])
+        );
+        assert!(split_command("log \"open").is_err());
+    }
+
+    #[test]
+    fn test_expand_alias_follows_chains_and_stops_loops() {
+        let builtins = args(&["commit", "log"]);
+        let mut aliases = BTreeMap::new();
+        aliases.insert("ci".to_string(), "commit --author human".to_string());
+        aliases.insert("save".to_string(), "ci --allow-empty".to_string());
+        aliases.insert("log".to_string(), "status".to_string());
+        aliases.insert("a".to_string(), "b".to_string());
+        aliases.insert("b".to_string(), "a --json".to_string());
+
+        let expanded = expand_with(os_args(&["gnu", "save", "msg"]), 1, &aliases, &builtins).unwrap();
+        assert_eq!(expanded, os_args(&["gnu", "commit", "--author", "human", "--allow-empty", "msg"]));
+
+        // Built-in commands can't be shadowed
+        let expanded = expand_with(os_args(&["gnu", "log"]), 1, &aliases, &builtins).unwrap();
+        assert_eq!(expanded, os_args(&["gnu", "log"]));
+
+        let err = expand_with(os_args(&["gnu", "a"]), 1, &aliases, &builtins).unwrap_err();
+        assert!(err.to_string().contains("a -> b -> a"), "{}", err);
+
+        assert_eq!(command_position(&os_args(&["gnu", "--vault", "/v", "--json", "ci"])).unwrap().0, 4);
+        assert!(command_position(&os_args(&["gnu", "--help"])).is_none());
+    }
+}
@@ -0,0 +1,57 @@
+use crate::errors::*;
+use crate::models::{CommitNotes, Note};
+use crate::storage::Storage;
+use crate::utils::*;
+use chrono::Utc;
+use colored::Colorize;
+
+/// Attach notes and tags to an existing commit without changing its hash.
+/// With nothing to add or remove, prints what is already attached.
+pub fn annotate(reference: &str, add: Vec<String>, tags: Vec<String>, untag: Vec<String>) -> Result<()> {
+    let vault_root = find_vault_root()?;
+    let storage = Storage::new(vault_root);
+    let commit = storage.resolve_commit(reference)?;
+
+    if add.is_empty() && tags.is_empty() && untag.is_empty() {
+        let notes = storage.read_notes(&commit.hash)?;
+        println!("{} {}", "commit".yellow(), short_hash(&commit.hash).yellow());
+        if notes.is_empty() {
+            println!("  {}", "No notes".dimmed());
+        }
+        print_notes(&notes);
+        return Ok(());
+    }
+
+    let _lock = storage.lock()?;
+    let mut notes = storage.read_notes(&commit.hash)?;
+    for text in add {
+        notes.notes.push(Note { text, timestamp: Utc::now() });
+    }
+    for tag in tags {
+        if !notes.has_tag(&tag) {
+            notes.tags.push(tag);
+        }
+    }
+    notes.tags.retain(|t| !untag.contains(t));
+    storage.write_notes(&commit.hash, &notes)?;
+
+    println!(
+        "{} {} \"{}\"",
+        "Annotated".green(),
+        short_hash(&commit.hash).yellow(),
+        commit.subject()
+    );
+    print_notes(&notes);
+
+    Ok(())
+}
+
+/// Print tags then notes, indented under a commit
+pub(crate) fn print_notes(notes: &CommitNotes) {
+    if !notes.tags.is_empty() {
+        println!("    Tags: {}", notes.tags.join(", ").cyan());
+    }
+    for note in &notes.notes {
+        println!("    Note: {} {}", note.text, format!("({})", relative_time(&note.timestamp)).dimmed());
+    }
+}
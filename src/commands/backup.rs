@@ -0,0 +1,159 @@
+use crate::errors::*;
+use crate::models::*;
+use crate::storage::Storage;
+use crate::utils::*;
+use chrono::Utc;
+use colored::Colorize;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+use tar::{Archive, Builder, Header};
+use walkdir::WalkDir;
+
+/// Archive the whole `.gitnu` store (objects, refs, logs, config, index)
+pub fn backup(output: Option<PathBuf>) -> Result<()> {
+    let vault_root = find_vault_root()?;
+    let storage = Storage::new(vault_root.clone());
+    // Hold the lock so the archive is a consistent point-in-time copy
+    let _lock = storage.lock_ignoring_read_only()?;
+    let config = storage.load_config()?;
+
+    let mut hashes = HashSet::new();
+    for log in storage.list_commit_logs()? {
+        for commit in storage.read_commits(&log)? {
+            hashes.insert(commit.hash);
+        }
+    }
+    let manifest = BackupManifest {
+        format_version: BackupManifest::FORMAT_VERSION,
+        vault_name: config.core.vault_name.clone(),
+        commit_count: hashes.len(),
+        created_at: Utc::now(),
+    };
+
+    let output = output.unwrap_or_else(|| {
+        PathBuf::from(format!(
+            "{}-backup-{}.tar.gz",
+            config.core.vault_name,
+            manifest.created_at.format("%Y%m%d-%H%M%S")
+        ))
+    });
+    let tar_gz = File::create(&output)?;
+    let mut tar = Builder::new(GzEncoder::new(tar_gz, Compression::default()));
+
+    // Manifest goes first so restores can validate before unpacking anything
+    let manifest_json = serde_json::to_vec_pretty(&manifest)?;
+    let mut header = Header::new_gnu();
+    header.set_size(manifest_json.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(manifest.created_at.timestamp() as u64);
+    header.set_cksum();
+    tar.append_data(&mut header, BackupManifest::TAR_ENTRY, manifest_json.as_slice())?;
+
+    let lock_file = storage.gitnu_dir().join("gitnu.lock");
+    for entry in WalkDir::new(storage.gitnu_dir()).sort_by_file_name().into_iter().filter_map(|e| e.ok()) {
+        let path = entry.path();
+        if path.is_file() && path != lock_file {
+            tar.append_path_with_name(path, relative_path(&vault_root, path))?;
+        }
+    }
+    tar.into_inner()?.finish()?;
+
+    println!(
+        "{} {} ({} commits, {})",
+        "Backed up to".green(),
+        output.display(),
+        manifest.commit_count,
+        format_size(fs::metadata(&output)?.len())
+    );
+    if !storage.objects_dir().starts_with(storage.gitnu_dir()) {
+        println!(
+            "  {} snapshots in {} (core.objects_dir) are not included; back that directory up separately",
+            "Note:".yellow(),
+            storage.objects_dir().display()
+        );
+    }
+
+    Ok(())
+}
+
+/// Unpack a backup into `into`, which must not already hold a vault
+pub fn restore_backup(file: &Path, into: Option<PathBuf>) -> Result<()> {
+    let target = into.unwrap_or_else(|| PathBuf::from("."));
+    if vault_exists(&target) {
+        return Err(GitnuError::AlreadyInitialized(target));
+    }
+
+    // Validate the whole archive before writing anything
+    let manifest = read_backup_manifest(file)?;
+
+    ensure_dir(&target)?;
+    let mut archive = Archive::new(GzDecoder::new(File::open(file)?));
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.to_path_buf();
+        if path != Path::new(BackupManifest::TAR_ENTRY) {
+            entry.unpack_in(&target)?;
+        }
+    }
+
+    // Check out HEAD so the restored vault is immediately usable
+    let storage = Storage::new(target.clone());
+    if !storage.domains_dir().exists() {
+        if let Some(head) = storage.get_head_commit()? {
+            storage.restore_snapshot(&head.hash)?;
+        }
+    }
+
+    println!(
+        "{} vault '{}' ({} commits, backed up {}) into {}",
+        "Restored".green(),
+        manifest.vault_name,
+        manifest.commit_count,
+        relative_time(&manifest.created_at),
+        target.display()
+    );
+
+    Ok(())
+}
+
+/// Read and check a backup's manifest, and that every entry lives in `.gitnu/`
+fn read_backup_manifest(file: &Path) -> Result<BackupManifest> {
+    let invalid = |reason: &str| {
+        GitnuError::Other(format!("{} is not a valid gitnu backup: {}", file.display(), reason))
+    };
+
+    let mut archive = Archive::new(GzDecoder::new(File::open(file)?));
+    let mut manifest: Option<BackupManifest> = None;
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.to_path_buf();
+
+        if path == Path::new(BackupManifest::TAR_ENTRY) {
+            let mut content = Vec::new();
+            entry.read_to_end(&mut content)?;
+            manifest = Some(serde_json::from_slice(&content).map_err(|_| invalid("unreadable manifest"))?);
+            continue;
+        }
+
+        let safe = path.components().all(|c| matches!(c, Component::Normal(_)));
+        if !safe || !path.starts_with(".gitnu") {
+            return Err(invalid(&format!("unexpected entry {}", path.display())));
+        }
+    }
+
+    let manifest = manifest.ok_or_else(|| invalid("missing manifest"))?;
+    if manifest.format_version > BackupManifest::FORMAT_VERSION {
+        return Err(invalid(&format!(
+            "format version {} is newer than this gnu supports ({})",
+            manifest.format_version,
+            BackupManifest::FORMAT_VERSION
+        )));
+    }
+
+    Ok(manifest)
+}
@@ -1,28 +1,70 @@
 use crate::errors::*;
+use crate::models::{BranchRef, Head};
 use crate::storage::Storage;
 use crate::utils::*;
+use chrono::Utc;
 use colored::Colorize;
+use std::collections::HashSet;
 
-pub fn branch_list() -> Result<()> {
+/// Restrict a branch listing to branches whose head is (or with `merged`
+/// false, isn't) an ancestor of `reference`
+#[derive(Debug, Clone)]
+pub struct MergedFilter {
+    pub reference: String,
+    pub merged: bool,
+}
+
+/// List branches; `verbose` adds how long ago each was created
+pub fn branch_list(verbose: bool, filter: Option<MergedFilter>) -> Result<()> {
     let vault_root = find_vault_root()?;
     let storage = Storage::new(vault_root);
 
-    let current_branch = storage.read_head()?;
-    let branches = storage.list_branches()?;
+    let current_head = storage.read_head()?;
+    let mut branches = storage.list_branches()?;
+    branches.sort();
+
+    if let Some(ref filter) = filter {
+        let target = storage.resolve_commit(&filter.reference)?;
+        let reachable: HashSet<String> = storage.ancestry(&target.hash)?.into_iter().map(|c| c.hash).collect();
+        let mut kept = Vec::new();
+        for branch in branches {
+            // Branches without commits are neither merged nor unmerged
+            if let Some(head) = storage.read_branch_ref(&branch)? {
+                if reachable.contains(&head) == filter.merged {
+                    kept.push(branch);
+                }
+            }
+        }
+        if kept.is_empty() {
+            let state = if filter.merged { "merged into" } else { "unmerged with" };
+            println!("{}", format!("No branches {} {}", state, filter.reference).dimmed());
+            return Ok(());
+        }
+        branches = kept;
+    }
 
     if branches.is_empty() {
         println!("{}", "No branches found".dimmed());
         return Ok(());
     }
 
+    if let (Head::Detached(ref hash), None) = (&current_head, &filter) {
+        println!("{} (HEAD detached at {})", "*".green(), short_hash(hash).yellow());
+    }
+
     for branch in branches {
-        let is_current = branch == current_branch;
+        let is_current = current_head.is_branch(&branch);
         let marker = if is_current { "*" } else { " " };
+        let description = storage.read_branch_meta(&branch)?.and_then(|m| m.description);
+        let age = match storage.branch_created_at(&branch)? {
+            Some(created) if verbose => format!(" (created {})", relative_time(&created)),
+            _ => String::new(),
+        };
         
         // Get branch head commit
         if let Some(commit_hash) = storage.read_branch_ref(&branch)? {
             if let Some(commit) = storage.find_commit(&commit_hash)? {
-                let short_hash = &commit.hash[..7];
+                let short_hash = short_hash(&commit.hash);
                 let branch_display = if is_current {
                     branch.green().to_string()
                 } else {
@@ -30,12 +72,16 @@ pub fn branch_list() -> Result<()> {
                 };
                 
                 println!(
-                    "{} {:<20} {} \"{}\"",
+                    "{} {:<20} {} \"{}\"{}",
                     marker.green(),
                     branch_display,
                     short_hash.yellow(),
-                    commit.message
+                    commit.subject(),
+                    age.dimmed()
                 );
+                if let Some(description) = description {
+                    println!("  {:<20} {}", "", description.dimmed());
+                }
             } else {
                 println!("{} {}", marker.green(), branch);
             }
@@ -51,6 +97,14 @@ pub fn branch_create(name: &str, description: Option<String>) -> Result<()> {
     let vault_root = find_vault_root()?;
     let storage = Storage::new(vault_root);
 
+    // `gnu branch <existing> --describe <text>` only updates the description
+    if let Some(ref text) = description {
+        if storage.read_branch_ref(name)?.is_some() {
+            return branch_describe(Some(name), text);
+        }
+    }
+    let _lock = storage.lock()?;
+
     // Check if branch already exists
     if storage.read_branch_ref(name)?.is_some() {
         return Err(GitnuError::BranchExists(name.to_string()));
@@ -68,13 +122,52 @@ pub fn branch_create(name: &str, description: Option<String>) -> Result<()> {
     };
 
     // Create branch pointing to current HEAD
-    storage.write_branch_ref(name, &head_hash)?;
+    storage.update_branch_ref(name, &head_hash, &format!("branch: created from {}", short_hash(&head_hash)))?;
+    storage.write_branch_meta(&BranchRef {
+        name: name.to_string(),
+        head: head_hash.clone(),
+        created_at: Utc::now(),
+        description: description.clone(),
+    })?;
 
     println!("{} branch '{}'", "Created".green(), name.green());
     if let Some(desc) = description {
         println!("  Description: {}", desc.dimmed());
     }
-    println!("  Starting at: {}", &head_hash[..7].yellow());
+    println!("  Starting at: {}", short_hash(&head_hash).yellow());
+
+    Ok(())
+}
+
+/// Set or replace the description of an existing branch (default: the current one)
+pub fn branch_describe(name: Option<&str>, description: &str) -> Result<()> {
+    let vault_root = find_vault_root()?;
+    let storage = Storage::new(vault_root);
+    let _lock = storage.lock()?;
+
+    let name = match name {
+        Some(n) => n.to_string(),
+        None => storage.current_branch()?,
+    };
+    let name = name.as_str();
+    let head = storage.read_branch_ref(name)?
+        .ok_or_else(|| GitnuError::BranchNotFound(name.to_string()))?;
+
+    // Branches from older versions have no metadata yet; their creation time is unknown
+    let mut meta = match storage.read_branch_meta(name)? {
+        Some(meta) => meta,
+        None => BranchRef {
+            name: name.to_string(),
+            head,
+            created_at: storage.branch_created_at(name)?.unwrap_or_else(Utc::now),
+            description: None,
+        },
+    };
+    meta.description = Some(description.to_string());
+    storage.write_branch_meta(&meta)?;
+
+    println!("{} branch '{}'", "Described".green(), name.green());
+    println!("  Description: {}", description.dimmed());
 
     Ok(())
 }
@@ -82,6 +175,7 @@ pub fn branch_create(name: &str, description: Option<String>) -> Result<()> {
 pub fn branch_delete(name: &str) -> Result<()> {
     let vault_root = find_vault_root()?;
     let storage = Storage::new(vault_root);
+    let _lock = storage.lock()?;
 
     // Check if branch exists
     if storage.read_branch_ref(name)?.is_none() {
@@ -89,8 +183,8 @@ pub fn branch_delete(name: &str) -> Result<()> {
     }
 
     // Check if it's the current branch
-    let current_branch = storage.read_head()?;
-    if current_branch == name {
+    let current_head = storage.read_head()?;
+    if current_head.is_branch(name) {
         return Err(GitnuError::Other(format!(
             "Cannot delete current branch '{}'. Switch to another branch first.",
             name
@@ -102,3 +196,129 @@ pub fn branch_delete(name: &str) -> Result<()> {
 
     Ok(())
 }
+
+/// Delete branches whose head is already reachable from the default branch,
+/// optionally only those created more than `older_than_days` days ago
+pub fn prune_branches(dry_run: bool, pattern: Option<String>, older_than_days: Option<i64>, yes: bool) -> Result<()> {
+    let vault_root = find_vault_root()?;
+    let storage = Storage::new(vault_root);
+    let lock = storage.lock()?;
+
+    let config = storage.load_config()?;
+    let base = config.core.default_branch;
+    let base_head = storage.read_branch_ref(&base)?
+        .ok_or_else(|| GitnuError::BranchNotFound(base.clone()))?;
+    let current_head = storage.read_head()?;
+
+    let pattern = match pattern {
+        Some(p) => Some(glob::Pattern::new(&p).map_err(|e| {
+            GitnuError::Other(format!("Invalid glob pattern '{}': {}", p, e))
+        })?),
+        None => None,
+    };
+
+    let mut branches = storage.list_branches()?;
+    branches.sort();
+
+    let graph = storage.commit_graph()?;
+    let mut merged = Vec::new();
+    for branch in branches {
+        if branch == base || current_head.is_branch(&branch) {
+            continue;
+        }
+        if pattern.as_ref().is_some_and(|p| !p.matches(&branch)) {
+            continue;
+        }
+        if let Some(days) = older_than_days {
+            let cutoff = Utc::now() - chrono::Duration::days(days);
+            if storage.branch_created_at(&branch)?.is_none_or(|created| created > cutoff) {
+                continue;
+            }
+        }
+        if let Some(head) = storage.read_branch_ref(&branch)? {
+            if graph.is_ancestor(&head, &base_head) {
+                merged.push((branch, head));
+            }
+        }
+    }
+
+    if merged.is_empty() {
+        println!("{}", format!("No branches fully merged into {}", base).dimmed());
+        return Ok(());
+    }
+
+    println!("Branches fully merged into {}:", base.green());
+    for (branch, head) in &merged {
+        println!("  {} {}", branch, short_hash(head).yellow());
+    }
+
+    if dry_run {
+        println!("{}", "Dry run: no branches deleted".dimmed());
+        return Ok(());
+    }
+
+    // Don't hold the vault lock while waiting on the prompt; the refs are
+    // checked again once it is re-taken
+    let _lock = if yes {
+        lock
+    } else {
+        drop(lock);
+        if !confirm(&format!("Delete {} branches?", merged.len()))? {
+            println!("{}", "Aborted".yellow());
+            return Ok(());
+        }
+        storage.lock()?
+    };
+
+    let base_head = storage.read_branch_ref(&base)?
+        .ok_or_else(|| GitnuError::BranchNotFound(base.clone()))?;
+    let current_head = storage.read_head()?;
+    let graph = storage.commit_graph()?;
+    for (branch, head) in &merged {
+        let unchanged = storage.read_branch_ref(branch)?.as_ref() == Some(head);
+        if !unchanged || current_head.is_branch(branch) || !graph.is_ancestor(head, &base_head) {
+            println!("{} branch '{}': it changed before the prompt was answered", "Kept".yellow(), branch);
+            continue;
+        }
+        storage.delete_branch(branch)?;
+        println!("{} branch '{}'", "Deleted".red(), branch);
+    }
+
+    Ok(())
+}
+
+/// List branches whose history contains the given commit
+pub fn branch_contains(reference: &str) -> Result<()> {
+    let vault_root = find_vault_root()?;
+    let storage = Storage::new(vault_root);
+
+    let commit = storage.resolve_commit(reference)?;
+    let current_head = storage.read_head()?;
+
+    let mut branches = storage.list_branches()?;
+    branches.sort();
+
+    let graph = storage.commit_graph()?;
+    let mut found = false;
+    for branch in branches {
+        if let Some(head) = storage.read_branch_ref(&branch)? {
+            if graph.is_ancestor(&commit.hash, &head) {
+                found = true;
+                if current_head.is_branch(&branch) {
+                    println!("{} {}", "*".green(), branch.green());
+                } else {
+                    println!("  {}", branch);
+                }
+            }
+        }
+    }
+
+    if !found {
+        println!(
+            "{}",
+            format!("No branch contains commit {}", short_hash(&commit.hash)).dimmed()
+        );
+    }
+
+    Ok(())
+}
@@ -1,18 +1,27 @@
 use crate::errors::*;
+use crate::models::{Commit, Head};
 use crate::storage::Storage;
 use crate::context::ContextManager;
+use crate::commands::commit::auto_commit_if_enabled;
+use crate::commands::rewind::commit_at_date;
+use crate::commands::diff::{changed_files, write_restore_preview, Side};
 use crate::utils::*;
 use colored::Colorize;
 
-pub fn checkout(target: &str, force: bool) -> Result<()> {
+/// Switch to a branch or commit; with `to_date`, detach at the last commit
+/// on the current line of history made on or before that date
+pub fn checkout(target: Option<&str>, to_date: Option<&str>, force: bool, dry_run: bool) -> Result<()> {
     let vault_root = find_vault_root()?;
     let storage = Storage::new(vault_root.clone());
+    // A dry run only reads, so it works on a locked or read-only vault
+    let _lock = if dry_run { None } else { Some(storage.lock()?) };
     let context_mgr = ContextManager::new(Storage::new(vault_root.clone()));
 
-    // Check for uncommitted changes
-    if !force && context_mgr.has_uncommitted_changes()? {
-        return Err(GitnuError::UncommittedChanges);
-    }
+    let target = match to_date {
+        Some(date) => commit_at_date(&storage, date)?.hash,
+        None => target.unwrap_or("HEAD").to_string(),
+    };
+    let target = target.as_str();
 
     // Determine if target is a branch or commit
     let (commit_hash, is_branch, branch_name) = if let Some(hash) = storage.read_branch_ref(target)? {
@@ -29,8 +38,49 @@ pub fn checkout(target: &str, force: bool) -> Result<()> {
         }
     };
 
-    // Restore snapshot
+    // Attaching a detached HEAD to a branch at the same commit keeps uncommitted changes
+    let detached_at = match storage.read_head()? {
+        Head::Detached(hash) => Some(hash),
+        Head::Branch(_) => None,
+    };
+    if is_branch && !force && detached_at.as_deref() == Some(commit_hash.as_str()) {
+        if dry_run {
+            println!("Checking out {} would attach HEAD to it, keeping uncommitted changes", target.green());
+            print_dry_run_note();
+            return Ok(());
+        }
+        storage.write_head(&branch_name)?;
+        println!("Switched to branch '{}'", branch_name.green());
+        return Ok(());
+    }
+
+    // Check for uncommitted changes
+    if !force && context_mgr.has_uncommitted_changes()? {
+        let message = format!("Auto-commit before checkout to {}", target);
+        let safe = if dry_run {
+            preview_auto_commit(&storage)?
+        } else {
+            auto_commit_if_enabled(&storage, &message)?
+        };
+        if !safe {
+            return Err(GitnuError::UncommittedChanges);
+        }
+    }
+
+    if dry_run {
+        let commit = storage.find_commit(&commit_hash)?
+            .ok_or_else(|| GitnuError::CommitNotFound(commit_hash.clone()))?;
+        println!("Checking out {} would restore commit {}", target.green(), short_hash(&commit_hash).yellow());
+        println!();
+        preview_restore(&storage, &commit)?;
+        return Ok(());
+    }
+
+    // Restore snapshot and the loaded context recorded with it
+    let commit = storage.find_commit(&commit_hash)?
+        .ok_or_else(|| GitnuError::CommitNotFound(commit_hash.clone()))?;
     storage.restore_snapshot(&commit_hash)?;
+    storage.restore_index(&commit)?;
 
     // Update HEAD
     if is_branch {
@@ -38,19 +88,40 @@ pub fn checkout(target: &str, force: bool) -> Result<()> {
         println!("Switched to branch '{}'", branch_name.green());
     } else {
         // Detached HEAD state
-        let head_path = storage.gitnu_dir().join("HEAD");
-        std::fs::write(head_path, &commit_hash)?;
-        println!("HEAD is now at {}", commit_hash[..7].yellow());
+        storage.write_detached_head(&commit_hash)?;
+        println!("HEAD is now at {}", short_hash(&commit_hash).yellow());
         println!("{}", "Note: You are in 'detached HEAD' state.".yellow());
     }
 
     // Show what changed
-    let commit = storage.find_commit(&commit_hash)?.unwrap();
-    println!("Restored context from commit {}", commit_hash[..7].yellow());
-    println!("  \"{} \"", commit.message.dimmed());
+    println!("Restored context from commit {}", short_hash(&commit_hash).yellow());
+    println!("  \"{} \"", commit.subject().dimmed());
     
     let summary = &commit.context_summary;
     println!("  {} domains, ~{} tokens", summary.domains_loaded.len(), summary.token_estimate);
 
     Ok(())
 }
+
+/// Print what restoring `commit` over the working directory would change.
+/// Shared by the `--dry-run` flags of checkout, rewind and merge.
+pub(crate) fn preview_restore(storage: &Storage, commit: &Commit) -> Result<()> {
+    let changes = changed_files(storage, &Side::Working, &Side::Commit(commit), None)?;
+    write_restore_preview(&mut std::io::stdout(), storage, &changes, commit)?;
+    print_dry_run_note();
+    Ok(())
+}
+
+/// The dry-run stand-in for `auto_commit_if_enabled`: says whether the
+/// working directory could be overwritten, and that it would be committed first
+pub(crate) fn preview_auto_commit(storage: &Storage) -> Result<bool> {
+    let enabled = storage.load_config()?.context.auto_commit;
+    if enabled {
+        println!("Uncommitted changes would be auto-committed first");
+    }
+    Ok(enabled)
+}
+
+pub(crate) fn print_dry_run_note() {
+    println!("{}", "Dry run: no files or refs were changed".dimmed());
+}
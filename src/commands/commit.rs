@@ -2,41 +2,149 @@ use crate::errors::*;
 use crate::models::*;
 use crate::storage::Storage;
 use crate::context::ContextManager;
+use crate::commands::gc::prune_snapshots;
 use crate::utils::*;
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use colored::Colorize;
+use std::path::PathBuf;
 
-pub fn commit(message: &str, author_type: &str, model: Option<String>) -> Result<()> {
+/// Options for `make_commit`
+#[derive(Debug, Clone, Default)]
+pub struct CommitOptions {
+    pub message: String,
+    /// "human" or "agent" (default: agent.default_author in config)
+    pub author: Option<String>,
+    /// Model name for agent commits (default: agent.model_hint in config)
+    pub model: Option<String>,
+    /// Record a commit even when nothing has changed
+    pub allow_empty: bool,
+    /// Fail instead of warning when files exceed context.max_file_size
+    pub no_large_files: bool,
+    /// Re-hash every file instead of trusting unchanged size and mtime
+    pub full: bool,
+    /// Timestamp to record instead of now, for backfilling history
+    pub date: Option<DateTime<Utc>>,
+    /// Commit files that still contain `<<<<<<<`/`>>>>>>>` conflict markers
+    pub allow_conflict_markers: bool,
+}
+
+/// Commit with `opts`, taking the message from `message` or, when that is
+/// `None`, from $EDITOR
+pub fn commit(message: Option<String>, opts: CommitOptions) -> Result<()> {
     let vault_root = find_vault_root()?;
-    let storage = Storage::new(vault_root.clone());
-    let context_mgr = ContextManager::new(Storage::new(vault_root.clone()));
+    let storage = Storage::new(vault_root);
+    // Refuse before opening an editor for a message that couldn't be used
+    storage.ensure_writable()?;
 
-    // Get current branch
-    let current_branch = storage.read_head()?;
+    // Ask for a message before locking so the editor can't hold the vault
+    let message = match message {
+        Some(m) => m,
+        None => {
+            // Finishing a merge proposes the merge's own message
+            let config = storage.load_config()?;
+            let initial = match storage.read_merge_msg()? {
+                Some(merge_msg) => merge_msg,
+                None => config.commit.template.clone().unwrap_or_default(),
+            };
+            let edited = edit_in_editor(&storage.gitnu_dir().join("COMMIT_EDITMSG"), &initial)?;
+            let message = strip_comments(&edited);
+            if message.is_empty() {
+                return Err(GitnuError::Other("Aborting commit due to empty message".to_string()));
+            }
+            message
+        }
+    };
 
-    // Get previous commit
-    let parent_commit = storage.get_head_commit()?;
-    let parent_hash = parent_commit.as_ref().map(|c| c.hash.clone());
+    if let Some(future) = opts.date.filter(|d| *d > Utc::now() + chrono::Duration::days(1)) {
+        println!(
+            "{}",
+            format!("Warning: --date {} is in the future", future.format("%Y-%m-%d %H:%M")).yellow()
+        );
+    }
 
-    // Calculate context summary
-    let summary = context_mgr.calculate_context_summary(parent_commit.as_ref())?;
+    let outcome = make_commit(&storage, &CommitOptions { message, ..opts })?;
 
-    // Check if there are changes
-    if parent_commit.is_some() 
-        && summary.files_added.is_empty() 
-        && summary.files_modified.is_empty() 
-        && summary.files_removed.is_empty() {
-        println!("{}", "No changes to commit".yellow());
-        return Ok(());
+    if !outcome.ownership_warnings.is_empty() {
+        println!("{}", "Warning: agent commit modifies human-owned files:".yellow());
+        for path in &outcome.ownership_warnings {
+            println!("    - {}", path.display());
+        }
+    }
+    if !outcome.large_files.is_empty() {
+        let config = storage.load_config()?;
+        println!(
+            "{}",
+            format!(
+                "Warning: committing files larger than {}:",
+                format_size(config.context.max_file_size)
+            )
+            .yellow()
+        );
+        for file in &outcome.large_files {
+            println!("    - {} ({})", file.path.display(), format_size(file.size));
+        }
+        println!("  Leave generated or binary files out of snapshots: add a pattern for them to .gnignore");
+    }
+    if !outcome.binary_files.is_empty() {
+        println!("{}", "Warning: committing binary files (left out of context):".yellow());
+        for path in &outcome.binary_files {
+            println!("    - {}", path.display());
+        }
     }
 
+    let commit = outcome.commit.ok_or(GitnuError::NothingToCommit)?;
+    let short_hash = short_hash(&commit.hash);
+    let summary = &commit.context_summary;
+
+    // Print summary
+    println!(
+        "{} {}",
+        format!("[{} {}]", outcome.branch, short_hash).green(),
+        commit.message
+    );
+    println!("  Author: {}", commit.author.display());
+    
+    let changes = summary.files_added.len() + summary.files_modified.len() + summary.files_removed.len();
+    println!(
+        "  {} files changed, {} insertions, {} deletions",
+        changes,
+        summary.files_added.len() + summary.files_modified.len(),
+        summary.files_removed.len()
+    );
+    println!(
+        "  Context: {} domains, ~{} tokens",
+        summary.domains_loaded.len(),
+        summary.token_estimate
+    );
+    if outcome.pruned_snapshots > 0 {
+        println!(
+            "  {}",
+            format!("Pruned {} (retention.auto_gc)", plural(outcome.pruned_snapshots, "old snapshot")).dimmed()
+        );
+    }
+
+    Ok(())
+}
+
+/// Commit the working directory with ownership and size checks, without
+/// printing anything. Warnings that don't block the commit are returned in
+/// the outcome.
+pub fn make_commit(storage: &Storage, opts: &CommitOptions) -> Result<CommitOutcome> {
+    let config = storage.load_config()?;
+    let message = opts.message.as_str();
+    let _lock = storage.lock()?;
+
+    // Fall back to the vault's configured attribution
+    let author_type = opts.author.clone().unwrap_or_else(|| config.agent.default_author.clone());
+
+    // Get current branch
+    let current_branch = storage.current_branch()?;
+
     // Create author
-    let author = match author_type {
-        "human" => Author::Human {
-            name: std::env::var("USER").unwrap_or_else(|_| "user".to_string()),
-        },
+    let author = match author_type.as_str() {
+        "human" => config.user.author(),
         "agent" => Author::Agent {
-            model: model.unwrap_or_else(|| "claude-3-5-sonnet".to_string()),
+            model: opts.model.clone().unwrap_or_else(|| config.agent.model_hint.clone()),
             session_id: None,
         },
         _ => {
@@ -47,60 +155,273 @@ pub fn commit(message: &str, author_type: &str, model: Option<String>) -> Result
         }
     };
 
+    let context_mgr = ContextManager::new(Storage::new(storage.vault_root.clone())).with_full_rehash(opts.full);
+    let parent_commit = storage.get_head_commit()?;
+    let changes = context_mgr.calculate_commit_summary(parent_commit.as_ref())?;
+
+    let checks = check_commit(storage, &config, message, &author, &changes, opts)?;
+
+    let binary_files = if config.context.warn_binary_files {
+        changes.binary_files.iter()
+            .filter(|p| changes.files_added.contains(p) || changes.files_modified.contains(p))
+            .cloned()
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    let commit = commit_with_summary(storage, message, author, opts.allow_empty, changes, opts.date)?;
+
+    let pruned_snapshots = if commit.is_some() && config.retention.auto_gc {
+        prune_snapshots(storage, &config.retention)?
+    } else {
+        0
+    };
+
+    Ok(CommitOutcome {
+        branch: current_branch,
+        commit,
+        ownership_warnings: checks.ownership_warnings,
+        large_files: checks.large_files,
+        binary_files,
+        pruned_snapshots,
+    })
+}
+
+/// Drop `#` comment lines from an edited message and trim surrounding whitespace
+fn strip_comments(message: &str) -> String {
+    message
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim()
+        .to_string()
+}
+
+/// What `check_commit` found that warns without blocking the commit
+struct CommitChecks {
+    ownership_warnings: Vec<PathBuf>,
+    large_files: Vec<LargeFile>,
+}
+
+/// The policy every commit path must pass, whether `gnu commit`, an
+/// auto-commit or `gnu watch`: `commit.require_prefix`, ownership
+/// enforcement for agents, conflict markers and large-file rejection.
+fn check_commit(
+    storage: &Storage,
+    config: &Config,
+    message: &str,
+    author: &Author,
+    changes: &ContextSummary,
+    opts: &CommitOptions,
+) -> Result<CommitChecks> {
+    if !config.commit.accepts(message) {
+        return Err(GitnuError::InvalidCommitMessage(config.commit.require_prefix.clone()));
+    }
+
+    // Check file ownership for agent commits
+    let ownership_warnings = if matches!(author, Author::Agent { .. }) {
+        check_ownership(config, changes)?
+    } else {
+        Vec::new()
+    };
+
+    if !opts.allow_conflict_markers {
+        check_conflict_markers(storage, changes)?;
+    }
+
+    let large_files = check_large_files(storage, config, changes, opts.no_large_files)?;
+    Ok(CommitChecks { ownership_warnings, large_files })
+}
+
+/// Human-owned files touched by an agent commit; an error when ownership is enforced
+fn check_ownership(config: &Config, summary: &ContextSummary) -> Result<Vec<PathBuf>> {
+    let violations: Vec<_> = summary.files_added.iter()
+        .chain(summary.files_modified.iter())
+        .chain(summary.files_removed.iter())
+        .filter(|p| config.ownership.owner_of(p) == Owner::Human)
+        .cloned()
+        .collect();
+
+    if !violations.is_empty() && config.ownership.enforce {
+        return Err(GitnuError::OwnershipViolation(violations));
+    }
+
+    Ok(violations)
+}
+
+/// Refuse to commit added or modified text files that still hold conflict
+/// markers from a half-resolved merge
+fn check_conflict_markers(storage: &Storage, changes: &ContextSummary) -> Result<()> {
+    let mut conflicted = Vec::new();
+    for path in changes.files_added.iter().chain(&changes.files_modified) {
+        if changes.binary_files.contains(path) {
+            continue;
+        }
+        if let Ok(content) = std::fs::read_to_string(storage.vault_root.join(path)) {
+            if has_conflict_markers(&content) {
+                conflicted.push(path.clone());
+            }
+        }
+    }
+    if !conflicted.is_empty() {
+        return Err(GitnuError::ConflictMarkers(conflicted));
+    }
+    Ok(())
+}
+
+/// Added or modified files over `context.max_file_size`; an error when rejected
+fn check_large_files(
+    storage: &Storage,
+    config: &Config,
+    summary: &ContextSummary,
+    reject: bool,
+) -> Result<Vec<LargeFile>> {
+    let mut large = Vec::new();
+    for path in summary.files_added.iter().chain(summary.files_modified.iter()) {
+        let size = std::fs::metadata(storage.vault_root.join(path))?.len();
+        if size > config.context.max_file_size {
+            large.push(LargeFile { path: path.clone(), size });
+        }
+    }
+
+    if !large.is_empty() && (reject || config.context.reject_large_files) {
+        return Err(GitnuError::LargeFiles(large.into_iter().map(|f| f.path).collect()));
+    }
+
+    Ok(large)
+}
+
+/// Snapshot the working directory as a new commit on the current branch,
+/// held to the same checks as `gnu commit`. The caller holds the vault lock.
+/// Returns `None` when there is nothing to commit, unless `allow_empty` is set.
+pub fn create_commit(
+    storage: &Storage,
+    message: &str,
+    author: Author,
+    allow_empty: bool,
+) -> Result<Option<Commit>> {
+    let config = storage.load_config()?;
+    let context_mgr = ContextManager::new(Storage::new(storage.vault_root.clone()));
+    let summary = context_mgr.calculate_commit_summary(storage.get_head_commit()?.as_ref())?;
+    check_commit(storage, &config, message, &author, &summary, &CommitOptions::default())?;
+    commit_with_summary(storage, message, author, allow_empty, summary, None)
+}
+
+/// `create_commit` with the working directory's summary already calculated,
+/// timestamped `date` (default now)
+fn commit_with_summary(
+    storage: &Storage,
+    message: &str,
+    author: Author,
+    allow_empty: bool,
+    summary: ContextSummary,
+    date: Option<DateTime<Utc>>,
+) -> Result<Option<Commit>> {
+    // Get current branch
+    let current_branch = storage.current_branch()?;
+
+    // Get previous commit
+    let parent_commit = storage.get_head_commit()?;
+    let parent_hash = parent_commit.as_ref().map(|c| c.hash.clone());
+
+    // A merge in progress can only be committed once every conflict is resolved
+    let merge_state = storage.read_merge_state()?;
+    if let Some(ref state) = merge_state {
+        let unresolved = state.unresolved().count();
+        if unresolved > 0 {
+            return Err(GitnuError::Other(format!(
+                "Merge in progress with {}\n  Run: gnu mergetool",
+                plural(unresolved, "unresolved file")
+            )));
+        }
+    }
+    let merge_parent = storage.read_merge_head()?;
+
+    // Check if there are changes (finishing a merge is always worth a commit)
+    if !allow_empty
+        && !storage.merge_in_progress()
+        && parent_commit.is_some()
+        && summary.files_added.is_empty() 
+        && summary.files_modified.is_empty() 
+        && summary.files_removed.is_empty()
+        && !storage.index_changed_since(parent_commit.as_ref())? {
+        return Ok(None);
+    }
+
+    // Store the tree first so its hash is part of the commit's: commits with
+    // the same parents, message and --date but different content must differ
+    let snapshot = storage.write_tree()?;
+
     // Create commit hash
     let mut commit_data = Vec::new();
+    commit_data.extend_from_slice(b"tree ");
+    commit_data.extend_from_slice(snapshot.tree.as_bytes());
+    commit_data.extend_from_slice(b"\n");
     if let Some(ref parent) = parent_hash {
         commit_data.extend_from_slice(b"parent ");
         commit_data.extend_from_slice(parent.as_bytes());
         commit_data.extend_from_slice(b"\n");
     }
+    if let Some(ref merge_parent) = merge_parent {
+        commit_data.extend_from_slice(b"parent ");
+        commit_data.extend_from_slice(merge_parent.as_bytes());
+        commit_data.extend_from_slice(b"\n");
+    }
     commit_data.extend_from_slice(message.as_bytes());
     commit_data.extend_from_slice(b"\n");
-    commit_data.extend_from_slice(Utc::now().to_rfc3339().as_bytes());
-    
+    let timestamp = date.unwrap_or_else(Utc::now);
+    commit_data.extend_from_slice(timestamp.to_rfc3339().as_bytes());
+
     let hash = compute_hash(&commit_data);
-    let short_hash = &hash[..7];
 
-    // Create snapshot
-    let snapshot_path = storage.create_snapshot(&hash)?;
+    let snapshot_path = storage.record_snapshot(&hash, &snapshot)?;
 
     // Create commit object
     let commit = Commit {
         hash: hash.clone(),
         parent: parent_hash,
-        timestamp: Utc::now(),
-        author: author.clone(),
+        merge_parent,
+        timestamp,
+        author,
         message: message.to_string(),
-        context_summary: summary.clone(),
-        snapshot_path: relative_path(&vault_root, &snapshot_path),
+        context_summary: summary,
+        snapshot_path: relative_path(&storage.vault_root, &snapshot_path),
+        index: Some(storage.load_index()?),
     };
 
     // Append to commit log
     storage.append_commit(&current_branch, &commit)?;
 
     // Update branch reference
-    storage.write_branch_ref(&current_branch, &hash)?;
+    storage.update_branch_ref(&current_branch, &hash, &format!("commit: {}", message))?;
+    storage.clear_merge_state()?;
 
-    // Print summary
-    println!(
-        "{} {}",
-        format!("[{} {}]", current_branch, short_hash).green(),
-        message
-    );
-    println!("  Author: {}", author.display());
-    
-    let changes = summary.files_added.len() + summary.files_modified.len() + summary.files_removed.len();
-    println!(
-        "  {} files changed, {} insertions, {} deletions",
-        changes,
-        summary.files_added.len() + summary.files_modified.len(),
-        summary.files_removed.len()
-    );
-    println!(
-        "  Context: {} domains, ~{} tokens",
-        summary.domains_loaded.len(),
-        summary.token_estimate
-    );
+    Ok(Some(commit))
+}
 
-    Ok(())
+/// Commit uncommitted changes if `context.auto_commit` is enabled.
+/// Returns true when the working directory is safe to overwrite; fails when
+/// the changes don't pass the checks `gnu commit` applies.
+pub fn auto_commit_if_enabled(storage: &Storage, message: &str) -> Result<bool> {
+    let config = storage.load_config()?;
+    if !config.context.auto_commit {
+        return Ok(false);
+    }
+
+    let author = Author::Agent {
+        model: "gitnu-auto".to_string(),
+        session_id: None,
+    };
+    if let Some(commit) = create_commit(storage, message, author, false)? {
+        println!(
+            "{} {} \"{}\"",
+            "Auto-committed".green(),
+            short_hash(&commit.hash).yellow(),
+            message
+        );
+    }
+
+    Ok(true)
 }
@@ -0,0 +1,94 @@
+use crate::errors::*;
+use crate::models::Config;
+use crate::storage::Storage;
+use crate::utils::*;
+use colored::Colorize;
+use toml::{Table, Value};
+
+/// Print the value of a dotted config key such as `user.name`
+pub fn config_get(key: &str) -> Result<()> {
+    let storage = Storage::new(find_vault_root()?);
+    let table = Table::try_from(storage.load_config()?)?;
+
+    match lookup(&table, key) {
+        Some(Value::String(s)) => println!("{}", s),
+        Some(value) => println!("{}", value),
+        None => return Err(unknown_key(key)),
+    }
+    Ok(())
+}
+
+/// Set a dotted config key, keeping the type of the value it replaces.
+/// The result must still be a valid config, so typos are rejected.
+pub fn config_set(key: &str, value: &str) -> Result<()> {
+    let storage = Storage::new(find_vault_root()?);
+    let _lock = storage.lock_ignoring_read_only()?;
+    let mut table = Table::try_from(storage.load_config()?)?;
+
+    let path: Vec<&str> = key.split('.').collect();
+    let (name, sections) = match path.split_last() {
+        Some((name, sections)) if !sections.is_empty() => (*name, sections),
+        _ => return Err(unknown_key(key)),
+    };
+
+    let new_value = match lookup(&table, key) {
+        Some(Value::Boolean(_)) => value.parse().map(Value::Boolean).map_err(|_| invalid_value(key, value, "true or false"))?,
+        Some(Value::Integer(_)) => value.parse().map(Value::Integer).map_err(|_| invalid_value(key, value, "a whole number"))?,
+        Some(Value::Float(_)) => value.parse().map(Value::Float).map_err(|_| invalid_value(key, value, "a number"))?,
+        Some(Value::Array(_)) | Some(Value::Table(_)) => {
+            return Err(GitnuError::Other(format!(
+                "'{}' is not a single value\n  Edit .gitnu/config.toml to change it",
+                key
+            )));
+        }
+        _ => Value::String(value.to_string()),
+    };
+
+    let mut section = &mut table;
+    for part in sections {
+        section = match section.entry(part.to_string()).or_insert_with(|| Value::Table(Table::new())) {
+            Value::Table(t) => t,
+            _ => return Err(unknown_key(key)),
+        };
+    }
+    section.insert(name.to_string(), new_value);
+
+    // Unknown keys are dropped by deserializing, so check the key survived
+    let config: Config = Value::Table(table).try_into()
+        .map_err(|e| GitnuError::Other(format!("Invalid value for '{}': {}", key, e)))?;
+    if lookup(&Table::try_from(&config)?, key).is_none() {
+        return Err(unknown_key(key));
+    }
+    let moved_objects = key == "core.objects_dir" && {
+        let dir = storage.resolve_objects_dir(config.core.objects_dir.as_deref());
+        Storage::check_objects_dir(&dir)?;
+        dir != storage.objects_dir()
+    };
+    storage.save_config(&config)?;
+
+    println!("{} {} = {}", "Set".green(), key, value);
+    if moved_objects && std::fs::read_dir(storage.objects_dir()).is_ok_and(|mut d| d.next().is_some()) {
+        println!(
+            "  Existing snapshots are still in {}\n  Move its contents to the new location before running other commands",
+            storage.objects_dir().display()
+        );
+    }
+    Ok(())
+}
+
+fn lookup<'a>(table: &'a Table, key: &str) -> Option<&'a Value> {
+    let mut parts = key.split('.');
+    let mut value = table.get(parts.next()?)?;
+    for part in parts {
+        value = value.as_table()?.get(part)?;
+    }
+    Some(value)
+}
+
+fn unknown_key(key: &str) -> GitnuError {
+    GitnuError::Other(format!("Unknown config key '{}'\n  Keys look like section.name, e.g. user.name", key))
+}
+
+fn invalid_value(key: &str, value: &str, expected: &str) -> GitnuError {
+    GitnuError::Other(format!("Invalid value '{}' for '{}': expected {}", value, key, expected))
+}
@@ -1,26 +1,52 @@
 use crate::errors::*;
+use crate::models::{Commit, ContextReport};
 use crate::storage::Storage;
-use crate::context::ContextManager;
+use crate::context::{ContextFilter, ContextManager, GroupBy, PackStrategy};
+use crate::commands::diff::{change_texts, changed_files, Side};
 use crate::utils::*;
 use colored::Colorize;
+use similar::TextDiff;
+use std::fs;
+use std::path::PathBuf;
 
-pub fn context(clipboard: bool, json: bool, compress: bool) -> Result<()> {
-    let vault_root = find_vault_root()?;
-    let storage = Storage::new(vault_root.clone());
-    let context_mgr = ContextManager::new(storage);
+/// Options controlling what `gnu context` renders and how
+#[derive(Debug, Clone, Default)]
+pub struct ContextOptions {
+    pub clipboard: bool,
+    /// Apply markdown compression
+    pub compress: bool,
+    /// Only files matching these globs
+    pub include: Vec<String>,
+    /// Skip files matching these globs
+    pub exclude: Vec<String>,
+    /// Every file, ignoring the loaded set
+    pub all: bool,
+    /// Only each file's headings, as a table of contents
+    pub outline: bool,
+    /// Render this branch's head instead of the working tree
+    pub branch: Option<String>,
+    /// Prompt scaffold with a `{{context}}` placeholder to wrap the output in
+    pub template: Option<PathBuf>,
+    /// Drop files until the context fits in this many tokens
+    pub budget: Option<usize>,
+    /// Which files to keep under `budget`
+    pub strategy: PackStrategy,
+    /// Only what changed since HEAD, as diffs
+    pub diff_only: bool,
+    /// Section the output by domain
+    pub group_by: GroupBy,
+}
 
-    let content = context_mgr.load_context(compress)?;
+pub fn context(opts: &ContextOptions, json: bool) -> Result<()> {
+    let vault_root = find_vault_root()?;
+    let storage = Storage::new(vault_root);
+    let report = context_report(&storage, opts)?;
+    let content = &report.content;
 
     if json {
         // Output as structured JSON
-        let files = context_mgr.get_all_files()?;
-        let json_output = serde_json::json!({
-            "files": files,
-            "content": content,
-            "token_estimate": estimate_tokens(&content),
-        });
-        println!("{}", serde_json::to_string_pretty(&json_output)?);
-    } else if clipboard {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else if opts.clipboard {
         // Copy to clipboard (placeholder - would need clipboard crate)
         println!("{}", "Clipboard support not yet implemented".yellow());
         println!("{}", "Context output:".bold());
@@ -30,5 +56,170 @@ pub fn context(clipboard: bool, json: bool, compress: bool) -> Result<()> {
         println!("{}", content);
     }
 
+    // Packing notes go to stderr so the context itself can still be piped
+    if let (Some(budget), false) = (opts.budget, json) {
+        let total = report.files.len() + report.dropped.len();
+        eprintln!(
+            "{} {} of {} (~{} of {} tokens)",
+            "Packed".green(),
+            report.files.len(),
+            plural(total, "file"),
+            report.token_estimate,
+            budget
+        );
+        for path in &report.dropped {
+            eprintln!("  {} {}", "dropped".dimmed(), path.display());
+        }
+    }
+
     Ok(())
 }
+
+/// Render the context (the working tree, or `branch`'s head) with the files
+/// that went into it
+pub fn context_report(storage: &Storage, opts: &ContextOptions) -> Result<ContextReport> {
+    // Render another branch's head from its snapshot, leaving the working tree alone
+    let commit = match opts.branch {
+        Some(ref name) => {
+            let hash = storage.read_branch_ref(name)?
+                .ok_or_else(|| GitnuError::BranchNotFound(name.clone()))?;
+            let commit = storage.find_commit(&hash)?
+                .ok_or(GitnuError::CommitNotFound(hash))?;
+            Some(commit)
+        }
+        None => None,
+    };
+    let context_mgr = ContextManager::new(Storage::new(storage.vault_root.clone()));
+
+    let filter = ContextFilter::new(&opts.include, &opts.exclude)?.with_all(opts.all);
+    let (mut content, files, dropped) = if opts.diff_only {
+        let head = storage.get_head_commit()?.ok_or_else(|| {
+            GitnuError::Other("No commits yet, so there are no changes to show\n  Run 'gnu context' without --diff-only".to_string())
+        })?;
+        let (content, files) = render_changes(storage, &head, &filter)?;
+        (content, files, Vec::new())
+    } else {
+        let files = match commit {
+            Some(ref commit) => context_mgr.context_files_at(commit, &filter)?,
+            None => context_mgr.context_files(&filter)?,
+        };
+        let (files, dropped) = match opts.budget {
+            Some(budget) => {
+                let packing = context_mgr.pack_files(files, budget, opts.strategy, commit.as_ref())?;
+                (packing.included, packing.dropped)
+            }
+            None => (files, Vec::new()),
+        };
+        let render = |files: &[PathBuf]| {
+            if opts.outline {
+                context_mgr.render_outline(files, commit.as_ref())
+            } else {
+                context_mgr.render_context(files, commit.as_ref(), opts.compress)
+            }
+        };
+        let content = match opts.group_by {
+            GroupBy::None => render(&files),
+            GroupBy::Domain => {
+                // An outline keeps its single title above the domain sections
+                let mut content = String::from(if opts.outline { "# Outline\n" } else { "" });
+                for (domain, group) in context_mgr.group_by_domain(&files)? {
+                    content.push_str(&format!("\n## Domain: {}\n", domain));
+                    content.push_str(render(&group).trim_start_matches("# Outline\n"));
+                }
+                content
+            }
+        };
+        (content, files, dropped)
+    };
+
+    if let Some(ref template_path) = opts.template {
+        let template = fs::read_to_string(template_path)
+            .map_err(|_| GitnuError::FileNotFound(template_path.clone()))?;
+        if !template.contains("{{context}}") {
+            return Err(GitnuError::Other(format!(
+                "Template {} has no {{{{context}}}} placeholder",
+                template_path.display()
+            )));
+        }
+
+        let branch = match opts.branch {
+            Some(ref name) => name.clone(),
+            None => storage.read_head()?.name().to_string(),
+        };
+        let commit = match commit {
+            Some(commit) => Some(commit),
+            None => storage.get_head_commit()?,
+        };
+        let summary = template_summary(&branch, commit.as_ref(), files.len(), estimate_tokens(&content));
+        content = template
+            .replace("{{summary}}", &summary)
+            .replace("{{branch}}", &branch)
+            .replace("{{commit}}", &commit.map(|c| short_hash(&c.hash).to_string()).unwrap_or_default())
+            // Last, so placeholders inside the vault's own files are left alone
+            .replace("{{context}}", &content);
+    }
+
+    Ok(ContextReport {
+        token_estimate: estimate_tokens(&content),
+        files,
+        dropped,
+        content,
+    })
+}
+
+/// Unified diffs of every file changed since `head` that passes the filter,
+/// with removed files reduced to a note. Returns the document and the paths in it.
+fn render_changes(storage: &Storage, head: &Commit, filter: &ContextFilter) -> Result<(String, Vec<PathBuf>)> {
+    let from = Side::Commit(head);
+    let mut content = format!("# Changes since {} \"{}\"\n", short_hash(&head.hash), head.subject());
+    let mut files = Vec::new();
+
+    for change in changed_files(storage, &from, &Side::Working, None)? {
+        if !filter.allows(&change.path, false) {
+            continue;
+        }
+        let path = change.path.display();
+        let (old_text, new_text) = change_texts(storage, &from, &Side::Working, &change)?;
+        match (&change.old, &change.new) {
+            (_, None) => {
+                content.push_str(&format!(
+                    "\n## Removed: {}\n\nFile deleted ({} lines).\n",
+                    path,
+                    old_text.lines().count()
+                ));
+            }
+            _ if is_binary(old_text.as_bytes()) || is_binary(new_text.as_bytes()) => {
+                let action = if change.old.is_none() { "Added" } else { "Modified" };
+                content.push_str(&format!("\n## {}: {}\n\nBinary file.\n", action, path));
+            }
+            (old, Some(_)) => {
+                let action = if old.is_none() { "Added" } else { "Modified" };
+                let diff = TextDiff::from_lines(&old_text, &new_text);
+                content.push_str(&format!("\n## {}: {}\n\n```diff\n", action, path));
+                content.push_str(&diff.unified_diff().context_radius(3).to_string());
+                if !content.ends_with('\n') {
+                    content.push('\n');
+                }
+                content.push_str("```\n");
+            }
+        }
+        files.push(change.path);
+    }
+
+    if files.is_empty() {
+        content.push_str("\nNo changes.\n");
+    }
+    Ok((content, files))
+}
+
+/// Short description of the rendered context for a template's `{{summary}}`
+fn template_summary(branch: &str, commit: Option<&Commit>, files: usize, tokens: usize) -> String {
+    let last_commit = match commit {
+        Some(c) => format!("{} \"{}\" ({})", short_hash(&c.hash), c.subject(), relative_time(&c.timestamp)),
+        None => "none yet".to_string(),
+    };
+    format!(
+        "- Branch: {}\n- Last commit: {}\n- Files: {} (~{} tokens)",
+        branch, last_commit, files, tokens
+    )
+}
@@ -1,162 +1,1012 @@
 use crate::errors::*;
+use crate::models::{Commit, DiffEntry, DiffReport, FileStat};
 use crate::storage::Storage;
+use crate::context::{ContextFilter, ContextManager};
+use crate::commands::load::read_for_tokens;
+use crate::commands::status::budget_colored;
 use crate::utils::*;
 use colored::Colorize;
-use std::collections::HashSet;
+use similar::TextDiff;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
 
-pub fn diff(source: Option<String>, target: Option<String>) -> Result<()> {
+/// How `gnu diff` renders changes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DiffFormat {
+    /// Context-level summary of added/modified/removed files
+    #[default]
+    Unified,
+    /// Standard unified-diff hunks consumable by `patch`
+    Patch,
+}
+
+impl DiffFormat {
+    pub fn parse(format: &str) -> Result<Self> {
+        match format {
+            "unified" => Ok(DiffFormat::Unified),
+            "patch" => Ok(DiffFormat::Patch),
+            _ => Err(GitnuError::Other(format!(
+                "Invalid diff format: {}. Use 'unified' or 'patch'",
+                format
+            ))),
+        }
+    }
+}
+
+/// Options for `gnu diff`
+#[derive(Debug, Clone, Default)]
+pub struct DiffOptions {
+    pub source: Option<String>,
+    pub target: Option<String>,
+    /// Only report files under `domains/<name>/`
+    pub domain: Option<String>,
+    pub format: DiffFormat,
+    /// Write to this file instead of stdout
+    pub output: Option<PathBuf>,
+    /// List the token delta of each changed file
+    pub token_breakdown: bool,
+    /// Describe the changes in one paragraph instead of listing them
+    pub summary: bool,
+    /// Unchanged lines shown around each hunk in patch output
+    pub context_lines: usize,
+    /// Preview what loading the staged files would add to the context
+    pub staged: bool,
+    /// Minimum percentage of shared lines for a removed and an added file
+    /// to be reported as a rename (100 = identical content only)
+    pub rename_threshold: u8,
+}
+
+/// Similarity `gnu diff` needs to call a removed and added file a rename
+pub const DEFAULT_RENAME_THRESHOLD: u8 = 50;
+
+/// One side of a comparison
+pub(crate) enum Side<'a> {
+    Commit(&'a Commit),
+    Working,
+    /// Nothing, e.g. the parent of a root commit
+    Empty,
+}
+
+pub fn diff(mut opts: DiffOptions) -> Result<()> {
     let vault_root = find_vault_root()?;
     let storage = Storage::new(vault_root);
 
-    let (source_commit, target_commit) = match (source, target) {
+    let output = opts.output.take();
+    let mut out: Box<dyn Write> = match output {
+        Some(ref path) => {
+            colored::control::set_override(false);
+            Box::new(File::create(path)?)
+        }
+        None => Box::new(std::io::stdout()),
+    };
+
+    write_diff(&mut out, &storage, opts)?;
+    out.flush()?;
+
+    if let Some(ref path) = output {
+        eprintln!("{} {}", "Wrote diff to".green(), path.display());
+    }
+
+    Ok(())
+}
+
+/// Everything `gnu diff` prints, written to `out` so `--output` captures all of it
+fn write_diff(out: &mut dyn Write, storage: &Storage, opts: DiffOptions) -> Result<()> {
+    let domain = opts.domain.as_deref();
+    if opts.staged {
+        return write_staged(out, storage);
+    }
+
+    let (source, target) = split_range(storage, opts.source, opts.target)?;
+    match (source, target) {
         (None, None) => {
             // Diff between HEAD and working directory
-            let head = storage.get_head_commit()?;
-            match head {
-                Some(h) => {
-                    println!("Changes since last commit:");
-                    show_working_diff(&storage, &h)?;
-                    return Ok(());
-                }
+            let head = match storage.get_head_commit()? {
+                Some(h) => h,
                 None => {
-                    println!("{}", "No commits yet".dimmed());
+                    writeln!(out, "{}", "No commits yet".dimmed())?;
                     return Ok(());
                 }
+            };
+            check_domain_exists(storage, domain, &[&head], true)?;
+            if opts.summary {
+                let token_delta = working_tokens(storage, domain)? as i64
+                    - commit_tokens(storage, &head, domain)? as i64;
+                let since = format!("Since {}", short_hash(&head.hash));
+                write_change_summary(out, storage, &Side::Commit(&head), &Side::Working, &since, token_delta, domain)?;
+            } else if opts.format == DiffFormat::Patch {
+                write_patch(
+                    out,
+                    storage,
+                    &Side::Commit(&head),
+                    &Side::Working,
+                    domain,
+                    opts.context_lines,
+                    opts.rename_threshold,
+                )?;
+            } else {
+                writeln!(out, "Changes since last commit:")?;
+                show_working_diff(out, storage, &head, domain, opts.rename_threshold)?;
+                if opts.token_breakdown {
+                    write_token_breakdown(out, storage, &Side::Commit(&head), &Side::Working, domain)?;
+                }
             }
         }
         (Some(s), None) => {
             // Diff between commit and working directory
-            let source_commit = storage.find_commit(&s)?
-                .ok_or_else(|| GitnuError::CommitNotFound(s.clone()))?;
-            println!("Changes between commit {} and working directory:", &source_commit.hash[..7].yellow());
-            show_working_diff(&storage, &source_commit)?;
-            return Ok(());
+            let source_commit = storage.resolve_commit(&s)?;
+            check_domain_exists(storage, domain, &[&source_commit], true)?;
+            if opts.summary {
+                let token_delta = working_tokens(storage, domain)? as i64
+                    - commit_tokens(storage, &source_commit, domain)? as i64;
+                let since = format!("Since {}", short_hash(&source_commit.hash));
+                write_change_summary(
+                    out,
+                    storage,
+                    &Side::Commit(&source_commit),
+                    &Side::Working,
+                    &since,
+                    token_delta,
+                    domain,
+                )?;
+            } else if opts.format == DiffFormat::Patch {
+                write_patch(
+                    out,
+                    storage,
+                    &Side::Commit(&source_commit),
+                    &Side::Working,
+                    domain,
+                    opts.context_lines,
+                    opts.rename_threshold,
+                )?;
+            } else {
+                writeln!(out, "Changes between commit {} and working directory:", short_hash(&source_commit.hash).yellow())?;
+                show_working_diff(out, storage, &source_commit, domain, opts.rename_threshold)?;
+                if opts.token_breakdown {
+                    write_token_breakdown(out, storage, &Side::Commit(&source_commit), &Side::Working, domain)?;
+                }
+            }
         }
         (Some(s), Some(t)) => {
             // Diff between two commits or branches
-            let source_commit = resolve_target(&storage, &s)?;
-            let target_commit = resolve_target(&storage, &t)?;
-            (source_commit, target_commit)
+            let source_commit = storage.resolve_commit(&s)?;
+            let target_commit = storage.resolve_commit(&t)?;
+            check_domain_exists(storage, domain, &[&source_commit, &target_commit], false)?;
+            if opts.summary {
+                let token_delta = commit_tokens(storage, &target_commit, domain)? as i64
+                    - commit_tokens(storage, &source_commit, domain)? as i64;
+                let since = format!(
+                    "Between {} and {}",
+                    short_hash(&source_commit.hash),
+                    short_hash(&target_commit.hash)
+                );
+                write_change_summary(
+                    out,
+                    storage,
+                    &Side::Commit(&source_commit),
+                    &Side::Commit(&target_commit),
+                    &since,
+                    token_delta,
+                    domain,
+                )?;
+            } else if opts.format == DiffFormat::Patch {
+                write_patch(
+                    out,
+                    storage,
+                    &Side::Commit(&source_commit),
+                    &Side::Commit(&target_commit),
+                    domain,
+                    opts.context_lines,
+                    opts.rename_threshold,
+                )?;
+            } else {
+                writeln!(
+                    out,
+                    "Comparing {}..{}",
+                    short_hash(&source_commit.hash).yellow(),
+                    short_hash(&target_commit.hash).yellow()
+                )?;
+                writeln!(out)?;
+                show_commit_diff(out, storage, &source_commit, &target_commit, domain, opts.rename_threshold)?;
+                if opts.token_breakdown {
+                    write_token_breakdown(
+                        out,
+                        storage,
+                        &Side::Commit(&source_commit),
+                        &Side::Commit(&target_commit),
+                        domain,
+                    )?;
+                }
+            }
         }
         (None, Some(_)) => {
             return Err(GitnuError::Other("Invalid diff arguments".to_string()));
         }
+    }
+
+    Ok(())
+}
+
+/// Expand a single `a..b` (direct) or `a...b` (changes on `b` since it
+/// diverged from `a`) argument into source and target. An empty side means HEAD.
+fn split_range(
+    storage: &Storage,
+    source: Option<String>,
+    target: Option<String>,
+) -> Result<(Option<String>, Option<String>)> {
+    let range = match (&source, &target) {
+        (Some(s), None) if s.contains("..") => s.clone(),
+        _ => return Ok((source, target)),
     };
+    let or_head = |side: &str| if side.is_empty() { "HEAD".to_string() } else { side.to_string() };
 
-    // Show diff between two commits
-    println!(
-        "Comparing {}..{}",
-        source_commit.hash[..7].yellow(),
-        target_commit.hash[..7].yellow()
-    );
-    println!();
+    if let Some((a, b)) = range.split_once("...") {
+        let (a, b) = (or_head(a), or_head(b));
+        let from = storage.resolve_commit(&a)?;
+        let to = storage.resolve_commit(&b)?;
+        let base = storage.merge_base(&from.hash, &to.hash)?.ok_or_else(|| {
+            GitnuError::Other(format!("'{}' and '{}' have no common ancestor", a, b))
+        })?;
+        return Ok((Some(base.hash), Some(b)));
+    }
 
-    show_commit_diff(&source_commit, &target_commit)?;
+    let (a, b) = range.split_once("..").unwrap_or((range.as_str(), ""));
+    Ok((Some(or_head(a)), Some(or_head(b))))
+}
 
-    Ok(())
+/// Changed files between `source` (default HEAD) and `target` (default the
+/// working directory), as data rather than rendered output
+pub fn diff_report(
+    storage: &Storage,
+    source: Option<&str>,
+    target: Option<&str>,
+    domain: Option<&str>,
+) -> Result<DiffReport> {
+    let (source, target) = split_range(storage, source.map(str::to_string), target.map(str::to_string))?;
+    let (source, target) = (source.as_deref(), target.as_deref());
+    let from_commit = match source {
+        Some(s) => storage.resolve_commit(s)?,
+        None => storage.get_head_commit()?
+            .ok_or_else(|| GitnuError::Other("No commits yet".to_string()))?,
+    };
+    let to_commit = target.map(|t| storage.resolve_commit(t)).transpose()?;
+
+    let from = Side::Commit(&from_commit);
+    let to = match to_commit {
+        Some(ref c) => Side::Commit(c),
+        None => Side::Working,
+    };
+    let changes = changed_files(storage, &from, &to, domain)?;
+    let (renames, changes) = find_renames(storage, &from, &to, changes, DEFAULT_RENAME_THRESHOLD)?;
+    let mut files: Vec<DiffEntry> = changes
+        .into_iter()
+        .map(|change| DiffEntry {
+            status: match (&change.old, &change.new) {
+                (None, _) => "added",
+                (_, None) => "removed",
+                _ => "modified",
+            }
+            .to_string(),
+            path: change.path,
+            from: None,
+        })
+        .collect();
+    files.extend(renames.into_iter().map(|rename| DiffEntry {
+        status: "renamed".to_string(),
+        path: rename.to,
+        from: Some(rename.from),
+    }));
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+
+    let to_tokens = match to_commit {
+        Some(ref c) => commit_tokens(storage, c, domain)?,
+        None => working_tokens(storage, domain)?,
+    };
+
+    Ok(DiffReport {
+        from: from_commit.hash.clone(),
+        to: to_commit.as_ref().map(|c| c.hash.clone()),
+        files,
+        token_delta: to_tokens as i64 - commit_tokens(storage, &from_commit, domain)? as i64,
+    })
 }
 
-fn resolve_target(storage: &Storage, target: &str) -> Result<crate::models::Commit> {
-    // Try as branch first
-    if let Some(hash) = storage.read_branch_ref(target)? {
-        if let Some(commit) = storage.find_commit(&hash)? {
-            return Ok(commit);
+/// File hashes on one side of a comparison
+fn side_files(storage: &Storage, side: &Side) -> Result<HashMap<PathBuf, String>> {
+    let mut files = HashMap::new();
+    match side {
+        Side::Commit(commit) => {
+            if let Some(manifest) = storage.read_manifest(&commit.hash)? {
+                for info in manifest.files {
+                    files.insert(info.path, info.hash);
+                }
+            }
+        }
+        Side::Working => {
+            let context_mgr = ContextManager::new(Storage::new(storage.vault_root.clone()));
+            for path in context_mgr.get_all_files()? {
+                let hash = hash_file(&storage.vault_root.join(&path))?;
+                files.insert(path, hash);
+            }
         }
+        Side::Empty => {}
     }
-    
-    // Try as commit hash
-    storage.find_commit(target)?
-        .ok_or_else(|| GitnuError::CommitNotFound(target.to_string()))
+    Ok(files)
 }
 
-fn show_working_diff(storage: &Storage, head_commit: &crate::models::Commit) -> Result<()> {
-    use crate::context::ContextManager;
-    
-    let context_mgr = ContextManager::new(Storage::new(storage.vault_root.clone()));
-    let summary = context_mgr.calculate_context_summary(Some(head_commit))?;
+/// Read one file's text from a side of a comparison
+fn read_side(storage: &Storage, side: &Side, path: &Path) -> Result<String> {
+    let bytes = match side {
+        Side::Commit(commit) => storage.read_file_from_snapshot(&commit.hash, path)?,
+        Side::Working => std::fs::read(storage.vault_root.join(path))?,
+        Side::Empty => Vec::new(),
+    };
+    Ok(String::from_utf8_lossy(&bytes).to_string())
+}
+
+/// A file that differs between two sides; `None` means absent on that side
+pub(crate) struct FileChange {
+    pub path: PathBuf,
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+/// Files whose content differs between two sides, sorted by path
+pub(crate) fn changed_files(
+    storage: &Storage,
+    from: &Side,
+    to: &Side,
+    domain: Option<&str>,
+) -> Result<Vec<FileChange>> {
+    let mut old_files = side_files(storage, from)?;
+    let mut new_files = side_files(storage, to)?;
 
-    println!("{}", "Context Changes:".bold());
+    let paths: BTreeSet<_> = old_files.keys()
+        .chain(new_files.keys())
+        .filter(|p| domain.is_none_or(|d| in_domain(p, d)))
+        .cloned()
+        .collect();
 
-    if !summary.files_added.is_empty() {
-        for file in &summary.files_added {
-            println!("+ Added file: {} ", file.display().to_string().green());
+    Ok(paths
+        .into_iter()
+        .map(|path| FileChange {
+            old: old_files.remove(&path),
+            new: new_files.remove(&path),
+            path,
+        })
+        .filter(|change| change.old != change.new)
+        .collect())
+}
+
+/// Old and new text of a changed file (empty when absent on a side)
+pub(crate) fn change_texts(storage: &Storage, from: &Side, to: &Side, change: &FileChange) -> Result<(String, String)> {
+    let old_text = match change.old {
+        Some(_) => read_side(storage, from, &change.path)?,
+        None => String::new(),
+    };
+    let new_text = match change.new {
+        Some(_) => read_side(storage, to, &change.path)?,
+        None => String::new(),
+    };
+    Ok((old_text, new_text))
+}
+
+/// A removed file matched with an added one
+pub(crate) struct Rename {
+    pub from: PathBuf,
+    pub to: PathBuf,
+    /// Percentage of lines the two versions share
+    pub similarity: u8,
+}
+
+/// Most removed x added files compared line by line for inexact renames, as
+/// git's `diff.renameLimit`: beyond `RENAME_LIMIT`² pairs only exact renames
+/// are found
+const RENAME_LIMIT: usize = 100;
+
+/// Pair removed files with added ones: first those with identical content
+/// hashes, then, for `threshold` below 100, the most similar remaining pair
+/// sharing at least `threshold` percent of their lines. Returns the renames and
+/// the changes left unpaired.
+pub(crate) fn find_renames(
+    storage: &Storage,
+    from: &Side,
+    to: &Side,
+    changes: Vec<FileChange>,
+    threshold: u8,
+) -> Result<(Vec<Rename>, Vec<FileChange>)> {
+    let (mut removed, rest): (Vec<FileChange>, Vec<FileChange>) =
+        changes.into_iter().partition(|c| c.new.is_none());
+    let (mut added, mut rest): (Vec<FileChange>, Vec<FileChange>) =
+        rest.into_iter().partition(|c| c.old.is_none());
+
+    let mut renames = Vec::new();
+    let mut added_by_hash: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (i, new) in added.iter().enumerate().rev() {
+        if let Some(ref hash) = new.new {
+            added_by_hash.entry(hash).or_default().push(i);
         }
     }
+    let mut paired = vec![false; added.len()];
+    removed.retain(|old| {
+        let candidates = old.old.as_deref().and_then(|hash| added_by_hash.get_mut(hash));
+        match candidates.and_then(|indices| indices.pop()) {
+            Some(i) => {
+                paired[i] = true;
+                renames.push(Rename { from: old.path.clone(), to: added[i].path.clone(), similarity: 100 });
+                false
+            }
+            None => true,
+        }
+    });
+    let mut paired = paired.into_iter();
+    added.retain(|_| !paired.next().unwrap_or(false));
+
+    let pairs = removed.len() * added.len();
+    if threshold < 100 && pairs > RENAME_LIMIT * RENAME_LIMIT {
+        eprintln!(
+            "{}",
+            format!(
+                "Skipped inexact rename detection: {} removed x {} added files is over the limit",
+                removed.len(),
+                added.len()
+            )
+            .yellow()
+        );
+    } else if threshold < 100 && pairs > 0 {
+        let texts = |side: &Side, list: &[FileChange]| -> Result<Vec<Option<String>>> {
+            list.iter()
+                .map(|c| {
+                    let text = read_side(storage, side, &c.path)?;
+                    Ok((!is_binary(text.as_bytes())).then_some(text))
+                })
+                .collect()
+        };
+        let mut old_texts = texts(from, &removed)?;
+        let mut new_texts = texts(to, &added)?;
 
-    if !summary.files_modified.is_empty() {
-        for file in &summary.files_modified {
-            println!("~ Modified: {}", file.display().to_string().yellow());
+        let mut i = 0;
+        while i < removed.len() {
+            let best = old_texts[i].as_ref().and_then(|old| {
+                let old_lines = old.lines().count();
+                new_texts.iter()
+                    .enumerate()
+                    .filter_map(|(j, new)| {
+                        let new = new.as_deref()?;
+                        // Files whose line counts differ too much can't share enough lines
+                        let new_lines = new.lines().count();
+                        if 200 * old_lines.min(new_lines) < threshold as usize * (old_lines + new_lines) {
+                            return None;
+                        }
+                        let ratio = TextDiff::from_lines(old.as_str(), new).ratio();
+                        // Round down so only identical content reports 100%
+                        Some((j, (ratio * 100.0) as u8))
+                    })
+                    .filter(|&(_, similarity)| similarity >= threshold)
+                    .max_by_key(|&(_, similarity)| similarity)
+            });
+            match best {
+                Some((j, similarity)) => {
+                    new_texts.remove(j);
+                    let to = added.remove(j).path;
+                    renames.push(Rename { from: removed.remove(i).path, to, similarity });
+                    old_texts.remove(i);
+                }
+                None => i += 1,
+            }
         }
     }
 
-    if !summary.files_removed.is_empty() {
-        for file in &summary.files_removed {
-            println!("- Removed: {}", file.display().to_string().red());
+    rest.extend(removed);
+    rest.extend(added);
+    rest.sort_by(|a, b| a.path.cmp(&b.path));
+    renames.sort_by(|a, b| a.to.cmp(&b.to));
+    Ok((renames, rest))
+}
+
+/// Report what writing `target`'s version of each changed file over the
+/// working directory would do, for `--dry-run` previews. Each change's `old`
+/// is the working copy and `new` the version in `target`.
+pub(crate) fn write_restore_preview(
+    out: &mut dyn Write,
+    storage: &Storage,
+    changes: &[FileChange],
+    target: &Commit,
+) -> Result<()> {
+    writeln!(out, "{}", "Context Changes:".bold())?;
+
+    let mut token_delta = 0i64;
+    for change in changes {
+        let path = change.path.display().to_string();
+        match (&change.old, &change.new) {
+            (None, _) => writeln!(out, "+ Would add: {}", path.green())?,
+            (_, None) => writeln!(out, "- Would remove: {}", path.red())?,
+            _ => writeln!(out, "~ Would modify: {}", path.yellow())?,
         }
+        let (old_text, new_text) = change_texts(storage, &Side::Working, &Side::Commit(target), change)?;
+        token_delta += estimate_tokens(&new_text) as i64 - estimate_tokens(&old_text) as i64;
     }
-
-    if summary.files_added.is_empty() && summary.files_modified.is_empty() && summary.files_removed.is_empty() {
-        println!("  {}", "No changes".dimmed());
+    if changes.is_empty() {
+        writeln!(out, "  {}", "No changes".dimmed())?;
     }
 
-    println!();
-    let token_delta = summary.token_estimate as i64 - head_commit.context_summary.token_estimate as i64;
+    writeln!(out)?;
     let sign = if token_delta >= 0 { "+" } else { "" };
-    println!("Token delta: {}{} tokens", sign, token_delta);
+    writeln!(out, "Token delta: {}{} tokens", sign, token_delta)?;
 
     Ok(())
 }
 
-fn show_commit_diff(
-    source: &crate::models::Commit,
-    target: &crate::models::Commit,
+/// Emit standard unified-diff hunks for every changed file, with
+/// `context_lines` unchanged lines around each hunk
+pub(crate) fn write_patch(
+    out: &mut dyn Write,
+    storage: &Storage,
+    from: &Side,
+    to: &Side,
+    domain: Option<&str>,
+    context_lines: usize,
+    rename_threshold: u8,
 ) -> Result<()> {
-    println!("{}", "Context Changes:".bold());
+    let changes = changed_files(storage, from, to, domain)?;
+    let (renames, changes) = find_renames(storage, from, to, changes, rename_threshold)?;
 
-    let source_files: HashSet<_> = source.context_summary.files_added.iter()
-        .chain(source.context_summary.files_modified.iter())
-        .collect();
-    
-    let target_files: HashSet<_> = target.context_summary.files_added.iter()
-        .chain(target.context_summary.files_modified.iter())
+    for rename in renames {
+        writeln!(out, "diff --git a/{} b/{}", rename.from.display(), rename.to.display())?;
+        writeln!(out, "similarity index {}%", rename.similarity)?;
+        writeln!(out, "rename from {}", rename.from.display())?;
+        writeln!(out, "rename to {}", rename.to.display())?;
+        if rename.similarity < 100 {
+            let old_text = read_side(storage, from, &rename.from)?;
+            let new_text = read_side(storage, to, &rename.to)?;
+            let text_diff = TextDiff::from_lines(&old_text, &new_text);
+            write!(
+                out,
+                "{}",
+                text_diff.unified_diff().context_radius(context_lines).header(
+                    &format!("a/{}", rename.from.display()),
+                    &format!("b/{}", rename.to.display())
+                )
+            )?;
+        }
+    }
+
+    for change in changes {
+        let (old_text, new_text) = change_texts(storage, from, to, &change)?;
+        let path = &change.path;
+        let old_header = match change.old {
+            Some(_) => format!("a/{}", path.display()),
+            None => "/dev/null".to_string(),
+        };
+        let new_header = match change.new {
+            Some(_) => format!("b/{}", path.display()),
+            None => "/dev/null".to_string(),
+        };
+
+        writeln!(out, "diff --git a/{} b/{}", path.display(), path.display())?;
+        let text_diff = TextDiff::from_lines(&old_text, &new_text);
+        write!(
+            out,
+            "{}",
+            text_diff.unified_diff().context_radius(context_lines).header(&old_header, &new_header)
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Lines inserted and deleted in one changed file
+fn line_counts(storage: &Storage, from: &Side, to: &Side, change: &FileChange) -> Result<(usize, usize)> {
+    let (old_text, new_text) = change_texts(storage, from, to, change)?;
+    Ok(line_changes(&old_text, &new_text))
+}
+
+/// Per-file line counts recorded when `to` was committed, usable when
+/// `from` is the parent it was committed against
+fn recorded_stats<'a>(from: &Side, to: &Side<'a>) -> Option<&'a [FileStat]> {
+    let Side::Commit(commit) = to else {
+        return None;
+    };
+    let against_parent = match from {
+        Side::Commit(parent) => commit.parent.as_deref() == Some(parent.hash.as_str()),
+        Side::Empty => commit.parent.is_none(),
+        Side::Working => false,
+    };
+    // Commits made before stats were recorded have none
+    let summary = &commit.context_summary;
+    (against_parent && summary.file_stats.len() == summary.files_changed()).then_some(&summary.file_stats)
+}
+
+/// One-paragraph description of the changes, suitable for an agent's notes
+fn write_change_summary(
+    out: &mut dyn Write,
+    storage: &Storage,
+    from: &Side,
+    to: &Side,
+    since: &str,
+    token_delta: i64,
+    domain: Option<&str>,
+) -> Result<()> {
+    let changes = changed_files(storage, from, to, domain)?;
+    let (mut added, mut modified, mut removed) = (0, 0, 0);
+    let (mut inserted, mut deleted) = (0, 0);
+    let mut domains = BTreeSet::new();
+
+    for change in &changes {
+        match (&change.old, &change.new) {
+            (None, _) => added += 1,
+            (_, None) => removed += 1,
+            _ => modified += 1,
+        }
+        let (ins, del) = line_counts(storage, from, to, change)?;
+        inserted += ins;
+        deleted += del;
+        if let Some(name) = change.path.strip_prefix("domains").ok().and_then(|p| p.iter().next()) {
+            domains.insert(name.to_os_string());
+        }
+    }
+
+    writeln!(
+        out,
+        "{}, you modified {} across {} ({:+}/-{} lines), added {} and removed {}. Token budget changed by {:+}.",
+        since,
+        plural(modified, "file"),
+        plural(domains.len(), "domain"),
+        inserted,
+        deleted,
+        plural(added, "file"),
+        removed,
+        token_delta
+    )?;
+
+    Ok(())
+}
+
+/// Per-file inserted/deleted line counts followed by a totals line
+pub(crate) fn write_stat(
+    out: &mut dyn Write,
+    storage: &Storage,
+    from: &Side,
+    to: &Side,
+    domain: Option<&str>,
+) -> Result<()> {
+    let stats: Vec<FileStat> = match recorded_stats(from, to) {
+        Some(recorded) => recorded.iter()
+            .filter(|s| domain.is_none_or(|d| in_domain(&s.path, d)))
+            .cloned()
+            .collect(),
+        None => {
+            let mut stats = Vec::new();
+            for change in changed_files(storage, from, to, domain)? {
+                let (added, removed) = line_counts(storage, from, to, &change)?;
+                stats.push(FileStat { path: change.path, added, removed });
+            }
+            stats
+        }
+    };
+    let width = stats.iter().map(|s| s.path.display().to_string().len()).max().unwrap_or(0);
+    let (mut total_inserted, mut total_deleted) = (0, 0);
+
+    for stat in &stats {
+        let (inserted, deleted) = (stat.added, stat.removed);
+        total_inserted += inserted;
+        total_deleted += deleted;
+
+        writeln!(
+            out,
+            " {:<width$} | {:>4} {}{}",
+            stat.path.display().to_string(),
+            inserted + deleted,
+            "+".repeat(inserted.min(40)).green(),
+            "-".repeat(deleted.min(40)).red(),
+            width = width
+        )?;
+    }
+
+    writeln!(
+        out,
+        " {} file{} changed, {} insertion{}(+), {} deletion{}(-)",
+        stats.len(),
+        if stats.len() == 1 { "" } else { "s" },
+        total_inserted,
+        if total_inserted == 1 { "" } else { "s" },
+        total_deleted,
+        if total_deleted == 1 { "" } else { "s" }
+    )?;
+
+    Ok(())
+}
+
+/// The staged files with their priority, reason and token cost, and how
+/// the active context's size would change if they were loaded
+fn write_staged(out: &mut dyn Write, storage: &Storage) -> Result<()> {
+    let index = storage.load_index()?;
+    if index.staged.is_empty() {
+        writeln!(out, "{}", "Nothing staged".dimmed())?;
+        return Ok(());
+    }
+
+    let context_mgr = ContextManager::new(Storage::new(storage.vault_root.clone()));
+    let in_context: HashSet<PathBuf> = context_mgr.context_files(&ContextFilter::new(&[], &[])?)?
+        .into_iter()
         .collect();
+    let mut current = 0;
+    for path in &in_context {
+        current += estimate_tokens(&read_for_tokens(storage, &storage.vault_root.join(path))?);
+    }
+
+    writeln!(out, "{}", format!("Staged ({}):", index.staged.len()).bold())?;
+    let width = index.staged.iter().map(|s| s.path.display().to_string().len()).max().unwrap_or(0);
+    let mut added = 0;
+    // Files an earlier staged entry already brings in (a directory and a file inside it)
+    let mut counted: HashSet<PathBuf> = HashSet::new();
+    for staged in &index.staged {
+        let full_path = storage.vault_root.join(&staged.path);
+        let (marker, cost) = if !full_path.exists() {
+            ("!".red(), "missing".red().to_string())
+        } else {
+            let files = if full_path.is_file() { vec![full_path.clone()] } else { storage.walk_files(&full_path)? };
+            // Only files not yet in context add to it
+            let mut tokens = 0;
+            let mut loaded = 0;
+            for file in &files {
+                let rel_path = relative_path(&storage.vault_root, file);
+                if in_context.contains(&rel_path) {
+                    loaded += 1;
+                    continue;
+                }
+                let file_tokens = estimate_tokens(&read_for_tokens(storage, file)?);
+                tokens += file_tokens;
+                if counted.insert(rel_path) {
+                    added += file_tokens;
+                }
+            }
+            if loaded == files.len() {
+                let tokens = estimate_tokens(&read_for_tokens(storage, &full_path)?);
+                ("=".dimmed(), format!("~{} tokens, already in context", tokens).dimmed().to_string())
+            } else if loaded > 0 {
+                ("+".green(), format!("~{} tokens ({} already in context)", tokens, plural(loaded, "file")))
+            } else {
+                ("+".green(), format!("~{} tokens", tokens))
+            }
+        };
+        writeln!(
+            out,
+            "  {} {:<width$}  [{}] \"{}\"  {}",
+            marker,
+            staged.path.display().to_string(),
+            staged.priority.display().yellow(),
+            staged.reason,
+            cost,
+            width = width
+        )?;
+    }
+
+    let max = storage.load_config()?.context.max_tokens;
+    writeln!(out)?;
+    writeln!(
+        out,
+        "Context: ~{} -> ~{} tokens ({:+}) of {}",
+        current,
+        budget_colored(current + added, max),
+        added,
+        max
+    )?;
+    Ok(())
+}
+
+/// List each changed file's token delta, largest change first
+fn write_token_breakdown(
+    out: &mut dyn Write,
+    storage: &Storage,
+    from: &Side,
+    to: &Side,
+    domain: Option<&str>,
+) -> Result<()> {
+    let mut deltas = Vec::new();
+    for change in changed_files(storage, from, to, domain)? {
+        let (old_text, new_text) = change_texts(storage, from, to, &change)?;
+        let delta = estimate_tokens(&new_text) as i64 - estimate_tokens(&old_text) as i64;
+        deltas.push((delta, change.path));
+    }
+
+    // Stable sort keeps ties ordered by path
+    deltas.sort_by_key(|(delta, _)| std::cmp::Reverse(delta.abs()));
+
+    writeln!(out)?;
+    writeln!(out, "{}", "Token Breakdown:".bold())?;
+    if deltas.is_empty() {
+        writeln!(out, "  {}", "No changes".dimmed())?;
+    }
+    for (delta, path) in deltas {
+        let amount = format!("{:+} tokens", delta);
+        let amount = if delta > 0 {
+            amount.green()
+        } else if delta < 0 {
+            amount.red()
+        } else {
+            amount.dimmed()
+        };
+        writeln!(out, "  {:>14}  {}", amount, path.display())?;
+    }
+
+    Ok(())
+}
+
+/// Whether a vault-relative path lives under `domains/<domain>/`
+fn in_domain(path: &Path, domain: &str) -> bool {
+    path.starts_with(Path::new("domains").join(domain))
+}
+
+/// Error unless the domain exists in one of the commits (or the working directory)
+fn check_domain_exists(
+    storage: &Storage,
+    domain: Option<&str>,
+    commits: &[&Commit],
+    include_working: bool,
+) -> Result<()> {
+    let domain = match domain {
+        Some(d) => d,
+        None => return Ok(()),
+    };
 
-    // Files in target but not in source
-    for file in target_files.difference(&source_files) {
-        println!("+ Added file: {}", file.display().to_string().green());
+    if include_working && storage.domains_dir().join(domain).is_dir() {
+        return Ok(());
     }
+    for commit in commits {
+        if let Some(manifest) = storage.read_manifest(&commit.hash)? {
+            if manifest.files.iter().any(|f| in_domain(&f.path, domain)) {
+                return Ok(());
+            }
+        }
+    }
+
+    Err(GitnuError::Other(format!(
+        "Domain '{}' not found on either side of the diff",
+        domain
+    )))
+}
 
-    // Files in source but not in target
-    for file in source_files.difference(&target_files) {
-        println!("- Removed: {}", file.display().to_string().red());
+/// Token estimate for a commit, scoped to a domain when one is given
+fn commit_tokens(storage: &Storage, commit: &Commit, domain: Option<&str>) -> Result<usize> {
+    let domain = match domain {
+        Some(d) => d,
+        None => return Ok(commit.context_summary.token_estimate),
+    };
+    let size: u64 = match storage.read_manifest(&commit.hash)? {
+        Some(manifest) => manifest.files.iter()
+            .filter(|f| in_domain(&f.path, domain))
+            .map(|f| f.size)
+            .sum(),
+        None => 0,
+    };
+    Ok(size as usize / 4)
+}
+
+/// Token estimate for the working directory, scoped to a domain when one is given
+fn working_tokens(storage: &Storage, domain: Option<&str>) -> Result<usize> {
+    let context_mgr = ContextManager::new(Storage::new(storage.vault_root.clone()));
+    let domain = match domain {
+        Some(d) => d,
+        None => return Ok(context_mgr.calculate_context_summary(None)?.token_estimate),
+    };
+
+    let mut size = 0u64;
+    for file in context_mgr.get_all_files()? {
+        if in_domain(&file, domain) {
+            size += std::fs::metadata(storage.vault_root.join(&file))?.len();
+        }
     }
+    Ok(size as usize / 4)
+}
+
+/// List added, modified, renamed and removed files under "Context Changes:"
+fn write_file_changes(
+    out: &mut dyn Write,
+    storage: &Storage,
+    from: &Side,
+    to: &Side,
+    domain: Option<&str>,
+    rename_threshold: u8,
+) -> Result<()> {
+    let changes = changed_files(storage, from, to, domain)?;
+    let (renames, changes) = find_renames(storage, from, to, changes, rename_threshold)?;
 
-    // Files in both (potentially modified)
-    for file in source_files.intersection(&target_files) {
-        println!("~ Modified: {}", file.display().to_string().yellow());
+    writeln!(out, "{}", "Context Changes:".bold())?;
+    for change in changes.iter().filter(|c| c.old.is_none()) {
+        writeln!(out, "+ Added file: {}", change.path.display().to_string().green())?;
+    }
+    for change in changes.iter().filter(|c| c.old.is_some() && c.new.is_some()) {
+        writeln!(out, "~ Modified: {}", change.path.display().to_string().yellow())?;
+    }
+    for rename in &renames {
+        let similarity = if rename.similarity < 100 {
+            format!(" ({}% similar)", rename.similarity)
+        } else {
+            String::new()
+        };
+        writeln!(
+            out,
+            "R Renamed: {} -> {}{}",
+            rename.from.display(),
+            rename.to.display().to_string().cyan(),
+            similarity.dimmed()
+        )?;
+    }
+    for change in changes.iter().filter(|c| c.new.is_none()) {
+        writeln!(out, "- Removed: {}", change.path.display().to_string().red())?;
     }
 
-    println!();
+    if changes.is_empty() && renames.is_empty() {
+        writeln!(out, "  {}", "No changes".dimmed())?;
+    }
+    Ok(())
+}
+
+fn show_working_diff(
+    out: &mut dyn Write,
+    storage: &Storage,
+    head_commit: &Commit,
+    domain: Option<&str>,
+    rename_threshold: u8,
+) -> Result<()> {
+    write_file_changes(out, storage, &Side::Commit(head_commit), &Side::Working, domain, rename_threshold)?;
+
+    writeln!(out)?;
+    let working = working_tokens(storage, domain)?;
+    let head_tokens = commit_tokens(storage, head_commit, domain)?;
+    let token_delta = working as i64 - head_tokens as i64;
+    let sign = if token_delta >= 0 { "+" } else { "" };
+    writeln!(out, "Token delta: {}{} tokens", sign, token_delta)?;
+
+    Ok(())
+}
+
+fn show_commit_diff(
+    out: &mut dyn Write,
+    storage: &Storage,
+    source: &Commit,
+    target: &Commit,
+    domain: Option<&str>,
+    rename_threshold: u8,
+) -> Result<()> {
+    write_file_changes(
+        out,
+        storage,
+        &Side::Commit(source),
+        &Side::Commit(target),
+        domain,
+        rename_threshold,
+    )?;
+
+    writeln!(out)?;
     
-    let token_delta = target.context_summary.token_estimate as i64 
-        - source.context_summary.token_estimate as i64;
+    let token_delta = commit_tokens(storage, target, domain)? as i64
+        - commit_tokens(storage, source, domain)? as i64;
     let sign = if token_delta >= 0 { "+" } else { "" };
-    println!("Token delta: {}{} tokens", sign, token_delta);
+    writeln!(out, "Token delta: {}{} tokens", sign, token_delta)?;
+
+    // Domain-level changes are irrelevant when scoped to one domain
+    if domain.is_some() {
+        return Ok(());
+    }
 
     // Show domain differences
     let source_domains: HashSet<_> = source.context_summary.domains_loaded.iter().collect();
     let target_domains: HashSet<_> = target.context_summary.domains_loaded.iter().collect();
 
     if source_domains != target_domains {
-        println!();
-        println!("{}", "Domain Changes:".bold());
+        writeln!(out)?;
+        writeln!(out, "{}", "Domain Changes:".bold())?;
         
         for domain in target_domains.difference(&source_domains) {
-            println!("+ Added domain: domains/{}/", domain.green());
+            writeln!(out, "+ Added domain: domains/{}/", domain.green())?;
         }
         
         for domain in source_domains.difference(&target_domains) {
-            println!("- Removed from context: domains/{}/", domain.red());
+            writeln!(out, "- Removed from context: domains/{}/", domain.red())?;
         }
     }
 
@@ -0,0 +1,294 @@
+use crate::errors::*;
+use crate::models::*;
+use crate::storage::Storage;
+use crate::utils::*;
+use crate::commands::gc::loose_objects;
+use colored::Colorize;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Severity {
+    Error,
+    Warning,
+}
+
+struct Finding {
+    severity: Severity,
+    message: String,
+    /// What `--fix` did about it, if anything
+    fixed: Option<String>,
+}
+
+/// Check the `.gitnu` store for damage and, with `fix`, repair what can be
+/// repaired without losing data
+pub fn doctor(fix: bool) -> Result<()> {
+    let vault_root = find_vault_root()?;
+    let storage = Storage::new(vault_root);
+    let _lock = if fix { storage.lock()? } else { storage.lock_ignoring_read_only()? };
+    let mut findings = Vec::new();
+
+    check_config(&storage, &mut findings);
+    check_index(&storage, fix, &mut findings)?;
+    let logs = check_commit_logs(&storage, fix, &mut findings)?;
+    check_refs(&storage, &logs, fix, &mut findings)?;
+    check_head(&storage, &logs, fix, &mut findings)?;
+    check_objects(&storage, &logs, &mut findings)?;
+
+    for finding in &findings {
+        let label = match finding.severity {
+            Severity::Error => "error:".red().bold(),
+            Severity::Warning => "warning:".yellow().bold(),
+        };
+        println!("{} {}", label, finding.message);
+        if let Some(ref fixed) = finding.fixed {
+            println!("  {} {}", "fixed:".green(), fixed);
+        }
+    }
+
+    let unresolved: Vec<_> = findings.iter().filter(|f| f.fixed.is_none()).collect();
+    let errors = unresolved.iter().filter(|f| f.severity == Severity::Error).count();
+    let warnings = unresolved.len() - errors;
+    if findings.is_empty() {
+        println!("{}", "No problems found".green());
+    } else if !unresolved.is_empty() {
+        println!();
+        println!("{}, {} remaining", plural(errors, "error"), plural(warnings, "warning"));
+        if !fix && errors > 0 {
+            println!("  Run 'gnu doctor --fix' to repair what can be fixed safely");
+        }
+    }
+
+    if errors > 0 {
+        return Err(GitnuError::Other(format!(
+            "Vault check failed with {}",
+            plural(errors, "unresolved error")
+        )));
+    }
+
+    Ok(())
+}
+
+fn error(message: String) -> Finding {
+    Finding { severity: Severity::Error, message, fixed: None }
+}
+
+fn warning(message: String) -> Finding {
+    Finding { severity: Severity::Warning, message, fixed: None }
+}
+
+fn check_config(storage: &Storage, findings: &mut Vec<Finding>) {
+    if let Err(e) = storage.load_config() {
+        findings.push(error(format!(
+            "config.toml is unreadable ({})\n  Fix it by hand or compare with a fresh 'gnu init'",
+            e
+        )));
+    }
+}
+
+/// An unreadable index only holds loaded/pinned state, so it is safe to recreate
+fn check_index(storage: &Storage, fix: bool, findings: &mut Vec<Finding>) -> Result<()> {
+    if let Err(e) = storage.load_index() {
+        let mut finding = error(format!("index.json is unreadable ({})", e));
+        if fix {
+            let path = storage.gitnu_dir().join("index.json");
+            fs::rename(&path, path.with_extension("json.bak"))?;
+            storage.save_index(&Index::default())?;
+            finding.fixed = Some("recreated an empty index (old copy in index.json.bak)".to_string());
+        }
+        findings.push(finding);
+    }
+    Ok(())
+}
+
+/// Parse every commit log line by line, returning the readable commits of each log
+fn check_commit_logs(
+    storage: &Storage,
+    fix: bool,
+    findings: &mut Vec<Finding>,
+) -> Result<HashMap<String, Vec<Commit>>> {
+    let mut logs = HashMap::new();
+    for log in storage.list_commit_logs()? {
+        let path = storage.commits_dir().join(format!("{}.jsonl", log));
+        let content = fs::read_to_string(&path)?;
+
+        let mut commits = Vec::new();
+        let mut good = Vec::new();
+        let mut bad = Vec::new();
+        for (number, line) in content.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str::<Commit>(line) {
+                Ok(commit) => {
+                    commits.push(commit);
+                    good.push(line);
+                }
+                Err(_) => bad.push((number + 1, line)),
+            }
+        }
+
+        if !bad.is_empty() {
+            let lines: Vec<String> = bad.iter().map(|(n, _)| n.to_string()).collect();
+            let mut finding = error(format!(
+                "commits/{}.jsonl has unreadable entries on line {}",
+                log,
+                lines.join(", ")
+            ));
+            if fix {
+                // Keep the damaged lines next to the log rather than dropping them
+                let corrupt_path = corrupt_backup_path(&path);
+                let corrupt: Vec<&str> = bad.iter().map(|(_, line)| *line).collect();
+                fs::write(&corrupt_path, corrupt.join("\n") + "\n")?;
+                atomic_write(&path, (good.join("\n") + "\n").as_bytes())?;
+                finding.fixed = Some(format!(
+                    "moved {} to {}",
+                    plural(bad.len(), "line"),
+                    relative_path(&storage.vault_root, &corrupt_path).display()
+                ));
+            }
+            findings.push(finding);
+        }
+        logs.insert(log, commits);
+    }
+    Ok(logs)
+}
+
+/// A fresh `<log>.corrupt-<timestamp>` path, so repeated fixes never
+/// overwrite an earlier backup
+fn corrupt_backup_path(log_path: &Path) -> PathBuf {
+    let stamp = chrono::Utc::now().format("%Y%m%dT%H%M%S");
+    let mut candidate = log_path.with_extension(format!("corrupt-{}", stamp));
+    let mut n = 1;
+    while candidate.exists() {
+        n += 1;
+        candidate = log_path.with_extension(format!("corrupt-{}-{}", stamp, n));
+    }
+    candidate
+}
+
+/// Newest commit in a branch's log whose snapshot is still stored
+fn last_valid_commit(storage: &Storage, logs: &HashMap<String, Vec<Commit>>, branch: &str) -> Result<Option<String>> {
+    if let Some(commits) = logs.get(branch) {
+        for commit in commits.iter().rev() {
+            if storage.has_snapshot(&commit.hash)? {
+                return Ok(Some(commit.hash.clone()));
+            }
+        }
+    }
+    Ok(None)
+}
+
+fn check_refs(
+    storage: &Storage,
+    logs: &HashMap<String, Vec<Commit>>,
+    fix: bool,
+    findings: &mut Vec<Finding>,
+) -> Result<()> {
+    let known: HashSet<&str> = logs.values().flatten().map(|c| c.hash.as_str()).collect();
+
+    let mut branches = storage.list_branches()?;
+    branches.sort();
+    for branch in branches {
+        let target = storage.read_branch_ref(&branch)?.unwrap_or_default();
+        if known.contains(target.as_str()) {
+            continue;
+        }
+
+        let mut finding = error(format!(
+            "branch '{}' points to missing commit {}",
+            branch,
+            short_hash(&target)
+        ));
+        if fix {
+            if let Some(hash) = last_valid_commit(storage, logs, &branch)? {
+                storage.update_branch_ref(&branch, &hash, "doctor: reset dangling ref")?;
+                finding.fixed = Some(format!("reset '{}' to {}", branch, short_hash(&hash)));
+            }
+        }
+        findings.push(finding);
+    }
+    Ok(())
+}
+
+fn check_head(
+    storage: &Storage,
+    logs: &HashMap<String, Vec<Commit>>,
+    fix: bool,
+    findings: &mut Vec<Finding>,
+) -> Result<()> {
+    let head = match storage.read_head() {
+        Ok(head) => head,
+        Err(e) => {
+            let mut finding = error(format!("HEAD is unreadable ({})", e));
+            let default_branch = storage.load_config()
+                .map(|config| config.core.default_branch)
+                .unwrap_or_else(|_| Config::default().core.default_branch);
+            if fix && storage.read_branch_ref(&default_branch)?.is_some() {
+                storage.write_head(&default_branch)?;
+                finding.fixed = Some(format!("pointed HEAD at '{}'", default_branch));
+            }
+            findings.push(finding);
+            return Ok(());
+        }
+    };
+
+    match head {
+        Head::Branch(name) => {
+            if storage.read_branch_ref(&name)?.is_some() {
+                return Ok(());
+            }
+            let mut finding = error(format!("HEAD points to branch '{}', which has no ref", name));
+            if fix {
+                if let Some(hash) = last_valid_commit(storage, logs, &name)? {
+                    storage.update_branch_ref(&name, &hash, "doctor: recreate ref")?;
+                    finding.fixed = Some(format!("recreated '{}' at {}", name, short_hash(&hash)));
+                }
+            }
+            findings.push(finding);
+        }
+        Head::Detached(hash) => {
+            if !logs.values().flatten().any(|c| c.hash == hash) {
+                findings.push(error(format!(
+                    "HEAD is detached at missing commit {}\n  Run 'gnu checkout <branch>' to reattach",
+                    short_hash(&hash)
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Every reachable commit needs a snapshot (unreachable ones may have been
+/// pruned by the retention policy); objects without a commit are only wasted space
+fn check_objects(storage: &Storage, logs: &HashMap<String, Vec<Commit>>, findings: &mut Vec<Finding>) -> Result<()> {
+    let reachable = storage.reachable_commits()?;
+    let mut checked = HashSet::new();
+    for commit in logs.values().flatten() {
+        if checked.insert(commit.hash.as_str())
+            && reachable.contains(&commit.hash)
+            && !storage.has_snapshot(&commit.hash)?
+        {
+            findings.push(error(format!(
+                "snapshot for commit {} \"{}\" is missing; it can't be checked out",
+                short_hash(&commit.hash),
+                commit.subject()
+            )));
+        }
+    }
+
+    let mut orphaned = 0;
+    for name in loose_objects(storage)? {
+        if !checked.contains(name.as_str()) {
+            orphaned += 1;
+        }
+    }
+    if orphaned > 0 {
+        findings.push(warning(format!(
+            "{} no commit refers to\n  Run 'gnu gc' to remove them",
+            plural(orphaned, "snapshot object")
+        )));
+    }
+    Ok(())
+}
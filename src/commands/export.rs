@@ -0,0 +1,100 @@
+use crate::errors::*;
+use crate::models::*;
+use crate::storage::Storage;
+use crate::context::ContextManager;
+use crate::utils::*;
+use chrono::Utc;
+use colored::Colorize;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::fs::{self, File};
+use std::path::PathBuf;
+use tar::{Builder, Header};
+
+pub fn export(format: &str, output: Option<PathBuf>) -> Result<()> {
+    let vault_root = find_vault_root()?;
+    let storage = Storage::new(vault_root.clone());
+    let context_mgr = ContextManager::new(Storage::new(vault_root.clone()));
+
+    let config = storage.load_config()?;
+    let metadata = ExportMetadata {
+        vault_name: config.core.vault_name.clone(),
+        branch: storage.read_head()?.name().to_string(),
+        commit: storage.get_head_commit()?.map(|c| c.hash),
+        exported_at: Utc::now(),
+    };
+    let files = context_mgr.get_all_files()?;
+
+    match format {
+        "md" => {
+            let header = format!(
+                "<!-- gitnu export: vault {}, branch {}, commit {} -->\n",
+                metadata.vault_name,
+                metadata.branch,
+                metadata.commit.as_deref().unwrap_or("none")
+            );
+            let content = header + &context_mgr.render_files(&files);
+            write_output(output, content.as_bytes())?;
+        }
+        "json" => {
+            let mut exported = Vec::new();
+            for path in files {
+                let bytes = fs::read(vault_root.join(&path))?;
+                exported.push(ExportedFile::new(path, bytes));
+            }
+            let bundle = ExportBundle { metadata, files: exported };
+            let content = serde_json::to_string_pretty(&bundle)?;
+            write_output(output, content.as_bytes())?;
+        }
+        "tar" => {
+            let output = output.unwrap_or_else(|| {
+                PathBuf::from(format!("{}-export.tar.gz", config.core.vault_name))
+            });
+            let tar_gz = File::create(&output)?;
+            let enc = GzEncoder::new(tar_gz, Compression::default());
+            let mut tar = Builder::new(enc);
+
+            // Metadata header goes first so importers can read it cheaply
+            let header_json = serde_json::to_vec_pretty(&metadata)?;
+            let mut header = Header::new_gnu();
+            header.set_size(header_json.len() as u64);
+            header.set_mode(0o644);
+            header.set_mtime(metadata.exported_at.timestamp() as u64);
+            header.set_cksum();
+            tar.append_data(&mut header, ExportMetadata::TAR_ENTRY, header_json.as_slice())?;
+
+            storage.append_domains(&mut tar)?;
+            tar.into_inner()?.finish()?;
+
+            eprintln!(
+                "{} {} files to {}",
+                "Exported".green(),
+                files.len(),
+                output.display()
+            );
+        }
+        _ => {
+            return Err(GitnuError::Other(format!(
+                "Invalid export format: {}. Use 'md', 'json', or 'tar'",
+                format
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// Write to the output file, or stdout when none is given
+fn write_output(output: Option<PathBuf>, content: &[u8]) -> Result<()> {
+    match output {
+        Some(path) => {
+            fs::write(&path, content)?;
+            eprintln!("{} {}", "Exported to".green(), path.display());
+        }
+        None => {
+            use std::io::Write;
+            std::io::stdout().write_all(content)?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,132 @@
+use crate::errors::*;
+use crate::models::{Commit, Head};
+use crate::storage::Storage;
+use crate::commands::gc::loose_objects;
+use crate::utils::*;
+use colored::Colorize;
+use std::collections::HashMap;
+
+/// Inspect the object store without changing anything: refs whose commit
+/// has no snapshot, log entries no object backs and, with `unreachable`,
+/// objects nothing points at any more. `gnu gc` removes objects and
+/// `gnu doctor --fix` repairs refs; this only reports.
+pub fn fsck(unreachable: bool, verbose: bool) -> Result<()> {
+    let vault_root = find_vault_root()?;
+    let storage = Storage::new(vault_root);
+
+    let mut commits: HashMap<String, Commit> = HashMap::new();
+    for log in storage.list_commit_logs()? {
+        for commit in storage.read_commits(&log)? {
+            commits.insert(commit.hash.clone(), commit);
+        }
+    }
+
+    // Refs, with the name each is known by
+    let mut refs: Vec<(String, String)> = Vec::new();
+    let mut branches = storage.list_branches()?;
+    branches.sort();
+    for branch in branches {
+        if let Some(hash) = storage.read_branch_ref(&branch)? {
+            refs.push((branch, hash));
+        }
+    }
+    if let Ok(Head::Detached(hash)) = storage.read_head() {
+        refs.push(("HEAD".to_string(), hash));
+    }
+    if let Some(hash) = storage.read_merge_head()? {
+        refs.push(("MERGE_HEAD".to_string(), hash));
+    }
+
+    let mut broken_refs = Vec::new();
+    for (name, hash) in &refs {
+        if !storage.has_snapshot(hash)? {
+            broken_refs.push((name.as_str(), hash.as_str()));
+        }
+    }
+
+    let mut missing: Vec<&Commit> = Vec::new();
+    for commit in commits.values() {
+        if !storage.has_snapshot(&commit.hash)? {
+            missing.push(commit);
+        }
+    }
+    missing.sort_by_key(|c| c.timestamp);
+
+    println!("Checked {} and {}", plural(commits.len(), "commit"), plural(refs.len(), "ref"));
+
+    if broken_refs.is_empty() {
+        println!("  {} every ref has its snapshot", "ok".green());
+    } else {
+        println!("  {} {} with a missing snapshot", "error".red().bold(), plural(broken_refs.len(), "ref"));
+        if verbose {
+            for (name, hash) in &broken_refs {
+                println!("    {} -> {}", name, short_hash(hash).yellow());
+            }
+        }
+    }
+
+    if missing.is_empty() {
+        println!("  {} every log entry has its snapshot", "ok".green());
+    } else {
+        let entries = if missing.len() == 1 { "log entry" } else { "log entries" };
+        println!("  {} {} {} without a snapshot", "warning".yellow().bold(), missing.len(), entries);
+        if verbose {
+            for commit in &missing {
+                println!("    {} {}", short_hash(&commit.hash).yellow(), commit.subject());
+            }
+        }
+    }
+
+    if unreachable {
+        let reachable = storage.reachable_commits()?;
+        let mut orphaned = Vec::new();
+        for hash in loose_objects(&storage)? {
+            if !reachable.contains(&hash) {
+                let size = dir_size(&storage.objects_dir().join(&hash));
+                orphaned.push((hash, size));
+            }
+        }
+        for (_, index) in storage.list_packs()? {
+            for (hash, entry) in index.entries {
+                if !reachable.contains(&hash) {
+                    orphaned.push((hash, entry.length));
+                }
+            }
+        }
+        orphaned.sort();
+
+        let total: u64 = orphaned.iter().map(|(_, size)| size).sum();
+        if orphaned.is_empty() {
+            println!("  {} no unreachable objects", "ok".green());
+        } else {
+            println!(
+                "  {} ({}) unreachable",
+                plural(orphaned.len(), "object"),
+                format_size(total)
+            );
+            if verbose {
+                for (hash, size) in &orphaned {
+                    let label = match commits.get(hash) {
+                        Some(commit) => commit.subject().to_string(),
+                        None => "no commit".dimmed().to_string(),
+                    };
+                    println!("    {} {} {}", short_hash(hash).yellow(), format_size(*size).dimmed(), label);
+                }
+            }
+            println!("  Run 'gnu gc' to remove what the retention policy allows");
+        }
+    }
+
+    if !verbose && (!broken_refs.is_empty() || !missing.is_empty()) {
+        println!("  Run 'gnu fsck --verbose' to list them");
+    }
+
+    if !broken_refs.is_empty() {
+        return Err(GitnuError::Other(format!(
+            "{} can't be checked out\n  Run: gnu doctor --fix",
+            plural(broken_refs.len(), "ref")
+        )));
+    }
+
+    Ok(())
+}
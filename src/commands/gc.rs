@@ -0,0 +1,111 @@
+use crate::errors::*;
+use crate::models::RetentionConfig;
+use crate::storage::Storage;
+use crate::utils::*;
+use chrono::{Duration, Utc};
+use colored::Colorize;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+
+/// Remove objects no commit refers to, and unreachable snapshots past the
+/// `[retention]` policy. With `repack`, consolidate snapshots older than
+/// `older_than_days` into a packfile.
+pub fn gc(repack: bool, older_than_days: i64) -> Result<()> {
+    let vault_root = find_vault_root()?;
+    let storage = Storage::new(vault_root);
+    let _lock = storage.lock()?;
+    let config = storage.load_config()?;
+
+    let size_before = dir_size(&storage.objects_dir());
+
+    let pruned = prune_snapshots(&storage, &config.retention)?;
+
+    // Every commit recorded in any log, including deleted branches
+    let mut timestamps = HashMap::new();
+    for log in storage.list_commit_logs()? {
+        for commit in storage.read_commits(&log)? {
+            timestamps.insert(commit.hash, commit.timestamp);
+        }
+    }
+
+    let mut to_pack = Vec::new();
+    let cutoff = Utc::now() - Duration::days(older_than_days);
+    for hash in loose_objects(&storage)? {
+        if repack && timestamps.get(&hash).is_some_and(|t| *t <= cutoff) {
+            to_pack.push(hash);
+        }
+    }
+    to_pack.sort();
+
+    if pruned > 0 {
+        println!("Removed {} unreachable objects", pruned);
+    }
+    if !to_pack.is_empty() {
+        let pack_path = storage.write_pack(&to_pack)?;
+        println!(
+            "Packed {} snapshots into {}",
+            to_pack.len(),
+            relative_path(&storage.vault_root, &pack_path).display()
+        );
+    } else if repack {
+        println!("{}", "No snapshots old enough to pack".dimmed());
+    }
+
+    let size_after = dir_size(&storage.objects_dir());
+    println!(
+        "{} {} reclaimed ({} -> {})",
+        "Done:".green(),
+        format_size(size_before.saturating_sub(size_after)),
+        format_size(size_before),
+        format_size(size_after)
+    );
+
+    Ok(())
+}
+
+/// Names of the loose snapshot directories under objects/
+pub(crate) fn loose_objects(storage: &Storage) -> Result<Vec<String>> {
+    let mut loose = Vec::new();
+    if !storage.objects_dir().exists() {
+        return Ok(loose);
+    }
+    for entry in fs::read_dir(storage.objects_dir())? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() && entry.path() != storage.pack_dir() && entry.path() != storage.trees_dir() {
+            loose.push(entry.file_name().to_string_lossy().to_string());
+        }
+    }
+    Ok(loose)
+}
+
+/// Remove loose snapshots no commit refers to, and snapshots of unreachable
+/// commits that are neither among the `keep_last` newest of their log nor
+/// younger than `keep_days`. Returns how many were removed.
+pub(crate) fn prune_snapshots(storage: &Storage, retention: &RetentionConfig) -> Result<usize> {
+    let reachable = storage.reachable_commits()?;
+    let cutoff = Utc::now() - Duration::days(retention.keep_days);
+
+    // Snapshots without a commit are never kept
+    let mut kept = HashSet::new();
+    for log in storage.list_commit_logs()? {
+        let commits = storage.read_commits(&log)?;
+        let recent = commits.len().saturating_sub(retention.keep_last);
+        for (i, commit) in commits.into_iter().enumerate() {
+            if i >= recent || reachable.contains(&commit.hash) || commit.timestamp > cutoff {
+                kept.insert(commit.hash);
+            }
+        }
+    }
+
+    let mut pruned = 0;
+    for hash in loose_objects(storage)? {
+        if !kept.contains(&hash) {
+            fs::remove_dir_all(storage.objects_dir().join(&hash))?;
+            pruned += 1;
+        }
+    }
+    if pruned > 0 {
+        storage.prune_trees()?;
+    }
+    Ok(pruned)
+}
@@ -0,0 +1,155 @@
+use crate::errors::*;
+use crate::models::*;
+use crate::storage::Storage;
+use crate::utils::*;
+use colored::Colorize;
+use flate2::read::GzDecoder;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+use tar::Archive;
+
+/// Merge an exported bundle's `domains/` files into the current vault.
+///
+/// Each domain is imported as `imported-<name>/` unless `into` names a target
+/// domain. History is left untouched; commit afterwards to record the import.
+pub fn import(file: &Path, into: Option<String>, force: bool) -> Result<()> {
+    let vault_root = find_vault_root()?;
+    let _lock = Storage::new(vault_root.clone()).lock()?;
+
+    if !file.exists() {
+        return Err(GitnuError::FileNotFound(file.to_path_buf()));
+    }
+
+    // The target is joined under domains/, so it may only name directories there
+    if let Some(ref target) = into {
+        let components: Vec<_> = Path::new(target).components().collect();
+        if components.is_empty() || components.iter().any(|c| !matches!(c, Component::Normal(_))) {
+            return Err(GitnuError::Other(format!(
+                "Invalid --into '{}'\n  Name a domain under domains/, e.g. --into research",
+                target
+            )));
+        }
+    }
+
+    let (metadata, files) = read_bundle(file)?;
+
+    // Map each bundle path to its destination in this vault
+    let mut planned = Vec::new();
+    for (path, content) in files {
+        let dest = match remap_path(&path, into.as_deref()) {
+            Some(d) => d,
+            None => {
+                println!("{} {} (outside domains/)", "Skipped:".yellow(), path.display());
+                continue;
+            }
+        };
+        planned.push((dest, content));
+    }
+
+    let conflicts: Vec<_> = planned.iter()
+        .filter(|(dest, _)| vault_root.join(dest).exists())
+        .map(|(dest, _)| dest.clone())
+        .collect();
+
+    if !conflicts.is_empty() && !force {
+        println!("{}", "Would overwrite:".bold());
+        for path in &conflicts {
+            println!("  ~ {}", path.display().to_string().yellow());
+        }
+        return Err(GitnuError::Other(format!(
+            "{} files already exist\n  Use --force to overwrite them\n  Or import into another domain with --into <domain>",
+            conflicts.len()
+        )));
+    }
+
+    for (dest, content) in &planned {
+        let full_path = vault_root.join(dest);
+        if let Some(parent) = full_path.parent() {
+            ensure_dir(parent)?;
+        }
+        fs::write(&full_path, content)?;
+        if conflicts.contains(dest) {
+            println!("  ~ Overwrote: {}", dest.display().to_string().yellow());
+        } else {
+            println!("  + Added: {}", dest.display().to_string().green());
+        }
+    }
+
+    println!();
+    match metadata {
+        Some(m) => println!(
+            "{} {} files from vault '{}' ({})",
+            "Imported".green(),
+            planned.len(),
+            m.vault_name,
+            m.commit.as_deref().map(short_hash).unwrap_or("no commits")
+        ),
+        None => println!("{} {} files", "Imported".green(), planned.len()),
+    }
+    println!("  Run 'gnu commit \"Import ...\"' to record the import");
+
+    Ok(())
+}
+
+/// Files in a bundle as (vault-relative path, content) pairs
+type BundleFiles = Vec<(PathBuf, Vec<u8>)>;
+
+/// Read a JSON or tar.gz bundle
+fn read_bundle(file: &Path) -> Result<(Option<ExportMetadata>, BundleFiles)> {
+    let mut head = [0u8; 2];
+    let is_gzip = File::open(file)?.read(&mut head)? == 2 && head == [0x1f, 0x8b];
+
+    if !is_gzip {
+        let content = fs::read_to_string(file)?;
+        let bundle: ExportBundle = serde_json::from_str(&content)?;
+        let files = bundle.files
+            .into_iter()
+            .map(|f| Ok((f.path.clone(), f.into_bytes()?)))
+            .collect::<Result<_>>()?;
+        return Ok((Some(bundle.metadata), files));
+    }
+
+    let mut metadata = None;
+    let mut files = Vec::new();
+    let mut archive = Archive::new(GzDecoder::new(File::open(file)?));
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let path = entry.path()?.to_path_buf();
+        let mut content = Vec::new();
+        entry.read_to_end(&mut content)?;
+
+        if path == Path::new(ExportMetadata::TAR_ENTRY) {
+            metadata = Some(serde_json::from_slice(&content)?);
+        } else {
+            files.push((path, content));
+        }
+    }
+
+    Ok((metadata, files))
+}
+
+/// `domains/<name>/rest` -> `domains/imported-<name>/rest` (or `domains/<into>/rest`)
+fn remap_path(path: &Path, into: Option<&str>) -> Option<PathBuf> {
+    let components: Vec<_> = path.components().collect();
+    if components.iter().any(|c| !matches!(c, Component::Normal(_))) {
+        return None;
+    }
+    if components.len() < 3 || components[0].as_os_str() != "domains" {
+        return None;
+    }
+
+    let domain = match into {
+        Some(target) => target.to_string(),
+        None => format!("imported-{}", components[1].as_os_str().to_string_lossy()),
+    };
+
+    let mut dest = PathBuf::from("domains").join(domain);
+    for component in &components[2..] {
+        dest.push(component);
+    }
+    Some(dest)
+}
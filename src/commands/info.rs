@@ -0,0 +1,93 @@
+use crate::errors::*;
+use crate::models::Head;
+use crate::storage::Storage;
+use crate::utils::*;
+use colored::Colorize;
+use std::collections::HashSet;
+
+/// Print where the vault is and how it is set up: name, root, branch, HEAD,
+/// commit and domain counts, and the config most commands depend on. Only
+/// reads config, refs and commit logs, so it is cheap to run anywhere.
+pub fn info(json: bool) -> Result<()> {
+    let vault_root = find_vault_root()?;
+    let storage = Storage::new(vault_root.clone());
+    let config = storage.load_config()?;
+
+    let head = storage.read_head()?;
+    let head_hash = match head {
+        Head::Branch(ref branch) => storage.read_branch_ref(branch)?,
+        Head::Detached(ref hash) => Some(hash.clone()),
+    };
+
+    // Distinct commits across every log, including deleted branches'
+    let mut commits = HashSet::new();
+    for log in storage.list_commit_logs()? {
+        commits.extend(storage.read_commits(&log)?.into_iter().map(|c| c.hash));
+    }
+
+    let domains_dir = storage.domains_dir();
+    let mut domains = 0;
+    if domains_dir.exists() {
+        for entry in std::fs::read_dir(&domains_dir)? {
+            let entry = entry?;
+            let name = entry.file_name();
+            if entry.file_type()?.is_dir() && !is_hidden(&name) && !name.to_string_lossy().starts_with('_') {
+                domains += 1;
+            }
+        }
+    }
+
+    if json {
+        let report = serde_json::json!({
+            "vault_name": config.core.vault_name,
+            "root": vault_root,
+            "branch": head.branch(),
+            "head": head_hash,
+            "detached": !matches!(head, Head::Branch(_)),
+            "total_commits": commits.len(),
+            "domains": domains,
+            "created_at": config.core.created_at,
+            "max_tokens": config.context.max_tokens,
+            "default_author": config.agent.default_author,
+            "read_only": config.core.read_only,
+        });
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+
+    println!("{}", config.core.vault_name.bold());
+    println!("Root:           {}", vault_root.display());
+    match head {
+        Head::Branch(ref branch) => println!("Branch:         {}", branch.green()),
+        Head::Detached(_) => println!("Branch:         {}", "detached HEAD".yellow()),
+    }
+    match head_hash {
+        Some(ref hash) => println!("HEAD:           {}", hash.yellow()),
+        None => println!("HEAD:           {}", "no commits yet".dimmed()),
+    }
+    println!("Commits:        {}", commits.len());
+    println!("Domains:        {}", domains);
+    println!("Created:        {}", config.core.created_at.format("%Y-%m-%d %H:%M"));
+    println!("Max tokens:     {}", config.context.max_tokens);
+    println!("Default author: {}", config.agent.default_author);
+    if config.core.read_only {
+        println!("{}", "Read-only: changes are refused (core.read_only)".yellow());
+    }
+
+    Ok(())
+}
+
+/// Whether the current directory (or `--vault`/GITNU_DIR) is inside a vault,
+/// printing nothing unless `print` asks for the vault root. Meant for
+/// scripts, which can branch on the exit status instead of parsing errors.
+pub fn is_vault(print: bool) -> bool {
+    match find_vault_root() {
+        Ok(root) => {
+            if print {
+                println!("{}", root.display());
+            }
+            true
+        }
+        Err(_) => false,
+    }
+}
@@ -3,7 +3,7 @@ use crate::models::*;
 use crate::storage::Storage;
 use crate::utils::*;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use colored::Colorize;
 use chrono::Utc;
 
@@ -139,6 +139,10 @@ gnu checkout main
 # Merge if successful, or leave branch if abandoned
 ```
 
+## Handling Failures
+
+`gnu` exits 2 when there is no vault, 3 when uncommitted changes would be lost, 4 on a merge conflict (or leftover conflict markers at commit) and 5 when a branch, commit or file isn't found. Check the exit status instead of parsing error text.
+
 ## Using the gitnu Skill
 
 For detailed command reference and workflows, use the gitnu skill:
@@ -1116,8 +1120,144 @@ const TODOS_MD_TEMPLATE: &str = r#"# Tasks and TODOs
 - [x] Initial project setup
 "#;
 
-pub fn init(name: Option<String>) -> Result<()> {
-    let current_dir = std::env::current_dir()?;
+const SOURCES_MD_TEMPLATE: &str = r#"# Sources
+
+Papers, articles, datasets, and conversations this research draws on.
+
+---
+
+[Sources will be appended here]
+"#;
+
+const HYPOTHESES_MD_TEMPLATE: &str = r#"# Hypotheses
+
+## Open
+- [ ] 
+
+## Supported
+- 
+
+## Rejected
+- 
+"#;
+
+/// A built-in set of files scaffolded into the project domain
+struct DomainTemplate {
+    name: &'static str,
+    description: &'static str,
+    files: &'static [(&'static str, &'static str)],
+}
+
+const BUILTIN_TEMPLATES: &[DomainTemplate] = &[
+    DomainTemplate {
+        name: "default",
+        description: "Software project: spec, learnings, decisions, todos",
+        files: &[
+            ("spec.md", SPEC_MD_TEMPLATE),
+            ("learnings.md", LEARNINGS_MD_TEMPLATE),
+            ("decisions.md", DECISIONS_MD_TEMPLATE),
+            ("todos.md", TODOS_MD_TEMPLATE),
+        ],
+    },
+    DomainTemplate {
+        name: "research",
+        description: "Research notebook: sources, hypotheses, learnings, todos",
+        files: &[
+            ("sources.md", SOURCES_MD_TEMPLATE),
+            ("hypotheses.md", HYPOTHESES_MD_TEMPLATE),
+            ("learnings.md", LEARNINGS_MD_TEMPLATE),
+            ("todos.md", TODOS_MD_TEMPLATE),
+        ],
+    },
+];
+
+/// Where user-defined templates live (~/.config/gitnu/templates)
+fn user_templates_dir() -> Option<PathBuf> {
+    if let Ok(xdg) = std::env::var("XDG_CONFIG_HOME") {
+        if !xdg.is_empty() {
+            return Some(PathBuf::from(xdg).join("gitnu/templates"));
+        }
+    }
+    std::env::var("HOME")
+        .ok()
+        .map(|home| PathBuf::from(home).join(".config/gitnu/templates"))
+}
+
+/// Template source selected with `--template`
+enum TemplateSource {
+    Builtin(&'static DomainTemplate),
+    User(PathBuf),
+}
+
+/// Resolve a template name; user templates shadow built-ins of the same name
+fn find_template(name: &str) -> Result<TemplateSource> {
+    if let Some(dir) = user_templates_dir() {
+        let user_dir = dir.join(name);
+        if user_dir.is_dir() {
+            return Ok(TemplateSource::User(user_dir));
+        }
+    }
+
+    BUILTIN_TEMPLATES
+        .iter()
+        .find(|t| t.name == name)
+        .map(TemplateSource::Builtin)
+        .ok_or_else(|| GitnuError::Other(format!(
+            "Unknown template '{}'\n  Run 'gnu init --list-templates' to see available templates",
+            name
+        )))
+}
+
+pub fn list_templates() -> Result<()> {
+    println!("{}", "Built-in templates:".bold());
+    for template in BUILTIN_TEMPLATES {
+        println!("  {:<12} {}", template.name.green(), template.description.dimmed());
+    }
+
+    if let Some(dir) = user_templates_dir() {
+        let mut user_templates = Vec::new();
+        if dir.is_dir() {
+            for entry in fs::read_dir(&dir)? {
+                let entry = entry?;
+                if entry.file_type()?.is_dir() {
+                    user_templates.push(entry.file_name().to_string_lossy().to_string());
+                }
+            }
+        }
+        user_templates.sort();
+
+        println!();
+        println!("{} ({})", "User templates:".bold(), dir.display());
+        if user_templates.is_empty() {
+            println!("  {}", "None".dimmed());
+        }
+        for name in user_templates {
+            println!("  {}", name.green());
+        }
+    }
+
+    Ok(())
+}
+
+/// Create a vault in the current directory. `bare` skips the agent/IDE
+/// scaffolding (skill, AGENTS.md, editor configs); `no_project_domain` skips
+/// `domains/<name>/`.
+pub fn init(
+    name: Option<String>,
+    template: &str,
+    reinit: bool,
+    bare: bool,
+    no_project_domain: bool,
+) -> Result<()> {
+    let current_dir = match vault_override() {
+        Some(root) => fs::canonicalize(&root)
+            .map_err(|_| GitnuError::FileNotFound(root))?,
+        None => std::env::current_dir()?,
+    };
+    if reinit && vault_exists(&current_dir) {
+        return reinit_scaffolding(&current_dir);
+    }
+    let template = find_template(template)?;
     let vault_name = name.unwrap_or_else(|| {
         current_dir
             .file_name()
@@ -1148,26 +1288,29 @@ pub fn init(name: Option<String>) -> Result<()> {
     let conventions_md = global_dir.join("conventions.md");
     fs::write(&conventions_md, CONVENTIONS_MD_TEMPLATE)?;
 
-    // Create skills directory
-    let skills_dir = global_dir.join("skills");
-    ensure_dir(&skills_dir)?;
-
     // Create project domain if name provided
-    if vault_name != "unnamed" {
-        create_project_domain(&current_dir, &vault_name)?;
+    let project_domain = vault_name != "unnamed" && !no_project_domain;
+    if project_domain {
+        create_project_domain(&current_dir, &vault_name, &template)?;
     }
 
-    // Create .claude/skills/gitnu/ with references
-    create_claude_skill(&current_dir)?;
+    if !bare {
+        // Create skills directory
+        let skills_dir = global_dir.join("skills");
+        ensure_dir(&skills_dir)?;
 
-    // Create AGENTS.md (cross-platform system prompt)
-    create_agents_md(&current_dir)?;
+        // Create .claude/skills/gitnu/ with references
+        create_claude_skill(&current_dir)?;
 
-    // Create .claude/config.json
-    create_claude_config(&current_dir)?;
+        // Create AGENTS.md (cross-platform system prompt)
+        create_agents_md(&current_dir)?;
 
-    // Create opencode.json (optional but recommended)
-    create_opencode_config(&current_dir)?;
+        // Create .claude/config.json
+        create_claude_config(&current_dir)?;
+
+        // Create opencode.json (optional but recommended)
+        create_opencode_config(&current_dir)?;
+    }
 
     // Create initial commit
     create_initial_commit(&storage, &vault_name)?;
@@ -1181,32 +1324,109 @@ pub fn init(name: Option<String>) -> Result<()> {
     println!("  {}", ".gitnu/".dimmed());
     println!("  {}", "domains/_global/agent.md".dimmed());
     println!("  {}", "domains/_global/conventions.md".dimmed());
-    if vault_name != "unnamed" {
+    if project_domain {
         println!("  {}", format!("domains/{}/", vault_name).dimmed());
     }
-    println!("  {}", ".claude/skills/gitnu/SKILL.md".dimmed());
-    println!("  {}", ".claude/skills/gitnu/references/".dimmed());
-    println!("  {}", "AGENTS.md".dimmed());
-    println!("  {}", ".claude/config.json".dimmed());
-    println!("  {}", "opencode.json".dimmed());
-    println!();
-    println!("{}", "Cross-platform compatible with:".bold());
-    println!("  {} Claude Code, OpenCode, Cursor, VS Code, Zed", "✓".green());
+    if !bare {
+        println!("  {}", ".claude/skills/gitnu/SKILL.md".dimmed());
+        println!("  {}", ".claude/skills/gitnu/references/".dimmed());
+        println!("  {}", "AGENTS.md".dimmed());
+        println!("  {}", ".claude/config.json".dimmed());
+        println!("  {}", "opencode.json".dimmed());
+        println!();
+        println!("{}", "Cross-platform compatible with:".bold());
+        println!("  {} Claude Code, OpenCode, Cursor, VS Code, Zed", "✓".green());
+    }
     println!();
     println!("{} Run 'gnu status' to see current state.", "✓".green().bold());
 
     Ok(())
 }
 
-fn create_project_domain(vault_root: &Path, project_name: &str) -> Result<()> {
+/// Regenerate missing tool scaffolding in an existing vault.
+/// Never touches commits, domains, config, or files that already exist.
+fn reinit_scaffolding(vault_root: &Path) -> Result<()> {
+    let mut created = Vec::new();
+    let mut present = Vec::new();
+
+    let skill_dir = vault_root.join(".claude/skills/gitnu");
+    let references_dir = skill_dir.join("references");
+    ensure_dir(&references_dir)?;
+
+    let scaffolds = [
+        (skill_dir.join("SKILL.md"), SKILL_TEMPLATE),
+        (references_dir.join("commands.md"), COMMANDS_REFERENCE),
+        (references_dir.join("workflows.md"), WORKFLOWS_REFERENCE),
+        (references_dir.join("examples.md"), EXAMPLES_REFERENCE),
+        (references_dir.join("troubleshooting.md"), TROUBLESHOOTING_REFERENCE),
+        (vault_root.join(".claude/config.json"), CLAUDE_CONFIG_TEMPLATE),
+        (vault_root.join("opencode.json"), OPENCODE_CONFIG_TEMPLATE),
+    ];
+
+    for (path, content) in &scaffolds {
+        let rel_path = relative_path(vault_root, path);
+        if path.exists() {
+            present.push(rel_path);
+        } else {
+            fs::write(path, content)?;
+            created.push(rel_path);
+        }
+    }
+
+    // AGENTS.md may be user-owned; only add the gitnu section if it's missing
+    let agents_md = vault_root.join("AGENTS.md");
+    let has_gitnu_section = agents_md.exists() && {
+        let existing = fs::read_to_string(&agents_md)?;
+        existing.contains("gitnu") || existing.contains("gnu summary")
+    };
+    if has_gitnu_section {
+        present.push("AGENTS.md".into());
+    } else {
+        create_agents_md(vault_root)?;
+        created.push("AGENTS.md".into());
+    }
+
+    println!("{}", "Reinitialized gitnu scaffolding".green().bold());
+    println!("  Vault root: {}", vault_root.display());
+    println!();
+    println!("{}", "Created:".bold());
+    if created.is_empty() {
+        println!("  {}", "Nothing (all scaffolding present)".dimmed());
+    }
+    for path in &created {
+        println!("  {}", path.display().to_string().green());
+    }
+    println!("{}", "Already present:".bold());
+    for path in &present {
+        println!("  {}", path.display().to_string().dimmed());
+    }
+
+    Ok(())
+}
+
+fn create_project_domain(vault_root: &Path, project_name: &str, template: &TemplateSource) -> Result<()> {
     let project_dir = vault_root.join("domains").join(project_name);
     ensure_dir(&project_dir)?;
 
     // Create template files
-    fs::write(project_dir.join("spec.md"), SPEC_MD_TEMPLATE)?;
-    fs::write(project_dir.join("learnings.md"), LEARNINGS_MD_TEMPLATE)?;
-    fs::write(project_dir.join("decisions.md"), DECISIONS_MD_TEMPLATE)?;
-    fs::write(project_dir.join("todos.md"), TODOS_MD_TEMPLATE)?;
+    match template {
+        TemplateSource::Builtin(t) => {
+            for (file_name, content) in t.files {
+                fs::write(project_dir.join(file_name), content)?;
+            }
+        }
+        TemplateSource::User(dir) => {
+            for entry in walkdir::WalkDir::new(dir).into_iter().filter_map(|e| e.ok()) {
+                if entry.file_type().is_file() {
+                    let dest = project_dir.join(relative_path(dir, entry.path()));
+                    if let Some(parent) = dest.parent() {
+                        ensure_dir(parent)?;
+                    }
+                    fs::copy(entry.path(), dest)?;
+                }
+            }
+        }
+    }
 
     Ok(())
 }
@@ -1345,38 +1565,42 @@ fn create_initial_commit(storage: &Storage, vault_name: &str) -> Result<()> {
     use crate::context::ContextManager;
     
     let context_mgr = ContextManager::new(Storage::new(storage.vault_root.clone()));
-    let summary = context_mgr.calculate_context_summary(None)?;
+    let summary = context_mgr.calculate_commit_summary(None)?;
+
+    let snapshot = storage.write_tree()?;
 
     // Create commit object
     let mut commit_data = Vec::new();
     commit_data.extend_from_slice(b"tree ");
+    commit_data.extend_from_slice(snapshot.tree.as_bytes());
+    commit_data.extend_from_slice(b"\n");
+    commit_data.extend_from_slice(b"vault ");
     commit_data.extend_from_slice(vault_name.as_bytes());
     commit_data.extend_from_slice(b"\n");
     commit_data.extend_from_slice(Utc::now().to_rfc3339().as_bytes());
     
     let hash = compute_hash(&commit_data);
-    let short_hash = &hash[..7];
+    let short_hash = short_hash(&hash);
 
-    // Create snapshot
-    let snapshot_path = storage.create_snapshot(&hash)?;
+    let snapshot_path = storage.record_snapshot(&hash, &snapshot)?;
 
     let commit = Commit {
         hash: hash.clone(),
         parent: None,
+        merge_parent: None,
         timestamp: Utc::now(),
-        author: Author::Human {
-            name: "user".to_string(),
-        },
+        author: storage.load_config()?.user.author(),
         message: "Initial commit".to_string(),
         context_summary: summary,
         snapshot_path: relative_path(&storage.vault_root, &snapshot_path),
+        index: Some(storage.load_index()?),
     };
 
     // Write to commit log
     storage.append_commit("main", &commit)?;
     
     // Update main branch ref
-    storage.write_branch_ref("main", &hash)?;
+    storage.update_branch_ref("main", &hash, "commit (initial): Initial commit")?;
 
     println!("{}", format!("[main {}] Initial commit", short_hash).dimmed());
 
@@ -1,110 +1,228 @@
 use crate::errors::*;
+use crate::models::{Index, LoadOutcome};
 use crate::storage::Storage;
+use crate::context::ContextManager;
+use crate::commands::status::budget_colored;
 use crate::utils::*;
 use crate::wikilink::resolve_wikilink;
 use colored::Colorize;
+use std::collections::HashSet;
+use std::path::PathBuf;
 
 pub fn load(path_or_link: &str, pin: bool, list: bool) -> Result<()> {
     let vault_root = find_vault_root()?;
-    let storage = Storage::new(vault_root.clone());
-    let mut index = storage.load_index()?;
+    let storage = Storage::new(vault_root);
 
     if list {
-        // Show what's currently loaded
-        println!("{}", "Currently loaded:".bold());
-        if index.loaded.is_empty() {
-            println!("  {}", "Nothing loaded".dimmed());
-        } else {
-            for path in &index.loaded {
-                let is_pinned = index.pinned.contains(path);
-                let marker = if is_pinned { "[pinned]" } else { "" };
-                println!("  - {} {}", path.display(), marker.yellow());
-            }
-        }
+        print_index(&storage, &storage.load_index()?)?;
         return Ok(());
     }
 
-    // Resolve path (could be wikilink)
-    let path = if path_or_link.starts_with("[[") {
-        resolve_wikilink(&vault_root, path_or_link)?
+    let outcomes = load_path(&storage, path_or_link, pin)?;
+
+    for outcome in &outcomes {
+        println!(
+            "{} {} (+{} tokens)",
+            "Loaded:".green(),
+            outcome.path.display(),
+            outcome.tokens
+        );
+    }
+    if outcomes.len() > 1 {
+        let total: usize = outcomes.iter().map(|o| o.tokens).sum();
+        println!("  {} (+{} tokens)", plural(outcomes.len(), "file"), total);
+    }
+
+    if pin {
+        println!("  {}", "Pinned (will always be included)".yellow());
+    }
+
+    Ok(())
+}
+
+/// Add a file or directory (path or wikilink) to the loaded set, optionally
+/// pinning it, and report how many tokens it contributes. A glob such as
+/// `domains/**/decisions.md` loads every matching file.
+pub fn load_path(storage: &Storage, path_or_link: &str, pin: bool) -> Result<Vec<LoadOutcome>> {
+    let vault_root = &storage.vault_root;
+    let _lock = storage.lock()?;
+    let mut index = storage.load_index()?;
+
+    let paths = if is_glob_pattern(path_or_link) {
+        let context_mgr = ContextManager::new(Storage::new(vault_root.clone()));
+        let matches = context_mgr.expand_patterns(&[path_or_link.to_string()])?;
+        if matches.is_empty() {
+            return Err(GitnuError::Other(format!("No files match '{}'", path_or_link)));
+        }
+        matches.into_iter().map(|rel| vault_root.join(rel)).collect()
     } else {
-        vault_root.join(path_or_link)
+        // Resolve path (could be wikilink)
+        let path = if path_or_link.starts_with("[[") {
+            resolve_wikilink(vault_root, path_or_link)?
+        } else {
+            resolve_path_arg(vault_root, path_or_link)
+        };
+
+        if !path.exists() {
+            return Err(GitnuError::FileNotFound(path));
+        }
+        // Agents pass paths over MCP too; never read outside the vault
+        vec![ensure_in_vault(vault_root, &path)?]
     };
 
-    if !path.exists() {
-        return Err(GitnuError::FileNotFound(path));
-    }
+    let mut outcomes = Vec::new();
+    for path in paths {
+        let rel_path = relative_path(vault_root, &path);
 
-    let rel_path = relative_path(&vault_root, &path);
+        // Add to loaded
+        if !index.loaded.contains(&rel_path) {
+            index.loaded.push(rel_path.clone());
+        }
 
-    // Add to loaded
-    if !index.loaded.contains(&rel_path) {
-        index.loaded.push(rel_path.clone());
-    }
+        // Add to pinned if requested
+        if pin && !index.pinned.contains(&rel_path) {
+            index.pinned.push(rel_path.clone());
+        }
 
-    // Add to pinned if requested
-    if pin && !index.pinned.contains(&rel_path) {
-        index.pinned.push(rel_path.clone());
+        outcomes.push(LoadOutcome {
+            tokens: estimate_tokens(&read_for_tokens(storage, &path)?),
+            path: rel_path,
+            pinned: pin,
+        });
     }
 
     storage.save_index(&index)?;
 
-    // Calculate tokens
-    let content = if path.is_file() {
-        std::fs::read_to_string(&path)?
+    Ok(outcomes)
+}
+
+/// Text of a file, or of every file in a directory, for token estimates.
+/// Binary files contribute nothing.
+pub(crate) fn read_for_tokens(storage: &Storage, path: &std::path::Path) -> Result<String> {
+    let files = if path.is_file() {
+        vec![path.to_path_buf()]
     } else {
-        // Load all files in directory
-        let mut total = String::new();
-        for entry in walkdir::WalkDir::new(&path).into_iter().filter_map(|e| e.ok()) {
-            if entry.file_type().is_file() {
-                if let Ok(content) = std::fs::read_to_string(entry.path()) {
-                    total.push_str(&content);
-                    total.push('\n');
-                }
-            }
-        }
-        total
+        storage.walk_files(path)?
     };
 
-    let tokens = estimate_tokens(&content);
+    let mut total = String::new();
+    for file in files {
+        let content = std::fs::read(&file)?;
+        if !is_binary(&content) {
+            total.push_str(&String::from_utf8_lossy(&content));
+            total.push('\n');
+        }
+    }
+    Ok(total)
+}
 
-    println!(
-        "{} {} (+{} tokens)",
-        "Loaded:".green(),
-        rel_path.display(),
-        tokens
-    );
+/// Print loaded, excluded, and staged entries of the index, with the token
+/// cost of each loaded or pinned entry against `context.max_tokens`
+fn print_index(storage: &Storage, index: &Index) -> Result<()> {
+    let config = storage.load_config()?;
+    let entries: Vec<_> = index.loaded.iter()
+        .chain(index.pinned.iter().filter(|p| !index.loaded.contains(p)))
+        .collect();
+
+    println!("{}", "Currently loaded:".bold());
+    if entries.is_empty() {
+        println!("  {}", "Nothing loaded".dimmed());
+    } else {
+        let width = entries.iter().map(|p| p.display().to_string().len()).max().unwrap_or(0);
+        let mut total = 0;
+        // Files an earlier entry already brings in (a directory and a file inside it)
+        let mut counted: HashSet<PathBuf> = HashSet::new();
+        for path in &entries {
+            let marker = if index.pinned.contains(path) { "[pinned]" } else { "" };
+            let full_path = storage.vault_root.join(path);
+            let tokens = if full_path.exists() {
+                let files = if full_path.is_file() { vec![full_path.clone()] } else { storage.walk_files(&full_path)? };
+                let mut tokens = 0;
+                for file in files {
+                    let file_tokens = estimate_tokens(&read_for_tokens(storage, &file)?);
+                    tokens += file_tokens;
+                    if counted.insert(file) {
+                        total += file_tokens;
+                    }
+                }
+                format!("~{} tokens", tokens)
+            } else {
+                "missing".to_string()
+            };
+            println!(
+                "  - {:<width$}  {} {}",
+                path.display().to_string(),
+                tokens.dimmed(),
+                marker.yellow(),
+                width = width
+            );
+        }
+        println!(
+            "  Total: ~{} of {} tokens",
+            budget_colored(total, config.context.max_tokens),
+            config.context.max_tokens
+        );
+    }
 
-    if pin {
-        println!("  {}", "Pinned (will always be included)".yellow());
+    if !index.excluded.is_empty() {
+        println!("{}", "Excluded:".bold());
+        for path in &index.excluded {
+            println!("  - {}", path.display().to_string().red());
+        }
+    }
+
+    if !index.staged.is_empty() {
+        println!("{}", "Staged:".bold());
+        for staged in &index.staged {
+            println!(
+                "  - {} [{}] \"{}\"",
+                staged.path.display(),
+                staged.priority.display().yellow(),
+                staged.reason.dimmed()
+            );
+        }
     }
 
     Ok(())
 }
 
-pub fn unload(path_or_link: Option<String>, all: bool) -> Result<()> {
+pub fn unload(path_or_link: Option<String>, all: bool, list: bool) -> Result<()> {
     let vault_root = find_vault_root()?;
     let storage = Storage::new(vault_root.clone());
+
+    if list {
+        print_index(&storage, &storage.load_index()?)?;
+        return Ok(());
+    }
+
+    let _lock = storage.lock()?;
     let mut index = storage.load_index()?;
 
     if all {
         // Unload everything except pinned
         let pinned_set: std::collections::HashSet<_> = index.pinned.iter().collect();
+        let before = index.loaded.len();
         index.loaded.retain(|p| pinned_set.contains(p));
+        let kept = index.loaded.len();
         storage.save_index(&index)?;
-        println!("{}", "Unloaded all non-pinned files".green());
+        println!(
+            "{} {} files ({} kept because pinned)",
+            "Unloaded".green(),
+            before - kept,
+            kept
+        );
         return Ok(());
     }
 
     let path_str = path_or_link.ok_or_else(|| {
-        GitnuError::Other("Must specify path or use --all".to_string())
+        GitnuError::Other("Must specify path or use --all/--list".to_string())
     })?;
 
     // Resolve path
     let path = if path_str.starts_with("[[") {
         resolve_wikilink(&vault_root, &path_str)?
     } else {
-        vault_root.join(&path_str)
+        resolve_path_arg(&vault_root, &path_str)
     };
 
     let rel_path = relative_path(&vault_root, &path);
@@ -119,16 +237,43 @@ pub fn unload(path_or_link: Option<String>, all: bool) -> Result<()> {
     Ok(())
 }
 
+/// Pin a path or wikilink into the index, or a glob pattern into config.
+///
+/// Concrete paths land in `Index.pinned`/`Index.excluded`; patterns containing
+/// `*` land in `pins.always_load`/`pins.never_load` and are expanded when
+/// context is rendered.
 pub fn pin(path_or_link: &str, exclude: bool) -> Result<()> {
     let vault_root = find_vault_root()?;
     let storage = Storage::new(vault_root.clone());
+    let _lock = storage.lock()?;
     let mut index = storage.load_index()?;
 
+    if is_glob_pattern(path_or_link) {
+        compile_patterns(&[path_or_link.to_string()])?;
+        let mut config = storage.load_config()?;
+        let patterns = if exclude {
+            &mut config.pins.never_load
+        } else {
+            &mut config.pins.always_load
+        };
+        if !patterns.iter().any(|p| p == path_or_link) {
+            patterns.push(path_or_link.to_string());
+        }
+        storage.save_config(&config)?;
+
+        if exclude {
+            println!("{} {} (pattern in pins.never_load)", "Excluded:".red(), path_or_link);
+        } else {
+            println!("{} {} (pattern in pins.always_load)", "Pinned:".green(), path_or_link);
+        }
+        return Ok(());
+    }
+
     // Resolve path
     let path = if path_or_link.starts_with("[[") {
         resolve_wikilink(&vault_root, path_or_link)?
     } else {
-        vault_root.join(path_or_link)
+        resolve_path_arg(&vault_root, path_or_link)
     };
 
     let rel_path = relative_path(&vault_root, &path);
@@ -156,10 +301,21 @@ pub fn pin(path_or_link: &str, exclude: bool) -> Result<()> {
     Ok(())
 }
 
+/// Unpin from both the index and the config patterns
 pub fn unpin(path_or_link: &str) -> Result<()> {
     let vault_root = find_vault_root()?;
     let storage = Storage::new(vault_root.clone());
+    let _lock = storage.lock()?;
     let mut index = storage.load_index()?;
+    let mut config = storage.load_config()?;
+
+    if is_glob_pattern(path_or_link) {
+        config.pins.always_load.retain(|p| p != path_or_link);
+        config.pins.never_load.retain(|p| p != path_or_link);
+        storage.save_config(&config)?;
+        println!("{} {}", "Unpinned:".yellow(), path_or_link);
+        return Ok(());
+    }
 
     // Resolve path
     let path = if path_or_link.starts_with("[[") {
@@ -176,6 +332,12 @@ pub fn unpin(path_or_link: &str) -> Result<()> {
     
     storage.save_index(&index)?;
 
+    // Remove matching literal entries from config patterns
+    let rel_str = rel_path.to_string_lossy();
+    config.pins.always_load.retain(|p| p.as_str() != rel_str);
+    config.pins.never_load.retain(|p| p.as_str() != rel_str);
+    storage.save_config(&config)?;
+
     println!("{} {}", "Unpinned:".yellow(), rel_path.display());
 
     Ok(())
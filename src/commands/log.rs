@@ -1,64 +1,127 @@
 use crate::errors::*;
+use crate::models::{AuthorStats, Commit, Head, LogReport};
 use crate::storage::Storage;
+use crate::commands::annotate::print_notes;
 use crate::utils::*;
+use crate::wikilink::resolve_wikilink;
 use colored::Colorize;
+use regex::Regex;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::PathBuf;
 
-pub fn log(oneline: bool, limit: Option<usize>, branch: Option<String>) -> Result<()> {
-    let vault_root = find_vault_root()?;
-    let storage = Storage::new(vault_root);
+/// Options controlling which commits `gnu log` shows and how
+#[derive(Debug, Clone, Default)]
+pub struct LogOptions {
+    pub oneline: bool,
+    pub limit: Option<usize>,
+    pub branch: Option<String>,
+    /// Author filter: "human", "agent", or a specific name/model
+    pub author: Option<String>,
+    /// Regex matched against the commit message
+    pub grep: Option<String>,
+    /// Only commits on or after this date (YYYY-MM-DD)
+    pub since: Option<String>,
+    /// Only commits on or before this date (YYYY-MM-DD)
+    pub until: Option<String>,
+    /// Only commits that changed this file (path or wikilink)
+    pub path: Option<String>,
+    /// Show oldest commits first
+    pub reverse: bool,
+    /// Print commit and file-change totals per author after the listing
+    pub shortstat: bool,
+    /// List changed files: basenames after each oneline entry, full paths otherwise
+    pub files: bool,
+    /// Show notes and tags attached with `gnu annotate`
+    pub notes: bool,
+    /// Only commits carrying this `gnu annotate` tag
+    pub tagged: Option<String>,
+    /// Interleave the commits of every branch, labelled by branch
+    pub branches: bool,
+    /// With `branches`, also include logs left by deleted branches
+    pub all: bool,
+    /// Render each commit through a template of `%` placeholders (see `format_commit`)
+    pub format: Option<String>,
+}
 
-    // Get branch to query
-    let branch_name = match branch {
-        Some(b) => b,
-        None => storage.read_head()?,
-    };
+/// How many file names `--oneline --files` shows before summarizing the rest
+const ONELINE_FILES_SHOWN: usize = 3;
 
-    // Get commits
-    let mut commits = storage.read_commits(&branch_name)?;
-    commits.reverse(); // Show newest first
+pub fn log(opts: LogOptions) -> Result<()> {
+    let vault_root = find_vault_root()?;
+    let storage = Storage::new(vault_root);
+    let report = log_report(&storage, &opts)?;
 
-    if commits.is_empty() {
+    if report.total_commits == 0 {
         println!("{}", "No commits yet".dimmed());
         return Ok(());
     }
+    if report.commits.is_empty() {
+        println!("{}", "No matching commits".dimmed());
+        return Ok(());
+    }
+
+    let head = storage.read_head()?;
+    let oneline = opts.oneline;
+
+    let commits_to_show = &report.commits;
 
-    // Apply limit
-    let commits_to_show = if let Some(lim) = limit {
-        &commits[..commits.len().min(lim)]
-    } else {
-        &commits
+    let marker_for = |head: &Head| match head {
+        Head::Branch(name) => format!(" (HEAD -> {})", name).yellow().to_string(),
+        Head::Detached(_) => " (HEAD)".yellow().to_string(),
     };
 
     // Get current HEAD to mark it
-    let head_commit = storage.get_head_commit()?;
-    let head_hash = head_commit.as_ref().map(|c| c.hash.as_str());
+    let head_hash = report.head.as_deref();
+    let label_width = report.branches.values().map(|b| b.len()).max().unwrap_or(0);
 
     for commit in commits_to_show {
-        let short_hash = &commit.hash[..7];
-        
-        if oneline {
+        let short_hash = short_hash(&commit.hash);
+
+        if let Some(ref format) = opts.format {
+            println!("{}", format_commit(format, commit));
+            if opts.notes {
+                print_notes(&storage.read_notes(&commit.hash)?);
+            }
+        } else if oneline {
             // One-line format
             let head_marker = if Some(commit.hash.as_str()) == head_hash {
-                format!(" (HEAD -> {})", branch_name).yellow().to_string()
+                marker_for(&head)
             } else {
                 String::new()
             };
             
+            let files = if opts.files {
+                oneline_files(commit)
+            } else {
+                String::new()
+            };
+            let label = match report.branches.get(&commit.hash) {
+                Some(branch) => format!(" {:<width$}", branch, width = label_width).cyan().to_string(),
+                None => String::new(),
+            };
             println!(
-                "{}{} {}",
+                "{}{}{} {}{}",
                 short_hash.yellow(),
+                label,
                 head_marker,
-                commit.message
+                commit.subject(),
+                files
             );
+            if opts.notes {
+                print_notes(&storage.read_notes(&commit.hash)?);
+            }
         } else {
             // Full format
             let head_marker = if Some(commit.hash.as_str()) == head_hash {
-                format!(" (HEAD -> {})", branch_name).yellow().to_string()
+                marker_for(&head)
             } else {
                 String::new()
             };
             
             println!("{} {}{}", "commit".yellow(), short_hash.yellow(), head_marker);
+            if let Some(branch) = report.branches.get(&commit.hash) {
+                println!("{} {}", "Branch:".bold(), branch.cyan());
+            }
             println!("{} {}", "Author:".bold(), commit.author.display());
             println!(
                 "{}   {}",
@@ -66,7 +129,9 @@ pub fn log(oneline: bool, limit: Option<usize>, branch: Option<String>) -> Resul
                 commit.timestamp.format("%a %b %d %H:%M:%S %Y")
             );
             println!();
-            println!("    {}", commit.message);
+            for line in commit.message.lines() {
+                println!("    {}", line);
+            }
             println!();
             println!(
                 "    Context: {} domains loaded, ~{} tokens",
@@ -75,30 +140,272 @@ pub fn log(oneline: bool, limit: Option<usize>, branch: Option<String>) -> Resul
             );
             
             if !commit.context_summary.files_modified.is_empty() {
-                print!("    Modified: ");
-                for (i, file) in commit.context_summary.files_modified.iter().enumerate() {
-                    if i > 0 {
-                        print!(", ");
-                    }
-                    print!("{}", file.file_name().unwrap_or_default().to_string_lossy());
-                }
-                println!();
+                println!("    Modified: {}", file_list(&commit.context_summary.files_modified, opts.files));
             }
-            
+
             if !commit.context_summary.files_added.is_empty() {
-                print!("    Added: ");
-                for (i, file) in commit.context_summary.files_added.iter().enumerate() {
-                    if i > 0 {
-                        print!(", ");
-                    }
-                    print!("{}", file.file_name().unwrap_or_default().to_string_lossy());
-                }
-                println!();
+                println!("    Added: {}", file_list(&commit.context_summary.files_added, opts.files));
             }
-            
+
+            if !commit.context_summary.file_stats.is_empty() {
+                let (insertions, deletions) = commit.context_summary.line_totals();
+                println!("    Lines: {} {}", format!("+{}", insertions).green(), format!("-{}", deletions).red());
+            }
+
+            if opts.notes {
+                print_notes(&storage.read_notes(&commit.hash)?);
+            }
+
             println!();
         }
     }
 
+    if opts.shortstat {
+        if oneline || opts.format.is_some() {
+            println!();
+        }
+        println!("{}", "Contributions:".bold());
+        let stats = AuthorStats::collect(commits_to_show);
+        let width = stats.iter().map(|s| s.author.len()).max().unwrap_or(0);
+        for s in stats {
+            println!(
+                "  {:<width$}  {}, {} changed, {} (+), {} (-)",
+                s.author,
+                plural(s.commits, "commit"),
+                plural(s.files_changed, "file"),
+                plural(s.insertions, "insertion"),
+                plural(s.deletions, "deletion"),
+                width = width
+            );
+        }
+    }
+
     Ok(())
 }
+
+/// Select the commits `gnu log` would list, applying every filter in `opts`
+/// (display flags such as `oneline` are ignored)
+pub fn log_report(storage: &Storage, opts: &LogOptions) -> Result<LogReport> {
+    let vault_root = &storage.vault_root;
+
+    // Get branch to query
+    let head = storage.read_head()?;
+    let branch_name = match opts.branch {
+        Some(ref b) => b.clone(),
+        None => head.name().to_string(),
+    };
+
+    // Get commits (a detached HEAD has no log, so walk its ancestry)
+    let mut branches = BTreeMap::new();
+    let mut commits = match head {
+        _ if opts.branches || opts.all => {
+            let logs = if opts.all { storage.list_commit_logs()? } else { storage.list_branches()? };
+            let mut commits = Vec::new();
+            for log in logs {
+                for commit in storage.read_commits(&log)? {
+                    // Each commit lives in the log of the branch it was made on
+                    if !branches.contains_key(&commit.hash) {
+                        branches.insert(commit.hash.clone(), log.clone());
+                        commits.push(commit);
+                    }
+                }
+            }
+            commits
+        }
+        Head::Detached(ref hash) if branch_name == *hash => {
+            let mut ancestry = storage.ancestry(hash)?;
+            ancestry.reverse();
+            ancestry
+        }
+        _ => storage.read_commits(&branch_name)?,
+    };
+    let total_commits = commits.len();
+
+    // Restrict to commits that touched a single file
+    if let Some(ref path_or_link) = opts.path {
+        let path = if path_or_link.starts_with("[[") {
+            resolve_wikilink(vault_root, path_or_link)?
+        } else {
+            resolve_path_arg(vault_root, path_or_link)
+        };
+        let rel_path = relative_path(vault_root, &path);
+        let touched = commits_touching(storage, &commits, &rel_path)?;
+        commits.retain(|c| touched.contains(&c.hash));
+    }
+
+    // Order by timestamp rather than log position, which rewinds and merges
+    // can disturb; the stable sort keeps append order for equal timestamps
+    commits.sort_by_key(|c| c.timestamp);
+    commits.reverse(); // Show newest first
+
+    // Apply filters
+    let grep = match opts.grep {
+        Some(ref pattern) => Some(Regex::new(pattern).map_err(|e| {
+            GitnuError::Other(format!("Invalid --grep pattern: {}", e))
+        })?),
+        None => None,
+    };
+    let since = opts.since.as_deref().map(parse_date).transpose()?;
+    // --until is inclusive of the whole day
+    let until = opts.until.as_deref().map(parse_date).transpose()?
+        .map(|d| d + chrono::Duration::days(1));
+
+    commits.retain(|commit| {
+        opts.author.as_deref().is_none_or(|a| commit.author.matches(a))
+            && grep.as_ref().is_none_or(|re| re.is_match(&commit.message))
+            && since.is_none_or(|s| commit.timestamp >= s)
+            && until.is_none_or(|u| commit.timestamp < u)
+    });
+
+    if let Some(ref tag) = opts.tagged {
+        let mut tagged = Vec::new();
+        for commit in commits {
+            if storage.read_notes(&commit.hash)?.has_tag(tag) {
+                tagged.push(commit);
+            }
+        }
+        commits = tagged;
+    }
+
+    // Apply limit, then flip so --reverse shows the same commits oldest first
+    if let Some(lim) = opts.limit {
+        commits.truncate(lim);
+    }
+    if opts.reverse {
+        commits.reverse();
+    }
+
+    let shown: HashSet<&str> = commits.iter().map(|c| c.hash.as_str()).collect();
+    branches.retain(|hash, _| shown.contains(hash.as_str()));
+    Ok(LogReport {
+        branch: if opts.branches || opts.all { "all".to_string() } else { branch_name },
+        head: storage.get_head_commit()?.map(|c| c.hash),
+        total_commits,
+        commits,
+        branches,
+    })
+}
+
+/// Comma-separated file names, or vault-relative paths when `full_paths` is set
+fn file_list(files: &[PathBuf], full_paths: bool) -> String {
+    files.iter()
+        .map(|file| if full_paths {
+            file.display().to_string()
+        } else {
+            file.file_name().unwrap_or_default().to_string_lossy().to_string()
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Expand `--format` placeholders for one commit: `%h`/`%H` short and full
+/// hash, `%s` subject, `%an` author name, `%ad` date, `%tr` relative time,
+/// `%t` token estimate, `%d` domains loaded, `%n` newline and `%%` a percent
+/// sign. Anything else is copied through unchanged.
+fn format_commit(format: &str, commit: &Commit) -> String {
+    let mut out = String::new();
+    let mut rest = format;
+    while let Some(at) = rest.find('%') {
+        out.push_str(&rest[..at]);
+        rest = &rest[at..];
+        let (value, len) = if rest.starts_with("%an") {
+            (commit.author.name().to_string(), 3)
+        } else if rest.starts_with("%ad") {
+            (commit.timestamp.format("%a %b %d %H:%M:%S %Y").to_string(), 3)
+        } else if rest.starts_with("%tr") {
+            (relative_time(&commit.timestamp), 3)
+        } else if rest.starts_with("%h") {
+            (short_hash(&commit.hash).to_string(), 2)
+        } else if rest.starts_with("%H") {
+            (commit.hash.clone(), 2)
+        } else if rest.starts_with("%s") {
+            (commit.subject().to_string(), 2)
+        } else if rest.starts_with("%t") {
+            (commit.context_summary.token_estimate.to_string(), 2)
+        } else if rest.starts_with("%d") {
+            (commit.context_summary.domains_loaded.len().to_string(), 2)
+        } else if rest.starts_with("%n") {
+            ("\n".to_string(), 2)
+        } else if rest.starts_with("%%") {
+            ("%".to_string(), 2)
+        } else {
+            ("%".to_string(), 1)
+        };
+        out.push_str(&value);
+        rest = &rest[len..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Basenames of the files a commit modified or added, e.g. " [a.md, b.md +2 more]"
+fn oneline_files(commit: &Commit) -> String {
+    let names: Vec<String> = commit.context_summary.files_modified.iter()
+        .chain(commit.context_summary.files_added.iter())
+        .map(|file| file.file_name().unwrap_or_default().to_string_lossy().to_string())
+        .collect();
+    if names.is_empty() {
+        return String::new();
+    }
+
+    let mut shown = names.iter().take(ONELINE_FILES_SHOWN).cloned().collect::<Vec<_>>().join(", ");
+    if names.len() > ONELINE_FILES_SHOWN {
+        shown.push_str(&format!(" +{} more", names.len() - ONELINE_FILES_SHOWN));
+    }
+    format!(" {}", format!("[{}]", shown).dimmed())
+}
+
+/// Hashes of commits where the file's content hash differs from the one in
+/// the commit's parent; a root commit counts if it contains the file
+fn commits_touching(
+    storage: &Storage,
+    commits: &[crate::models::Commit],
+    rel_path: &std::path::Path,
+) -> Result<HashSet<String>> {
+    // Content hash of the file in a commit, or None when the commit has no
+    // manifest; parents are shared between commits, so cache the lookups
+    let mut file_hashes: HashMap<String, Option<Option<String>>> = HashMap::new();
+    let mut file_hash = |hash: &str| -> Result<Option<Option<String>>> {
+        if let Some(cached) = file_hashes.get(hash) {
+            return Ok(cached.clone());
+        }
+        let found = storage.read_manifest(hash)?.map(|manifest| {
+            manifest.files
+                .into_iter()
+                .find(|f| f.path == rel_path)
+                .map(|f| f.hash)
+        });
+        file_hashes.insert(hash.to_string(), found.clone());
+        Ok(found)
+    };
+
+    let mut touched = HashSet::new();
+    for commit in commits {
+        let current = file_hash(&commit.hash)?;
+        let parent = match commit.parent {
+            Some(ref parent) => file_hash(parent)?,
+            None => Some(None),
+        };
+
+        match (current, parent) {
+            (Some(current), Some(parent)) => {
+                if current != parent {
+                    touched.insert(commit.hash.clone());
+                }
+            }
+            _ => {
+                // No manifest to compare - fall back to the recorded summary
+                let summary = &commit.context_summary;
+                if summary.files_added.iter()
+                    .chain(summary.files_modified.iter())
+                    .chain(summary.files_removed.iter())
+                    .any(|p| p == rel_path)
+                {
+                    touched.insert(commit.hash.clone());
+                }
+            }
+        }
+    }
+
+    Ok(touched)
+}
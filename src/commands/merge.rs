@@ -1,18 +1,38 @@
 use crate::errors::*;
 use crate::storage::Storage;
 use crate::context::ContextManager;
+use crate::models::{Commit, ConflictFile, MergeState};
+use crate::commands::commit::auto_commit_if_enabled;
+use crate::commands::checkout::{preview_auto_commit, print_dry_run_note};
+use crate::commands::diff::{changed_files, write_restore_preview, FileChange, Side};
 use crate::utils::*;
 use colored::Colorize;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
 
-pub fn merge(source_branch: &str, into_branch: Option<String>, squash: bool) -> Result<()> {
+/// Merge a branch into the current one (or `into_branch`). Files changed on
+/// only one side since the merge base are taken from that side; files both
+/// sides changed are merged line by line. Lines both changed are left between
+/// conflict markers (binary files are left as they are) and the merge stays
+/// in progress until `gnu commit` finishes it. A squash merges the same way
+/// but doesn't record the source as a second parent.
+pub fn merge(source_branch: &str, into_branch: Option<String>, squash: bool, dry_run: bool) -> Result<()> {
     let vault_root = find_vault_root()?;
     let storage = Storage::new(vault_root.clone());
+    let _lock = if dry_run { None } else { Some(storage.lock()?) };
     let context_mgr = ContextManager::new(Storage::new(vault_root.clone()));
 
+    if storage.merge_in_progress() {
+        return Err(GitnuError::Other(
+            "A merge is already in progress\n  Finish it: gnu commit \"message\"\n  Or abandon it: gnu merge --abort".to_string(),
+        ));
+    }
+
     // Get target branch (current if not specified)
     let target_branch = match into_branch {
         Some(b) => b,
-        None => storage.read_head()?,
+        None => storage.current_branch()?,
     };
 
     // Get commits
@@ -21,11 +41,26 @@ pub fn merge(source_branch: &str, into_branch: Option<String>, squash: bool) ->
     let target_commit_hash = storage.read_branch_ref(&target_branch)?
         .ok_or_else(|| GitnuError::BranchNotFound(target_branch.clone()))?;
 
-    let source_commit = storage.find_commit(&source_commit_hash)?
+    let mut source_commit = storage.find_commit(&source_commit_hash)?
         .ok_or_else(|| GitnuError::CommitNotFound(source_commit_hash.clone()))?;
-    let target_commit = storage.find_commit(&target_commit_hash)?
+    let mut target_commit = storage.find_commit(&target_commit_hash)?
         .ok_or_else(|| GitnuError::CommitNotFound(target_commit_hash.clone()))?;
 
+    // Only once both sides resolve: preserve uncommitted work before files
+    // are overwritten when auto-commit is on
+    if dry_run {
+        if context_mgr.has_uncommitted_changes()? {
+            preview_auto_commit(&storage)?;
+        }
+    } else if context_mgr.has_uncommitted_changes()? {
+        let message = format!("Auto-commit before merge of {}", source_branch);
+        if auto_commit_if_enabled(&storage, &message)? {
+            // The auto-commit may have moved either branch
+            source_commit = storage.resolve_commit(source_branch)?;
+            target_commit = storage.resolve_commit(&target_branch)?;
+        }
+    }
+
     println!(
         "Merging {} into {}",
         source_branch.green(),
@@ -33,44 +68,97 @@ pub fn merge(source_branch: &str, into_branch: Option<String>, squash: bool) ->
     );
 
     // Check if we're on the target branch
-    let current_branch = storage.read_head()?;
-    if current_branch != target_branch {
+    let head = storage.read_head()?;
+    let switching = !head.is_branch(&target_branch);
+
+    if dry_run {
+        // The real merge starts from the target's snapshot when switching to it
+        let before = PreviewBase::new(&storage, switching.then_some(&target_commit))?;
+        if switching {
+            println!("Would switch to branch '{}' first", target_branch.green());
+        }
+        println!();
+        if squash {
+            let squashed = squashed_commits(&storage, &source_commit, &target_commit)?;
+            println!("Squashing {}", plural(squashed.len(), "commit"));
+        }
+        preview_merge(&storage, &before, &source_commit, &target_commit, (&target_branch, source_branch))?;
+        return Ok(());
+    }
+
+    if switching {
         println!("Switching to branch '{}'...", target_branch.green());
         // Restore target branch state
         storage.restore_snapshot(&target_commit.hash)?;
         storage.write_head(&target_branch)?;
     }
 
-    // Perform merge (simplified - just copy source files over target)
-    // In a real implementation, this would do smart merging of markdown files
-    storage.restore_snapshot(&source_commit.hash)?;
-
-    // Calculate new context summary
-    let summary = context_mgr.calculate_context_summary(Some(&target_commit))?;
-
-    // Create merge commit
     let merge_message = if squash {
-        format!("Merge {}: {} (squashed)", source_branch, source_commit.message)
+        squash_message(&storage, source_branch, &source_commit, &target_commit)?
     } else {
-        format!("Merge {}: {}", source_branch, source_commit.message)
+        format!("Merge {}: {}", source_branch, source_commit.subject())
     };
+    let plan = merge_plan(&storage, &source_commit, &target_commit, (&target_branch, source_branch))?;
+    apply_changes(&storage, &source_commit, &plan.apply)?;
+    for file in &plan.merged {
+        write_merged(&storage, file)?;
+    }
+
+    let conflicts: Vec<&MergedFile> = plan.merged.iter().filter(|f| f.conflicted).collect();
+    if !conflicts.is_empty() {
+        // A squash records no second parent, so only its message is kept
+        if squash {
+            storage.write_merge_msg(&merge_message)?;
+        } else {
+            storage.write_merge_head(&source_commit.hash, &merge_message)?;
+        }
+        storage.write_merge_state(&MergeState {
+            source: Some(source_branch.to_string()),
+            files: conflicts.iter()
+                .map(|f| ConflictFile { path: f.path.clone(), resolved: false, binary: f.text.is_none() })
+                .collect(),
+        })?;
+
+        println!();
+        println!("{}", "Conflicts in:".red().bold());
+        for file in &conflicts {
+            match file.text {
+                Some(_) => println!("  {}", file.path.display()),
+                None => println!("  {} (binary, left as on {})", file.path.display(), target_branch),
+            }
+        }
+        return Err(GitnuError::MergeConflict("Merge".to_string(), conflicts.len()));
+    }
+    let applied = plan.apply.len() + plan.merged.len();
+
+    // Calculate new context summary
+    let summary = context_mgr.calculate_commit_summary(Some(&target_commit))?;
 
+    let snapshot = storage.write_tree()?;
     let mut commit_data = Vec::new();
+    commit_data.extend_from_slice(b"tree ");
+    commit_data.extend_from_slice(snapshot.tree.as_bytes());
+    commit_data.extend_from_slice(b"\n");
     commit_data.extend_from_slice(b"parent ");
     commit_data.extend_from_slice(target_commit.hash.as_bytes());
-    commit_data.extend_from_slice(b"\nparent ");
-    commit_data.extend_from_slice(source_commit.hash.as_bytes());
     commit_data.extend_from_slice(b"\n");
+    // A squash collapses the source history, so it isn't recorded as a parent
+    if !squash {
+        commit_data.extend_from_slice(b"parent ");
+        commit_data.extend_from_slice(source_commit.hash.as_bytes());
+        commit_data.extend_from_slice(b"\n");
+    }
     commit_data.extend_from_slice(merge_message.as_bytes());
     commit_data.extend_from_slice(b"\n");
     commit_data.extend_from_slice(chrono::Utc::now().to_rfc3339().as_bytes());
     
     let hash = compute_hash(&commit_data);
-    let snapshot_path = storage.create_snapshot(&hash)?;
+    let snapshot_path = storage.record_snapshot(&hash, &snapshot)?;
 
-    let merge_commit = crate::models::Commit {
+    let merge_commit = Commit {
         hash: hash.clone(),
         parent: Some(target_commit.hash.clone()),
+        merge_parent: (!squash).then(|| source_commit.hash.clone()),
         timestamp: chrono::Utc::now(),
         author: crate::models::Author::Agent {
             model: "gitnu-merge".to_string(),
@@ -79,18 +167,238 @@ pub fn merge(source_branch: &str, into_branch: Option<String>, squash: bool) ->
         message: merge_message.clone(),
         context_summary: summary,
         snapshot_path: relative_path(&vault_root, &snapshot_path),
+        index: Some(storage.load_index()?),
     };
 
     // Save merge commit
     storage.append_commit(&target_branch, &merge_commit)?;
-    storage.write_branch_ref(&target_branch, &hash)?;
+    let action = if squash { "merge --squash" } else { "merge" };
+    storage.update_branch_ref(&target_branch, &hash, &format!("{}: {}", action, source_branch))?;
 
     println!();
     println!("{}", "Merge successful!".green().bold());
-    println!("  Auto-merged files from {}", source_branch);
+    println!("  Applied {} from {}", plural(applied, "changed file"), source_branch);
     println!();
-    println!("Created merge commit {}", hash[..7].yellow());
-    println!("  \"{}\"", merge_message);
+    println!("Created merge commit {}", short_hash(&hash).yellow());
+    println!("  \"{}\"", merge_commit.subject());
 
     Ok(())
 }
+
+/// The source commits not yet in `target`, oldest first
+fn squashed_commits(storage: &Storage, source: &Commit, target: &Commit) -> Result<Vec<Commit>> {
+    let graph = storage.commit_graph()?;
+    let already_merged: HashSet<&str> = match graph.merge_base(&target.hash, &source.hash) {
+        Some(b) => graph.ancestry(&b.hash).into_iter().map(|c| c.hash.as_str()).collect(),
+        None => HashSet::new(),
+    };
+    let mut squashed: Vec<Commit> = graph.ancestry(&source.hash)
+        .into_iter()
+        .filter(|c| !already_merged.contains(c.hash.as_str()))
+        .cloned()
+        .collect();
+    squashed.sort_by_key(|c| c.timestamp);
+
+    Ok(squashed)
+}
+
+/// Abandon a merge stopped on conflicts, restoring the pre-merge state of the
+/// current branch
+pub fn merge_abort() -> Result<()> {
+    let vault_root = find_vault_root()?;
+    let storage = Storage::new(vault_root);
+    let _lock = storage.lock()?;
+
+    if !storage.merge_in_progress() {
+        return Err(GitnuError::Other("No merge in progress".to_string()));
+    }
+
+    let head = storage.get_head_commit()?
+        .ok_or_else(|| GitnuError::CommitNotFound("HEAD".to_string()))?;
+    storage.restore_snapshot(&head.hash)?;
+    storage.restore_index(&head)?;
+    storage.clear_merge_state()?;
+
+    println!("{}", "Merge aborted".yellow());
+    println!("  Restored {} \"{}\"", short_hash(&head.hash).yellow(), head.subject());
+    Ok(())
+}
+
+/// Changes a three-way merge takes from the source, measured from the merge base
+struct MergePlan {
+    /// Changed only on the source side
+    apply: Vec<FileChange>,
+    /// Changed differently on both sides, combined line by line
+    merged: Vec<MergedFile>,
+}
+
+/// A file both sides changed
+struct MergedFile {
+    path: PathBuf,
+    /// The three-way merge of both sides, or `None` for binary files, which
+    /// can't be merged and are left as the target has them
+    text: Option<String>,
+    /// Whether the file was deleted on either side
+    deleted: bool,
+    /// Whether the merge left conflict markers (always for binary files)
+    conflicted: bool,
+}
+
+/// Labels are the target and source names written next to conflict markers
+fn merge_plan(storage: &Storage, source: &Commit, target: &Commit, labels: (&str, &str)) -> Result<MergePlan> {
+    let base = storage.merge_base(&target.hash, &source.hash)?;
+    let from = match base {
+        Some(ref b) => Side::Commit(b),
+        None => Side::Empty,
+    };
+    let ours: HashMap<PathBuf, Option<String>> = changed_files(storage, &from, &Side::Commit(target), None)?
+        .into_iter()
+        .map(|c| (c.path, c.new))
+        .collect();
+
+    let mut plan = MergePlan { apply: Vec::new(), merged: Vec::new() };
+    for change in changed_files(storage, &from, &Side::Commit(source), None)? {
+        match ours.get(&change.path) {
+            None => plan.apply.push(change),
+            // Both sides made the same change
+            Some(new) if *new == change.new => {}
+            Some(new) => {
+                let read = |commit: Option<&Commit>, side: &Option<String>| -> Result<Vec<u8>> {
+                    match (commit, side) {
+                        (Some(commit), Some(_)) => storage.read_file_from_snapshot(&commit.hash, &change.path),
+                        _ => Ok(Vec::new()),
+                    }
+                };
+                let base_content = read(base.as_ref(), &change.old)?;
+                let our_content = read(Some(target), new)?;
+                let their_content = read(Some(source), &change.new)?;
+                let deleted = new.is_none() || change.new.is_none();
+
+                if [&base_content, &our_content, &their_content].iter().any(|content| is_binary(content)) {
+                    plan.merged.push(MergedFile { path: change.path, text: None, deleted, conflicted: true });
+                    continue;
+                }
+                // Not binary, so all three are valid UTF-8
+                let (text, conflicted) = merge_lines(
+                    &String::from_utf8_lossy(&base_content),
+                    &String::from_utf8_lossy(&our_content),
+                    &String::from_utf8_lossy(&their_content),
+                    labels,
+                );
+                plan.merged.push(MergedFile { path: change.path, text: Some(text), deleted, conflicted });
+            }
+        }
+    }
+    Ok(plan)
+}
+
+/// Write `source`'s version of each changed file into the working directory
+fn apply_changes(storage: &Storage, source: &Commit, changes: &[FileChange]) -> Result<()> {
+    for change in changes {
+        let path = storage.vault_root.join(&change.path);
+        if change.new.is_some() {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(&path, storage.read_file_from_snapshot(&source.hash, &change.path)?)?;
+        } else if path.exists() {
+            fs::remove_file(&path)?;
+        }
+    }
+    Ok(())
+}
+
+/// Write a merged file into the working directory. Binary files keep the
+/// target's version, and a clean merge that empties a file one side deleted
+/// removes it.
+fn write_merged(storage: &Storage, file: &MergedFile) -> Result<()> {
+    let Some(ref text) = file.text else {
+        return Ok(());
+    };
+    let path = storage.vault_root.join(&file.path);
+    if text.is_empty() && file.deleted && !file.conflicted {
+        if path.exists() {
+            fs::remove_file(&path)?;
+        }
+        return Ok(());
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&path, text)?;
+    Ok(())
+}
+
+/// What a previewed merge would write over: the working directory, or the
+/// target's snapshot when the merge switches to the target first
+enum PreviewBase {
+    Working,
+    Snapshot(HashMap<PathBuf, String>),
+}
+
+impl PreviewBase {
+    fn new(storage: &Storage, switch_to: Option<&Commit>) -> Result<Self> {
+        let Some(commit) = switch_to else {
+            return Ok(PreviewBase::Working);
+        };
+        let files = storage.read_manifest(&commit.hash)?
+            .map(|m| m.files.into_iter().map(|f| (f.path, f.hash)).collect())
+            .unwrap_or_default();
+        Ok(PreviewBase::Snapshot(files))
+    }
+
+    /// Content hash of a file before the merge, `None` if it doesn't exist
+    fn hash(&self, storage: &Storage, rel_path: &Path) -> Result<Option<String>> {
+        match self {
+            PreviewBase::Working => {
+                let path = storage.vault_root.join(rel_path);
+                Ok(if path.is_file() { Some(hash_file(&path)?) } else { None })
+            }
+            PreviewBase::Snapshot(files) => Ok(files.get(rel_path).cloned()),
+        }
+    }
+}
+
+/// Preview a merge: the source-only changes against what it starts from,
+/// then the files that would conflict
+fn preview_merge(
+    storage: &Storage,
+    before: &PreviewBase,
+    source: &Commit,
+    target: &Commit,
+    labels: (&str, &str),
+) -> Result<()> {
+    let plan = merge_plan(storage, source, target, labels)?;
+    let mut changes = Vec::new();
+    for change in plan.apply {
+        let old = before.hash(storage, &change.path)?;
+        if old != change.new {
+            changes.push(FileChange { path: change.path, old, new: change.new });
+        }
+    }
+
+    write_restore_preview(&mut std::io::stdout(), storage, &changes, source)?;
+    if plan.merged.iter().any(|f| f.conflicted) {
+        println!();
+        for file in plan.merged.iter().filter(|f| f.conflicted) {
+            println!("! Would conflict: {}", file.path.display().to_string().red());
+        }
+    }
+    print_dry_run_note();
+    Ok(())
+}
+
+/// The squash commit message: the source branch and the subjects of the
+/// commits it collapses
+fn squash_message(storage: &Storage, source_branch: &str, source: &Commit, target: &Commit) -> Result<String> {
+    let squashed = squashed_commits(storage, source, target)?;
+    let mut message = format!(
+        "Squash merge {} ({})\n",
+        source_branch,
+        plural(squashed.len(), "commit")
+    );
+    for commit in &squashed {
+        message.push_str(&format!("\n- {}", commit.subject()));
+    }
+    Ok(message)
+}
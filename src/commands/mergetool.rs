@@ -0,0 +1,258 @@
+use crate::errors::*;
+use crate::models::{ConflictFile, MergeState};
+use crate::storage::Storage;
+use crate::utils::*;
+use colored::Colorize;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Side of a conflict to keep
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Keep {
+    Ours,
+    Theirs,
+}
+
+/// Walk every file under `domains/` that still has conflict markers and
+/// resolve it: interactively (edit, keep ours, keep theirs, skip), or by
+/// keeping one side everywhere. Progress is tracked in MERGE_STATE.json so
+/// `gnu commit` knows whether the merge can be finished.
+pub fn mergetool(keep: Option<Keep>) -> Result<()> {
+    let vault_root = find_vault_root()?;
+    let storage = Storage::new(vault_root.clone());
+    let config = storage.load_config()?;
+
+    let mut state = storage.read_merge_state()?.unwrap_or_default();
+    let conflicted = conflicted_files(&storage)?;
+    for file in state.files.iter_mut().filter(|f| !f.binary) {
+        file.resolved = !conflicted.contains(&file.path);
+    }
+    for path in conflicted {
+        if !state.files.iter().any(|f| f.path == path) {
+            state.files.push(ConflictFile { path, resolved: false, binary: false });
+        }
+    }
+
+    if state.files.is_empty() {
+        println!("{}", "No conflicts to resolve".green());
+        return Ok(());
+    }
+    save_state(&storage, &state)?;
+
+    let pending: Vec<(PathBuf, bool)> = state.unresolved().map(|f| (f.path.clone(), f.binary)).collect();
+    for (i, (path, binary)) in pending.iter().enumerate() {
+        let full_path = vault_root.join(path);
+        if *binary {
+            let choice = match keep {
+                Some(side) => side,
+                None => {
+                    println!();
+                    println!("{} {} (binary, {} of {})", "Conflict in".bold(), path.display(), i + 1, pending.len());
+                    match prompt("keep [o]urs, keep [t]heirs, [s]kip?")?.as_str() {
+                        "o" | "ours" => Keep::Ours,
+                        "t" | "theirs" => Keep::Theirs,
+                        _ => {
+                            println!("  {}", "Skipped".dimmed());
+                            continue;
+                        }
+                    }
+                }
+            };
+            if choice == Keep::Theirs {
+                take_their_file(&storage, path)?;
+            }
+            println!("  {} {}", "Resolved".green(), path.display());
+            if let Some(file) = state.files.iter_mut().find(|f| &f.path == path) {
+                file.resolved = true;
+            }
+            save_state(&storage, &state)?;
+            continue;
+        }
+        let content = fs::read_to_string(&full_path)?;
+
+        let choice = match keep {
+            Some(side) => Some(side),
+            None => {
+                println!();
+                println!("{} {} ({} of {})", "Conflict in".bold(), path.display(), i + 1, pending.len());
+                print_conflicts(&content);
+                match prompt("[e]dit, keep [o]urs, keep [t]heirs, [s]kip?")?.as_str() {
+                    "o" | "ours" => Some(Keep::Ours),
+                    "t" | "theirs" => Some(Keep::Theirs),
+                    "e" | "edit" => {
+                        open_in_editor(&full_path, config.merge.tool.as_deref())?;
+                        None
+                    }
+                    _ => {
+                        println!("  {}", "Skipped".dimmed());
+                        continue;
+                    }
+                }
+            }
+        };
+        if let Some(side) = choice {
+            fs::write(&full_path, take_side(&content, side))?;
+        }
+
+        // Editors can leave markers behind, so check the file itself
+        let resolved = !has_conflict_markers(&fs::read_to_string(&full_path)?);
+        if resolved {
+            println!("  {} {}", "Resolved".green(), path.display());
+        } else {
+            println!("  {} still has conflict markers", path.display().to_string().yellow());
+        }
+        if let Some(file) = state.files.iter_mut().find(|f| &f.path == path) {
+            file.resolved = resolved;
+        }
+        save_state(&storage, &state)?;
+    }
+
+    let remaining = state.unresolved().count();
+    println!();
+    if remaining == 0 {
+        println!("{}", "All conflicts resolved".green());
+        println!("  Run: gnu commit \"message\" to finish the merge");
+    } else {
+        println!(
+            "{} unresolved\n  Run 'gnu mergetool' again to continue",
+            plural(remaining, "file").yellow()
+        );
+    }
+
+    Ok(())
+}
+
+/// Replace a binary file with the merged branch's version, which has no
+/// markers to pick from
+fn take_their_file(storage: &Storage, path: &Path) -> Result<()> {
+    let merge_head = storage.read_merge_head()?
+        .ok_or_else(|| GitnuError::Other(format!("No merged commit to take {} from", path.display())))?;
+    let full_path = storage.vault_root.join(path);
+    let theirs = storage.read_manifest(&merge_head)?
+        .is_some_and(|manifest| manifest.files.iter().any(|f| f.path == path));
+    if theirs {
+        if let Some(parent) = full_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&full_path, storage.read_file_from_snapshot(&merge_head, path)?)?;
+    } else if full_path.exists() {
+        fs::remove_file(&full_path)?;
+    }
+    Ok(())
+}
+
+fn save_state(storage: &Storage, state: &MergeState) -> Result<()> {
+    let _lock = storage.lock()?;
+    storage.write_merge_state(state)
+}
+
+/// Vault-relative paths of domain files containing conflict markers
+pub(crate) fn conflicted_files(storage: &Storage) -> Result<Vec<PathBuf>> {
+    let mut conflicted = Vec::new();
+    for path in storage.domain_files()? {
+        if let Ok(content) = fs::read_to_string(&path) {
+            if has_conflict_markers(&content) {
+                conflicted.push(relative_path(&storage.vault_root, &path));
+            }
+        }
+    }
+    conflicted.sort();
+    Ok(conflicted)
+}
+
+fn prompt(question: &str) -> Result<String> {
+    print!("{} ", question);
+    std::io::stdout().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(answer.trim().to_lowercase())
+}
+
+/// One stretch of a conflicted file
+enum Segment<'a> {
+    Common(&'a str),
+    Conflict { ours: Vec<&'a str>, theirs: Vec<&'a str> },
+}
+
+/// Split text on conflict markers. A diff3-style `|||||||` base section is dropped.
+fn segments(content: &str) -> Vec<Segment<'_>> {
+    enum In {
+        Common,
+        Ours,
+        Base,
+        Theirs,
+    }
+
+    let mut segments = Vec::new();
+    let mut state = In::Common;
+    let (mut ours, mut theirs) = (Vec::new(), Vec::new());
+    for line in content.lines() {
+        match state {
+            In::Common if line.starts_with("<<<<<<<") => state = In::Ours,
+            In::Common => segments.push(Segment::Common(line)),
+            In::Ours if line.starts_with("|||||||") => state = In::Base,
+            In::Ours | In::Base if line.starts_with("=======") => state = In::Theirs,
+            In::Ours => ours.push(line),
+            In::Base => {}
+            In::Theirs if line.starts_with(">>>>>>>") => {
+                segments.push(Segment::Conflict {
+                    ours: std::mem::take(&mut ours),
+                    theirs: std::mem::take(&mut theirs),
+                });
+                state = In::Common;
+            }
+            In::Theirs => theirs.push(line),
+        }
+    }
+    segments
+}
+
+/// Keep one side of every conflict, dropping the markers
+pub(crate) fn take_side(content: &str, keep: Keep) -> String {
+    let mut lines = Vec::new();
+    for segment in segments(content) {
+        match segment {
+            Segment::Common(line) => lines.push(line),
+            Segment::Conflict { ours, theirs } => {
+                lines.extend(if keep == Keep::Ours { ours } else { theirs });
+            }
+        }
+    }
+    let mut resolved = lines.join("\n");
+    if content.ends_with('\n') {
+        resolved.push('\n');
+    }
+    resolved
+}
+
+fn print_conflicts(content: &str) {
+    let conflicts = segments(content).into_iter().filter_map(|segment| match segment {
+        Segment::Conflict { ours, theirs } => Some((ours, theirs)),
+        Segment::Common(_) => None,
+    });
+    for (n, (ours, theirs)) in conflicts.enumerate() {
+        println!("  {}", format!("Conflict {}", n + 1).dimmed());
+        println!("  {}", "Ours:".green());
+        for line in ours {
+            println!("    {}", line);
+        }
+        println!("  {}", "Theirs:".red());
+        for line in theirs {
+            println!("    {}", line);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_take_side_drops_markers() {
+        let content = "intro\n<<<<<<< main\nours\n||||||| base\nold\n=======\ntheirs\n>>>>>>> explore\noutro\n";
+        assert_eq!(take_side(content, Keep::Ours), "intro\nours\noutro\n");
+        assert_eq!(take_side(content, Keep::Theirs), "intro\ntheirs\noutro\n");
+        assert!(!has_conflict_markers(&take_side(content, Keep::Ours)));
+    }
+}
@@ -13,17 +13,55 @@ pub mod load;
 pub mod resolve;
 pub mod context;
 pub mod summary;
+pub mod export;
+pub mod import;
+pub mod mv;
+pub mod gc;
+pub mod stats;
+pub mod watch;
+pub mod show;
+pub mod backup;
+pub mod undo;
+pub mod serve;
+pub mod annotate;
+pub mod doctor;
+pub mod squash;
+pub mod mergetool;
+pub mod fsck;
+pub mod config;
+pub mod alias;
+pub mod info;
+pub mod revert;
 
-pub use init::init;
-pub use status::status;
-pub use commit::commit;
-pub use log::log;
-pub use branch::{branch_list, branch_create, branch_delete};
+pub use init::{init, list_templates};
+pub use status::{status, status_report};
+pub use commit::{commit, make_commit, CommitOptions};
+pub use log::{log, log_report, LogOptions};
+pub use branch::{branch_list, MergedFilter, branch_create, branch_describe, branch_delete, branch_contains, prune_branches};
 pub use checkout::checkout;
 pub use rewind::rewind;
-pub use diff::diff;
-pub use merge::merge;
-pub use load::{load, unload, pin, unpin};
+pub use diff::{diff, diff_report, DiffFormat, DiffOptions};
+pub use merge::{merge, merge_abort};
+pub use load::{load, load_path, unload, pin, unpin};
 pub use resolve::resolve;
-pub use context::context;
-pub use summary::summary;
+pub use context::{context, context_report, ContextOptions};
+pub use summary::{summary, summary_report};
+pub use export::export;
+pub use import::import;
+pub use mv::mv;
+pub use gc::gc;
+pub use stats::{stats, token_trend};
+pub use watch::watch;
+pub use show::show;
+pub use backup::{backup, restore_backup};
+pub use undo::undo;
+pub use serve::serve;
+pub use annotate::annotate;
+pub use doctor::doctor;
+pub use squash::squash;
+pub use mergetool::{mergetool, Keep};
+pub use fsck::fsck;
+pub use config::{config_get, config_set};
+pub use alias::{alias, expand_alias};
+pub use info::{info, is_vault};
+pub use revert::revert;
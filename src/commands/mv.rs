@@ -0,0 +1,154 @@
+use crate::errors::*;
+use crate::storage::Storage;
+use crate::utils::*;
+use crate::wikilink::{resolve_wikilink, rewrite_wikilinks};
+use colored::Colorize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub fn mv(source: &str, destination: &str) -> Result<()> {
+    let vault_root = find_vault_root()?;
+    let storage = Storage::new(vault_root.clone());
+    let _lock = storage.lock()?;
+    let domains_dir = storage.domains_dir();
+
+    // Compare canonical, vault-rooted paths whether or not the cwd is in the vault
+    let src = resolve_path_arg(&vault_root, source);
+    if !src.exists() {
+        return Err(GitnuError::FileNotFound(src));
+    }
+    let src = ensure_in_vault(&vault_root, &src)?;
+
+    // Moving onto an existing directory places the source inside it
+    let mut dst = ensure_in_vault(&vault_root, &resolve_new_path_arg(&vault_root, destination))?;
+    if dst.is_dir() {
+        if let Some(name) = src.file_name() {
+            dst = dst.join(name);
+        }
+    }
+    if dst.exists() {
+        return Err(GitnuError::Other(format!(
+            "Destination already exists: {}",
+            relative_path(&vault_root, &dst).display()
+        )));
+    }
+    if !src.starts_with(&domains_dir) || !dst.starts_with(&domains_dir) || src == domains_dir {
+        return Err(GitnuError::Other(
+            "gnu mv only moves files and directories under domains/".to_string(),
+        ));
+    }
+    if dst.starts_with(&src) {
+        return Err(GitnuError::Other(
+            "Cannot move a directory into itself".to_string(),
+        ));
+    }
+
+    // Old path -> new path for every file being moved
+    let moved: HashMap<PathBuf, PathBuf> = if src.is_file() {
+        HashMap::from([(src.clone(), dst.clone())])
+    } else {
+        storage.walk_files(&src)?
+            .into_iter()
+            .map(|old| {
+                let new = dst.join(old.strip_prefix(&src).unwrap_or(&old));
+                (old, new)
+            })
+            .collect()
+    };
+
+    let files_before = storage.domain_files()?;
+    let files_after: Vec<PathBuf> = files_before
+        .iter()
+        .map(|f| moved.get(f).cloned().unwrap_or_else(|| f.clone()))
+        .collect();
+
+    // Work out link rewrites against the tree as it was before the move, so
+    // only links that actually resolved to a moved file are touched
+    let mut resolved: HashMap<String, Option<PathBuf>> = HashMap::new();
+    let mut rewrites = Vec::new();
+    for file in files_before.iter().filter(|f| is_markdown(f)) {
+        let content = match fs::read_to_string(file) {
+            Ok(c) => c,
+            Err(_) => continue,
+        };
+        let (updated, count) = rewrite_wikilinks(&content, |target| {
+            let old = resolved
+                .entry(target.to_string())
+                .or_insert_with(|| resolve_wikilink(&vault_root, target).ok())
+                .as_ref()?;
+            let new = moved.get(old)?;
+            Some(link_target(&domains_dir, &files_after, target, new))
+        });
+        if count > 0 {
+            let new_location = moved.get(file).cloned().unwrap_or_else(|| file.clone());
+            rewrites.push((new_location, updated, count));
+        }
+    }
+
+    if let Some(parent) = dst.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::rename(&src, &dst)?;
+
+    for (path, content, _) in &rewrites {
+        fs::write(path, content)?;
+    }
+
+    // Keep loaded, pinned and excluded entries pointing at the new location
+    let old_rel = relative_path(&vault_root, &src);
+    let new_rel = relative_path(&vault_root, &dst);
+    let mut index = storage.load_index()?;
+    for entry in index.loaded.iter_mut()
+        .chain(index.pinned.iter_mut())
+        .chain(index.excluded.iter_mut())
+    {
+        if let Ok(rest) = entry.strip_prefix(&old_rel) {
+            *entry = new_rel.join(rest);
+        }
+    }
+    storage.save_index(&index)?;
+
+    println!(
+        "{} {} -> {}",
+        "Moved".green(),
+        old_rel.display(),
+        new_rel.display()
+    );
+
+    let total: usize = rewrites.iter().map(|(_, _, count)| count).sum();
+    if total == 0 {
+        println!("  {}", "No wikilinks needed updating".dimmed());
+    } else {
+        println!("  Updated {} links in {} files:", total, rewrites.len());
+        for (path, _, count) in &rewrites {
+            println!("    {} ({})", relative_path(&vault_root, path).display(), count);
+        }
+    }
+
+    Ok(())
+}
+
+fn is_markdown(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "md")
+}
+
+/// New link target for a moved file, in the same form as the original link.
+/// Bare names fall back to the path form when the new name would be ambiguous.
+fn link_target(domains_dir: &Path, files_after: &[PathBuf], old_target: &str, new: &Path) -> String {
+    let stem = new.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+    let same_stem = files_after.iter()
+        .filter(|f| f.file_stem().is_some_and(|s| s.to_string_lossy() == stem))
+        .count();
+
+    if !old_target.contains('/') && same_stem <= 1 {
+        return stem;
+    }
+
+    let rel = relative_path(domains_dir, new).to_string_lossy().to_string();
+    if old_target.ends_with(".md") {
+        rel
+    } else {
+        rel.trim_end_matches(".md").to_string()
+    }
+}
@@ -1,11 +1,39 @@
 use crate::errors::*;
+use crate::storage::Storage;
 use crate::utils::*;
-use crate::wikilink::resolve_wikilink;
+use crate::wikilink::{resolve_wikilink_with, wikilink_targets};
+use colored::Colorize;
 
-pub fn resolve(wikilink: &str) -> Result<()> {
+pub fn resolve(wikilink: Option<&str>, all: bool, fuzzy: bool, json: bool) -> Result<()> {
     let vault_root = find_vault_root()?;
-    
-    match resolve_wikilink(&vault_root, wikilink) {
+
+    if all {
+        let targets = wikilink_targets(&Storage::new(vault_root.clone()))?;
+        if json {
+            println!("{}", serde_json::to_string_pretty(&targets)?);
+            return Ok(());
+        }
+
+        let width = targets.iter().map(|t| t.name.len()).max().unwrap_or(0);
+        for target in &targets {
+            match target.path {
+                Some(ref path) => println!("{:<width$}  {}", target.name, path.display(), width = width),
+                None => println!(
+                    "{:<width$}  {} ({} matches)",
+                    target.name,
+                    "AMBIGUOUS".yellow(),
+                    target.candidates.len(),
+                    width = width
+                ),
+            }
+        }
+        return Ok(());
+    }
+
+    let wikilink = wikilink.ok_or_else(|| {
+        GitnuError::Other("Must specify a wikilink or use --all".to_string())
+    })?;
+    match resolve_wikilink_with(&vault_root, wikilink, fuzzy) {
         Ok(path) => {
             println!("{}", path.display());
             Ok(())
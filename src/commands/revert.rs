@@ -0,0 +1,198 @@
+use crate::errors::*;
+use crate::models::{ConflictFile, MergeState};
+use crate::storage::Storage;
+use crate::context::ContextManager;
+use crate::commands::commit::{auto_commit_if_enabled, make_commit, CommitOptions};
+use crate::commands::diff::{changed_files, Side};
+use crate::utils::*;
+use colored::Colorize;
+use std::fs;
+use std::path::Path;
+
+/// Undo the changes `target` made (against its parent) on top of the current
+/// branch and record that as a new commit, keeping history intact unlike
+/// `gnu rewind`. Lines changed again since `target` are left between
+/// conflict markers, and the revert is finished with `gnu commit` once
+/// they are resolved.
+pub fn revert(target: &str, message: Option<String>, no_commit: bool) -> Result<()> {
+    let vault_root = find_vault_root()?;
+    let storage = Storage::new(vault_root.clone());
+    let context_mgr = ContextManager::new(Storage::new(vault_root.clone()));
+
+    // make_commit takes the lock itself, so only hold it while files change
+    let (commit, message) = {
+        let _lock = storage.lock()?;
+
+        if storage.merge_in_progress() {
+            return Err(GitnuError::Other(
+                "A merge is in progress\n  Finish it: gnu commit \"message\"\n  Or abandon it: gnu merge --abort".to_string(),
+            ));
+        }
+
+        let commit = storage.resolve_commit(target)?;
+        if commit.merge_parent.is_some() {
+            return Err(GitnuError::Other(format!(
+                "Cannot revert merge commit {} \"{}\"",
+                short_hash(&commit.hash),
+                commit.subject()
+            )));
+        }
+        let parent = match commit.parent {
+            Some(ref hash) => Some(
+                storage.find_commit(hash)?
+                    .ok_or_else(|| GitnuError::CommitNotFound(hash.clone()))?,
+            ),
+            None => None,
+        };
+
+        // The revert commit must only hold the revert
+        if context_mgr.has_uncommitted_changes()? {
+            let auto_message = format!("Auto-commit before revert of {}", short_hash(&commit.hash));
+            if !auto_commit_if_enabled(&storage, &auto_message)? {
+                return Err(GitnuError::Other(
+                    "Uncommitted changes would be mixed into the revert\n  Commit them first: gnu commit \"message\"".to_string(),
+                ));
+            }
+        }
+
+        let message = message.unwrap_or_else(|| {
+            format!("Revert \"{}\"\n\nThis reverts commit {}.", commit.subject(), commit.hash)
+        });
+
+        let from = match parent {
+            Some(ref p) => Side::Commit(p),
+            None => Side::Empty,
+        };
+        let labels = (
+            storage.read_head()?.name().to_string(),
+            format!("revert {}", short_hash(&commit.hash)),
+        );
+
+        let mut reverted = 0;
+        let mut conflicts = Vec::new();
+        let mut skipped = Vec::new();
+        for change in changed_files(&storage, &from, &Side::Commit(&commit), None)? {
+            let path = vault_root.join(&change.path);
+            let current = if path.is_file() { Some(hash_file(&path)?) } else { None };
+
+            if current == change.old {
+                // Already undone by a later commit
+                continue;
+            }
+            let parent_content = match (&parent, &change.old) {
+                (Some(p), Some(_)) => Some(storage.read_file_from_snapshot(&p.hash, &change.path)?),
+                _ => None,
+            };
+
+            // Untouched since the commit: take the parent's version as is
+            if current == change.new {
+                write_or_remove(&path, parent_content.as_deref())?;
+                reverted += 1;
+                continue;
+            }
+
+            // Changed again since: undo only the commit's own lines
+            let base = match change.new {
+                Some(_) => storage.read_file_from_snapshot(&commit.hash, &change.path)?,
+                None => Vec::new(),
+            };
+            let ours = match current {
+                Some(_) => fs::read(&path)?,
+                None => Vec::new(),
+            };
+            let theirs = parent_content.unwrap_or_default();
+            if [&base, &ours, &theirs].iter().any(|content| is_binary(content)) {
+                skipped.push(change.path);
+                continue;
+            }
+
+            let (merged, conflicted) = merge_lines(
+                &String::from_utf8_lossy(&base),
+                &String::from_utf8_lossy(&ours),
+                &String::from_utf8_lossy(&theirs),
+                (&labels.0, &labels.1),
+            );
+            if merged.is_empty() && change.old.is_none() {
+                write_or_remove(&path, None)?;
+            } else {
+                write_or_remove(&path, Some(merged.as_bytes()))?;
+            }
+            reverted += 1;
+            if conflicted {
+                conflicts.push(change.path);
+            }
+        }
+
+        println!(
+            "Reverting {} \"{}\"",
+            short_hash(&commit.hash).yellow(),
+            commit.subject()
+        );
+        for path in &skipped {
+            println!(
+                "  {} {} (binary, changed since; revert it by hand)",
+                "Skipped".yellow(),
+                path.display()
+            );
+        }
+
+        if !conflicts.is_empty() {
+            storage.write_merge_msg(&message)?;
+            storage.write_merge_state(&MergeState {
+                source: None,
+                files: conflicts.iter()
+                    .map(|path| ConflictFile { path: path.clone(), resolved: false, binary: false })
+                    .collect(),
+            })?;
+
+            println!();
+            println!("{}", "Conflicts in:".red().bold());
+            for path in &conflicts {
+                println!("  {}", path.display());
+            }
+            return Err(GitnuError::MergeConflict("Revert".to_string(), conflicts.len()));
+        }
+
+        if reverted == 0 {
+            println!("{}", "Nothing to revert: its changes are already undone".yellow());
+            return Ok(());
+        }
+        println!("  Restored {}", plural(reverted, "file"));
+        (commit, message)
+    };
+
+    if no_commit {
+        println!("  Not committed (--no-commit)\n  Run: gnu commit \"message\"");
+        return Ok(());
+    }
+
+    let outcome = make_commit(&storage, &CommitOptions { message, ..Default::default() })?;
+    match outcome.commit {
+        Some(revert_commit) => {
+            println!();
+            println!(
+                "{} {}",
+                format!("[{} {}]", outcome.branch, short_hash(&revert_commit.hash)).green(),
+                revert_commit.subject()
+            );
+        }
+        None => println!("{}", format!("Nothing to commit: {} left no changes", short_hash(&commit.hash)).yellow()),
+    }
+
+    Ok(())
+}
+
+/// Write `content` to `path`, or remove the file when there is none
+fn write_or_remove(path: &Path, content: Option<&[u8]>) -> Result<()> {
+    match content {
+        Some(content) => {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(path, content)?;
+        }
+        None if path.exists() => fs::remove_file(path)?,
+        None => {}
+    }
+    Ok(())
+}
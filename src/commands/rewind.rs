@@ -1,43 +1,89 @@
 use crate::errors::*;
+use crate::models::Commit;
 use crate::storage::Storage;
+use crate::context::ContextManager;
+use crate::commands::commit::auto_commit_if_enabled;
+use crate::commands::checkout::{preview_auto_commit, preview_restore, print_dry_run_note};
 use crate::utils::*;
 use colored::Colorize;
 
-pub fn rewind(target: &str, soft: bool) -> Result<()> {
+/// Move the current branch back to `target`, or with `to_date` to the last
+/// commit made on or before that date
+pub fn rewind(target: Option<&str>, to_date: Option<&str>, soft: bool, dry_run: bool) -> Result<()> {
     let vault_root = find_vault_root()?;
-    let storage = Storage::new(vault_root);
+    let storage = Storage::new(vault_root.clone());
+    let _lock = if dry_run { None } else { Some(storage.lock()?) };
+    let context_mgr = ContextManager::new(Storage::new(vault_root));
 
-    // Find target commit
-    let commit = storage.find_commit(target)?;
-    let commit = match commit {
-        Some(c) => c,
-        None => return Err(GitnuError::CommitNotFound(target.to_string())),
+    let target = match to_date {
+        Some(date) => commit_at_date(&storage, date)?.hash,
+        None => target.unwrap_or("HEAD").to_string(),
     };
+    let target = target.as_str();
+
+    // Resolve the target first so a typo fails before anything is committed
+    let commit = storage.resolve_commit(target)?;
+
+    // Preserve uncommitted work before a hard rewind when auto-commit is on
+    if !soft && context_mgr.has_uncommitted_changes()? {
+        let message = format!("Auto-commit before rewind to {}", target);
+        if dry_run {
+            preview_auto_commit(&storage)?;
+        } else {
+            auto_commit_if_enabled(&storage, &message)?;
+        }
+    }
 
     // Get current branch
-    let current_branch = storage.read_head()?;
+    let current_branch = storage.current_branch()?;
+
+    if dry_run {
+        println!(
+            "Rewinding would move {} to commit {} \"{}\"",
+            current_branch.green(),
+            short_hash(&commit.hash).yellow(),
+            commit.subject()
+        );
+        println!();
+        if soft {
+            println!("  Working directory unchanged (--soft)");
+            print_dry_run_note();
+        } else {
+            preview_restore(&storage, &commit)?;
+        }
+        return Ok(());
+    }
 
     // Update branch ref to target commit
-    storage.write_branch_ref(&current_branch, &commit.hash)?;
+    storage.update_branch_ref(
+        &current_branch,
+        &commit.hash,
+        &format!("rewind: to {}", short_hash(&commit.hash)),
+    )?;
 
     if !soft {
-        // Restore snapshot
+        // Restore snapshot and the loaded context recorded with it
         storage.restore_snapshot(&commit.hash)?;
+        let stale = storage.restore_index(&commit)?;
         println!(
             "{} {} to commit {} \"{}\"",
             "Rewound".yellow(),
             current_branch.green(),
-            commit.hash[..7].yellow(),
-            commit.message
+            short_hash(&commit.hash).yellow(),
+            commit.subject()
         );
-        println!("  Restored context from {}", commit.hash[..7].yellow());
+        println!("  Restored context from {}", short_hash(&commit.hash).yellow());
+        if stale > 0 {
+            let entries = if stale == 1 { "entry" } else { "entries" };
+            println!("  Cleaned {} stale index {}", stale, entries);
+        }
     } else {
         println!(
             "{} {} to commit {} \"{}\"",
             "Rewound".yellow(),
             current_branch.green(),
-            commit.hash[..7].yellow(),
-            commit.message
+            short_hash(&commit.hash).yellow(),
+            commit.subject()
         );
         println!("  Working directory unchanged (--soft)");
     }
@@ -49,3 +95,23 @@ pub fn rewind(target: &str, soft: bool) -> Result<()> {
 
     Ok(())
 }
+
+/// The last commit on the current line of history made on or before `date`
+/// (`YYYY-MM-DD` or `YYYY-MM-DDTHH:MM`), reporting which one was picked
+pub(crate) fn commit_at_date(storage: &Storage, date: &str) -> Result<Commit> {
+    let until = parse_date_until(date)?;
+    let commit = storage.commit_before(until)?.ok_or_else(|| {
+        GitnuError::Other(format!(
+            "No commit on '{}' at or before {}",
+            storage.read_head().map(|h| h.name().to_string()).unwrap_or_default(),
+            date
+        ))
+    })?;
+    println!(
+        "Using commit {} \"{}\" from {}",
+        short_hash(&commit.hash).yellow(),
+        commit.subject(),
+        commit.timestamp.format("%Y-%m-%d %H:%M")
+    );
+    Ok(commit)
+}
@@ -0,0 +1,249 @@
+use crate::errors::*;
+use crate::storage::Storage;
+use crate::commands::commit::{make_commit, CommitOptions};
+use crate::commands::context::{context_report, ContextOptions};
+use crate::context::{GroupBy, PackStrategy};
+use crate::commands::diff::diff_report;
+use crate::commands::load::load_path;
+use crate::commands::status::status_report;
+use crate::commands::summary::summary_report;
+use crate::utils::*;
+use serde_json::{json, Value};
+use std::io::{BufRead, Write};
+
+/// Protocol revision offered when the client doesn't ask for one
+const PROTOCOL_VERSION: &str = "2025-06-18";
+
+// JSON-RPC error codes
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+/// Serve the vault to agents. Only the Model Context Protocol over stdio
+/// (newline-delimited JSON-RPC) is supported.
+pub fn serve(mcp: bool) -> Result<()> {
+    if !mcp {
+        return Err(GitnuError::Other(
+            "No protocol selected\n  Run: gnu serve --mcp".to_string(),
+        ));
+    }
+
+    let storage = Storage::new(find_vault_root()?);
+    let stdin = std::io::stdin();
+    let mut stdout = std::io::stdout();
+
+    for line in stdin.lock().lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let response = match serde_json::from_str::<Value>(&line) {
+            Ok(request) => handle_request(&storage, &request),
+            Err(e) => Some(error_response(Value::Null, PARSE_ERROR, &e.to_string())),
+        };
+        if let Some(response) = response {
+            writeln!(stdout, "{}", serde_json::to_string(&response)?)?;
+            stdout.flush()?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Answer one JSON-RPC message; notifications (no `id`) get no response
+fn handle_request(storage: &Storage, request: &Value) -> Option<Value> {
+    let id = request.get("id").cloned()?;
+    let method = request["method"].as_str().unwrap_or("");
+    let params = &request["params"];
+
+    let result = match method {
+        "initialize" => json!({
+            "protocolVersion": params["protocolVersion"].as_str().unwrap_or(PROTOCOL_VERSION),
+            "capabilities": { "tools": {} },
+            "serverInfo": { "name": "gitnu", "version": env!("CARGO_PKG_VERSION") },
+        }),
+        "ping" => json!({}),
+        "tools/list" => json!({ "tools": tool_definitions() }),
+        "tools/call" => {
+            let name = params["name"].as_str().unwrap_or("");
+            let args = &params["arguments"];
+            match call_tool(storage, name, args) {
+                Some(Ok(value)) => json!({
+                    "content": [{ "type": "text", "text": value.to_string() }],
+                    "structuredContent": value,
+                    "isError": false,
+                }),
+                // Tool failures are reported to the model rather than as protocol errors
+                Some(Err(e)) => json!({
+                    "content": [{ "type": "text", "text": e.to_string() }],
+                    "isError": true,
+                }),
+                None => return Some(error_response(id, INVALID_PARAMS, &format!("Unknown tool: {}", name))),
+            }
+        }
+        _ => return Some(error_response(id, METHOD_NOT_FOUND, &format!("Method not found: {}", method))),
+    };
+
+    Some(json!({ "jsonrpc": "2.0", "id": id, "result": result }))
+}
+
+fn error_response(id: Value, code: i64, message: &str) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": code, "message": message },
+    })
+}
+
+/// Run a tool by name, or `None` if there is no such tool
+fn call_tool(storage: &Storage, name: &str, args: &Value) -> Option<Result<Value>> {
+    let str_arg = |key: &str| args[key].as_str().map(str::to_string);
+    let bool_arg = |key: &str| args[key].as_bool().unwrap_or(false);
+    let list_arg = |key: &str| -> Vec<String> {
+        args[key].as_array()
+            .map(|items| items.iter().filter_map(|v| v.as_str().map(str::to_string)).collect())
+            .unwrap_or_default()
+    };
+
+    let result = match name {
+        "status" => status_report(storage).and_then(to_value),
+        "commit" => match (str_arg("message"), str_arg("date").as_deref().map(parse_timestamp).transpose()) {
+            (Some(message), Ok(date)) => make_commit(storage, &CommitOptions {
+                message,
+                author: str_arg("author"),
+                model: str_arg("model"),
+                allow_empty: bool_arg("allow_empty"),
+                no_large_files: bool_arg("no_large_files"),
+                full: bool_arg("full"),
+                date,
+                allow_conflict_markers: bool_arg("allow_conflict_markers"),
+            })
+            .and_then(to_value),
+            (None, _) => Err(missing_argument("message")),
+            (_, Err(e)) => Err(e),
+        },
+        "load" => match str_arg("path") {
+            // Tool results must be objects, so wrap the list of loaded paths
+            Some(path) => load_path(storage, &path, bool_arg("pin")).map(|loaded| json!({ "loaded": loaded })),
+            None => Err(missing_argument("path")),
+        },
+        "context" => match (
+            args["strategy"].as_str().map(PackStrategy::parse).transpose(),
+            args["group_by"].as_str().map(GroupBy::parse).transpose(),
+        ) {
+            (Ok(strategy), Ok(group_by)) => context_report(storage, &ContextOptions {
+                compress: bool_arg("compress"),
+                include: list_arg("include"),
+                exclude: list_arg("exclude"),
+                all: bool_arg("all"),
+                outline: bool_arg("outline"),
+                branch: str_arg("branch"),
+                budget: args["budget"].as_u64().map(|b| b as usize),
+                strategy: strategy.unwrap_or_default(),
+                diff_only: bool_arg("diff_only"),
+                group_by: group_by.unwrap_or_default(),
+                ..Default::default()
+            })
+            .and_then(to_value),
+            (Err(e), _) | (_, Err(e)) => Err(e),
+        },
+        "diff" => diff_report(
+            storage,
+            str_arg("source").as_deref(),
+            str_arg("target").as_deref(),
+            str_arg("domain").as_deref(),
+        )
+        .and_then(to_value),
+        "summary" => summary_report(storage).and_then(to_value),
+        _ => return None,
+    };
+
+    Some(result)
+}
+
+fn to_value<T: serde::Serialize>(report: T) -> Result<Value> {
+    Ok(serde_json::to_value(report)?)
+}
+
+fn missing_argument(name: &str) -> GitnuError {
+    GitnuError::Other(format!("Missing required argument '{}'", name))
+}
+
+/// Tool names, descriptions and argument schemas advertised by `tools/list`
+fn tool_definitions() -> Value {
+    let no_args = json!({ "type": "object", "properties": {} });
+    json!([
+        {
+            "name": "status",
+            "description": "Current branch, last commit, active context and uncommitted changes",
+            "inputSchema": no_args,
+        },
+        {
+            "name": "commit",
+            "description": "Checkpoint the current context as a new commit",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "message": { "type": "string", "description": "Commit message" },
+                    "author": { "type": "string", "enum": ["human", "agent"] },
+                    "model": { "type": "string", "description": "Model name for agent commits" },
+                    "allow_empty": { "type": "boolean" },
+                    "no_large_files": { "type": "boolean" },
+                    "full": { "type": "boolean", "description": "Re-hash files even if size and mtime are unchanged" },
+                    "date": { "type": "string", "description": "Backdate the commit (RFC 3339 or YYYY-MM-DD)" },
+                    "allow_conflict_markers": { "type": "boolean" },
+                },
+                "required": ["message"],
+            },
+        },
+        {
+            "name": "load",
+            "description": "Load a file or directory (path, [[wikilink]] or glob) into the active context",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "path": { "type": "string" },
+                    "pin": { "type": "boolean", "description": "Always include this path" },
+                },
+                "required": ["path"],
+            },
+        },
+        {
+            "name": "context",
+            "description": "Render the active context as markdown with the files it includes",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "compress": { "type": "boolean" },
+                    "include": { "type": "array", "items": { "type": "string" } },
+                    "exclude": { "type": "array", "items": { "type": "string" } },
+                    "all": { "type": "boolean", "description": "Ignore the loaded set" },
+                    "outline": { "type": "boolean", "description": "Only headings, as a table of contents" },
+                    "branch": { "type": "string", "description": "Render another branch's head" },
+                    "budget": { "type": "integer", "description": "Leave out files until the context fits in this many tokens" },
+                    "strategy": { "type": "string", "enum": ["recent", "priority", "pinned-first"] },
+                    "diff_only": { "type": "boolean", "description": "Only diffs of what changed since the last commit" },
+                    "group_by": { "type": "string", "enum": ["none", "domain"] },
+                },
+            },
+        },
+        {
+            "name": "diff",
+            "description": "Files changed between two commits, or since a commit (default HEAD)",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "source": { "type": "string" },
+                    "target": { "type": "string", "description": "Omit to compare with the working directory" },
+                    "domain": { "type": "string" },
+                },
+            },
+        },
+        {
+            "name": "summary",
+            "description": "Vault overview: branches, domains and uncommitted files",
+            "inputSchema": no_args,
+        },
+    ])
+}
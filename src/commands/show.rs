@@ -0,0 +1,63 @@
+use crate::errors::*;
+use crate::storage::Storage;
+use crate::commands::diff::{changed_files, write_patch, write_stat, Side, DEFAULT_RENAME_THRESHOLD};
+use crate::utils::*;
+use colored::Colorize;
+use std::io::Write;
+
+/// Print one commit's details and its changes against the (first) parent
+pub fn show(reference: &str, stat: bool, name_only: bool, context_lines: usize) -> Result<()> {
+    let vault_root = find_vault_root()?;
+    let storage = Storage::new(vault_root);
+    let commit = storage.resolve_commit(reference)?;
+
+    let parent = match commit.parent {
+        Some(ref hash) => storage.find_commit(hash)?,
+        None => None,
+    };
+
+    println!("{} {}", "commit".yellow(), commit.hash.yellow());
+    if let (Some(first), Some(second)) = (&commit.parent, &commit.merge_parent) {
+        println!("{}  {} {}", "Merge:".bold(), short_hash(first), short_hash(second));
+    }
+    println!("{} {}", "Author:".bold(), commit.author.display());
+    println!(
+        "{}   {}",
+        "Date:".bold(),
+        commit.timestamp.format("%a %b %d %H:%M:%S %Y")
+    );
+    println!();
+    for line in commit.message.lines() {
+        println!("    {}", line);
+    }
+    println!();
+    println!(
+        "    Context: {} domains loaded, ~{} tokens",
+        commit.context_summary.domains_loaded.len(),
+        commit.context_summary.token_estimate
+    );
+    println!();
+
+    if commit.merge_parent.is_some() {
+        println!("{}", "Changes shown against the first parent".dimmed());
+    }
+
+    let from = match parent {
+        Some(ref p) => Side::Commit(p),
+        None => Side::Empty,
+    };
+    let to = Side::Commit(&commit);
+    let mut out = std::io::stdout();
+
+    if name_only {
+        for change in changed_files(&storage, &from, &to, None)? {
+            writeln!(out, "{}", change.path.display())?;
+        }
+    } else if stat {
+        write_stat(&mut out, &storage, &from, &to, None)?;
+    } else {
+        write_patch(&mut out, &storage, &from, &to, None, context_lines, DEFAULT_RENAME_THRESHOLD)?;
+    }
+
+    Ok(())
+}
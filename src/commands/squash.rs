@@ -0,0 +1,151 @@
+use crate::errors::*;
+use crate::models::Commit;
+use crate::storage::Storage;
+use crate::context::ContextManager;
+use crate::utils::*;
+use chrono::Utc;
+use colored::Colorize;
+use std::collections::HashSet;
+
+/// Collapse the last `count` commits on the current branch into one commit
+/// holding the current working state. The replaced commits are dropped from
+/// the branch log, so their snapshots are left for `gnu gc`.
+pub fn squash(count: usize, message: Option<String>) -> Result<()> {
+    let vault_root = find_vault_root()?;
+    let storage = Storage::new(vault_root.clone());
+    let _lock = storage.lock()?;
+    let context_mgr = ContextManager::new(Storage::new(vault_root.clone()));
+
+    if count < 2 {
+        return Err(GitnuError::Other("Squash needs at least 2 commits".to_string()));
+    }
+
+    let branch = storage.current_branch()?;
+    let head = storage.get_head_commit()?
+        .ok_or_else(|| GitnuError::Other(format!("Branch '{}' has no commits", branch)))?;
+
+    // Follow first parents back from HEAD; every squashed commit must live in this branch's log
+    let log = storage.read_commits(&branch)?;
+    let mut squashed = Vec::new();
+    let mut current = Some(head);
+    while squashed.len() < count {
+        let commit = match current {
+            Some(c) => c,
+            None => {
+                return Err(GitnuError::Other(format!(
+                    "Branch '{}' only has {} to squash",
+                    branch,
+                    plural(squashed.len(), "commit")
+                )));
+            }
+        };
+        if !log.iter().any(|c| c.hash == commit.hash) {
+            return Err(GitnuError::Other(format!(
+                "Cannot squash past {}: it was made on another branch\n  Branch '{}' has {} of its own",
+                short_hash(&commit.hash),
+                branch,
+                plural(squashed.len(), "commit")
+            )));
+        }
+        if commit.merge_parent.is_some() {
+            return Err(GitnuError::Other(format!(
+                "Cannot squash merge commit {} \"{}\"",
+                short_hash(&commit.hash),
+                commit.subject()
+            )));
+        }
+        current = match commit.parent {
+            Some(ref parent) => storage.find_commit(parent)?,
+            None => None,
+        };
+        squashed.push(commit);
+    }
+    squashed.reverse();
+    let parent = current;
+
+    let hashes: HashSet<&str> = squashed.iter().map(|c| c.hash.as_str()).collect();
+    check_not_shared(&storage, &branch, &hashes)?;
+
+    let message = message.unwrap_or_else(|| {
+        squashed.iter().map(|c| c.message.as_str()).collect::<Vec<_>>().join("\n\n")
+    });
+
+    let parent_hash = parent.as_ref().map(|c| c.hash.clone());
+    let snapshot = storage.write_tree()?;
+    let mut commit_data = Vec::new();
+    commit_data.extend_from_slice(b"tree ");
+    commit_data.extend_from_slice(snapshot.tree.as_bytes());
+    commit_data.extend_from_slice(b"\n");
+    if let Some(ref parent) = parent_hash {
+        commit_data.extend_from_slice(b"parent ");
+        commit_data.extend_from_slice(parent.as_bytes());
+        commit_data.extend_from_slice(b"\n");
+    }
+    commit_data.extend_from_slice(message.as_bytes());
+    commit_data.extend_from_slice(b"\n");
+    commit_data.extend_from_slice(Utc::now().to_rfc3339().as_bytes());
+
+    let hash = compute_hash(&commit_data);
+    let snapshot_path = storage.record_snapshot(&hash, &snapshot)?;
+
+    let newest = &squashed[squashed.len() - 1];
+    let commit = Commit {
+        hash: hash.clone(),
+        parent: parent_hash,
+        merge_parent: None,
+        timestamp: Utc::now(),
+        author: newest.author.clone(),
+        message,
+        context_summary: context_mgr.calculate_commit_summary(parent.as_ref())?,
+        snapshot_path: relative_path(&vault_root, &snapshot_path),
+        index: Some(storage.load_index()?),
+    };
+
+    let mut rewritten: Vec<Commit> = log.into_iter()
+        .filter(|c| !hashes.contains(c.hash.as_str()))
+        .collect();
+    rewritten.push(commit.clone());
+    storage.write_commits(&branch, &rewritten)?;
+    storage.update_branch_ref(&branch, &hash, &format!("squash: {} commits", squashed.len()))?;
+
+    println!(
+        "{} {} into {} \"{}\"",
+        "Squashed".green(),
+        plural(squashed.len(), "commit"),
+        short_hash(&hash).yellow(),
+        commit.subject()
+    );
+    for old in &squashed {
+        println!("  - {} {}", short_hash(&old.hash).dimmed(), old.subject());
+    }
+    println!("  Old snapshots stay in .gitnu/objects/ until 'gnu gc'");
+
+    Ok(())
+}
+
+/// Refuse to drop commits another branch builds on or points at
+fn check_not_shared(storage: &Storage, branch: &str, hashes: &HashSet<&str>) -> Result<()> {
+    let shared = |other: &str| -> GitnuError {
+        GitnuError::Other(format!(
+            "Cannot squash: branch '{}' shares these commits\n  Delete or rebuild '{}' first",
+            other, other
+        ))
+    };
+
+    for other in storage.list_branches()? {
+        if other != branch && storage.read_branch_ref(&other)?.is_some_and(|h| hashes.contains(h.as_str())) {
+            return Err(shared(&other));
+        }
+    }
+    // Logs of deleted branches count too, since merges may still reach them
+    for other in storage.list_commit_logs()? {
+        let builds_on = storage.read_commits(&other)?.iter().any(|c| {
+            c.parent.as_deref().is_some_and(|p| hashes.contains(p))
+                || c.merge_parent.as_deref().is_some_and(|p| hashes.contains(p))
+        });
+        if other != branch && builds_on {
+            return Err(shared(&other));
+        }
+    }
+    Ok(())
+}
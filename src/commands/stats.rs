@@ -0,0 +1,201 @@
+use crate::errors::*;
+use crate::models::*;
+use crate::storage::Storage;
+use crate::utils::*;
+use colored::Colorize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::PathBuf;
+
+pub fn stats(json: bool) -> Result<()> {
+    let vault_root = find_vault_root()?;
+    let storage = Storage::new(vault_root);
+
+    // Commits reachable from each live branch, including those it was created from
+    let mut branches = storage.list_branches()?;
+    branches.sort();
+    let graph = storage.commit_graph()?;
+    let mut commits_per_branch = BTreeMap::new();
+    for branch in &branches {
+        let count = match storage.read_branch_ref(branch)? {
+            Some(head) => graph.ancestry(&head).len(),
+            None => 0,
+        };
+        commits_per_branch.insert(branch.clone(), count);
+    }
+
+    // Every distinct commit in history, including deleted branches' logs
+    let mut seen = HashSet::new();
+    let mut commits: Vec<Commit> = Vec::new();
+    for log in storage.list_commit_logs()? {
+        for commit in storage.read_commits(&log)? {
+            if seen.insert(commit.hash.clone()) {
+                commits.push(commit);
+            }
+        }
+    }
+
+    let mut tracked: HashSet<PathBuf> = HashSet::new();
+    let mut snapshots = 0;
+    let mut edits: HashMap<PathBuf, usize> = HashMap::new();
+    for commit in &commits {
+        if let Some(manifest) = storage.read_manifest(&commit.hash)? {
+            snapshots += 1;
+            tracked.extend(manifest.files.into_iter().map(|f| f.path));
+        }
+        for path in &commit.context_summary.files_modified {
+            *edits.entry(path.clone()).or_default() += 1;
+        }
+    }
+
+    let largest_file = match storage.get_head_commit()? {
+        Some(head) => storage.read_manifest(&head.hash)?
+            .and_then(|m| m.files.into_iter().max_by_key(|f| f.size)),
+        None => None,
+    };
+    let most_edited = edits.into_iter()
+        .max_by(|a, b| a.1.cmp(&b.1).then_with(|| b.0.cmp(&a.0)));
+
+    // Cadence over the span of history, counting a partial day as one
+    let first = commits.iter().map(|c| c.timestamp).min();
+    let last = commits.iter().map(|c| c.timestamp).max();
+    let days = match (first, last) {
+        (Some(first), Some(last)) => (last - first).num_days() + 1,
+        _ => 1,
+    };
+    let commits_per_day = commits.len() as f64 / days as f64;
+
+    let objects_size = dir_size(&storage.objects_dir());
+
+    if json {
+        let report = serde_json::json!({
+            "commits_per_branch": commits_per_branch,
+            "total_commits": commits.len(),
+            "files_tracked": tracked.len(),
+            "snapshots": snapshots,
+            "objects_size": objects_size,
+            "largest_file": largest_file.as_ref().map(|f| serde_json::json!({
+                "path": f.path,
+                "size": f.size,
+            })),
+            "most_edited_file": most_edited.as_ref().map(|(path, count)| serde_json::json!({
+                "path": path,
+                "edits": count,
+            })),
+            "commits_per_day": commits_per_day,
+        });
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+
+    println!("{}", "Vault statistics".bold());
+    println!();
+
+    println!("{}", "Commits per branch:".bold());
+    for (branch, count) in &commits_per_branch {
+        println!("  {:<20} {}", branch.green(), count);
+    }
+    println!();
+
+    println!("Total commits:     {}", commits.len());
+    println!("Files tracked:     {}", tracked.len());
+    println!("Snapshots:         {}", snapshots);
+    println!("Object store size: {}", format_size(objects_size));
+    match largest_file {
+        Some(f) => println!("Largest file:      {} ({})", f.path.display(), format_size(f.size)),
+        None => println!("Largest file:      {}", "none".dimmed()),
+    }
+    match most_edited {
+        Some((path, count)) => println!(
+            "Most edited:       {} ({} edit{})",
+            path.display(),
+            count,
+            if count == 1 { "" } else { "s" }
+        ),
+        None => println!("Most edited:       {}", "none".dimmed()),
+    }
+    println!(
+        "Commit cadence:    {:.1} commits/day over {} day{}",
+        commits_per_day,
+        days,
+        if days == 1 { "" } else { "s" }
+    );
+
+    Ok(())
+}
+
+/// Token estimate of every commit on the current branch, oldest first,
+/// following first parents from HEAD, with a sparkline and per-commit deltas
+pub fn token_trend(json: bool) -> Result<()> {
+    let vault_root = find_vault_root()?;
+    let storage = Storage::new(vault_root);
+
+    let graph = storage.commit_graph()?;
+    let mut commits: Vec<&Commit> = match storage.get_head_commit()? {
+        Some(head) => graph.first_parents(&head.hash).collect(),
+        None => Vec::new(),
+    };
+    commits.reverse();
+
+    let tokens: Vec<usize> = commits.iter().map(|c| c.context_summary.token_estimate).collect();
+    let deltas: Vec<i64> = tokens.iter()
+        .enumerate()
+        .map(|(i, &t)| t as i64 - i.checked_sub(1).map_or(0, |p| tokens[p] as i64))
+        .collect();
+
+    if json {
+        let points: Vec<_> = commits.iter().zip(&deltas)
+            .map(|(commit, delta)| serde_json::json!({
+                "hash": commit.hash,
+                "timestamp": commit.timestamp,
+                "subject": commit.subject(),
+                "tokens": commit.context_summary.token_estimate,
+                "delta": delta,
+            }))
+            .collect();
+        let report = serde_json::json!({
+            "branch": storage.read_head()?.name(),
+            "commits": points,
+        });
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+
+    if commits.is_empty() {
+        println!("{}", "No commits yet".dimmed());
+        return Ok(());
+    }
+
+    println!("{}", "Token estimate over time".bold());
+    println!("  {}  {} -> {} tokens", sparkline(&tokens).cyan(), tokens[0], tokens[tokens.len() - 1]);
+    println!();
+    for (commit, delta) in commits.iter().zip(&deltas) {
+        let delta = match delta {
+            d if *d > 0 => format!("{:+}", d).red(),
+            d if *d < 0 => format!("{:+}", d).green(),
+            _ => "0".dimmed(),
+        };
+        println!(
+            "{}  {}  {:>8}  {:>8}  {}",
+            commit.timestamp.format("%Y-%m-%d"),
+            short_hash(&commit.hash).yellow(),
+            commit.context_summary.token_estimate,
+            delta,
+            commit.subject()
+        );
+    }
+
+    Ok(())
+}
+
+/// One block character per value, scaled between the smallest and largest
+fn sparkline(values: &[usize]) -> String {
+    const BLOCKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    let min = values.iter().copied().min().unwrap_or(0);
+    let max = values.iter().copied().max().unwrap_or(0);
+    values.iter()
+        .map(|&v| match max - min {
+            0 => BLOCKS[0],
+            range => BLOCKS[(v - min) * (BLOCKS.len() - 1) / range],
+        })
+        .collect()
+}
@@ -1,79 +1,100 @@
 use crate::errors::*;
+use crate::models::*;
 use crate::storage::Storage;
 use crate::context::ContextManager;
 use crate::utils::*;
-use colored::Colorize;
+use colored::{ColoredString, Colorize};
 
-pub fn status() -> Result<()> {
+/// Print the working state; `include_unchanged` lists files that were saved
+/// since the last commit without their content changing
+pub fn status(include_unchanged: bool) -> Result<()> {
     let vault_root = find_vault_root()?;
-    let storage = Storage::new(vault_root.clone());
-    let context_mgr = ContextManager::new(Storage::new(vault_root.clone()));
+    let report = status_report(&Storage::new(vault_root))?;
 
     // Get current branch
-    let current_branch = storage.read_head()?;
-    println!("{} {}", "On branch:".bold(), current_branch.green());
+    if report.detached {
+        println!(
+            "{} {}",
+            "HEAD detached at".bold().yellow(),
+            short_hash(&report.branch).yellow()
+        );
+    } else {
+        println!("{} {}", "On branch:".bold(), report.branch.green());
+    }
 
     // Get last commit
-    if let Some(commit) = storage.get_head_commit()? {
-        let short_hash = &commit.hash[..7];
+    if let Some(ref commit) = report.last_commit {
+        let short_hash = short_hash(&commit.hash);
         let time_ago = relative_time(&commit.timestamp);
         println!(
             "{} {} \"{}\" ({})",
             "Last commit:".bold(),
             short_hash.yellow(),
-            commit.message,
+            commit.subject(),
             time_ago.dimmed()
         );
     } else {
         println!("{}", "No commits yet".dimmed());
     }
 
+    if let Some(ref merge) = report.merge {
+        let unresolved = merge.unresolved().count();
+        println!(
+            "{} {} of {} resolved",
+            "Merge in progress:".bold().yellow(),
+            merge.files.len() - unresolved,
+            plural(merge.files.len(), "conflicted file")
+        );
+        if unresolved > 0 {
+            println!("  Run: gnu mergetool");
+        }
+    }
+
     println!();
 
     // Show active context
-    let index = storage.load_index()?;
-    let all_files = context_mgr.get_all_files()?;
-    
-    if !all_files.is_empty() {
-        let summary = context_mgr.calculate_context_summary(storage.get_head_commit()?.as_ref())?;
+    if report.tracked_files > 0 {
+        let over_budget = report.token_estimate > report.max_tokens;
         println!(
-            "{} (estimated {} tokens):",
+            "{} (estimated {} of {} tokens):",
             "Active Context".bold(),
-            summary.token_estimate.to_string().cyan()
+            budget_colored(report.token_estimate, report.max_tokens),
+            report.max_tokens
         );
+        if over_budget {
+            println!(
+                "  {}",
+                "Over the context.max_tokens budget: run 'gnu unload --all' or raise max_tokens in .gitnu/config.toml".red()
+            );
+        }
 
         // Show loaded files
-        if !index.loaded.is_empty() || !all_files.is_empty() {
-            println!("  {}:", "Loaded".green());
-            let files_to_show: Vec<_> = if !index.loaded.is_empty() {
-                index.loaded.iter().collect()
-            } else {
-                all_files.iter().collect()
-            };
-            
-            for file in files_to_show.iter().take(10) {
-                println!("    - {}", file.display().to_string().dimmed());
-            }
-            if files_to_show.len() > 10 {
-                println!("    {} ({} more files)", "...".dimmed(), files_to_show.len() - 10);
-            }
+        println!("  {}:", "Loaded".green());
+        for file in report.active_files.iter().take(10) {
+            println!("    - {}", file.display().to_string().dimmed());
+        }
+        if report.active_files.len() > 10 {
+            println!("    {} ({} more files)", "...".dimmed(), report.active_files.len() - 10);
         }
 
-        // Show pinned files
-        if !index.pinned.is_empty() {
+        // Show pinned files and patterns
+        if !report.pinned.is_empty() || !report.pin_patterns.is_empty() {
             println!("  {}:", "Pinned".blue());
-            for file in &index.pinned {
+            for file in &report.pinned {
                 println!("    - {}", file.display().to_string().dimmed());
             }
+            for pin in &report.pin_patterns {
+                println!("    - {} ({} files)", pin.pattern.dimmed(), pin.matched);
+            }
         }
 
         println!();
     }
 
     // Show staged files
-    if !index.staged.is_empty() {
+    if !report.staged.is_empty() {
         println!("{}", "Staged (ready to include):".bold());
-        for staged in &index.staged {
+        for staged in &report.staged {
             println!(
                 "    - {} [{}] \"{}\"",
                 staged.path.display().to_string().dimmed(),
@@ -85,38 +106,114 @@ pub fn status() -> Result<()> {
     }
 
     // Show modified files
-    let modified = context_mgr.get_modified_files()?;
-    if !modified.is_empty() {
+    if !report.modified.is_empty() {
         println!("{}", "Modified since last commit:".bold());
-        for file in &modified {
-            // Get line count change
-            let full_path = vault_root.join(file);
-            if full_path.exists() {
-                if let Ok(content) = std::fs::read_to_string(&full_path) {
-                    let lines = content.lines().count();
-                    println!("    - {} (+{} lines)", file.display().to_string().dimmed(), lines);
-                }
+        for file in &report.modified {
+            if let Some(lines) = file.lines {
+                println!("    - {} (+{} lines)", file.path.display().to_string().dimmed(), lines);
+            }
+        }
+        println!();
+    }
+
+    // Show deleted files
+    if !report.removed.is_empty() {
+        println!("{}", "Deleted since last commit:".bold());
+        for file in &report.removed {
+            println!("    - {}", file.display().to_string().red());
+        }
+        println!();
+    }
+
+    if report.index_changed {
+        println!("{}", "Loaded context changed since last commit (load, pin or exclude)".bold());
+        println!("    Commit to keep it with this branch; checkout restores the recorded one");
+        println!();
+    }
+
+    // Saving a file without changing it bumps its mtime but isn't a change
+    if !report.touched.is_empty() {
+        if include_unchanged {
+            println!("{}", "Saved but unchanged (not modified):".bold());
+            for file in &report.touched {
+                println!("    - {}", file.display().to_string().dimmed());
             }
+        } else {
+            println!(
+                "{}",
+                format!(
+                    "{} saved since the last commit with identical content (not modified); list with --include-unchanged",
+                    plural(report.touched.len(), "file")
+                )
+                .dimmed()
+            );
         }
         println!();
     }
 
     // Show untracked domains
+    if !report.untracked_domains.is_empty() {
+        println!("{}", "Untracked domains:".bold());
+        for domain in &report.untracked_domains {
+            println!("    - domains/{} ({} files)", domain.name.dimmed(), domain.file_count);
+        }
+    }
+
+    Ok(())
+}
+
+/// Collect the working state shown by `gnu status`
+pub fn status_report(storage: &Storage) -> Result<StatusReport> {
+    let context_mgr = ContextManager::new(Storage::new(storage.vault_root.clone()));
+    let head = storage.read_head()?;
+    let last_commit = storage.get_head_commit()?;
+
+    let index = storage.load_index()?;
+    let all_files = context_mgr.get_all_files()?;
+    let summary = context_mgr.calculate_context_summary(last_commit.as_ref())?;
+
+    let active_files = if !index.loaded.is_empty() {
+        index.loaded.clone()
+    } else {
+        all_files.clone()
+    };
+
+    let config = storage.load_config()?;
+    let mut pin_patterns = Vec::new();
+    for pattern in &config.pins.always_load {
+        let matched = context_mgr.expand_patterns(std::slice::from_ref(pattern))?.len();
+        pin_patterns.push(PinPattern { pattern: pattern.clone(), matched });
+    }
+
+    // Only genuinely changed files are re-read for their line counts
+    let modified = summary.files_modified.iter()
+        .chain(&summary.files_added)
+        .filter(|file| storage.vault_root.join(file).exists())
+        .map(|file| {
+            let lines = std::fs::read_to_string(storage.vault_root.join(file))
+                .ok()
+                .map(|content| content.lines().count());
+            ModifiedFile { path: file.clone(), lines }
+        })
+        .collect();
+    let removed = summary.files_removed.clone();
+    let touched = context_mgr.touched_files(last_commit.as_ref())?;
+    let index_changed = storage.index_changed_since(last_commit.as_ref())?;
+
+    // Domains with files that the index doesn't know about
+    let mut untracked_domains = Vec::new();
     let domains_dir = storage.domains_dir();
     if domains_dir.exists() {
-        let mut untracked_domains = Vec::new();
+        let include_hidden = storage.include_hidden()?;
         for entry in std::fs::read_dir(&domains_dir)? {
             let entry = entry?;
-            if entry.file_type()?.is_dir() {
+            if entry.file_type()?.is_dir() && (include_hidden || !is_hidden(&entry.file_name())) {
                 let domain_name = entry.file_name().to_string_lossy().to_string();
                 
                 // Check if this domain has any tracked files
-                let has_files = walkdir::WalkDir::new(entry.path())
-                    .into_iter()
-                    .filter_map(|e| e.ok())
-                    .any(|e| e.file_type().is_file());
+                let file_count = storage.walk_files(&entry.path())?.len();
                 
-                if has_files && !domain_name.starts_with('_') {
+                if file_count > 0 && !domain_name.starts_with('_') {
                     // Check if in index
                     let domain_tracked = all_files.iter().any(|f| {
                         f.to_str()
@@ -125,24 +222,41 @@ pub fn status() -> Result<()> {
                     });
                     
                     if !domain_tracked {
-                        untracked_domains.push(domain_name);
+                        untracked_domains.push(DomainSummary { name: domain_name, file_count });
                     }
                 }
             }
         }
-
-        if !untracked_domains.is_empty() {
-            println!("{}", "Untracked domains:".bold());
-            for domain in untracked_domains {
-                let count = walkdir::WalkDir::new(domains_dir.join(&domain))
-                    .into_iter()
-                    .filter_map(|e| e.ok())
-                    .filter(|e| e.file_type().is_file())
-                    .count();
-                println!("    - domains/{} ({} files)", domain.dimmed(), count);
-            }
-        }
     }
 
-    Ok(())
+    Ok(StatusReport {
+        branch: head.name().to_string(),
+        detached: head.branch().is_none(),
+        last_commit,
+        token_estimate: summary.token_estimate,
+        max_tokens: config.context.max_tokens,
+        tracked_files: all_files.len(),
+        active_files,
+        pinned: index.pinned,
+        pin_patterns,
+        staged: index.staged,
+        modified,
+        removed,
+        touched,
+        index_changed,
+        untracked_domains,
+        merge: storage.read_merge_state()?,
+    })
+}
+
+/// A token count colored against the budget: yellow from 80%, red once exceeded
+pub(crate) fn budget_colored(tokens: usize, max_tokens: usize) -> ColoredString {
+    let text = tokens.to_string();
+    if tokens > max_tokens {
+        text.red()
+    } else if tokens * 5 > max_tokens * 4 {
+        text.yellow()
+    } else {
+        text.cyan()
+    }
 }
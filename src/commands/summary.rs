@@ -1,29 +1,41 @@
 use crate::errors::*;
+use crate::models::*;
 use crate::storage::Storage;
 use crate::context::ContextManager;
 use crate::utils::*;
 use colored::Colorize;
+use std::collections::HashSet;
 
-pub fn summary() -> Result<()> {
+pub fn summary(json: bool, lines: usize) -> Result<()> {
     let vault_root = find_vault_root()?;
     let storage = Storage::new(vault_root.clone());
     let context_mgr = ContextManager::new(Storage::new(vault_root.clone()));
 
+    if json {
+        let report = summary_report(&storage)?;
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+
     println!("{}", "# gitnu Summary".bold());
     println!();
 
     // Current State
     println!("{}", "## Current State".bold());
-    let current_branch = storage.read_head()?;
-    println!("- Branch: {}", current_branch.green());
+    let head = storage.read_head()?;
+    let current_branch = head.name().to_string();
+    match head {
+        Head::Branch(ref name) => println!("- Branch: {}", name.green()),
+        Head::Detached(ref hash) => println!("- HEAD detached at {}", short_hash(hash).yellow()),
+    }
 
     if let Some(commit) = storage.get_head_commit()? {
-        let short_hash = &commit.hash[..7];
+        let short_hash = short_hash(&commit.hash);
         let time_ago = relative_time(&commit.timestamp);
         println!(
             "- Last commit: {} \"{}\" ({})",
             short_hash.yellow(),
-            commit.message,
+            commit.subject(),
             time_ago.dimmed()
         );
         println!(
@@ -40,6 +52,45 @@ pub fn summary() -> Result<()> {
 
     println!();
 
+    // Recent History
+    println!("{}", "## Recent History".bold());
+    let commits = match head {
+        Head::Branch(ref name) => storage.read_commits(name)?,
+        Head::Detached(ref hash) => {
+            let mut ancestry = storage.ancestry(hash)?;
+            ancestry.reverse();
+            ancestry
+        }
+    };
+    if commits.is_empty() {
+        println!("- No commits yet");
+    } else {
+        for commit in commits.iter().rev().take(lines) {
+            println!(
+                "- {} {} ({})",
+                short_hash(&commit.hash).yellow(),
+                commit.subject(),
+                relative_time(&commit.timestamp).dimmed()
+            );
+        }
+        let contributions: Vec<String> = AuthorStats::collect(&commits)
+            .iter()
+            .map(|s| {
+                format!(
+                    "{} {} ({}, +{}/-{} lines)",
+                    s.author,
+                    plural(s.commits, "commit"),
+                    plural(s.files_changed, "file"),
+                    s.insertions,
+                    s.deletions
+                )
+            })
+            .collect();
+        println!("- Contributions: {}", contributions.join(", "));
+    }
+
+    println!();
+
     // What You Know
     println!("{}", "## What You Know".bold());
     let domains_dir = storage.domains_dir();
@@ -74,6 +125,17 @@ pub fn summary() -> Result<()> {
     // What's Changed
     println!("{}", "## What's Changed".bold());
     let modified = context_mgr.get_modified_files()?;
+    if let Some(pending) = pending_changes(&context_mgr, storage.get_head_commit()?.as_ref())? {
+        println!(
+            "- Pending: {} modified, {} added, {} removed ({:+}/-{} lines), {:+} tokens since last commit",
+            pending.modified,
+            pending.added,
+            pending.removed,
+            pending.lines_added,
+            pending.lines_removed,
+            pending.token_delta
+        );
+    }
     if !modified.is_empty() {
         println!("- Modified files:");
         for file in &modified {
@@ -87,20 +149,117 @@ pub fn summary() -> Result<()> {
 
     // Available Branches
     println!("{}", "## Available Branches".bold());
-    let current_branch = storage.read_head()?;
     let branches = storage.list_branches()?;
     for branch in branches {
+        let description = storage.read_branch_meta(&branch)?
+            .and_then(|m| m.description)
+            .map(|d| format!(": {}", d))
+            .unwrap_or_default();
         if branch == current_branch {
-            println!("- {} (current)", branch.green());
+            println!("- {} (current){}", branch.green(), description);
         } else if let Some(hash) = storage.read_branch_ref(&branch)? {
             if let Some(_commit) = storage.find_commit(&hash)? {
                 let commits = storage.read_commits(&current_branch)?;
                 let branch_commits = storage.read_commits(&branch)?;
                 let diverged = branch_commits.len().abs_diff(commits.len());
-                println!("- {} (diverged {} commits)", branch, diverged);
+                println!("- {} (diverged {} commits){}", branch, diverged, description);
             }
         }
     }
 
     Ok(())
 }
+
+/// Collect the structured summary emitted by `gnu summary --json`
+pub fn summary_report(storage: &Storage) -> Result<SummaryReport> {
+    let context_mgr = ContextManager::new(Storage::new(storage.vault_root.clone()));
+    let current_head = storage.read_head()?;
+    let head_commit = storage.get_head_commit()?;
+
+    let head = head_commit.as_ref().map(|c| HeadSummary {
+        hash: c.hash.clone(),
+        message: c.message.clone(),
+        timestamp: c.timestamp,
+        author: c.author.clone(),
+        token_estimate: c.context_summary.token_estimate,
+    });
+
+    // Domains with file counts
+    let mut domains = Vec::new();
+    let domains_dir = storage.domains_dir();
+    if domains_dir.exists() {
+        let include_hidden = storage.include_hidden()?;
+        for entry in std::fs::read_dir(&domains_dir)? {
+            let entry = entry?;
+            if entry.file_type()?.is_dir() && (include_hidden || !is_hidden(&entry.file_name())) {
+                let file_count = storage.walk_files(&entry.path())?.len();
+                domains.push(DomainSummary {
+                    name: entry.file_name().to_string_lossy().to_string(),
+                    file_count,
+                });
+            }
+        }
+    }
+    domains.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let mut uncommitted_files = context_mgr.get_modified_files()?;
+    uncommitted_files.sort();
+    let pending = pending_changes(&context_mgr, head_commit.as_ref())?;
+
+    // Branches with ahead/behind relative to the current branch
+    let graph = storage.commit_graph()?;
+    let current_ancestry: HashSet<&str> = match head_commit {
+        Some(ref c) => graph.ancestry(&c.hash).into_iter().map(|c| c.hash.as_str()).collect(),
+        None => HashSet::new(),
+    };
+    let mut branch_names = storage.list_branches()?;
+    branch_names.sort();
+
+    let mut branches = Vec::new();
+    for name in branch_names {
+        let head = storage.read_branch_ref(&name)?;
+        let ancestry: HashSet<&str> = match head {
+            Some(ref hash) => graph.ancestry(hash).into_iter().map(|c| c.hash.as_str()).collect(),
+            None => HashSet::new(),
+        };
+        branches.push(BranchSummary {
+            current: current_head.is_branch(&name),
+            ahead: ancestry.difference(&current_ancestry).count(),
+            behind: current_ancestry.difference(&ancestry).count(),
+            description: storage.read_branch_meta(&name)?.and_then(|m| m.description),
+            name,
+            head,
+        });
+    }
+
+    Ok(SummaryReport {
+        schema_version: SummaryReport::SCHEMA_VERSION,
+        branch: current_head.name().to_string(),
+        detached: current_head.branch().is_none(),
+        head,
+        domains,
+        uncommitted_files,
+        pending,
+        branches,
+    })
+}
+
+/// Counts of files, lines and tokens changed since `head`, or `None` when
+/// the working directory matches it
+fn pending_changes(context_mgr: &ContextManager, head: Option<&Commit>) -> Result<Option<PendingChanges>> {
+    let mut current = context_mgr.calculate_context_summary(head)?;
+    if current.files_changed() == 0 {
+        return Ok(None);
+    }
+    current.file_stats = context_mgr.line_stats(head, &current)?;
+    let (lines_added, lines_removed) = current.line_totals();
+    let recorded = head.map_or(0, |c| c.context_summary.token_estimate);
+    Ok(Some(PendingChanges {
+        modified: current.files_modified.len(),
+        added: current.files_added.len(),
+        removed: current.files_removed.len(),
+        lines_added,
+        lines_removed,
+        token_delta: current.token_estimate as i64 - recorded as i64,
+    }))
+}
@@ -0,0 +1,100 @@
+use crate::errors::*;
+use crate::models::ReflogEntry;
+use crate::storage::Storage;
+use crate::context::ContextManager;
+use crate::utils::*;
+use colored::Colorize;
+
+/// Move the current branch back to where it was before its last operation
+pub fn undo(soft: bool) -> Result<()> {
+    let vault_root = find_vault_root()?;
+    let storage = Storage::new(vault_root.clone());
+    let _lock = storage.lock()?;
+    let context_mgr = ContextManager::new(Storage::new(vault_root));
+
+    let branch = storage.current_branch()?;
+    let reflog = storage.read_reflog(&branch)?;
+    let entry = match last_undoable(&reflog) {
+        Some(e) => e,
+        None => {
+            return Err(GitnuError::Other(format!(
+                "Nothing to undo on branch '{}'",
+                branch
+            )));
+        }
+    };
+    let previous = match entry.old {
+        Some(ref hash) => hash.clone(),
+        None => {
+            return Err(GitnuError::Other(format!(
+                "Cannot undo '{}': it created branch '{}'",
+                entry.action, branch
+            )));
+        }
+    };
+
+    // History rewrites (squash) drop the old commits from the log
+    if storage.find_commit(&previous)?.is_none() {
+        return Err(GitnuError::Other(format!(
+            "Cannot undo '{}': commit {} is no longer in any log",
+            entry.action,
+            short_hash(&previous)
+        )));
+    }
+
+    if !soft && context_mgr.has_uncommitted_changes()? {
+        return Err(GitnuError::Other(
+            "Uncommitted changes would be lost\n  Commit them first: gnu commit \"message\"\n  Or keep the working directory as-is: gnu undo --soft".to_string(),
+        ));
+    }
+
+    // Restore before moving the ref, so a failed restore leaves the branch where it was
+    if !soft {
+        if !storage.has_snapshot(&previous)? {
+            return Err(GitnuError::Other(format!(
+                "Cannot undo '{}': the snapshot of {} is missing\n  Run: gnu doctor",
+                entry.action,
+                short_hash(&previous)
+            )));
+        }
+        storage.restore_snapshot(&previous)?;
+        if let Some(commit) = storage.find_commit(&previous)? {
+            storage.restore_index(&commit)?;
+        }
+    }
+    storage.update_branch_ref(&branch, &previous, &format!("undo: {}", entry.action))?;
+
+    match storage.find_commit(&entry.new)? {
+        Some(commit) if entry.action.starts_with("commit") || entry.action.starts_with("merge") => {
+            println!(
+                "{} {}: {}",
+                "Undid commit".yellow(),
+                short_hash(&commit.hash).yellow(),
+                commit.subject()
+            );
+        }
+        _ => println!("{} {}", "Undid".yellow(), entry.action),
+    }
+    println!("  {} is now at {}", branch.green(), short_hash(&previous).yellow());
+    if soft {
+        println!("  Working directory unchanged (--soft)");
+    }
+
+    Ok(())
+}
+
+/// Most recent reflog entry that hasn't already been undone. Each undo
+/// entry cancels the closest earlier operation, so repeated undos walk back.
+fn last_undoable(reflog: &[ReflogEntry]) -> Option<&ReflogEntry> {
+    let mut pending_undos = 0;
+    for entry in reflog.iter().rev() {
+        if entry.is_undo() {
+            pending_undos += 1;
+        } else if pending_undos > 0 {
+            pending_undos -= 1;
+        } else {
+            return Some(entry);
+        }
+    }
+    None
+}
@@ -0,0 +1,123 @@
+use crate::errors::*;
+use crate::models::*;
+use crate::storage::Storage;
+use crate::context::ContextManager;
+use crate::commands::commit::create_commit;
+use crate::utils::*;
+use colored::Colorize;
+use notify::{RecursiveMode, Watcher};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::{Duration, Instant};
+
+/// How often the loop wakes up to check the debounce timer and Ctrl-C
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Watch `domains/` and commit once changes have been quiet for `interval`
+pub fn watch(interval: &str, message_prefix: &str) -> Result<()> {
+    let vault_root = find_vault_root()?;
+    let storage = Storage::new(vault_root.clone());
+    let interval = parse_duration(interval)?;
+
+    if !storage.load_config()?.context.auto_commit {
+        return Err(GitnuError::Other(
+            "context.auto_commit is disabled\n  Set auto_commit = true in .gitnu/config.toml to let 'gnu watch' commit".to_string(),
+        ));
+    }
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let handler_stop = Arc::clone(&stop);
+    ctrlc::set_handler(move || handler_stop.store(true, Ordering::SeqCst))
+        .map_err(|e| GitnuError::Other(format!("Failed to install Ctrl-C handler: {}", e)))?;
+
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx)
+        .map_err(|e| GitnuError::Other(format!("Failed to start file watcher: {}", e)))?;
+    watcher
+        .watch(&storage.domains_dir(), RecursiveMode::Recursive)
+        .map_err(|e| GitnuError::Other(format!("Failed to watch domains/: {}", e)))?;
+
+    println!(
+        "Watching {} (commit after {}s of quiet, Ctrl-C to stop)",
+        "domains/".green(),
+        interval.as_secs()
+    );
+
+    let mut last_change: Option<Instant> = None;
+    while !stop.load(Ordering::SeqCst) {
+        match rx.recv_timeout(POLL_INTERVAL) {
+            Ok(Ok(_event)) => last_change = Some(Instant::now()),
+            Ok(Err(e)) => eprintln!("{} {}", "Watch error:".red(), e),
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+            Err(mpsc::RecvTimeoutError::Disconnected) => break,
+        }
+
+        if last_change.is_some_and(|t| t.elapsed() >= interval) {
+            last_change = None;
+            commit_changes(&storage, message_prefix);
+        }
+    }
+
+    // Don't drop edits made just before Ctrl-C
+    if last_change.is_some() {
+        commit_changes(&storage, message_prefix);
+    }
+    println!("{}", "Stopped watching".dimmed());
+
+    Ok(())
+}
+
+/// Commit pending changes, reporting (not propagating) failures so the
+/// watcher keeps running
+fn commit_changes(storage: &Storage, message_prefix: &str) {
+    if let Err(e) = try_commit_changes(storage, message_prefix) {
+        eprintln!("{} {}", "Auto-commit failed:".red(), e);
+    }
+}
+
+/// Commit through `create_commit`, so the same checks as `gnu commit` apply
+/// and a failing one skips this round
+fn try_commit_changes(storage: &Storage, message_prefix: &str) -> Result<()> {
+    let _lock = storage.lock()?;
+    let context_mgr = ContextManager::new(Storage::new(storage.vault_root.clone()));
+    let parent_commit = storage.get_head_commit()?;
+    let summary = context_mgr.calculate_context_summary(parent_commit.as_ref())?;
+
+    let message = format!("{}{}", message_prefix, describe_changes(&summary));
+    let author = Author::Agent {
+        model: "gitnu-watch".to_string(),
+        session_id: None,
+    };
+    if let Some(commit) = create_commit(storage, &message, author, false)? {
+        println!(
+            "{} {} \"{}\"",
+            "Auto-committed".green(),
+            short_hash(&commit.hash).yellow(),
+            message
+        );
+    }
+
+    Ok(())
+}
+
+/// One-line description of the changed files for a generated commit message
+fn describe_changes(summary: &ContextSummary) -> String {
+    let changed: Vec<String> = summary.files_added.iter()
+        .chain(summary.files_modified.iter())
+        .chain(summary.files_removed.iter())
+        .filter_map(|p| p.file_name().map(|n| n.to_string_lossy().to_string()))
+        .collect();
+
+    const SHOWN: usize = 3;
+    let mut names = changed.iter().take(SHOWN).cloned().collect::<Vec<_>>().join(", ");
+    if changed.len() > SHOWN {
+        names.push_str(&format!(" and {} more", changed.len() - SHOWN));
+    }
+
+    format!(
+        "Update {} file{}: {}",
+        changed.len(),
+        if changed.len() == 1 { "" } else { "s" },
+        names
+    )
+}
@@ -4,62 +4,190 @@ use crate::storage::Storage;
 use crate::utils::*;
 use std::fs;
 use std::path::{Path, PathBuf};
-use walkdir::WalkDir;
+
+/// One-off include/exclude globs applied when rendering context
+#[derive(Default)]
+pub struct ContextFilter {
+    include: Option<Vec<glob::Pattern>>,
+    exclude: Option<Vec<glob::Pattern>>,
+    all: bool,
+}
+
+impl ContextFilter {
+    pub fn new(include: &[String], exclude: &[String]) -> Result<Self> {
+        Ok(ContextFilter {
+            include: build_patterns(include)?,
+            exclude: build_patterns(exclude)?,
+            all: false,
+        })
+    }
+
+    /// Ignore the loaded set and render every file in `domains/`
+    pub fn with_all(mut self, all: bool) -> Self {
+        self.all = all;
+        self
+    }
+
+    /// Whether a file passes the filter; always-load pins bypass `--include`
+    pub fn allows(&self, path: &Path, always_load: bool) -> bool {
+        if let Some(ref exclude) = self.exclude {
+            if exclude.iter().any(|p| p.matches_path(path)) {
+                return false;
+            }
+        }
+        match self.include {
+            Some(ref include) => always_load || include.iter().any(|p| p.matches_path(path)),
+            None => true,
+        }
+    }
+}
+
+/// Compiled patterns, or None when no flag was given
+fn build_patterns(patterns: &[String]) -> Result<Option<Vec<glob::Pattern>>> {
+    if patterns.is_empty() {
+        return Ok(None);
+    }
+    compile_patterns(patterns).map(Some)
+}
+
+/// Which files `gnu context --budget` keeps when not everything fits
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PackStrategy {
+    /// Most recently modified first
+    #[default]
+    Recent,
+    /// Staged files by priority, then the rest by recency
+    Priority,
+    /// Pinned and always-load files regardless of budget, then the rest by recency
+    PinnedFirst,
+}
+
+impl PackStrategy {
+    pub fn parse(strategy: &str) -> Result<Self> {
+        match strategy {
+            "recent" => Ok(PackStrategy::Recent),
+            "priority" => Ok(PackStrategy::Priority),
+            "pinned-first" => Ok(PackStrategy::PinnedFirst),
+            _ => Err(GitnuError::Other(format!(
+                "Invalid packing strategy: {}. Use 'recent', 'priority' or 'pinned-first'",
+                strategy
+            ))),
+        }
+    }
+}
+
+/// How `gnu context` arranges the files it renders
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum GroupBy {
+    /// One flat list of files
+    #[default]
+    None,
+    /// A `## Domain:` section per domain
+    Domain,
+}
+
+impl GroupBy {
+    pub fn parse(group_by: &str) -> Result<Self> {
+        match group_by {
+            "none" => Ok(GroupBy::None),
+            "domain" => Ok(GroupBy::Domain),
+            _ => Err(GitnuError::Other(format!(
+                "Invalid grouping: {}. Use 'domain' or 'none'",
+                group_by
+            ))),
+        }
+    }
+}
+
+/// Files chosen by `ContextManager::pack_files`
+#[derive(Debug, Clone, Default)]
+pub struct Packing {
+    /// Kept files, in their original order
+    pub included: Vec<PathBuf>,
+    /// Files that didn't fit, in the order they were considered
+    pub dropped: Vec<PathBuf>,
+    /// Estimated tokens of the included files once rendered
+    pub tokens: usize,
+}
 
 pub struct ContextManager {
     storage: Storage,
+    /// Read and hash every file even when its size and mtime match the parent manifest
+    full_rehash: bool,
 }
 
 impl ContextManager {
     pub fn new(storage: Storage) -> Self {
-        ContextManager { storage }
+        ContextManager { storage, full_rehash: false }
+    }
+
+    /// Don't trust size and mtime to detect unchanged files
+    pub fn with_full_rehash(mut self, full: bool) -> Self {
+        self.full_rehash = full;
+        self
     }
 
-    /// Calculate context summary for current state
+    /// Calculate context summary for current state.
+    ///
+    /// Files whose size and mtime match the previous commit's manifest are
+    /// taken to be unchanged and are not re-read. An edit that keeps both
+    /// (e.g. a tool restoring the old mtime) goes unnoticed unless the manager
+    /// was built `with_full_rehash`.
     pub fn calculate_context_summary(&self, previous_commit: Option<&Commit>) -> Result<ContextSummary> {
-        let domains_dir = self.storage.domains_dir();
         let mut domains_loaded = Vec::new();
         let mut files_modified = Vec::new();
         let mut files_added = Vec::new();
         let mut files_removed = Vec::new();
-        let mut total_content = String::new();
+        let mut binary_files = Vec::new();
+        // Same count `estimate_tokens` would see if all text files were joined with newlines
+        let mut text_bytes = 0;
+
+        let manifest = match previous_commit {
+            Some(prev) => self.storage.read_manifest_with_stats(&prev.hash)?,
+            None => None,
+        };
+        let cached: std::collections::HashMap<&Path, &FileInfo> = match manifest {
+            Some(ref m) if !self.full_rehash => m.files.iter().map(|f| (f.path.as_path(), f)).collect(),
+            _ => std::collections::HashMap::new(),
+        };
 
         // Collect current files
         let mut current_files = std::collections::HashMap::new();
-        if domains_dir.exists() {
-            for entry in WalkDir::new(&domains_dir).into_iter().filter_map(|e| e.ok()) {
-                let path = entry.path();
-                if path.is_file() {
-                    let rel_path = relative_path(&self.storage.vault_root, path);
-                    
-                    // Track domains
-                    if let Some(domain) = self.extract_domain(&rel_path) {
-                        if !domains_loaded.contains(&domain) {
-                            domains_loaded.push(domain);
-                        }
-                    }
+        for path in self.storage.domain_files()? {
+            let rel_path = relative_path(&self.storage.vault_root, &path);
 
-                    // Read content for token estimation
-                    if let Ok(content) = fs::read_to_string(path) {
-                        total_content.push_str(&content);
-                        total_content.push('\n');
-                    }
+            // Track domains
+            if let Some(domain) = self.extract_domain(&rel_path) {
+                if !domains_loaded.contains(&domain) {
+                    domains_loaded.push(domain);
+                }
+            }
 
-                    current_files.insert(rel_path.clone(), hash_file(path)?);
+            let metadata = fs::metadata(&path)?;
+            let unchanged = cached.get(rel_path.as_path()).filter(|info| {
+                info.size == metadata.len() && info.mtime.is_some() && info.mtime == mtime_nanos(&metadata)
+            });
+            let (hash, binary) = match unchanged {
+                Some(info) => (info.hash.clone(), info.binary),
+                None => {
+                    let content = fs::read(&path)?;
+                    (compute_hash(&content), is_binary(&content))
                 }
+            };
+
+            // Binary files only count as present, not toward the token estimate
+            if binary {
+                binary_files.push(rel_path.clone());
+            } else {
+                text_bytes += metadata.len() as usize + 1;
             }
+
+            current_files.insert(rel_path.clone(), hash);
         }
 
         // Compare with previous commit if available
-        if let Some(prev) = previous_commit {
-            let manifest_path = self.storage.objects_dir()
-                .join(&prev.hash)
-                .join("manifest.json");
-            
-            if manifest_path.exists() {
-                let manifest_content = fs::read_to_string(manifest_path)?;
-                let manifest: Manifest = serde_json::from_str(&manifest_content)?;
-                
+        if previous_commit.is_some() {
+            if let Some(manifest) = manifest {
                 let mut previous_files = std::collections::HashMap::new();
                 for file_info in manifest.files {
                     previous_files.insert(file_info.path.clone(), file_info.hash);
@@ -89,7 +217,7 @@ impl ContextManager {
             files_added = current_files.keys().cloned().collect();
         }
 
-        let token_estimate = estimate_tokens(&total_content);
+        let token_estimate = text_bytes / 4;
 
         Ok(ContextSummary {
             domains_loaded,
@@ -97,9 +225,47 @@ impl ContextManager {
             files_added,
             files_removed,
             token_estimate,
+            binary_files,
+            file_stats: Vec::new(),
         })
     }
 
+    /// `calculate_context_summary` plus the per-file line counts a commit
+    /// records, so log and diff --stat needn't reopen its snapshot. Only
+    /// worth the extra reads when a commit is about to be made.
+    pub fn calculate_commit_summary(&self, previous_commit: Option<&Commit>) -> Result<ContextSummary> {
+        let mut summary = self.calculate_context_summary(previous_commit)?;
+        summary.file_stats = self.line_stats(previous_commit, &summary)?;
+        Ok(summary)
+    }
+
+    /// Lines added and removed in each file `summary` lists as changed since
+    /// `previous_commit`
+    pub fn line_stats(&self, previous_commit: Option<&Commit>, summary: &ContextSummary) -> Result<Vec<FileStat>> {
+        let ContextSummary { files_added, files_modified, files_removed, .. } = summary;
+
+        let mut file_stats = Vec::new();
+        for path in files_added.iter().chain(files_modified).chain(files_removed) {
+            let old = match previous_commit {
+                Some(prev) if !files_added.contains(path) => self.storage.read_file_from_snapshot(&prev.hash, path)?,
+                _ => Vec::new(),
+            };
+            let new = if files_removed.contains(path) {
+                Vec::new()
+            } else {
+                fs::read(self.storage.vault_root.join(path))?
+            };
+            let (added, removed) = if is_binary(&old) || is_binary(&new) {
+                (0, 0)
+            } else {
+                line_changes(&String::from_utf8_lossy(&old), &String::from_utf8_lossy(&new))
+            };
+            file_stats.push(FileStat { path: path.clone(), added, removed });
+        }
+        file_stats.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(file_stats)
+    }
+
     /// Extract domain name from path (e.g., "domains/myproject/spec.md" -> "myproject")
     fn extract_domain(&self, path: &Path) -> Option<String> {
         let components: Vec<_> = path.components()
@@ -113,6 +279,34 @@ impl ContextManager {
         }
     }
 
+    /// Files saved since `previous_commit` (their mtime changed) whose
+    /// content is still identical, so they don't count as modified
+    pub fn touched_files(&self, previous_commit: Option<&Commit>) -> Result<Vec<PathBuf>> {
+        let manifest = match previous_commit {
+            Some(prev) => self.storage.read_manifest_with_stats(&prev.hash)?,
+            None => None,
+        };
+        let Some(manifest) = manifest else {
+            return Ok(Vec::new());
+        };
+
+        let mut touched = Vec::new();
+        for info in &manifest.files {
+            let path = self.storage.vault_root.join(&info.path);
+            let Ok(metadata) = fs::metadata(&path) else {
+                continue;
+            };
+            // Manifests from before mtimes were recorded can't tell
+            if info.mtime.is_none() || info.mtime == mtime_nanos(&metadata) || info.size != metadata.len() {
+                continue;
+            }
+            if compute_hash(&fs::read(&path)?) == info.hash {
+                touched.push(info.path.clone());
+            }
+        }
+        Ok(touched)
+    }
+
     /// Get list of modified files since last commit
     pub fn get_modified_files(&self) -> Result<Vec<PathBuf>> {
         let head_commit = self.storage.get_head_commit()?;
@@ -125,55 +319,207 @@ impl ContextManager {
         Ok(modified)
     }
 
-    /// Check if there are uncommitted changes
+    /// Get list of files deleted since last commit
+    pub fn get_removed_files(&self) -> Result<Vec<PathBuf>> {
+        let head_commit = self.storage.get_head_commit()?;
+        let summary = self.calculate_context_summary(head_commit.as_ref())?;
+        Ok(summary.files_removed)
+    }
+
+    /// Check if there are uncommitted changes (including deletions)
     pub fn has_uncommitted_changes(&self) -> Result<bool> {
-        let modified = self.get_modified_files()?;
-        Ok(!modified.is_empty())
+        let head_commit = self.storage.get_head_commit()?;
+        let summary = self.calculate_context_summary(head_commit.as_ref())?;
+        // Checkout replaces the index too, so unrecorded load/pin changes count
+        Ok(!summary.files_added.is_empty()
+            || !summary.files_modified.is_empty()
+            || !summary.files_removed.is_empty()
+            || self.storage.index_changed_since(head_commit.as_ref())?)
     }
 
     /// Get all files in context
     pub fn get_all_files(&self) -> Result<Vec<PathBuf>> {
-        let domains_dir = self.storage.domains_dir();
-        let mut files = Vec::new();
-        
-        if domains_dir.exists() {
-            for entry in WalkDir::new(&domains_dir).into_iter().filter_map(|e| e.ok()) {
-                let path = entry.path();
-                if path.is_file() {
-                    let rel_path = relative_path(&self.storage.vault_root, path);
-                    files.push(rel_path);
-                }
+        Ok(self.storage.domain_files()?
+            .iter()
+            .map(|path| relative_path(&self.storage.vault_root, path))
+            .collect())
+    }
+
+    /// Files in context matching any of the glob patterns
+    pub fn expand_patterns(&self, patterns: &[String]) -> Result<Vec<PathBuf>> {
+        let patterns = compile_patterns(patterns)?;
+        Ok(self.get_all_files()?
+            .into_iter()
+            .filter(|f| patterns.iter().any(|p| p.matches_path(f)))
+            .collect())
+    }
+
+    /// Files rendered into context after excludes and the one-off filter.
+    ///
+    /// When anything is explicitly loaded, only loaded and pinned files are
+    /// rendered; otherwise (or with `--all`) every file in `domains/` is.
+    pub fn context_files(&self, filter: &ContextFilter) -> Result<Vec<PathBuf>> {
+        self.select_context_files(self.get_all_files()?, filter)
+    }
+
+    /// Files of a commit's snapshot that would be rendered into context
+    pub fn context_files_at(&self, commit: &Commit, filter: &ContextFilter) -> Result<Vec<PathBuf>> {
+        let files = match self.storage.read_manifest(&commit.hash)? {
+            Some(manifest) => manifest.files.into_iter().map(|f| f.path).collect(),
+            None => Vec::new(),
+        };
+        self.select_context_files(files, filter)
+    }
+
+    /// Apply the index, pins and one-off filter to a list of candidate files
+    fn select_context_files(&self, files: Vec<PathBuf>, filter: &ContextFilter) -> Result<Vec<PathBuf>> {
+        let index = self.storage.load_index()?;
+        let config = self.storage.load_config()?;
+        let never_load = compile_patterns(&config.pins.never_load)?;
+        let always_load_patterns = compile_patterns(&config.pins.always_load)?;
+        let use_loaded = !filter.all && !index.loaded.is_empty();
+
+        Ok(files
+            .into_iter()
+            .filter(|f| !index.excluded.contains(f) && !never_load.iter().any(|p| p.matches_path(f)))
+            .filter(|f| {
+                let always_load = always_load_patterns.iter().any(|p| p.matches_path(f));
+                (!use_loaded
+                    || always_load
+                    || index.loaded.iter().chain(index.pinned.iter()).any(|l| f.starts_with(l)))
+                    && filter.allows(f, always_load)
+            })
+            .collect())
+    }
+
+    /// Render files into one document, from `commit`'s snapshot when given
+    /// and the working tree otherwise
+    pub fn render_context(&self, files: &[PathBuf], commit: Option<&Commit>, compress: bool) -> String {
+        let content = match commit {
+            Some(commit) => {
+                let contents = self.storage.read_files_from_snapshot(&commit.hash, files).unwrap_or_default();
+                render_with(files, |path| contents.get(path).cloned())
             }
+            None => self.render_files(files),
+        };
+
+        if compress {
+            self.compress_markdown(&content)
+        } else {
+            content
         }
-        
-        Ok(files)
     }
 
-    /// Load context as single document
-    pub fn load_context(&self, compress: bool) -> Result<String> {
-        let domains_dir = self.storage.domains_dir();
-        let mut content = String::new();
-        
-        if domains_dir.exists() {
-            for entry in WalkDir::new(&domains_dir).into_iter().filter_map(|e| e.ok()) {
-                let path = entry.path();
-                if path.is_file() {
-                    let rel_path = relative_path(&self.storage.vault_root, path);
-                    content.push_str(&format!("\n# File: {}\n\n", rel_path.display()));
-                    
-                    if let Ok(file_content) = fs::read_to_string(path) {
-                        content.push_str(&file_content);
-                        content.push_str("\n\n");
-                    }
-                }
+    /// Heading hierarchy of each file, as a table of contents
+    pub fn render_outline(&self, files: &[PathBuf], commit: Option<&Commit>) -> String {
+        match commit {
+            Some(commit) => outline_with(files, |path| self.storage.read_file_from_snapshot(&commit.hash, path).ok()),
+            None => outline_with(files, |path| fs::read(self.storage.vault_root.join(path)).ok()),
+        }
+    }
+
+    /// Split files by domain, keeping their order within each domain. Domains
+    /// named by `pins.always_load` come first, in pattern order, then the rest
+    /// alphabetically; files outside `domains/` are grouped under "other".
+    pub fn group_by_domain(&self, files: &[PathBuf]) -> Result<Vec<(String, Vec<PathBuf>)>> {
+        let config = self.storage.load_config()?;
+        let always_load = compile_patterns(&config.pins.always_load)?;
+
+        let mut groups: Vec<(String, Vec<PathBuf>)> = Vec::new();
+        for file in files {
+            let domain = self.extract_domain(file).unwrap_or_else(|| "other".to_string());
+            match groups.iter_mut().find(|(name, _)| *name == domain) {
+                Some((_, group)) => group.push(file.clone()),
+                None => groups.push((domain, vec![file.clone()])),
             }
         }
-        
-        if compress {
-            content = self.compress_markdown(&content);
+
+        let priority = |group: &[PathBuf]| {
+            always_load.iter()
+                .position(|p| group.iter().any(|f| p.matches_path(f)))
+                .unwrap_or(usize::MAX)
+        };
+        groups.sort_by(|a, b| priority(&a.1).cmp(&priority(&b.1)).then_with(|| a.0.cmp(&b.0)));
+        Ok(groups)
+    }
+
+    /// Choose which of `files` to render so the context stays within `budget`
+    /// tokens. Files are considered in `strategy` order and each one that still
+    /// fits is kept, so a large file doesn't crowd out smaller ones after it.
+    pub fn pack_files(
+        &self,
+        files: Vec<PathBuf>,
+        budget: usize,
+        strategy: PackStrategy,
+        commit: Option<&Commit>,
+    ) -> Result<Packing> {
+        let index = self.storage.load_index()?;
+        let config = self.storage.load_config()?;
+        let always_load = compile_patterns(&config.pins.always_load)?;
+        let pinned = |f: &Path| {
+            always_load.iter().any(|p| p.matches_path(f)) || index.pinned.iter().any(|p| f.starts_with(p))
+        };
+        let rank = |f: &Path| match index.staged.iter().find(|s| f.starts_with(&s.path)).map(|s| &s.priority) {
+            Some(Priority::High) => 0,
+            Some(Priority::Medium) => 1,
+            Some(Priority::Low) => 2,
+            None => 3,
+        };
+
+        // For a commit, modification times come from its manifest and contents
+        // from a single pass over its snapshot
+        let mtimes: Option<std::collections::HashMap<PathBuf, Option<i64>>> = match commit {
+            Some(commit) => self.storage.read_manifest(&commit.hash)?
+                .map(|m| m.files.into_iter().map(|i| (i.path, i.mtime)).collect()),
+            None => None,
+        };
+        let contents = commit.map(|commit| {
+            self.storage.read_files_from_snapshot(&commit.hash, &files).unwrap_or_default()
+        });
+        let modified = |f: &Path| -> i64 {
+            let mtime = match mtimes {
+                Some(ref m) => m.get(f).copied().flatten(),
+                None => fs::metadata(self.storage.vault_root.join(f)).ok().and_then(|m| mtime_nanos(&m)),
+            };
+            mtime.unwrap_or(0)
+        };
+        let read = |f: &Path| match contents {
+            Some(ref c) => c.get(f).cloned(),
+            None => fs::read(self.storage.vault_root.join(f)).ok(),
+        };
+
+        let mut order: Vec<(usize, i64, &PathBuf)> = files.iter()
+            .map(|f| {
+                let group = match strategy {
+                    PackStrategy::Recent => 0,
+                    PackStrategy::Priority => rank(f),
+                    PackStrategy::PinnedFirst => usize::from(!pinned(f)),
+                };
+                (group, modified(f), f)
+            })
+            .collect();
+        order.sort_by(|a, b| a.0.cmp(&b.0).then(b.1.cmp(&a.1)));
+
+        let mut packing = Packing::default();
+        let mut kept = std::collections::HashSet::new();
+        for (_, _, path) in order {
+            let tokens = estimate_tokens(&render_with(std::slice::from_ref(path), read));
+            let forced = strategy == PackStrategy::PinnedFirst && pinned(path);
+            if forced || packing.tokens + tokens <= budget {
+                packing.tokens += tokens;
+                kept.insert(path.clone());
+            } else {
+                packing.dropped.push(path.clone());
+            }
         }
-        
-        Ok(content)
+        packing.included = files.into_iter().filter(|f| kept.contains(f)).collect();
+
+        Ok(packing)
+    }
+
+    /// Concatenate files into one annotated markdown document
+    pub fn render_files(&self, files: &[PathBuf]) -> String {
+        render_with(files, |path| fs::read(self.storage.vault_root.join(path)).ok())
     }
 
     /// Simple markdown compression
@@ -188,3 +534,70 @@ impl ContextManager {
             .join("\n\n")
     }
 }
+
+/// List each file with its markdown headings nested by level, reading each
+/// with `read`. Headings inside fenced code blocks are ignored; other files
+/// are listed by path only.
+fn outline_with<F>(files: &[PathBuf], read: F) -> String
+where
+    F: Fn(&Path) -> Option<Vec<u8>>,
+{
+    let mut content = String::from("# Outline\n");
+
+    for rel_path in files {
+        content.push_str(&format!("\n## {}\n", rel_path.display()));
+        if rel_path.extension().is_none_or(|e| e != "md") {
+            continue;
+        }
+        let text = match read(rel_path) {
+            Some(bytes) if !is_binary(&bytes) => String::from_utf8_lossy(&bytes).to_string(),
+            _ => continue,
+        };
+
+        let mut in_fence = false;
+        for line in text.lines() {
+            let trimmed = line.trim_start();
+            if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+                in_fence = !in_fence;
+                continue;
+            }
+            let level = trimmed.chars().take_while(|&c| c == '#').count();
+            if in_fence || level == 0 || level > 6 || !trimmed[level..].starts_with(' ') {
+                continue;
+            }
+            content.push_str(&format!("{}- {}\n", "  ".repeat(level - 1), trimmed[level..].trim()));
+        }
+    }
+
+    content
+}
+
+/// Concatenate files into one document, reading each with `read`. Binary
+/// files are replaced by a placeholder naming the file and its size.
+fn render_with<F>(files: &[PathBuf], read: F) -> String
+where
+    F: Fn(&Path) -> Option<Vec<u8>>,
+{
+    let mut content = String::new();
+
+    for rel_path in files {
+        content.push_str(&format!("\n# File: {}\n\n", rel_path.display()));
+
+        match read(rel_path) {
+            Some(bytes) if is_binary(&bytes) => {
+                content.push_str(&format!(
+                    "[binary file: {}, {}]\n\n",
+                    rel_path.display(),
+                    format_size(bytes.len() as u64)
+                ));
+            }
+            Some(bytes) => {
+                content.push_str(&String::from_utf8_lossy(&bytes));
+                content.push_str("\n\n");
+            }
+            None => {}
+        }
+    }
+
+    content
+}
@@ -6,6 +6,9 @@ pub enum GitnuError {
     #[error("No gitnu vault found in current directory or parents\n  Run 'gnu init' to create a new vault\n  Or navigate to an existing vault directory")]
     NoVaultFound,
 
+    #[error("No gitnu vault at {0}\n  Check --vault or GITNU_DIR points at a directory containing .gitnu\n  Or create one there: gnu --vault {0} init")]
+    VaultNotFoundAt(PathBuf),
+
     #[error("Vault already initialized at {0}")]
     AlreadyInitialized(PathBuf),
 
@@ -21,18 +24,45 @@ pub enum GitnuError {
     #[error("Uncommitted changes would be lost\n  Commit your changes first: gnu commit \"message\"\n  Or discard them with: gnu checkout --force")]
     UncommittedChanges,
 
-    #[error("Merge conflict in {0}\n  Edit the file to resolve conflicts (look for <<<<<<< markers)\n  Then run: gnu commit \"Resolved merge conflict\"")]
-    MergeConflict(String),
+    #[error("{0} stopped with {}\n  Resolve them: gnu mergetool\n  Then finish: gnu commit\n  Or abandon it: gnu merge --abort", crate::utils::plural(*.1, "conflicted file"))]
+    MergeConflict(String, usize),
+
+    #[error("Conflict markers left in: {}\n  Resolve them first: gnu mergetool\n  Or commit them on purpose: gnu commit --allow-conflict-markers", .0.iter().map(|p| p.display().to_string()).collect::<Vec<_>>().join(", "))]
+    ConflictMarkers(Vec<PathBuf>),
 
     #[error("Wikilink '{0}' not found in vault")]
     WikilinkNotFound(String),
 
+    #[error("Wikilink '{0}' not found in vault, did you mean [[{1}]]?\n  Resolve it anyway: gnu resolve \"[[{0}]]\" --fuzzy")]
+    WikilinkDidYouMean(String, String),
+
     #[error("Wikilink '{0}' is ambiguous, matches: {}", .1.iter().map(|p| p.display().to_string()).collect::<Vec<_>>().join(", "))]
     WikilinkAmbiguous(String, Vec<PathBuf>),
 
     #[error("File not found: {0}")]
     FileNotFound(PathBuf),
 
+    #[error("Agent commit modifies human-owned files: {}\n  Ask a human to commit these changes: gnu commit \"message\" --author human\n  Or disable enforcement with ownership.enforce = false", .0.iter().map(|p| p.display().to_string()).collect::<Vec<_>>().join(", "))]
+    OwnershipViolation(Vec<PathBuf>),
+
+    #[error("Commit contains files larger than context.max_file_size: {}\n  Leave generated or binary files out of snapshots: add a pattern for them to .gnignore\n  Or raise context.max_file_size in .gitnu/config.toml", .0.iter().map(|p| p.display().to_string()).collect::<Vec<_>>().join(", "))]
+    LargeFiles(Vec<PathBuf>),
+
+    #[error("Vault is locked by another gnu process ({0})\n  Wait for it to finish and try again\n  If no other process is running, delete the lock file")]
+    VaultLocked(PathBuf),
+
+    #[error("HEAD is detached at {0}\n  Create a branch here first: gnu branch <name>\n  Then switch to it (changes are kept): gnu checkout <name>")]
+    DetachedHead(String),
+
+    #[error("Commit message must start with one of: {}\n  Example: gnu commit \"{} ...\"\n  Allowed prefixes are set by commit.require_prefix in .gitnu/config.toml", .0.join(", "), .0.first().map(String::as_str).unwrap_or(""))]
+    InvalidCommitMessage(Vec<String>),
+
+    #[error("Vault is read-only (core.read_only = true)\n  Read commands such as log, diff, context and status still work\n  To allow changes: gnu config set core.read_only false")]
+    ReadOnly,
+
+    #[error("No changes to commit\n  Edit files under domains/ first\n  Or record a checkpoint anyway: gnu commit \"message\" --allow-empty")]
+    NothingToCommit,
+
     #[error("Invalid commit reference: {0}")]
     InvalidCommitRef(String),
 
@@ -52,4 +82,71 @@ pub enum GitnuError {
     Other(String),
 }
 
+/// Exit status for arguments clap couldn't parse (sysexits' EX_USAGE)
+pub const EXIT_USAGE: i32 = 64;
+
+impl GitnuError {
+    /// Process exit status for this error, so scripts can tell failures apart.
+    /// 1 is kept for unexpected errors; the full table is in the README.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            GitnuError::NoVaultFound | GitnuError::VaultNotFoundAt(_) => 2,
+            GitnuError::UncommittedChanges => 3,
+            GitnuError::MergeConflict(_, _) | GitnuError::ConflictMarkers(_) => 4,
+            GitnuError::BranchNotFound(_)
+            | GitnuError::CommitNotFound(_)
+            | GitnuError::InvalidCommitRef(_)
+            | GitnuError::WikilinkNotFound(_)
+            | GitnuError::WikilinkDidYouMean(_, _)
+            | GitnuError::FileNotFound(_) => 5,
+            GitnuError::AlreadyInitialized(_) | GitnuError::BranchExists(_) => 6,
+            GitnuError::WikilinkAmbiguous(_, _) => 7,
+            GitnuError::OwnershipViolation(_)
+            | GitnuError::LargeFiles(_)
+            | GitnuError::InvalidCommitMessage(_) => 8,
+            GitnuError::VaultLocked(_) => 9,
+            GitnuError::DetachedHead(_) => 10,
+            GitnuError::ReadOnly => 11,
+            GitnuError::NothingToCommit => 12,
+            GitnuError::Io(_)
+            | GitnuError::Json(_)
+            | GitnuError::Toml(_)
+            | GitnuError::TomlSerialize(_)
+            | GitnuError::Other(_) => 1,
+        }
+    }
+
+    /// Stable machine-readable name of the error variant
+    pub fn kind(&self) -> &'static str {
+        match self {
+            GitnuError::NoVaultFound => "no_vault_found",
+            GitnuError::VaultNotFoundAt(_) => "vault_not_found_at",
+            GitnuError::AlreadyInitialized(_) => "already_initialized",
+            GitnuError::BranchExists(_) => "branch_exists",
+            GitnuError::BranchNotFound(_) => "branch_not_found",
+            GitnuError::CommitNotFound(_) => "commit_not_found",
+            GitnuError::UncommittedChanges => "uncommitted_changes",
+            GitnuError::MergeConflict(_, _) => "merge_conflict",
+            GitnuError::ConflictMarkers(_) => "conflict_markers",
+            GitnuError::WikilinkNotFound(_) => "wikilink_not_found",
+            GitnuError::WikilinkDidYouMean(_, _) => "wikilink_did_you_mean",
+            GitnuError::WikilinkAmbiguous(_, _) => "wikilink_ambiguous",
+            GitnuError::FileNotFound(_) => "file_not_found",
+            GitnuError::OwnershipViolation(_) => "ownership_violation",
+            GitnuError::LargeFiles(_) => "large_files",
+            GitnuError::VaultLocked(_) => "vault_locked",
+            GitnuError::DetachedHead(_) => "detached_head",
+            GitnuError::InvalidCommitMessage(_) => "invalid_commit_message",
+            GitnuError::InvalidCommitRef(_) => "invalid_commit_ref",
+            GitnuError::ReadOnly => "read_only",
+            GitnuError::NothingToCommit => "nothing_to_commit",
+            GitnuError::Io(_) => "io",
+            GitnuError::Json(_) => "json",
+            GitnuError::Toml(_) => "toml",
+            GitnuError::TomlSerialize(_) => "toml_serialize",
+            GitnuError::Other(_) => "other",
+        }
+    }
+}
+
 pub type Result<T> = std::result::Result<T, GitnuError>;
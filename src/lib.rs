@@ -1,4 +1,8 @@
 // Core library for gitnu - git-like version control for knowledge and context
+//
+// Commands print for the CLI; embedders should call the report functions
+// instead (`make_commit`, `status_report`, `log_report`), which take a
+// `Storage` for the vault and return serializable results.
 
 pub mod models;
 pub mod storage;
@@ -1,12 +1,27 @@
-use clap::{Parser, Subcommand};
+use clap::{CommandFactory, Parser, Subcommand};
 use gitnu::commands::*;
 use colored::Colorize;
+use gitnu::context::{GroupBy, PackStrategy};
+use std::ffi::OsString;
+use std::io::IsTerminal;
 
 #[derive(Parser)]
 #[command(name = "gnu")]
 #[command(about = "gitnu - Version-controlled knowledge operating system for AI agents", long_about = None)]
 #[command(version)]
 struct Cli {
+    /// Output as JSON where supported, including errors
+    #[arg(long, global = true)]
+    json: bool,
+
+    /// Disable colored output (also off when NO_COLOR is set or stdout isn't a terminal)
+    #[arg(long, global = true)]
+    no_color: bool,
+
+    /// Vault root to operate on instead of searching from the current directory (or set GITNU_DIR)
+    #[arg(long, global = true, value_name = "PATH")]
+    vault: Option<std::path::PathBuf>,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -18,27 +33,75 @@ enum Commands {
         /// Name of the vault/project
         #[arg(short, long)]
         name: Option<String>,
+
+        /// Domain template to scaffold (built-in or ~/.config/gitnu/templates/<name>)
+        #[arg(long, default_value = "default")]
+        template: String,
+
+        /// List available templates
+        #[arg(long)]
+        list_templates: bool,
+
+        /// Regenerate missing scaffolding in an existing vault
+        #[arg(long)]
+        reinit: bool,
+
+        /// Only create .gitnu/ and domains/_global/, without agent or editor scaffolding
+        #[arg(long)]
+        bare: bool,
+
+        /// Don't create the domains/<name>/ project domain
+        #[arg(long)]
+        no_project_domain: bool,
     },
 
     /// Show current context state
-    Status,
+    Status {
+        /// List files saved since the last commit whose content didn't change
+        #[arg(long)]
+        include_unchanged: bool,
+    },
 
     /// Create a checkpoint of current context
     Commit {
-        /// Commit message
-        message: String,
+        /// Commit message (opens $EDITOR with commit.template when omitted)
+        message: Option<String>,
 
-        /// Author type: human or agent
-        #[arg(long, default_value = "agent")]
-        author: String,
+        /// Author type: human or agent (default: agent.default_author in config)
+        #[arg(long)]
+        author: Option<String>,
 
-        /// Model name (for agent commits)
+        /// Model name for agent commits (default: agent.model_hint in config)
         #[arg(long)]
         model: Option<String>,
+
+        /// Record a commit even when nothing has changed (e.g. a session marker)
+        #[arg(long)]
+        allow_empty: bool,
+
+        /// Fail instead of warning when files exceed context.max_file_size
+        #[arg(long)]
+        no_large_files: bool,
+
+        /// Re-hash every file instead of skipping those whose size and mtime are unchanged
+        #[arg(long)]
+        full: bool,
+
+        /// Record the commit at this time instead of now, for backfilling
+        /// history (RFC 3339 or YYYY-MM-DD)
+        #[arg(long, visible_alias = "author-date", value_name = "DATE")]
+        date: Option<String>,
+
+        /// Commit even if files still contain merge conflict markers
+        #[arg(long)]
+        allow_conflict_markers: bool,
     },
 
     /// Show commit history
     Log {
+        /// Only show commits that changed this file (path or wikilink)
+        path: Option<String>,
+
         /// Show one line per commit
         #[arg(long)]
         oneline: bool,
@@ -50,6 +113,73 @@ enum Commands {
         /// Show log for specific branch
         #[arg(short, long)]
         branch: Option<String>,
+
+        /// Filter by author: human, agent, or a specific name/model
+        #[arg(long)]
+        author: Option<String>,
+
+        /// Filter by regex over the commit message
+        #[arg(long)]
+        grep: Option<String>,
+
+        /// Show commits on or after date (YYYY-MM-DD)
+        #[arg(long)]
+        since: Option<String>,
+
+        /// Show commits on or before date (YYYY-MM-DD)
+        #[arg(long)]
+        until: Option<String>,
+
+        /// Show oldest commits first
+        #[arg(long)]
+        reverse: bool,
+
+        /// Summarize commits, files and lines changed per author
+        #[arg(long)]
+        shortstat: bool,
+
+        /// List changed files (basenames with --oneline, full paths otherwise)
+        #[arg(long)]
+        files: bool,
+
+        /// Show notes and tags added with 'gnu annotate'
+        #[arg(long)]
+        notes: bool,
+
+        /// Only show commits with this tag
+        #[arg(long, value_name = "TAG")]
+        tagged: Option<String>,
+
+        /// Interleave the history of every branch, labelled by branch
+        #[arg(long, conflicts_with = "branch")]
+        branches: bool,
+
+        /// Like --branches, also including logs of deleted branches
+        #[arg(long, conflicts_with = "branch")]
+        all: bool,
+
+        /// Print each commit through a template: %h %H %s %an %ad %tr %t %d %n %%
+        #[arg(long, value_name = "TEMPLATE", conflicts_with = "oneline")]
+        format: Option<String>,
+    },
+
+    /// Show a commit's details and its changes
+    Show {
+        /// Commit, branch, or HEAD (default: HEAD)
+        #[arg(default_value = "HEAD")]
+        reference: String,
+
+        /// Show per-file line counts instead of the full diff
+        #[arg(long)]
+        stat: bool,
+
+        /// Only list the names of changed files
+        #[arg(long, conflicts_with = "stat")]
+        name_only: bool,
+
+        /// Unchanged lines shown around each hunk
+        #[arg(long, value_name = "N", default_value_t = 3)]
+        context_lines: usize,
     },
 
     /// Manage branches
@@ -61,57 +191,194 @@ enum Commands {
         #[arg(short = 'd', long)]
         delete: Option<String>,
 
-        /// Description for new branch
-        #[arg(long)]
+        /// Describe the named branch (creating it if needed), or the current one
+        #[arg(long, value_name = "TEXT")]
         describe: Option<String>,
+
+        /// List branches containing this commit
+        #[arg(long, value_name = "REF")]
+        contains: Option<String>,
+
+        /// List only branches whose head is reachable from REF (default HEAD)
+        #[arg(long, value_name = "REF", num_args = 0..=1, default_missing_value = "HEAD", conflicts_with_all = ["no_merged", "name", "delete"])]
+        merged: Option<String>,
+
+        /// List only branches with commits REF (default HEAD) doesn't have
+        #[arg(long, value_name = "REF", num_args = 0..=1, default_missing_value = "HEAD", conflicts_with_all = ["name", "delete"])]
+        no_merged: Option<String>,
+
+        /// Show when each branch was created
+        #[arg(short, long)]
+        verbose: bool,
+    },
+
+    /// Delete branches already merged into the default branch
+    PruneBranches {
+        /// List merged branches without deleting them
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Only consider branches matching glob (e.g. "explore-*")
+        #[arg(long)]
+        pattern: Option<String>,
+
+        /// Only consider branches created more than this many days ago
+        #[arg(long, value_name = "DAYS")]
+        older_than: Option<i64>,
+
+        /// Skip the confirmation prompt
+        #[arg(short, long)]
+        yes: bool,
     },
 
     /// Switch branches or restore commits
     Checkout {
         /// Branch name or commit hash
-        target: String,
+        #[arg(required_unless_present = "to_date")]
+        target: Option<String>,
+
+        /// Check out the last commit on the current branch at or before YYYY-MM-DD[THH:MM] (UTC)
+        #[arg(long, value_name = "DATE", conflicts_with = "target")]
+        to_date: Option<String>,
 
         /// Force checkout, discarding uncommitted changes
         #[arg(short, long)]
         force: bool,
+
+        /// Show which files would change without touching anything
+        #[arg(long)]
+        dry_run: bool,
     },
 
     /// Roll back to a previous commit
     Rewind {
         /// Commit reference (hash or HEAD~N)
-        target: String,
+        #[arg(required_unless_present = "to_date")]
+        target: Option<String>,
+
+        /// Rewind to the last commit at or before YYYY-MM-DD[THH:MM] (UTC)
+        #[arg(long, value_name = "DATE", conflicts_with = "target")]
+        to_date: Option<String>,
+
+        /// Keep working directory unchanged
+        #[arg(long)]
+        soft: bool,
+
+        /// Show which files would change without touching anything
+        #[arg(long)]
+        dry_run: bool,
+    },
 
+    /// Undo the last operation that moved the current branch
+    Undo {
         /// Keep working directory unchanged
         #[arg(long)]
         soft: bool,
     },
 
+    /// Undo a past commit's changes with a new commit, keeping history
+    Revert {
+        /// Commit to revert (hash or HEAD~N)
+        target: String,
+
+        /// Message for the revert commit (default: Revert "<subject>")
+        #[arg(short, long)]
+        message: Option<String>,
+
+        /// Apply the inverse changes without committing them
+        #[arg(long)]
+        no_commit: bool,
+    },
+
+    /// Collapse the last N commits on the current branch into one
+    Squash {
+        /// Number of commits to combine
+        count: usize,
+
+        /// Message for the new commit (default: the squashed messages)
+        #[arg(short, long)]
+        message: Option<String>,
+    },
+
+    /// Resolve files left with conflict markers, one at a time
+    Mergetool {
+        /// Keep our side of every conflict without prompting
+        #[arg(long, conflicts_with = "theirs")]
+        ours: bool,
+
+        /// Keep their side of every conflict without prompting
+        #[arg(long)]
+        theirs: bool,
+    },
+
     /// Show changes between commits or branches
     Diff {
-        /// Source commit/branch
+        /// Source commit/branch, or a range: a..b (direct) or a...b (b's changes since it diverged from a)
         source: Option<String>,
 
         /// Target commit/branch
         target: Option<String>,
+
+        /// Only show changes under domains/<name>/
+        #[arg(long)]
+        domain: Option<String>,
+
+        /// Output format: unified (context summary) or patch (unified-diff hunks)
+        #[arg(long, default_value = "unified")]
+        format: String,
+
+        /// Write the diff to a file instead of stdout
+        #[arg(short, long)]
+        output: Option<std::path::PathBuf>,
+
+        /// Show the token delta of each changed file
+        #[arg(long)]
+        token_breakdown: bool,
+
+        /// Describe the changes in a single paragraph
+        #[arg(long)]
+        summary: bool,
+
+        /// Unchanged lines shown around each hunk in patch output
+        #[arg(long, value_name = "N", default_value_t = 3)]
+        context_lines: usize,
+
+        /// Minimum percentage of lines a removed and an added file must share
+        /// to be shown as a rename; 100 matches identical content only
+        #[arg(long, value_name = "PCT", default_value_t = 50, value_parser = clap::value_parser!(u8).range(0..=100))]
+        rename_threshold: u8,
+
+        /// Preview the staged files: their priority, token cost and effect on the context
+        #[arg(long, conflicts_with_all = ["source", "target", "summary", "token_breakdown"])]
+        staged: bool,
     },
 
     /// Merge learnings from one branch into another
     Merge {
         /// Source branch to merge from
-        source: String,
+        #[arg(required_unless_present = "abort")]
+        source: Option<String>,
 
         /// Target branch to merge into (default: current)
         #[arg(long)]
         into: Option<String>,
 
-        /// Squash all commits into one
+        /// Apply the branch's net changes as one commit, without recording it as a parent
         #[arg(long)]
         squash: bool,
+
+        /// Show which files would change without touching anything
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Abandon a merge stopped on conflicts and restore the pre-merge state
+        #[arg(long, conflicts_with_all = ["source", "into", "squash", "dry_run"])]
+        abort: bool,
     },
 
     /// Load domains/files into active context
     Load {
-        /// Path or wikilink to load
+        /// Path, wikilink, or glob (e.g. "domains/**/decisions.md") to load
         path: Option<String>,
 
         /// Pin this file (always include)
@@ -131,6 +398,10 @@ enum Commands {
         /// Unload all non-pinned files
         #[arg(long)]
         all: bool,
+
+        /// List loaded, excluded, and staged files
+        #[arg(short, long)]
+        list: bool,
     },
 
     /// Pin files to always include in context
@@ -149,10 +420,81 @@ enum Commands {
         path: String,
     },
 
+    /// Watch domains/ and auto-commit after changes settle
+    Watch {
+        /// Quiet period before committing (e.g. 30s, 5m)
+        #[arg(long, default_value = "30s")]
+        interval: String,
+
+        /// Prefix for generated commit messages
+        #[arg(long, default_value = "watch: ")]
+        message_prefix: String,
+    },
+
+    /// Show vault metrics: commits, files, storage size and cadence
+    Stats {
+        /// Show how the context's token estimate grew over the current branch's history
+        #[arg(long)]
+        tokens: bool,
+    },
+
+    /// Show vault name, location, branch, HEAD and key settings
+    Info,
+
+    /// Exit 0 inside a vault and 2 outside one, printing nothing
+    IsVault {
+        /// Print the vault root when found
+        #[arg(long)]
+        print: bool,
+    },
+
+    /// Clean up unreachable objects and optionally pack old snapshots
+    Gc {
+        /// Consolidate old snapshots into a single packfile
+        #[arg(long)]
+        repack: bool,
+
+        /// Only pack snapshots older than this many days
+        #[arg(long, default_value_t = 14)]
+        older_than: i64,
+    },
+
+    /// Back up the whole .gitnu store, or restore one
+    Backup {
+        /// Archive to write (default: <vault>-backup-<timestamp>.tar.gz)
+        #[arg(short, long, conflicts_with = "restore")]
+        output: Option<std::path::PathBuf>,
+
+        /// Restore this backup instead of creating one
+        #[arg(long)]
+        restore: Option<std::path::PathBuf>,
+
+        /// Directory to restore into (default: current directory)
+        #[arg(long, requires = "restore")]
+        into: Option<std::path::PathBuf>,
+    },
+
+    /// Move a file or domain and update wikilinks that pointed to it
+    Mv {
+        /// Current path (e.g., domains/auth/spec.md)
+        source: String,
+
+        /// New path or existing directory to move into
+        destination: String,
+    },
+
     /// Resolve wikilink to full path
     Resolve {
         /// Wikilink to resolve (e.g., [[spec]])
-        wikilink: String,
+        wikilink: Option<String>,
+
+        /// List every name a wikilink can resolve to
+        #[arg(long, conflicts_with = "wikilink")]
+        all: bool,
+
+        /// Accept the single close match when nothing matches exactly
+        #[arg(long)]
+        fuzzy: bool,
     },
 
     /// Output current active context
@@ -161,40 +503,312 @@ enum Commands {
         #[arg(long)]
         clipboard: bool,
 
-        /// Output as JSON
-        #[arg(long)]
-        json: bool,
-
         /// Apply markdown compression
         #[arg(long)]
         compress: bool,
+
+        /// Only include files matching glob (repeatable)
+        #[arg(long)]
+        include: Vec<String>,
+
+        /// Exclude files matching glob (repeatable)
+        #[arg(long)]
+        exclude: Vec<String>,
+
+        /// Include every file, ignoring the loaded set
+        #[arg(long)]
+        all: bool,
+
+        /// Emit only each file's headings, as a table of contents
+        #[arg(long, conflicts_with = "compress")]
+        outline: bool,
+
+        /// Render the context at another branch's head without checking it out
+        #[arg(long)]
+        branch: Option<String>,
+
+        /// Wrap the output in a prompt file, filling {{context}}, {{summary}}, {{branch}} and {{commit}}
+        #[arg(long, value_name = "FILE")]
+        template: Option<std::path::PathBuf>,
+
+        /// Leave out files until the context fits in this many tokens
+        #[arg(long, value_name = "TOKENS")]
+        budget: Option<usize>,
+
+        /// Files to keep under --budget: recent, priority or pinned-first
+        #[arg(long, default_value = "recent", requires = "budget")]
+        strategy: String,
+
+        /// Only what changed since the last commit, as unified diffs
+        #[arg(long, conflicts_with_all = ["outline", "branch", "budget", "compress"])]
+        diff_only: bool,
+
+        /// Arrange files in sections: domain (always-load domains first) or none
+        #[arg(long, value_name = "GROUPING", default_value = "none", conflicts_with = "diff_only")]
+        group_by: String,
+    },
+
+    /// Bundle the vault for sharing outside .gitnu
+    Export {
+        /// Output format: md, json, or tar
+        #[arg(long, default_value = "md")]
+        format: String,
+
+        /// Write to file instead of stdout
+        #[arg(short, long)]
+        output: Option<std::path::PathBuf>,
+    },
+
+    /// Merge an exported bundle into the current vault
+    Import {
+        /// Bundle produced by 'gnu export' (.tar.gz or .json)
+        file: std::path::PathBuf,
+
+        /// Import into this domain instead of imported-<name>/
+        #[arg(long)]
+        into: Option<String>,
+
+        /// Overwrite existing files
+        #[arg(short, long)]
+        force: bool,
     },
 
     /// Generate summary of current context state
-    Summary,
+    Summary {
+        /// Show last N commits
+        #[arg(long, default_value_t = 5)]
+        lines: usize,
+    },
+
+    /// Attach notes or tags to an existing commit
+    Annotate {
+        /// Commit hash, branch name, or HEAD
+        reference: String,
+
+        /// Add a note (repeatable)
+        #[arg(long, value_name = "NOTE")]
+        add: Vec<String>,
+
+        /// Add a tag such as "milestone" (repeatable)
+        #[arg(long)]
+        tag: Vec<String>,
+
+        /// Remove a tag (repeatable)
+        #[arg(long, value_name = "TAG")]
+        untag: Vec<String>,
+    },
+
+    /// Check the vault for damage such as dangling refs or missing snapshots
+    Doctor {
+        /// Repair what can be fixed without losing data
+        #[arg(long)]
+        fix: bool,
+    },
+
+    /// Read or change a setting in .gitnu/config.toml
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+
+    /// Define, list or remove command shortcuts (the [alias] config section)
+    Alias {
+        /// Alias name (omit to list every alias)
+        name: Option<String>,
+
+        /// Command it expands to, e.g. "commit --author human"
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        command: Vec<String>,
+
+        /// Remove the alias
+        #[arg(long, requires = "name", conflicts_with = "command")]
+        unset: bool,
+    },
+
+    /// Report on the object store without changing it
+    Fsck {
+        /// Also list objects no branch, reflog entry or tag reaches
+        #[arg(long)]
+        unreachable: bool,
+
+        /// List each problem instead of only counting them
+        #[arg(short, long)]
+        verbose: bool,
+    },
+
+    /// Serve the vault to agents over stdio
+    Serve {
+        /// Speak the Model Context Protocol (JSON-RPC over stdin/stdout)
+        #[arg(long)]
+        mcp: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigAction {
+    /// Print a setting, e.g. user.name
+    Get {
+        key: String,
+    },
+
+    /// Change a setting, e.g. gnu config set user.name "Ada Lovelace"
+    Set {
+        key: String,
+        value: String,
+    },
 }
 
 fn main() {
-    let cli = Cli::parse();
+    let args: Vec<OsString> = std::env::args_os().collect();
+    // Until clap has parsed the arguments, --json can only be looked for
+    let wants_json = || args.iter().any(|a| a == "--json");
+    let expanded = match expand_alias(args.clone(), &builtin_commands()) {
+        Ok(expanded) => expanded,
+        Err(e) => exit_with_error(e, wants_json()),
+    };
+    let cli = match Cli::try_parse_from(expanded) {
+        Ok(cli) => cli,
+        // Help and --version print to stdout and succeed
+        Err(e) if !e.use_stderr() => e.exit(),
+        Err(e) if wants_json() => {
+            let error = serde_json::json!({
+                "error": "usage",
+                "message": e.render().to_string().trim_end(),
+                "exit_code": gitnu::errors::EXIT_USAGE,
+            });
+            eprintln!("{}", error);
+            std::process::exit(gitnu::errors::EXIT_USAGE);
+        }
+        Err(e) => {
+            let _ = e.print();
+            std::process::exit(gitnu::errors::EXIT_USAGE);
+        }
+    };
+    let json = cli.json;
+
+    let no_color_env = std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+    if cli.no_color || no_color_env || !std::io::stdout().is_terminal() {
+        colored::control::set_override(false);
+    }
+
+    // Every command finds its vault through find_vault_root, which honors --vault
+    if let Some(vault) = cli.vault.clone() {
+        gitnu::utils::set_vault_override(vault);
+    }
 
     let result = match cli.command {
-        Commands::Init { name } => init(name),
-        Commands::Status => status(),
-        Commands::Commit { message, author, model } => commit(&message, &author, model),
-        Commands::Log { oneline, limit, branch } => log(oneline, limit, branch),
-        Commands::Branch { name, delete, describe } => {
-            if let Some(branch_name) = delete {
+        Commands::Init { name, template, list_templates: list, reinit, bare, no_project_domain } => {
+            if list {
+                list_templates()
+            } else {
+                init(name, &template, reinit, bare, no_project_domain)
+            }
+        }
+        Commands::Status { include_unchanged } => status(include_unchanged),
+        Commands::Commit { message, author, model, allow_empty, no_large_files, full, date, allow_conflict_markers } => {
+            date.as_deref().map(gitnu::utils::parse_timestamp).transpose().and_then(|date| {
+                commit(message, CommitOptions {
+                    message: String::new(),
+                    author,
+                    model,
+                    allow_empty,
+                    no_large_files,
+                    full,
+                    date,
+                    allow_conflict_markers,
+                })
+            })
+        }
+        Commands::Log { path, oneline, limit, branch, author, grep, since, until, reverse, shortstat, files, notes, tagged, branches, all, format } => log(LogOptions {
+            oneline,
+            limit,
+            branch,
+            author,
+            grep,
+            since,
+            until,
+            path,
+            reverse,
+            shortstat,
+            files,
+            notes,
+            tagged,
+            branches,
+            all,
+            format,
+        }),
+        Commands::Show { reference, stat, name_only, context_lines } => {
+            show(&reference, stat, name_only, context_lines)
+        }
+        Commands::Branch { name, delete, describe, contains, merged, no_merged, verbose } => {
+            if let Some(reference) = contains {
+                branch_contains(&reference)
+            } else if let Some(branch_name) = delete {
                 branch_delete(&branch_name)
             } else if let Some(branch_name) = name {
                 branch_create(&branch_name, describe)
+            } else if let Some(text) = describe {
+                branch_describe(None, &text)
             } else {
-                branch_list()
+                let filter = match (merged, no_merged) {
+                    (Some(reference), _) => Some(MergedFilter { reference, merged: true }),
+                    (_, Some(reference)) => Some(MergedFilter { reference, merged: false }),
+                    _ => None,
+                };
+                branch_list(verbose, filter)
             }
         }
-        Commands::Checkout { target, force } => checkout(&target, force),
-        Commands::Rewind { target, soft } => rewind(&target, soft),
-        Commands::Diff { source, target } => diff(source, target),
-        Commands::Merge { source, into, squash } => merge(&source, into, squash),
+        Commands::PruneBranches { dry_run, pattern, older_than, yes } => {
+            prune_branches(dry_run, pattern, older_than, yes)
+        }
+        Commands::Checkout { target, to_date, force, dry_run } => {
+            checkout(target.as_deref(), to_date.as_deref(), force, dry_run)
+        }
+        Commands::Rewind { target, to_date, soft, dry_run } => {
+            rewind(target.as_deref(), to_date.as_deref(), soft, dry_run)
+        }
+        Commands::Undo { soft } => undo(soft),
+        Commands::Revert { target, message, no_commit } => revert(&target, message, no_commit),
+        Commands::Squash { count, message } => squash(count, message),
+        Commands::Mergetool { ours, theirs } => {
+            let keep = if ours {
+                Some(Keep::Ours)
+            } else if theirs {
+                Some(Keep::Theirs)
+            } else {
+                None
+            };
+            mergetool(keep)
+        }
+        Commands::Diff {
+            source,
+            target,
+            domain,
+            format,
+            output,
+            token_breakdown,
+            summary,
+            context_lines,
+            rename_threshold,
+            staged,
+        } => DiffFormat::parse(&format).and_then(|format| {
+            diff(DiffOptions {
+                source,
+                target,
+                domain,
+                format,
+                output,
+                token_breakdown,
+                summary,
+                context_lines,
+                staged,
+                rename_threshold,
+            })
+        }),
+        Commands::Merge { source, into, squash, dry_run, abort } => match source {
+            Some(source) if !abort => merge(&source, into, squash, dry_run),
+            _ => merge_abort(),
+        },
         Commands::Load { path, pin, list } => {
             if list {
                 load("", false, true)
@@ -206,16 +820,100 @@ fn main() {
                 ))
             }
         }
-        Commands::Unload { path, all } => unload(path, all),
+        Commands::Unload { path, all, list } => unload(path, all, list),
         Commands::Pin { path, exclude } => pin(&path, exclude),
         Commands::Unpin { path } => unpin(&path),
-        Commands::Resolve { wikilink } => resolve(&wikilink),
-        Commands::Context { clipboard, json, compress } => context(clipboard, json, compress),
-        Commands::Summary => summary(),
+        Commands::Watch { interval, message_prefix } => watch(&interval, &message_prefix),
+        Commands::Stats { tokens: false } => stats(json),
+        Commands::Stats { tokens: true } => token_trend(json),
+        Commands::Info => info(json),
+        Commands::IsVault { print } => {
+            if !is_vault(print) {
+                std::process::exit(gitnu::GitnuError::NoVaultFound.exit_code());
+            }
+            Ok(())
+        }
+        Commands::Gc { repack, older_than } => gc(repack, older_than),
+        Commands::Backup { output, restore, into } => match restore {
+            Some(file) => restore_backup(&file, into),
+            None => backup(output),
+        },
+        Commands::Mv { source, destination } => mv(&source, &destination),
+        Commands::Resolve { wikilink, all, fuzzy } => resolve(wikilink.as_deref(), all, fuzzy, json),
+        Commands::Context {
+            clipboard,
+            compress,
+            include,
+            exclude,
+            all,
+            outline,
+            branch,
+            template,
+            budget,
+            strategy,
+            diff_only,
+            group_by,
+        } => PackStrategy::parse(&strategy)
+            .and_then(|strategy| Ok((strategy, GroupBy::parse(&group_by)?)))
+            .and_then(|(strategy, group_by)| {
+                context(
+                    &ContextOptions {
+                        clipboard,
+                        compress,
+                        include,
+                        exclude,
+                        all,
+                        outline,
+                        branch,
+                        template,
+                        budget,
+                        strategy,
+                        diff_only,
+                        group_by,
+                    },
+                    json,
+                )
+            }),
+        Commands::Summary { lines } => summary(json, lines),
+        Commands::Annotate { reference, add, tag, untag } => annotate(&reference, add, tag, untag),
+        Commands::Doctor { fix } => doctor(fix),
+        Commands::Alias { name, command, unset } => alias(name, command, unset, &builtin_commands()),
+        Commands::Fsck { unreachable, verbose } => fsck(unreachable, verbose),
+        Commands::Config { action } => match action {
+            ConfigAction::Get { key } => config_get(&key),
+            ConfigAction::Set { key, value } => config_set(&key, &value),
+        },
+        Commands::Serve { mcp } => serve(mcp),
+        Commands::Export { format, output } => export(&format, output),
+        Commands::Import { file, into, force } => import(&file, into, force),
     };
 
     if let Err(e) = result {
+        exit_with_error(e, json);
+    }
+}
+
+/// Subcommand names and their clap aliases, which user aliases can't shadow
+fn builtin_commands() -> Vec<String> {
+    let cli = Cli::command();
+    let mut names = vec!["help".to_string()];
+    for command in cli.get_subcommands() {
+        names.push(command.get_name().to_string());
+        names.extend(command.get_all_aliases().map(str::to_string));
+    }
+    names
+}
+
+fn exit_with_error(e: gitnu::GitnuError, json: bool) -> ! {
+    if json {
+        let error = serde_json::json!({
+            "error": e.kind(),
+            "message": e.to_string(),
+            "exit_code": e.exit_code(),
+        });
+        eprintln!("{}", error);
+    } else {
         eprintln!("{} {}", "Error:".red().bold(), e);
-        std::process::exit(1);
     }
+    std::process::exit(e.exit_code());
 }
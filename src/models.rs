@@ -1,24 +1,43 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 
 /// Represents a commit in the gitnu vault
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Commit {
     pub hash: String,
     pub parent: Option<String>,
+    /// Second parent of a merge commit (the merged branch head)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub merge_parent: Option<String>,
     pub timestamp: DateTime<Utc>,
     pub author: Author,
     pub message: String,
     pub context_summary: ContextSummary,
     pub snapshot_path: PathBuf,
+    /// Loaded, pinned and excluded files when the commit was made, restored
+    /// by checkout and rewind. Absent on commits from older versions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub index: Option<Index>,
+}
+
+impl Commit {
+    /// First line of the message, for one-line listings
+    pub fn subject(&self) -> &str {
+        self.message.lines().next().unwrap_or("")
+    }
 }
 
 /// Author of a commit - either human or AI agent
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "lowercase")]
 pub enum Author {
-    Human { name: String },
+    Human {
+        name: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        email: Option<String>,
+    },
     Agent { 
         model: String, 
         #[serde(skip_serializing_if = "Option::is_none")]
@@ -29,10 +48,37 @@ pub enum Author {
 impl Author {
     pub fn display(&self) -> String {
         match self {
-            Author::Human { name } => format!("Human ({})", name),
+            Author::Human { name, email: Some(email) } => format!("Human ({} <{}>)", name, email),
+            Author::Human { name, .. } => format!("Human ({})", name),
             Author::Agent { model, .. } => format!("Agent ({})", model),
         }
     }
+
+    /// Person's name for human commits, model for agent commits
+    pub fn name(&self) -> &str {
+        match self {
+            Author::Human { name, .. } => name,
+            Author::Agent { model, .. } => model,
+        }
+    }
+
+    /// Grouping used for contribution stats: all humans together, agents per model
+    pub fn group(&self) -> String {
+        match self {
+            Author::Human { .. } => "Human".to_string(),
+            Author::Agent { .. } => self.display(),
+        }
+    }
+
+    /// Match against an author filter: "human", "agent", or a specific name/model
+    pub fn matches(&self, filter: &str) -> bool {
+        match self {
+            Author::Human { name, email } => {
+                filter == "human" || filter == name || email.as_deref() == Some(filter)
+            }
+            Author::Agent { model, .. } => filter == "agent" || filter == model,
+        }
+    }
 }
 
 /// Summary of what's in the context at commit time
@@ -43,19 +89,115 @@ pub struct ContextSummary {
     pub files_added: Vec<PathBuf>,
     pub files_removed: Vec<PathBuf>,
     pub token_estimate: usize,
+    /// Binary or non-UTF-8 files, snapshotted but left out of `token_estimate`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub binary_files: Vec<PathBuf>,
+    /// Lines added and removed in each changed file, against the parent commit.
+    /// Empty in commits made before these were recorded.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub file_stats: Vec<FileStat>,
+}
+
+impl ContextSummary {
+    /// Number of files added, modified or removed
+    pub fn files_changed(&self) -> usize {
+        self.files_added.len() + self.files_modified.len() + self.files_removed.len()
+    }
+
+    /// Total lines added and removed across `file_stats`
+    pub fn line_totals(&self) -> (usize, usize) {
+        self.file_stats.iter().fold((0, 0), |(a, r), s| (a + s.added, r + s.removed))
+    }
+}
+
+/// Line counts for one changed file; binary files count as 0/0
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileStat {
+    pub path: PathBuf,
+    pub added: usize,
+    pub removed: usize,
+}
+
+/// Commits and files changed by one author group
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuthorStats {
+    pub author: String,
+    pub commits: usize,
+    pub files_changed: usize,
+    /// Lines added and removed, from the stats recorded in each commit
+    pub insertions: usize,
+    pub deletions: usize,
+}
+
+impl AuthorStats {
+    /// Totals per `Author::group`, most commits first
+    pub fn collect(commits: &[Commit]) -> Vec<AuthorStats> {
+        let mut stats: Vec<AuthorStats> = Vec::new();
+        for commit in commits {
+            let group = commit.author.group();
+            let files = commit.context_summary.files_changed();
+            let (insertions, deletions) = commit.context_summary.line_totals();
+            match stats.iter_mut().find(|s| s.author == group) {
+                Some(s) => {
+                    s.commits += 1;
+                    s.files_changed += files;
+                    s.insertions += insertions;
+                    s.deletions += deletions;
+                }
+                None => stats.push(AuthorStats {
+                    author: group,
+                    commits: 1,
+                    files_changed: files,
+                    insertions,
+                    deletions,
+                }),
+            }
+        }
+        stats.sort_by(|a, b| b.commits.cmp(&a.commits).then_with(|| a.author.cmp(&b.author)));
+        stats
+    }
+}
+
+/// What HEAD points at
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Head {
+    Branch(String),
+    Detached(String),
 }
 
-/// Reference to a branch
+impl Head {
+    /// Branch name, or `None` when detached
+    pub fn branch(&self) -> Option<&str> {
+        match self {
+            Head::Branch(name) => Some(name),
+            Head::Detached(_) => None,
+        }
+    }
+
+    /// Branch name or commit hash
+    pub fn name(&self) -> &str {
+        match self {
+            Head::Branch(name) | Head::Detached(name) => name,
+        }
+    }
+
+    pub fn is_branch(&self, name: &str) -> bool {
+        self.branch() == Some(name)
+    }
+}
+
+/// Reference to a branch, with the metadata stored in `refs/meta/<branch>.json`
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BranchRef {
     pub name: String,
+    /// Current head; refreshed from `refs/heads` whenever the metadata is read
     pub head: String,
     pub created_at: DateTime<Utc>,
     pub description: Option<String>,
 }
 
 /// The staging area / relevance queue
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
 pub struct Index {
     #[serde(default)]
     pub staged: Vec<StagedFile>,
@@ -67,15 +209,29 @@ pub struct Index {
     pub loaded: Vec<PathBuf>,
 }
 
+impl Index {
+    /// Drop loaded, pinned, excluded and staged paths that no longer exist
+    /// under `vault_root`. Returns how many entries were removed.
+    pub fn retain_existing(&mut self, vault_root: &Path) -> usize {
+        let before = self.loaded.len() + self.pinned.len() + self.excluded.len() + self.staged.len();
+        let exists = |p: &PathBuf| vault_root.join(p).exists();
+        self.loaded.retain(exists);
+        self.pinned.retain(exists);
+        self.excluded.retain(exists);
+        self.staged.retain(|s| exists(&s.path));
+        before - (self.loaded.len() + self.pinned.len() + self.excluded.len() + self.staged.len())
+    }
+}
+
 /// A file staged for inclusion in context
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct StagedFile {
     pub path: PathBuf,
     pub reason: String,
     pub priority: Priority,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum Priority {
     High,
@@ -100,6 +256,108 @@ pub struct Config {
     pub context: ContextConfig,
     pub agent: AgentConfig,
     pub pins: PinsConfig,
+    #[serde(default)]
+    pub ownership: OwnershipConfig,
+    #[serde(default)]
+    pub commit: CommitConfig,
+    #[serde(default)]
+    pub retention: RetentionConfig,
+    #[serde(default)]
+    pub merge: MergeConfig,
+    #[serde(default)]
+    pub user: UserConfig,
+    /// Command shortcuts, e.g. `ci = "commit --author human"`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub alias: BTreeMap<String, String>,
+}
+
+/// Who human commits are attributed to
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UserConfig {
+    /// Falls back to $USER, then $USERNAME, then "user"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+}
+
+impl UserConfig {
+    /// Author for a human commit
+    pub fn author(&self) -> Author {
+        let name = self.name.clone()
+            .or_else(|| std::env::var("USER").ok())
+            .or_else(|| std::env::var("USERNAME").ok())
+            .filter(|n| !n.is_empty())
+            .unwrap_or_else(|| "user".to_string());
+        Author::Human { name, email: self.email.clone() }
+    }
+}
+
+/// Conflict resolution settings
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MergeConfig {
+    /// Program `gnu mergetool` opens conflicted files with (default: $VISUAL/$EDITOR)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool: Option<String>,
+}
+
+/// How long snapshots of commits no branch can reach are kept. Snapshots of
+/// reachable commits are never pruned.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetentionConfig {
+    /// Always keep the snapshots of this many most recent commits per branch log
+    #[serde(default = "default_keep_last")]
+    pub keep_last: usize,
+    /// Keep unreachable snapshots younger than this many days
+    #[serde(default = "default_keep_days")]
+    pub keep_days: i64,
+    /// Prune after every commit instead of waiting for `gnu gc`
+    #[serde(default)]
+    pub auto_gc: bool,
+}
+
+fn default_keep_last() -> usize {
+    10
+}
+
+fn default_keep_days() -> i64 {
+    30
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        RetentionConfig {
+            keep_last: default_keep_last(),
+            keep_days: default_keep_days(),
+            auto_gc: false,
+        }
+    }
+}
+
+/// Commit message conventions
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CommitConfig {
+    /// Pre-filled into the editor when `gnu commit` is run without a message
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template: Option<String>,
+    /// Allowed first words of a commit message (e.g. "Add", "Decision")
+    #[serde(default)]
+    pub require_prefix: Vec<String>,
+}
+
+impl CommitConfig {
+    /// Whether a message starts with one of the required prefixes as a whole
+    /// word, so "Add" accepts "Add notes" and "Add: notes" but not "Address"
+    pub fn accepts(&self, message: &str) -> bool {
+        self.require_prefix.is_empty()
+            || self.require_prefix.iter().any(|prefix| {
+                let Some(rest) = message.strip_prefix(prefix.as_str()) else {
+                    return false;
+                };
+                let ends_word = |c: Option<char>| c.is_none_or(|c| !c.is_alphanumeric());
+                ends_word(prefix.chars().last()) || ends_word(rest.chars().next())
+            })
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -107,6 +365,16 @@ pub struct CoreConfig {
     pub vault_name: String,
     pub default_branch: String,
     pub created_at: DateTime<Utc>,
+    /// Track dotfiles and dot-directories (e.g. `.obsidian/`) inside domains
+    #[serde(default)]
+    pub include_hidden: bool,
+    /// Refuse every command that would change the vault
+    #[serde(default)]
+    pub read_only: bool,
+    /// Where snapshots are stored instead of `.gitnu/objects`; relative
+    /// paths are taken from the vault root
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub objects_dir: Option<PathBuf>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -114,6 +382,19 @@ pub struct ContextConfig {
     pub max_tokens: usize,
     pub auto_commit: bool,
     pub compress_snapshots: bool,
+    /// Warn when committing a file larger than this many bytes
+    #[serde(default = "default_max_file_size")]
+    pub max_file_size: u64,
+    /// Refuse commits containing files over `max_file_size` instead of warning
+    #[serde(default)]
+    pub reject_large_files: bool,
+    /// Warn when a commit adds or modifies binary files under domains/
+    #[serde(default)]
+    pub warn_binary_files: bool,
+}
+
+fn default_max_file_size() -> u64 {
+    1024 * 1024
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -128,6 +409,54 @@ pub struct PinsConfig {
     pub never_load: Vec<String>,
 }
 
+/// Who is allowed to modify a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Owner {
+    Human,
+    Agent,
+    Both,
+}
+
+/// File-ownership rules mapping glob patterns to owners
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OwnershipConfig {
+    /// Refuse agent commits that touch human-owned files
+    #[serde(default)]
+    pub enforce: bool,
+    #[serde(default)]
+    pub rules: BTreeMap<String, Owner>,
+}
+
+impl Default for OwnershipConfig {
+    fn default() -> Self {
+        let mut rules = BTreeMap::new();
+        for pattern in ["**/spec.md", "**/prd.md", "**/requirements.md"] {
+            rules.insert(pattern.to_string(), Owner::Human);
+        }
+        for pattern in ["**/learnings.md", "**/decisions.md", "**/patterns.md"] {
+            rules.insert(pattern.to_string(), Owner::Agent);
+        }
+        OwnershipConfig { enforce: false, rules }
+    }
+}
+
+impl OwnershipConfig {
+    /// Owner of a vault-relative path; the longest matching pattern wins
+    pub fn owner_of(&self, path: &Path) -> Owner {
+        self.rules
+            .iter()
+            .filter(|(pattern, _)| {
+                glob::Pattern::new(pattern)
+                    .map(|p| p.matches_path(path))
+                    .unwrap_or(false)
+            })
+            .max_by_key(|(pattern, _)| pattern.len())
+            .map(|(_, owner)| *owner)
+            .unwrap_or(Owner::Both)
+    }
+}
+
 impl Default for Config {
     fn default() -> Self {
         Config {
@@ -135,11 +464,17 @@ impl Default for Config {
                 vault_name: "unnamed".to_string(),
                 default_branch: "main".to_string(),
                 created_at: Utc::now(),
+                include_hidden: false,
+                read_only: false,
+                objects_dir: None,
             },
             context: ContextConfig {
                 max_tokens: 100_000,
                 auto_commit: false,
                 compress_snapshots: true,
+                max_file_size: default_max_file_size(),
+                reject_large_files: false,
+                warn_binary_files: false,
             },
             agent: AgentConfig {
                 default_author: "agent".to_string(),
@@ -153,6 +488,296 @@ impl Default for Config {
                     "domains/archive/*".to_string(),
                 ],
             },
+            ownership: OwnershipConfig::default(),
+            commit: CommitConfig::default(),
+            retention: RetentionConfig::default(),
+            merge: MergeConfig::default(),
+            user: UserConfig::default(),
+            alias: BTreeMap::new(),
+        }
+    }
+}
+
+/// Machine-readable output of `gnu summary --json`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SummaryReport {
+    /// Bumped whenever the schema changes incompatibly
+    pub schema_version: u32,
+    /// Current branch, or the commit hash when HEAD is detached
+    pub branch: String,
+    #[serde(default)]
+    pub detached: bool,
+    pub head: Option<HeadSummary>,
+    pub domains: Vec<DomainSummary>,
+    pub uncommitted_files: Vec<PathBuf>,
+    /// Size of the uncommitted work, `None` when there is none
+    #[serde(default)]
+    pub pending: Option<PendingChanges>,
+    pub branches: Vec<BranchSummary>,
+}
+
+/// How much the working directory differs from HEAD
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingChanges {
+    pub modified: usize,
+    pub added: usize,
+    pub removed: usize,
+    pub lines_added: usize,
+    pub lines_removed: usize,
+    /// Estimated tokens now minus those recorded in HEAD
+    pub token_delta: i64,
+}
+
+impl SummaryReport {
+    pub const SCHEMA_VERSION: u32 = 1;
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HeadSummary {
+    pub hash: String,
+    pub message: String,
+    pub timestamp: DateTime<Utc>,
+    pub author: Author,
+    pub token_estimate: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DomainSummary {
+    pub name: String,
+    pub file_count: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BranchSummary {
+    pub name: String,
+    pub current: bool,
+    pub head: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Commits on this branch not reachable from the current branch
+    pub ahead: usize,
+    /// Commits on the current branch not reachable from this branch
+    pub behind: usize,
+}
+
+/// Result of `commands::commit::make_commit`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommitOutcome {
+    pub branch: String,
+    /// The new commit, or `None` when there was nothing to commit
+    pub commit: Option<Commit>,
+    /// Human-owned files an agent commit touched while ownership isn't enforced
+    #[serde(default)]
+    pub ownership_warnings: Vec<PathBuf>,
+    /// Committed files larger than `context.max_file_size`
+    #[serde(default)]
+    pub large_files: Vec<LargeFile>,
+    /// Binary files added or modified, reported when `context.warn_binary_files` is set
+    #[serde(default)]
+    pub binary_files: Vec<PathBuf>,
+    /// Snapshots removed by `retention.auto_gc` after the commit
+    #[serde(default)]
+    pub pruned_snapshots: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LargeFile {
+    pub path: PathBuf,
+    pub size: u64,
+}
+
+/// Result of `commands::status::status_report`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatusReport {
+    /// Current branch, or the commit hash when HEAD is detached
+    pub branch: String,
+    #[serde(default)]
+    pub detached: bool,
+    pub last_commit: Option<Commit>,
+    /// Estimated tokens of the working directory
+    pub token_estimate: usize,
+    /// Budget from `context.max_tokens`
+    pub max_tokens: usize,
+    /// Number of files under `domains/`
+    pub tracked_files: usize,
+    /// Loaded files, or every tracked file when nothing is loaded explicitly
+    pub active_files: Vec<PathBuf>,
+    pub pinned: Vec<PathBuf>,
+    pub pin_patterns: Vec<PinPattern>,
+    pub staged: Vec<StagedFile>,
+    pub modified: Vec<ModifiedFile>,
+    pub removed: Vec<PathBuf>,
+    /// Saved since the last commit but with identical content, so not modified
+    #[serde(default)]
+    pub touched: Vec<PathBuf>,
+    /// Loaded, pinned, excluded or staged files differ from the last commit's
+    #[serde(default)]
+    pub index_changed: bool,
+    pub untracked_domains: Vec<DomainSummary>,
+    /// Conflict resolution progress while a merge is unfinished
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub merge: Option<MergeState>,
+}
+
+/// A `pins.always_load` pattern and how many files it currently matches
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PinPattern {
+    pub pattern: String,
+    pub matched: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModifiedFile {
+    pub path: PathBuf,
+    /// Line count of the working copy, `None` when it isn't valid UTF-8
+    pub lines: Option<usize>,
+}
+
+/// Result of `commands::log::log_report`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogReport {
+    pub branch: String,
+    /// Hash of the commit HEAD points at
+    pub head: Option<String>,
+    /// Commits on the branch before any filters were applied
+    pub total_commits: usize,
+    /// Matching commits, newest first unless `reverse` was requested
+    pub commits: Vec<Commit>,
+    /// Branch each commit was made on, when logging across branches
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub branches: BTreeMap<String, String>,
+}
+
+/// Result of `commands::load::load_path`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoadOutcome {
+    /// Vault-relative path that was loaded
+    pub path: PathBuf,
+    pub tokens: usize,
+    pub pinned: bool,
+}
+
+/// A name `[[name]]` can link to, as listed by `gnu resolve --all`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WikilinkTarget {
+    /// File stem, or `domain/stem` for the path form
+    pub name: String,
+    /// Vault-relative path, or `None` when the name is ambiguous
+    pub path: Option<PathBuf>,
+    /// Every file the name matches when it is ambiguous
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub candidates: Vec<PathBuf>,
+}
+
+/// Rendered context, as emitted by `gnu context --json`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContextReport {
+    pub files: Vec<PathBuf>,
+    /// Files left out to stay within `--budget`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dropped: Vec<PathBuf>,
+    pub content: String,
+    pub token_estimate: usize,
+}
+
+/// Result of `commands::diff::diff_report`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiffReport {
+    /// Commit compared from
+    pub from: String,
+    /// Commit compared to, or `None` for the working directory
+    pub to: Option<String>,
+    pub files: Vec<DiffEntry>,
+    pub token_delta: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiffEntry {
+    pub path: PathBuf,
+    /// "added", "modified", "renamed" or "removed"
+    pub status: String,
+    /// Previous path of a renamed file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from: Option<PathBuf>,
+}
+
+/// Metadata header describing where an export came from
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportMetadata {
+    pub vault_name: String,
+    pub branch: String,
+    pub commit: Option<String>,
+    pub exported_at: DateTime<Utc>,
+}
+
+impl ExportMetadata {
+    /// Name of the metadata entry inside a tar export
+    pub const TAR_ENTRY: &'static str = "gitnu-export.json";
+}
+
+/// Header of a `gnu backup` archive of the whole `.gitnu` store
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupManifest {
+    /// Bumped whenever the archive layout changes, so restores can migrate
+    pub format_version: u32,
+    pub vault_name: String,
+    pub commit_count: usize,
+    pub created_at: DateTime<Utc>,
+}
+
+impl BackupManifest {
+    pub const FORMAT_VERSION: u32 = 1;
+
+    /// Name of the manifest entry inside a backup archive
+    pub const TAR_ENTRY: &'static str = "gitnu-backup.json";
+}
+
+/// A portable bundle produced by `gnu export --format json`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportBundle {
+    pub metadata: ExportMetadata,
+    pub files: Vec<ExportedFile>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportedFile {
+    pub path: PathBuf,
+    pub content: String,
+    /// "base64" for binary files; text is stored as is
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<String>,
+}
+
+impl ExportedFile {
+    pub const BASE64: &'static str = "base64";
+
+    /// Store text as is and anything else base64-encoded, so binary files
+    /// survive the round trip through JSON
+    pub fn new(path: PathBuf, bytes: Vec<u8>) -> Self {
+        use base64::Engine;
+        match String::from_utf8(bytes) {
+            Ok(content) => ExportedFile { path, content, encoding: None },
+            Err(e) => ExportedFile {
+                path,
+                content: base64::engine::general_purpose::STANDARD.encode(e.into_bytes()),
+                encoding: Some(Self::BASE64.to_string()),
+            },
+        }
+    }
+
+    /// The file's original bytes
+    pub fn into_bytes(self) -> crate::errors::Result<Vec<u8>> {
+        use base64::Engine;
+        match self.encoding.as_deref() {
+            None => Ok(self.content.into_bytes()),
+            Some(Self::BASE64) => base64::engine::general_purpose::STANDARD
+                .decode(&self.content)
+                .map_err(|e| crate::errors::GitnuError::Other(format!(
+                    "Invalid base64 content for {}: {}", self.path.display(), e
+                ))),
+            Some(other) => Err(crate::errors::GitnuError::Other(format!(
+                "Unknown encoding '{}' for {}", other, self.path.display()
+            ))),
         }
     }
 }
@@ -164,6 +789,111 @@ pub struct Manifest {
     pub total_files: usize,
     pub total_size: u64,
     pub created_at: DateTime<Utc>,
+    /// Tree under `objects/trees/` holding the snapshot. Absent for snapshots
+    /// stored per commit, before trees were shared.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tree: Option<String>,
+}
+
+/// One movement of a branch ref, appended to `.gitnu/logs/<branch>.jsonl`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReflogEntry {
+    /// Previous target, `None` when the operation created the branch
+    pub old: Option<String>,
+    pub new: String,
+    pub timestamp: DateTime<Utc>,
+    /// What moved the ref, e.g. "commit: Add notes" or "rewind: to abc1234"
+    pub action: String,
+}
+
+impl ReflogEntry {
+    /// Whether this entry records a `gnu undo`
+    pub fn is_undo(&self) -> bool {
+        self.action.starts_with("undo")
+    }
+}
+
+/// Notes and tags attached to a commit by `gnu annotate`, stored in
+/// `.gitnu/notes/<hash>.json` outside the commit so it keeps its hash
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CommitNotes {
+    #[serde(default)]
+    pub notes: Vec<Note>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+impl CommitNotes {
+    pub fn is_empty(&self) -> bool {
+        self.notes.is_empty() && self.tags.is_empty()
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t == tag)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Note {
+    pub text: String,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// Conflict resolution progress, kept in `.gitnu/MERGE_STATE.json` while a
+/// merge is unfinished. `gnu commit` refuses to run until every file is
+/// resolved; the merged head itself is kept in `.gitnu/MERGE_HEAD`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MergeState {
+    /// Branch being merged, when known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    pub files: Vec<ConflictFile>,
+}
+
+impl MergeState {
+    pub fn unresolved(&self) -> impl Iterator<Item = &ConflictFile> {
+        self.files.iter().filter(|f| !f.resolved)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConflictFile {
+    pub path: PathBuf,
+    pub resolved: bool,
+    /// Binary files get no markers: the current branch's version is left in
+    /// place until a side is picked
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub binary: bool,
+}
+
+/// Index of a packfile written by `gnu gc --repack`, keyed by commit hash
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PackIndex {
+    pub entries: BTreeMap<String, PackEntry>,
+}
+
+/// Location of one snapshot inside a packfile
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PackEntry {
+    pub offset: u64,
+    pub length: u64,
+    pub manifest: Option<Manifest>,
+}
+
+/// Size and mtime of each file as written by the last snapshot restore, so
+/// files a checkout merely rewrote aren't taken for edited ones. Only valid
+/// while `commit` is the commit that was restored.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StatCache {
+    pub commit: String,
+    pub files: BTreeMap<PathBuf, RestoredStat>,
+}
+
+/// What a file looked like on disk right after it was restored
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RestoredStat {
+    pub size: u64,
+    pub mtime: Option<i64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -171,4 +901,9 @@ pub struct FileInfo {
     pub path: PathBuf,
     pub hash: String,
     pub size: u64,
+    /// Modification time (nanoseconds since the epoch) when the snapshot was taken
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mtime: Option<i64>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub binary: bool,
 }
@@ -4,20 +4,53 @@ use crate::utils::*;
 use flate2::write::GzEncoder;
 use flate2::read::GzDecoder;
 use flate2::Compression;
+use std::collections::HashMap;
 use std::fs::{self, File};
-use std::io::{Write, BufRead, BufReader};
-use std::path::PathBuf;
+use std::io::{Write, BufRead, BufReader, BufWriter, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
 use chrono::Utc;
-use tar::{Archive, Builder};
+use fs2::FileExt;
+use rayon::prelude::*;
+use std::time::{Duration, Instant};
+use tar::{Archive, Builder, EntryType, Header};
 use walkdir::WalkDir;
 
+/// How long to wait for another process to release the vault lock
+const LOCK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Exclusive advisory lock on the vault, released when dropped
+pub struct VaultLock {
+    file: File,
+}
+
+impl Drop for VaultLock {
+    fn drop(&mut self) {
+        let _ = FileExt::unlock(&self.file);
+    }
+}
+
+/// A tree written by `Storage::write_tree`, not yet tied to a commit
+pub struct Snapshot {
+    /// Hash of every path and content hash in the tree
+    pub tree: String,
+    /// The tree's tarball
+    pub path: PathBuf,
+    manifest: Manifest,
+}
+
 pub struct Storage {
     pub vault_root: PathBuf,
+    /// Resolved from `core.objects_dir` once, since every snapshot path derives from it
+    objects_dir: PathBuf,
 }
 
 impl Storage {
     pub fn new(vault_root: PathBuf) -> Self {
-        Storage { vault_root }
+        let mut storage = Storage { objects_dir: PathBuf::new(), vault_root };
+        // A missing or unreadable config (before init, or for doctor) means the default
+        let configured = storage.load_config().ok().and_then(|c| c.core.objects_dir);
+        storage.objects_dir = storage.resolve_objects_dir(configured.as_deref());
+        storage
     }
 
     pub fn gitnu_dir(&self) -> PathBuf {
@@ -29,17 +62,97 @@ impl Storage {
     }
 
     pub fn objects_dir(&self) -> PathBuf {
-        self.gitnu_dir().join("objects")
+        self.objects_dir.clone()
+    }
+
+    /// Object store for a `core.objects_dir` setting, relative to the vault root
+    pub fn resolve_objects_dir(&self, configured: Option<&Path>) -> PathBuf {
+        match configured {
+            Some(dir) => self.vault_root.join(dir),
+            None => self.gitnu_dir().join("objects"),
+        }
+    }
+
+    /// Create an object store directory if needed and make sure files can be
+    /// written to it
+    pub fn check_objects_dir(dir: &Path) -> Result<()> {
+        let probe = dir.join(".gitnu-write-test");
+        ensure_dir(dir)
+            .and_then(|_| Ok(fs::write(&probe, b"")?))
+            .and_then(|_| Ok(fs::remove_file(&probe)?))
+            .map_err(|e| GitnuError::Other(format!(
+                "Object store {} is not writable: {}\n  Fix core.objects_dir in .gitnu/config.toml",
+                dir.display(),
+                e
+            )))
+    }
+
+    pub fn pack_dir(&self) -> PathBuf {
+        self.objects_dir().join("pack")
+    }
+
+    /// Snapshot tarballs keyed by the hash of their content
+    pub fn trees_dir(&self) -> PathBuf {
+        self.objects_dir().join("trees")
     }
 
     pub fn refs_dir(&self) -> PathBuf {
         self.gitnu_dir().join("refs/heads")
     }
 
+    pub fn branch_meta_dir(&self) -> PathBuf {
+        self.gitnu_dir().join("refs/meta")
+    }
+
     pub fn commits_dir(&self) -> PathBuf {
         self.gitnu_dir().join("commits")
     }
 
+    pub fn logs_dir(&self) -> PathBuf {
+        self.gitnu_dir().join("logs")
+    }
+
+    pub fn notes_dir(&self) -> PathBuf {
+        self.gitnu_dir().join("notes")
+    }
+
+    /// Take the vault-wide lock held by mutating commands, refusing when the
+    /// vault is read-only
+    pub fn lock(&self) -> Result<VaultLock> {
+        self.ensure_writable()?;
+        self.lock_ignoring_read_only()
+    }
+
+    /// Fail if `core.read_only` is set. An unreadable config doesn't count,
+    /// so `gnu doctor` can still get at it.
+    pub fn ensure_writable(&self) -> Result<()> {
+        if self.load_config().is_ok_and(|c| c.core.read_only) {
+            return Err(GitnuError::ReadOnly);
+        }
+        Ok(())
+    }
+
+    /// Take the vault lock for commands that only read, or that must work on
+    /// a read-only vault (such as `gnu config set core.read_only false`)
+    pub fn lock_ignoring_read_only(&self) -> Result<VaultLock> {
+        let lock_path = self.gitnu_dir().join("gitnu.lock");
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&lock_path)?;
+
+        let start = Instant::now();
+        while file.try_lock_exclusive().is_err() {
+            if start.elapsed() >= LOCK_TIMEOUT {
+                return Err(GitnuError::VaultLocked(lock_path));
+            }
+            std::thread::sleep(Duration::from_millis(50));
+        }
+
+        Ok(VaultLock { file })
+    }
+
     /// Initialize vault structure
     pub fn init(&self, vault_name: &str) -> Result<()> {
         let gitnu = self.gitnu_dir();
@@ -72,7 +185,7 @@ impl Storage {
     pub fn save_config(&self, config: &Config) -> Result<()> {
         let path = self.gitnu_dir().join("config.toml");
         let content = toml::to_string_pretty(config)?;
-        fs::write(path, content)?;
+        atomic_write(&path, content.as_bytes())?;
         Ok(())
     }
 
@@ -87,7 +200,7 @@ impl Storage {
     pub fn save_index(&self, index: &Index) -> Result<()> {
         let path = self.gitnu_dir().join("index.json");
         let content = serde_json::to_string_pretty(index)?;
-        fs::write(path, content)?;
+        atomic_write(&path, content.as_bytes())?;
         Ok(())
     }
 
@@ -104,26 +217,104 @@ impl Storage {
     /// Write HEAD reference
     pub fn write_head(&self, branch: &str) -> Result<()> {
         let path = self.gitnu_dir().join("HEAD");
-        fs::write(path, format!("ref: refs/heads/{}", branch))?;
+        atomic_write(&path, format!("ref: refs/heads/{}", branch).as_bytes())?;
         Ok(())
     }
 
-    /// Read HEAD reference (returns branch name)
-    pub fn read_head(&self) -> Result<String> {
+    /// Read HEAD reference
+    pub fn read_head(&self) -> Result<Head> {
         let path = self.gitnu_dir().join("HEAD");
         let content = fs::read_to_string(path)?;
         
         if let Some(branch) = content.strip_prefix("ref: refs/heads/") {
-            Ok(branch.trim().to_string())
+            Ok(Head::Branch(branch.trim().to_string()))
         } else {
-            Ok(content.trim().to_string())
+            Ok(Head::Detached(content.trim().to_string()))
+        }
+    }
+
+    /// Name of the checked-out branch; errors when HEAD is detached
+    pub fn current_branch(&self) -> Result<String> {
+        match self.read_head()? {
+            Head::Branch(name) => Ok(name),
+            Head::Detached(hash) => Err(GitnuError::DetachedHead(short_hash(&hash).to_string())),
         }
     }
 
+    /// Point HEAD directly at a commit (detached HEAD)
+    pub fn write_detached_head(&self, commit_hash: &str) -> Result<()> {
+        let path = self.gitnu_dir().join("HEAD");
+        atomic_write(&path, commit_hash.as_bytes())?;
+        Ok(())
+    }
+
     /// Write branch reference
     pub fn write_branch_ref(&self, branch: &str, commit_hash: &str) -> Result<()> {
         let path = self.refs_dir().join(branch);
-        fs::write(path, commit_hash)?;
+        atomic_write(&path, commit_hash.as_bytes())?;
+        Ok(())
+    }
+
+    /// Move a branch ref and record the move in the branch's reflog
+    pub fn update_branch_ref(&self, branch: &str, commit_hash: &str, action: &str) -> Result<()> {
+        let entry = ReflogEntry {
+            old: self.read_branch_ref(branch)?,
+            new: commit_hash.to_string(),
+            timestamp: Utc::now(),
+            action: action.to_string(),
+        };
+
+        ensure_dir(&self.logs_dir())?;
+        let log_path = self.logs_dir().join(format!("{}.jsonl", branch));
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(log_path)?;
+        let line = format!("{}\n", serde_json::to_string(&entry)?);
+        file.write_all(line.as_bytes())?;
+        file.sync_data()?;
+
+        self.write_branch_ref(branch, commit_hash)
+    }
+
+    /// Every recorded move of a branch ref, oldest first
+    pub fn read_reflog(&self, branch: &str) -> Result<Vec<ReflogEntry>> {
+        let log_path = self.logs_dir().join(format!("{}.jsonl", branch));
+        if !log_path.exists() {
+            return Ok(vec![]);
+        }
+
+        let mut entries = Vec::new();
+        for line in BufReader::new(File::open(log_path)?).lines() {
+            let line = line?;
+            if !line.trim().is_empty() {
+                entries.push(serde_json::from_str(&line)?);
+            }
+        }
+        Ok(entries)
+    }
+
+    /// Notes attached to a commit (empty when it has none)
+    pub fn read_notes(&self, commit_hash: &str) -> Result<CommitNotes> {
+        let path = self.notes_dir().join(format!("{}.json", commit_hash));
+        if !path.exists() {
+            return Ok(CommitNotes::default());
+        }
+        let content = fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    /// Replace a commit's notes, removing the file once nothing is left
+    pub fn write_notes(&self, commit_hash: &str, notes: &CommitNotes) -> Result<()> {
+        let path = self.notes_dir().join(format!("{}.json", commit_hash));
+        if notes.is_empty() {
+            if path.exists() {
+                fs::remove_file(path)?;
+            }
+            return Ok(());
+        }
+        ensure_dir(&self.notes_dir())?;
+        atomic_write(&path, serde_json::to_string_pretty(notes)?.as_bytes())?;
         Ok(())
     }
 
@@ -137,6 +328,94 @@ impl Storage {
         Ok(Some(content.trim().to_string()))
     }
 
+    /// Conflict resolution progress of an unfinished merge, if one is in progress
+    pub fn read_merge_state(&self) -> Result<Option<MergeState>> {
+        let path = self.gitnu_dir().join("MERGE_STATE.json");
+        if !path.exists() {
+            return Ok(None);
+        }
+        Ok(Some(serde_json::from_str(&fs::read_to_string(path)?)?))
+    }
+
+    pub fn write_merge_state(&self, state: &MergeState) -> Result<()> {
+        let path = self.gitnu_dir().join("MERGE_STATE.json");
+        atomic_write(&path, serde_json::to_string_pretty(state)?.as_bytes())
+    }
+
+    /// Record an unfinished merge: the merged head, which the next commit
+    /// takes as its second parent, and the message it proposes
+    pub fn write_merge_head(&self, commit_hash: &str, message: &str) -> Result<()> {
+        atomic_write(&self.gitnu_dir().join("MERGE_HEAD"), commit_hash.as_bytes())?;
+        self.write_merge_msg(message)
+    }
+
+    /// Propose the message for the commit that finishes a stopped merge or revert
+    pub fn write_merge_msg(&self, message: &str) -> Result<()> {
+        atomic_write(&self.gitnu_dir().join("MERGE_MSG"), message.as_bytes())
+    }
+
+    /// The merged head the next commit takes as its second parent
+    pub fn read_merge_head(&self) -> Result<Option<String>> {
+        let path = self.gitnu_dir().join("MERGE_HEAD");
+        if !path.exists() {
+            return Ok(None);
+        }
+        Ok(Some(fs::read_to_string(path)?.trim().to_string()))
+    }
+
+    pub fn read_merge_msg(&self) -> Result<Option<String>> {
+        let path = self.gitnu_dir().join("MERGE_MSG");
+        if !path.exists() {
+            return Ok(None);
+        }
+        Ok(Some(fs::read_to_string(path)?))
+    }
+
+    /// Whether a merge is waiting for conflicts to be resolved and committed
+    pub fn merge_in_progress(&self) -> bool {
+        self.gitnu_dir().join("MERGE_HEAD").exists() || self.gitnu_dir().join("MERGE_STATE.json").exists()
+    }
+
+    /// Forget the merge in progress, once committed or abandoned
+    pub fn clear_merge_state(&self) -> Result<()> {
+        for name in ["MERGE_HEAD", "MERGE_MSG", "MERGE_STATE.json"] {
+            let path = self.gitnu_dir().join(name);
+            if path.exists() {
+                fs::remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Stored metadata of a branch, or `None` for branches created without it
+    pub fn read_branch_meta(&self, branch: &str) -> Result<Option<BranchRef>> {
+        let path = self.branch_meta_dir().join(format!("{}.json", branch));
+        if !path.exists() {
+            return Ok(None);
+        }
+        let mut meta: BranchRef = serde_json::from_str(&fs::read_to_string(path)?)?;
+        if let Some(head) = self.read_branch_ref(branch)? {
+            meta.head = head;
+        }
+        Ok(Some(meta))
+    }
+
+    /// When a branch was created: from its metadata, or for branches made
+    /// before metadata existed, its first reflog entry
+    pub fn branch_created_at(&self, branch: &str) -> Result<Option<chrono::DateTime<Utc>>> {
+        if let Some(meta) = self.read_branch_meta(branch)? {
+            return Ok(Some(meta.created_at));
+        }
+        Ok(self.read_reflog(branch)?.first().map(|e| e.timestamp))
+    }
+
+    /// Save a branch's metadata
+    pub fn write_branch_meta(&self, meta: &BranchRef) -> Result<()> {
+        ensure_dir(&self.branch_meta_dir())?;
+        let path = self.branch_meta_dir().join(format!("{}.json", meta.name));
+        atomic_write(&path, serde_json::to_string_pretty(meta)?.as_bytes())
+    }
+
     /// List all branches
     pub fn list_branches(&self) -> Result<Vec<String>> {
         let refs_dir = self.refs_dir();
@@ -162,87 +441,322 @@ impl Storage {
         if path.exists() {
             fs::remove_file(path)?;
         }
+        // A recreated branch starts with a fresh reflog and metadata
+        let reflog = self.logs_dir().join(format!("{}.jsonl", branch));
+        if reflog.exists() {
+            fs::remove_file(reflog)?;
+        }
+        let meta = self.branch_meta_dir().join(format!("{}.json", branch));
+        if meta.exists() {
+            fs::remove_file(meta)?;
+        }
         Ok(())
     }
 
-    /// Create snapshot of domains directory
-    pub fn create_snapshot(&self, commit_hash: &str) -> Result<PathBuf> {
-        let object_dir = self.objects_dir().join(commit_hash);
-        ensure_dir(&object_dir)?;
-
-        let snapshot_path = object_dir.join("snapshot.tar.gz");
-        let tar_gz = File::create(&snapshot_path)?;
-        let enc = GzEncoder::new(tar_gz, Compression::default());
-        let mut tar = Builder::new(enc);
+    /// Whether hidden files are tracked (`core.include_hidden`)
+    pub fn include_hidden(&self) -> Result<bool> {
+        Ok(self.load_config()?.core.include_hidden)
+    }
 
-        let domains_dir = self.domains_dir();
-        if domains_dir.exists() {
-            for entry in WalkDir::new(&domains_dir).into_iter().filter_map(|e| e.ok()) {
-                let path = entry.path();
-                if path.is_file() {
-                    let rel_path = relative_path(&self.vault_root, path);
-                    tar.append_path_with_name(path, &rel_path)?;
-                }
-            }
+    /// Glob patterns from `.gnignore` at the vault root, one per line; blank
+    /// lines and `#` comments are skipped
+    pub fn ignore_patterns(&self) -> Result<Vec<glob::Pattern>> {
+        let path = self.vault_root.join(".gnignore");
+        if !path.is_file() {
+            return Ok(vec![]);
         }
+        let patterns: Vec<String> = fs::read_to_string(path)?
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(String::from)
+            .collect();
+        compile_patterns(&patterns)
+    }
 
-        tar.finish()?;
+    /// Every file under `dir`, sorted by path. Entries whose name starts with
+    /// `.` are skipped unless `core.include_hidden` is set, as are those
+    /// matching `.gnignore`; all walks over `domains/` go through here so
+    /// snapshots, status and context agree.
+    pub fn walk_files(&self, dir: &Path) -> Result<Vec<PathBuf>> {
+        if !dir.exists() {
+            return Ok(vec![]);
+        }
+        let include_hidden = self.include_hidden()?;
+        let ignored = self.ignore_patterns()?;
+        Ok(WalkDir::new(dir)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_entry(|e| {
+                e.depth() == 0
+                    || ((include_hidden || !is_hidden(e.file_name()))
+                        && !is_ignored(&ignored, &relative_path(&self.vault_root, e.path())))
+            })
+            .filter_map(|e| e.ok())
+            .filter(|e| e.path().is_file())
+            .map(|e| e.into_path())
+            .collect())
+    }
 
-        // Create manifest
-        self.create_manifest(commit_hash)?;
+    /// Every tracked file under `domains/`
+    pub fn domain_files(&self) -> Result<Vec<PathBuf>> {
+        self.walk_files(&self.domains_dir())
+    }
 
-        Ok(snapshot_path)
+    /// Snapshot the domains directory for a commit: `write_tree` followed by
+    /// `record_snapshot`. Returns the path of the tarball.
+    pub fn create_snapshot(&self, commit_hash: &str) -> Result<PathBuf> {
+        let snapshot = self.write_tree()?;
+        self.record_snapshot(commit_hash, &snapshot)
     }
 
-    /// Create manifest for snapshot
-    fn create_manifest(&self, commit_hash: &str) -> Result<()> {
-        let domains_dir = self.domains_dir();
-        let mut files = Vec::new();
-        let mut total_size = 0u64;
+    /// Store the current domains directory as a tree.
+    ///
+    /// The tarball is stored once per distinct tree under `objects/trees/`, so
+    /// commits with identical content (reverts, empty commits) share it. The
+    /// tree hash is known before any commit refers to it, so commits can
+    /// include it in their own hash.
+    pub fn write_tree(&self) -> Result<Snapshot> {
+        // Read and hash every file once, across all cores, so the tree hash,
+        // tarball and manifest agree
+        let mut entries: Vec<(FileInfo, Vec<u8>)> = self.domain_files()?
+            .par_iter()
+            .map(|path| {
+                let metadata = fs::metadata(path)?;
+                let content = fs::read(path)?;
+                let info = FileInfo {
+                    path: relative_path(&self.vault_root, path),
+                    hash: compute_hash(&content),
+                    size: content.len() as u64,
+                    mtime: mtime_nanos(&metadata),
+                    binary: is_binary(&content),
+                };
+                Ok((info, content))
+            })
+            .collect::<Result<_>>()?;
+        // The manifest and tarball must list files in the same order every time
+        entries.sort_by(|a, b| a.0.path.cmp(&b.0.path));
 
-        if domains_dir.exists() {
-            for entry in WalkDir::new(&domains_dir).into_iter().filter_map(|e| e.ok()) {
-                let path = entry.path();
-                if path.is_file() {
-                    let metadata = fs::metadata(path)?;
-                    let size = metadata.len();
-                    total_size += size;
-                    
-                    let hash = hash_file(path)?;
-                    let rel_path = relative_path(&self.vault_root, path);
-                    
-                    files.push(FileInfo {
-                        path: rel_path,
-                        hash,
-                        size,
-                    });
+        let files: Vec<FileInfo> = entries.iter().map(|(info, _)| info.clone()).collect();
+        let tree = tree_hash(&files);
+
+        if !self.objects_dir.exists() {
+            Self::check_objects_dir(&self.objects_dir)?;
+        }
+        let tree_dir = self.trees_dir().join(&tree);
+        let snapshot_path = tree_dir.join("snapshot.tar.gz");
+        if !snapshot_path.exists() {
+            ensure_dir(&tree_dir)?;
+            // Stream the tarball straight to disk rather than building it in memory
+            atomic_write_with(&snapshot_path, |file| {
+                let mut tar = Builder::new(GzEncoder::new(BufWriter::new(file), Compression::default()));
+                for (info, content) in &entries {
+                    append_entry(&mut tar, &info.path, content)?;
                 }
-            }
+                tar.into_inner()?.finish()?.flush()?;
+                Ok(())
+            })?;
         }
 
         let manifest = Manifest {
             total_files: files.len(),
-            total_size,
+            total_size: files.iter().map(|f| f.size).sum(),
             created_at: Utc::now(),
             files,
+            tree: Some(tree.clone()),
         };
+        Ok(Snapshot { tree, path: snapshot_path, manifest })
+    }
 
-        let manifest_path = self.objects_dir().join(commit_hash).join("manifest.json");
-        let content = serde_json::to_string_pretty(&manifest)?;
-        fs::write(manifest_path, content)?;
+    /// Record that `commit_hash` uses a tree written by `write_tree`, in its
+    /// `objects/<hash>/manifest.json`. A commit hash already recorded with a
+    /// different tree is refused rather than overwritten, since that would
+    /// silently change what the existing commit restores.
+    pub fn record_snapshot(&self, commit_hash: &str, snapshot: &Snapshot) -> Result<PathBuf> {
+        let object_dir = self.objects_dir().join(commit_hash);
+        if let Some(existing) = self.read_manifest(commit_hash)? {
+            if existing.tree.as_deref() != Some(snapshot.tree.as_str()) {
+                return Err(GitnuError::Other(format!(
+                    "Commit {} is already stored with different content\n  Refusing to overwrite its snapshot",
+                    short_hash(commit_hash)
+                )));
+            }
+        }
+        ensure_dir(&object_dir)?;
+        let content = serde_json::to_string_pretty(&snapshot.manifest)?;
+        atomic_write(&object_dir.join("manifest.json"), content.as_bytes())?;
+
+        Ok(snapshot.path.clone())
+    }
 
+    /// Append every file under `domains/` to a tar archive.
+    ///
+    /// Entries are sorted and their headers normalized (mtime, owner, mode) so
+    /// identical content always produces identical bytes.
+    pub fn append_domains<W: Write>(&self, tar: &mut Builder<W>) -> Result<()> {
+        for path in self.domain_files()? {
+            let rel_path = relative_path(&self.vault_root, &path);
+            append_entry(tar, &rel_path, &fs::read(&path)?)?;
+        }
         Ok(())
     }
 
-    /// Restore snapshot
-    pub fn restore_snapshot(&self, commit_hash: &str) -> Result<()> {
-        let snapshot_path = self.objects_dir()
-            .join(commit_hash)
-            .join("snapshot.tar.gz");
+    /// Read the manifest recorded for a commit, if one exists
+    pub fn read_manifest(&self, commit_hash: &str) -> Result<Option<Manifest>> {
+        let manifest_path = self.objects_dir().join(commit_hash).join("manifest.json");
+        if !manifest_path.exists() {
+            return Ok(self.find_packed(commit_hash)?.and_then(|(_, entry)| entry.manifest));
+        }
+        let content = fs::read_to_string(manifest_path)?;
+        Ok(Some(serde_json::from_str(&content)?))
+    }
 
-        if !snapshot_path.exists() {
-            return Err(GitnuError::CommitNotFound(commit_hash.to_string()));
+    /// Tarball of a loose commit: its shared tree, or the per-commit
+    /// snapshot written before trees existed
+    fn loose_snapshot(&self, commit_hash: &str) -> Result<Option<PathBuf>> {
+        let object_dir = self.objects_dir().join(commit_hash);
+        let legacy = object_dir.join("snapshot.tar.gz");
+        if legacy.exists() {
+            return Ok(Some(legacy));
+        }
+        if !object_dir.join("manifest.json").exists() {
+            return Ok(None);
+        }
+        let tree = self.read_manifest(commit_hash)?.and_then(|m| m.tree);
+        Ok(tree
+            .map(|tree| self.trees_dir().join(tree).join("snapshot.tar.gz"))
+            .filter(|path| path.exists()))
+    }
+
+    /// Whether a commit's snapshot is stored, loose or packed
+    pub fn has_snapshot(&self, commit_hash: &str) -> Result<bool> {
+        Ok(self.loose_snapshot(commit_hash)?.is_some() || self.find_packed(commit_hash)?.is_some())
+    }
+
+    /// Open a commit's gzipped snapshot, whether loose or packed
+    fn open_snapshot(&self, commit_hash: &str) -> Result<Box<dyn Read>> {
+        if let Some(snapshot_path) = self.loose_snapshot(commit_hash)? {
+            return Ok(Box::new(File::open(snapshot_path)?));
+        }
+
+        match self.find_packed(commit_hash)? {
+            Some((pack_path, entry)) => {
+                let mut pack = File::open(pack_path)?;
+                pack.seek(SeekFrom::Start(entry.offset))?;
+                Ok(Box::new(pack.take(entry.length)))
+            }
+            None => Err(GitnuError::CommitNotFound(commit_hash.to_string())),
+        }
+    }
+
+    /// Delete trees no loose commit uses any more. Returns how many were removed.
+    pub fn prune_trees(&self) -> Result<usize> {
+        let trees_dir = self.trees_dir();
+        if !trees_dir.exists() {
+            return Ok(0);
+        }
+
+        let mut used = std::collections::HashSet::new();
+        for entry in fs::read_dir(self.objects_dir())? {
+            let entry = entry?;
+            let manifest_path = entry.path().join("manifest.json");
+            if manifest_path.exists() {
+                let manifest: Manifest = serde_json::from_str(&fs::read_to_string(manifest_path)?)?;
+                used.extend(manifest.tree);
+            }
+        }
+
+        let mut pruned = 0;
+        for entry in fs::read_dir(&trees_dir)? {
+            let entry = entry?;
+            if !used.contains(entry.file_name().to_string_lossy().as_ref()) {
+                fs::remove_dir_all(entry.path())?;
+                pruned += 1;
+            }
         }
+        Ok(pruned)
+    }
+
+    /// Every packfile with its index, in pack order
+    pub fn list_packs(&self) -> Result<Vec<(PathBuf, PackIndex)>> {
+        let pack_dir = self.pack_dir();
+        if !pack_dir.exists() {
+            return Ok(vec![]);
+        }
+
+        let mut packs = Vec::new();
+        for entry in fs::read_dir(pack_dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|e| e == "idx") {
+                let index: PackIndex = serde_json::from_str(&fs::read_to_string(&path)?)?;
+                packs.push((path.with_extension("pack"), index));
+            }
+        }
+        packs.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok(packs)
+    }
+
+    /// Locate a commit's snapshot inside a packfile
+    fn find_packed(&self, commit_hash: &str) -> Result<Option<(PathBuf, PackEntry)>> {
+        for (pack_path, mut index) in self.list_packs()? {
+            if let Some(entry) = index.entries.remove(commit_hash) {
+                return Ok(Some((pack_path, entry)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Move loose snapshots into a new packfile and delete the loose objects.
+    /// Returns the path of the pack written.
+    pub fn write_pack(&self, commit_hashes: &[String]) -> Result<PathBuf> {
+        let pack_dir = self.pack_dir();
+        ensure_dir(&pack_dir)?;
+
+        let number = self.list_packs()?.len() + 1;
+        let pack_path = pack_dir.join(format!("pack-{:04}.pack", number));
+        let mut pack = File::create(&pack_path)?;
+        let mut index = PackIndex::default();
+        let mut offset = 0;
+
+        // Commits sharing a tree share its bytes in the pack too
+        let mut written: std::collections::HashMap<String, (u64, u64)> = std::collections::HashMap::new();
+        for hash in commit_hashes {
+            let manifest = self.read_manifest(hash)?;
+            let tree = manifest.as_ref().and_then(|m| m.tree.clone());
+            let (entry_offset, length) = match tree.as_ref().and_then(|t| written.get(t)) {
+                Some(&location) => location,
+                None => {
+                    let snapshot_path = self.loose_snapshot(hash)?
+                        .ok_or_else(|| GitnuError::CommitNotFound(hash.clone()))?;
+                    let bytes = fs::read(snapshot_path)?;
+                    pack.write_all(&bytes)?;
+                    let location = (offset, bytes.len() as u64);
+                    offset += bytes.len() as u64;
+                    if let Some(tree) = tree {
+                        written.insert(tree, location);
+                    }
+                    location
+                }
+            };
+            index.entries.insert(hash.clone(), PackEntry { offset: entry_offset, length, manifest });
+        }
+        pack.sync_all()?;
+
+        // The index is written last so a partial pack is never consulted
+        let content = serde_json::to_string_pretty(&index)?;
+        atomic_write(&pack_path.with_extension("idx"), content.as_bytes())?;
+
+        for hash in commit_hashes {
+            fs::remove_dir_all(self.objects_dir().join(hash))?;
+        }
+        self.prune_trees()?;
+
+        Ok(pack_path)
+    }
+
+    /// Restore snapshot
+    pub fn restore_snapshot(&self, commit_hash: &str) -> Result<()> {
+        let tar_gz = self.open_snapshot(commit_hash)?;
 
         // Clear domains directory first
         let domains_dir = self.domains_dir();
@@ -252,14 +766,112 @@ impl Storage {
         ensure_dir(&domains_dir)?;
 
         // Extract snapshot
-        let tar_gz = File::open(snapshot_path)?;
         let dec = GzDecoder::new(tar_gz);
         let mut archive = Archive::new(dec);
+        // Snapshot mtimes are normalized, so stamp restored files with the current time
+        archive.set_preserve_mtime(false);
         archive.unpack(&self.vault_root)?;
 
+        // Remember those new mtimes, or every restored file looks edited
+        let mut cache = StatCache { commit: commit_hash.to_string(), ..Default::default() };
+        if let Some(manifest) = self.read_manifest(commit_hash)? {
+            for info in manifest.files {
+                if let Ok(metadata) = fs::metadata(self.vault_root.join(&info.path)) {
+                    cache.files.insert(info.path, RestoredStat { size: metadata.len(), mtime: mtime_nanos(&metadata) });
+                }
+            }
+        }
+        atomic_write(&self.stat_cache_path(), serde_json::to_string(&cache)?.as_bytes())?;
+
         Ok(())
     }
 
+    fn stat_cache_path(&self) -> PathBuf {
+        self.gitnu_dir().join("stat_cache.json")
+    }
+
+    /// A commit's manifest with each file's size and mtime as last seen on
+    /// disk: from the stat cache if that commit was the last one restored,
+    /// otherwise as recorded when the snapshot was taken
+    pub fn read_manifest_with_stats(&self, commit_hash: &str) -> Result<Option<Manifest>> {
+        let mut manifest = match self.read_manifest(commit_hash)? {
+            Some(m) => m,
+            None => return Ok(None),
+        };
+        // A missing or unreadable cache only costs re-hashing
+        let cache: Option<StatCache> = fs::read_to_string(self.stat_cache_path())
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok());
+        if let Some(cache) = cache.filter(|c| c.commit == commit_hash) {
+            for info in manifest.files.iter_mut() {
+                if let Some(stat) = cache.files.get(&info.path) {
+                    info.size = stat.size;
+                    info.mtime = stat.mtime;
+                }
+            }
+        }
+        Ok(Some(manifest))
+    }
+
+    /// Put back the loaded/pinned/excluded state recorded with a commit after
+    /// its snapshot is restored. Commits that predate recorded indexes keep the
+    /// current one. Either way, entries for paths missing from the restored
+    /// files are dropped; returns how many.
+    pub fn restore_index(&self, commit: &Commit) -> Result<usize> {
+        let mut index = match commit.index {
+            Some(ref index) => index.clone(),
+            None => self.load_index()?,
+        };
+        let stale = index.retain_existing(&self.vault_root);
+        self.save_index(&index)?;
+        Ok(stale)
+    }
+
+    /// Whether the loaded/pinned/excluded state differs from the one recorded
+    /// with `commit`. Commits that predate recorded indexes never differ.
+    pub fn index_changed_since(&self, commit: Option<&Commit>) -> Result<bool> {
+        match commit.and_then(|c| c.index.as_ref()) {
+            Some(recorded) => Ok(*recorded != self.load_index()?),
+            None => Ok(false),
+        }
+    }
+
+    /// Read a single file out of a commit's snapshot without unpacking it
+    pub fn read_file_from_snapshot(&self, commit_hash: &str, rel_path: &Path) -> Result<Vec<u8>> {
+        let tar_gz = self.open_snapshot(commit_hash)?;
+        let mut archive = Archive::new(GzDecoder::new(tar_gz));
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            if entry.path()? == rel_path {
+                let mut content = Vec::new();
+                entry.read_to_end(&mut content)?;
+                return Ok(content);
+            }
+        }
+
+        Err(GitnuError::FileNotFound(rel_path.to_path_buf()))
+    }
+
+    /// Read several files out of a commit's snapshot in one pass over it.
+    /// Paths the snapshot doesn't have are left out.
+    pub fn read_files_from_snapshot(&self, commit_hash: &str, rel_paths: &[PathBuf]) -> Result<HashMap<PathBuf, Vec<u8>>> {
+        let wanted: std::collections::HashSet<&PathBuf> = rel_paths.iter().collect();
+        let tar_gz = self.open_snapshot(commit_hash)?;
+        let mut archive = Archive::new(GzDecoder::new(tar_gz));
+        let mut contents = HashMap::new();
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            let path = entry.path()?.to_path_buf();
+            if wanted.contains(&path) {
+                let mut content = Vec::new();
+                entry.read_to_end(&mut content)?;
+                contents.insert(path, content);
+            }
+        }
+
+        Ok(contents)
+    }
+
     /// Append commit to branch log
     pub fn append_commit(&self, branch: &str, commit: &Commit) -> Result<()> {
         let log_path = self.commits_dir().join(format!("{}.jsonl", branch));
@@ -268,11 +880,24 @@ impl Storage {
             .append(true)
             .open(log_path)?;
         
-        let line = serde_json::to_string(commit)?;
-        writeln!(file, "{}", line)?;
+        // Append the whole line in one write so a crash can't interleave records
+        let line = format!("{}\n", serde_json::to_string(commit)?);
+        file.write_all(line.as_bytes())?;
+        file.sync_data()?;
         Ok(())
     }
 
+    /// Replace a branch log wholesale, used when history is rewritten
+    pub fn write_commits(&self, branch: &str, commits: &[Commit]) -> Result<()> {
+        let log_path = self.commits_dir().join(format!("{}.jsonl", branch));
+        let mut content = String::new();
+        for commit in commits {
+            content.push_str(&serde_json::to_string(commit)?);
+            content.push('\n');
+        }
+        atomic_write(&log_path, content.as_bytes())
+    }
+
     /// Read commits from branch log
     pub fn read_commits(&self, branch: &str) -> Result<Vec<Commit>> {
         let log_path = self.commits_dir().join(format!("{}.jsonl", branch));
@@ -295,9 +920,29 @@ impl Storage {
         Ok(commits)
     }
 
-    /// Find commit by hash (searches all branches)
+    /// Names of every commit log, including logs of deleted branches
+    pub fn list_commit_logs(&self) -> Result<Vec<String>> {
+        let commits_dir = self.commits_dir();
+        if !commits_dir.exists() {
+            return Ok(vec![]);
+        }
+
+        let mut logs = Vec::new();
+        for entry in fs::read_dir(commits_dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|e| e == "jsonl") {
+                if let Some(stem) = path.file_stem().and_then(|s| s.to_str()) {
+                    logs.push(stem.to_string());
+                }
+            }
+        }
+        Ok(logs)
+    }
+
+    /// Find commit by hash (searches every commit log, so commits from
+    /// pruned branches stay reachable through merge parents)
     pub fn find_commit(&self, hash: &str) -> Result<Option<Commit>> {
-        for branch in self.list_branches()? {
+        for branch in self.list_commit_logs()? {
             let commits = self.read_commits(&branch)?;
             for commit in commits {
                 if commit.hash.starts_with(hash) {
@@ -308,9 +953,128 @@ impl Storage {
         Ok(None)
     }
 
-    /// Get current HEAD commit
+    /// Resolve a branch name, `HEAD`, or (abbreviated) commit hash to a commit,
+    /// optionally followed by `~N` to step back N first parents
+    pub fn resolve_commit(&self, reference: &str) -> Result<Commit> {
+        // `<ref>~N` walks N first parents back
+        if let Some((base, steps)) = reference.rsplit_once('~') {
+            let steps: usize = if steps.is_empty() {
+                1
+            } else {
+                steps.parse().map_err(|_| GitnuError::InvalidCommitRef(reference.to_string()))?
+            };
+            let base = self.resolve_commit(base)?;
+            return self.commit_graph()?
+                .first_parents(&base.hash)
+                .nth(steps)
+                .cloned()
+                .ok_or_else(|| GitnuError::CommitNotFound(reference.to_string()));
+        }
+
+        if reference == "HEAD" {
+            return self.get_head_commit()?
+                .ok_or_else(|| GitnuError::CommitNotFound(reference.to_string()));
+        }
+
+        // Try as branch first
+        if let Some(hash) = self.read_branch_ref(reference)? {
+            if let Some(commit) = self.find_commit(&hash)? {
+                return Ok(commit);
+            }
+        }
+
+        // Try as commit hash
+        self.find_commit(reference)?
+            .ok_or_else(|| GitnuError::CommitNotFound(reference.to_string()))
+    }
+
+    /// Every commit in every log, read once, for walks that visit many commits
+    pub fn commit_graph(&self) -> Result<CommitGraph> {
+        let mut commits = HashMap::new();
+        for log in self.list_commit_logs()? {
+            for commit in self.read_commits(&log)? {
+                commits.insert(commit.hash.clone(), commit);
+            }
+        }
+        Ok(CommitGraph { commits })
+    }
+
+    /// All commits reachable from a commit through parent and merge-parent links
+    pub fn ancestry(&self, hash: &str) -> Result<Vec<Commit>> {
+        Ok(self.commit_graph()?.ancestry(hash).into_iter().cloned().collect())
+    }
+
+    /// Newest commit made before `until`, following first parents back from HEAD
+    pub fn commit_before(&self, until: chrono::DateTime<Utc>) -> Result<Option<Commit>> {
+        let Some(head) = self.get_head_commit()? else {
+            return Ok(None);
+        };
+        Ok(self.commit_graph()?
+            .first_parents(&head.hash)
+            .find(|commit| commit.timestamp < until)
+            .cloned())
+    }
+
+    /// Hashes of every commit something may still need, and their ancestors.
+    /// The roots are branch heads, a detached HEAD, every commit a branch's
+    /// reflog mentions (`gnu undo` goes back to them), a pending MERGE_HEAD and
+    /// commits with notes. gc, fsck and doctor all agree on this set.
+    pub fn reachable_commits(&self) -> Result<std::collections::HashSet<String>> {
+        let graph = self.commit_graph()?;
+
+        let mut queue: Vec<String> = Vec::new();
+        for branch in self.list_branches()? {
+            queue.extend(self.read_branch_ref(&branch)?);
+            for entry in self.read_reflog(&branch)? {
+                queue.extend(entry.old);
+                queue.push(entry.new);
+            }
+        }
+        if let Ok(Head::Detached(hash)) = self.read_head() {
+            queue.push(hash);
+        }
+        queue.extend(self.read_merge_head()?);
+        if self.notes_dir().exists() {
+            for entry in fs::read_dir(self.notes_dir())? {
+                let path = entry?.path();
+                if path.extension().is_some_and(|e| e == "json") {
+                    queue.extend(path.file_stem().map(|s| s.to_string_lossy().to_string()));
+                }
+            }
+        }
+
+        let mut reachable = std::collections::HashSet::new();
+        while let Some(hash) = queue.pop() {
+            if !reachable.insert(hash.clone()) {
+                continue;
+            }
+            if let Some(commit) = graph.commits.get(&hash) {
+                queue.extend(commit.parent.clone());
+                queue.extend(commit.merge_parent.clone());
+            }
+        }
+        Ok(reachable)
+    }
+
+    /// Whether `ancestor` is reachable from `descendant` (a commit is its own ancestor)
+    pub fn is_ancestor(&self, ancestor: &str, descendant: &str) -> Result<bool> {
+        Ok(self.commit_graph()?.is_ancestor(ancestor, descendant))
+    }
+
+    /// Nearest commit reachable from both `a` and `b`, if they share history
+    pub fn merge_base(&self, a: &str, b: &str) -> Result<Option<Commit>> {
+        Ok(self.commit_graph()?.merge_base(a, b).cloned())
+    }
+
+    /// Get current HEAD commit. On a detached HEAD this is the checked-out
+    /// commit, so changes (deletions included) are measured against what is
+    /// actually on disk rather than against a branch.
     pub fn get_head_commit(&self) -> Result<Option<Commit>> {
-        let branch = self.read_head()?;
+        let branch = match self.read_head()? {
+            Head::Branch(name) => name,
+            // Detached HEAD stores the commit hash itself
+            Head::Detached(hash) => return self.find_commit(&hash),
+        };
         let commit_hash = match self.read_branch_ref(&branch)? {
             Some(hash) => hash,
             None => return Ok(None),
@@ -318,3 +1082,248 @@ impl Storage {
         self.find_commit(&commit_hash)
     }
 }
+
+/// Every commit of the vault keyed by hash, so walking history is a map
+/// lookup per step instead of a scan of every log
+pub struct CommitGraph {
+    commits: HashMap<String, Commit>,
+}
+
+impl CommitGraph {
+    /// Commit with this full or abbreviated hash
+    pub fn get(&self, hash: &str) -> Option<&Commit> {
+        self.commits.get(hash)
+            .or_else(|| self.commits.values().find(|c| c.hash.starts_with(hash)))
+    }
+
+    /// All commits reachable from a commit through parent and merge-parent
+    /// links, breadth-first starting with the commit itself
+    pub fn ancestry(&self, hash: &str) -> Vec<&Commit> {
+        let mut commits = Vec::new();
+        let mut seen = std::collections::HashSet::new();
+        let mut queue = std::collections::VecDeque::from([hash.to_string()]);
+
+        while let Some(current) = queue.pop_front() {
+            if !seen.insert(current.clone()) {
+                continue;
+            }
+            if let Some(commit) = self.get(&current) {
+                queue.extend(commit.parent.clone());
+                queue.extend(commit.merge_parent.clone());
+                commits.push(commit);
+            }
+        }
+        commits
+    }
+
+    /// A commit and its first parents, newest first
+    pub fn first_parents<'a>(&'a self, hash: &str) -> impl Iterator<Item = &'a Commit> + 'a {
+        std::iter::successors(self.get(hash), move |commit| {
+            commit.parent.as_deref().and_then(|parent| self.commits.get(parent))
+        })
+    }
+
+    /// Whether `ancestor` is reachable from `descendant` (a commit is its own ancestor)
+    pub fn is_ancestor(&self, ancestor: &str, descendant: &str) -> bool {
+        self.ancestry(descendant).iter().any(|c| c.hash.starts_with(ancestor))
+    }
+
+    /// Nearest commit reachable from both `a` and `b`, if they share history
+    pub fn merge_base(&self, a: &str, b: &str) -> Option<&Commit> {
+        let reachable_from_b: std::collections::HashSet<&str> = self.ancestry(b)
+            .into_iter()
+            .map(|c| c.hash.as_str())
+            .collect();
+        // ancestry() walks breadth-first, so the first shared commit is the closest
+        self.ancestry(a).into_iter().find(|c| reachable_from_b.contains(c.hash.as_str()))
+    }
+}
+
+/// Hash identifying a snapshot's content: every path with its content hash
+fn tree_hash(files: &[FileInfo]) -> String {
+    let mut listing = String::new();
+    for file in files {
+        listing.push_str(&format!("{} {}\n", file.hash, file.path.display()));
+    }
+    compute_hash(listing.as_bytes())
+}
+
+/// Whether `.gnignore` leaves out `rel_path`: a pattern with a `/` matches the
+/// path from the vault root, one without matches any file or directory name
+fn is_ignored(patterns: &[glob::Pattern], rel_path: &Path) -> bool {
+    patterns.iter().any(|pattern| {
+        if pattern.as_str().contains('/') {
+            pattern.matches_path(rel_path)
+        } else {
+            rel_path.file_name().is_some_and(|name| pattern.matches(&name.to_string_lossy()))
+        }
+    })
+}
+
+/// Append one file with a normalized header
+fn append_entry<W: Write>(tar: &mut Builder<W>, rel_path: &Path, content: &[u8]) -> Result<()> {
+    let mut header = Header::new_gnu();
+    header.set_size(content.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(0);
+    header.set_uid(0);
+    header.set_gid(0);
+    header.set_entry_type(EntryType::Regular);
+    tar.append_data(&mut header, rel_path, content)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn snapshot_vault() -> (TempDir, Storage) {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = Storage::new(temp_dir.path().to_path_buf());
+        storage.init("test").unwrap();
+
+        let domain_dir = storage.domains_dir().join("auth");
+        fs::create_dir_all(&domain_dir).unwrap();
+        fs::write(domain_dir.join("spec.md"), "# Spec").unwrap();
+        fs::write(domain_dir.join("notes.md"), "# Notes").unwrap();
+
+        storage.create_snapshot("abc1234").unwrap();
+        (temp_dir, storage)
+    }
+
+    #[test]
+    fn test_read_file_from_snapshot() {
+        let (_temp_dir, storage) = snapshot_vault();
+
+        let content = storage
+            .read_file_from_snapshot("abc1234", Path::new("domains/auth/spec.md"))
+            .unwrap();
+        assert_eq!(content, b"# Spec");
+    }
+
+    #[test]
+    fn test_read_file_from_snapshot_missing_file() {
+        let (_temp_dir, storage) = snapshot_vault();
+
+        let result = storage.read_file_from_snapshot("abc1234", Path::new("domains/auth/nope.md"));
+        assert!(matches!(result, Err(GitnuError::FileNotFound(_))));
+    }
+
+    #[test]
+    fn test_read_file_from_snapshot_missing_commit() {
+        let (_temp_dir, storage) = snapshot_vault();
+
+        let result = storage.read_file_from_snapshot("deadbeef", Path::new("domains/auth/spec.md"));
+        assert!(matches!(result, Err(GitnuError::CommitNotFound(_))));
+    }
+
+    #[test]
+    fn test_read_files_from_snapshot() {
+        let (_temp_dir, storage) = snapshot_vault();
+
+        let paths = [PathBuf::from("domains/auth/spec.md"), PathBuf::from("domains/auth/nope.md")];
+        let contents = storage.read_files_from_snapshot("abc1234", &paths).unwrap();
+        assert_eq!(contents.len(), 1);
+        assert_eq!(contents[&paths[0]], b"# Spec");
+    }
+
+    #[test]
+    fn test_snapshots_of_identical_content_share_a_tree() {
+        let (_temp_dir, storage) = snapshot_vault();
+        let first = storage.objects_dir().join("trees");
+        let trees = || fs::read_dir(&first).unwrap().count();
+        assert_eq!(trees(), 1);
+
+        // Rewriting the files bumps their mtimes without changing content
+        let domain_dir = storage.domains_dir().join("auth");
+        fs::write(domain_dir.join("spec.md"), "# Spec").unwrap();
+        fs::write(domain_dir.join("notes.md"), "# Notes").unwrap();
+        storage.create_snapshot("def5678").unwrap();
+        assert_eq!(trees(), 1);
+
+        let tree = |hash: &str| storage.read_manifest(hash).unwrap().unwrap().tree;
+        assert_eq!(tree("abc1234"), tree("def5678"));
+        assert!(storage.has_snapshot("def5678").unwrap());
+
+        // Each tree is gone once no loose commit uses it
+        fs::write(domain_dir.join("spec.md"), "# Spec v2").unwrap();
+        storage.create_snapshot("0a0b0c0").unwrap();
+        assert_eq!(trees(), 2);
+        storage.write_pack(&["abc1234".to_string(), "def5678".to_string()]).unwrap();
+        assert_eq!(trees(), 1);
+        let spec = Path::new("domains/auth/spec.md");
+        assert_eq!(storage.read_file_from_snapshot("def5678", spec).unwrap(), b"# Spec");
+        assert_eq!(storage.read_file_from_snapshot("0a0b0c0", spec).unwrap(), b"# Spec v2");
+    }
+
+    #[test]
+    fn test_record_snapshot_refuses_a_different_tree() {
+        let (_temp_dir, storage) = snapshot_vault();
+        let spec = Path::new("domains/auth/spec.md");
+
+        // Recording the same tree again is harmless
+        storage.create_snapshot("abc1234").unwrap();
+
+        fs::write(storage.vault_root.join(spec), "# Other").unwrap();
+        assert!(storage.create_snapshot("abc1234").is_err());
+        assert_eq!(storage.read_file_from_snapshot("abc1234", spec).unwrap(), b"# Spec");
+    }
+
+    #[test]
+    fn test_parallel_manifest_matches_sequential_hashing() {
+        let (_temp_dir, storage) = snapshot_vault();
+        for i in 0..50 {
+            let dir = storage.domains_dir().join(format!("d{}", i % 4)).join(format!("sub{}", i % 3));
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join(format!("n{}.md", i)), format!("note {}", i)).unwrap();
+        }
+        storage.create_snapshot("def5678").unwrap();
+
+        let manifest = storage.read_manifest("def5678").unwrap().unwrap();
+        let sequential: Vec<(PathBuf, String)> = storage.domain_files().unwrap().iter()
+            .map(|path| (relative_path(&storage.vault_root, path), compute_hash(&fs::read(path).unwrap())))
+            .collect();
+        let recorded: Vec<(PathBuf, String)> = manifest.files.iter().map(|f| (f.path.clone(), f.hash.clone())).collect();
+        assert_eq!(recorded, sequential);
+        assert_eq!(manifest.total_size, manifest.files.iter().map(|f| f.size).sum::<u64>());
+        assert_eq!(manifest.tree, Some(tree_hash(&manifest.files)));
+    }
+
+    #[test]
+    fn test_hidden_entries_are_not_tracked() {
+        let (_temp_dir, storage) = snapshot_vault();
+        let obsidian = storage.domains_dir().join("auth/.obsidian");
+        fs::create_dir_all(&obsidian).unwrap();
+        fs::write(obsidian.join("workspace.json"), "{}").unwrap();
+        fs::write(storage.domains_dir().join("auth/.DS_Store"), "").unwrap();
+
+        storage.create_snapshot("def5678").unwrap();
+        let manifest = storage.read_manifest("def5678").unwrap().unwrap();
+        let paths: Vec<_> = manifest.files.iter().map(|f| f.path.clone()).collect();
+        assert_eq!(
+            paths,
+            vec![PathBuf::from("domains/auth/notes.md"), PathBuf::from("domains/auth/spec.md")]
+        );
+
+        let mut config = storage.load_config().unwrap();
+        config.core.include_hidden = true;
+        storage.save_config(&config).unwrap();
+        assert_eq!(storage.domain_files().unwrap().len(), 4);
+    }
+
+    #[test]
+    fn test_read_packed_snapshot() {
+        let (_temp_dir, storage) = snapshot_vault();
+        fs::write(storage.domains_dir().join("auth/spec.md"), "# Spec v2").unwrap();
+        storage.create_snapshot("def5678").unwrap();
+
+        storage.write_pack(&["abc1234".to_string(), "def5678".to_string()]).unwrap();
+        assert!(!storage.objects_dir().join("abc1234").exists());
+
+        let spec = Path::new("domains/auth/spec.md");
+        assert_eq!(storage.read_file_from_snapshot("abc1234", spec).unwrap(), b"# Spec");
+        assert_eq!(storage.read_file_from_snapshot("def5678", spec).unwrap(), b"# Spec v2");
+        assert_eq!(storage.read_manifest("def5678").unwrap().unwrap().total_files, 2);
+    }
+}
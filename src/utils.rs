@@ -2,9 +2,41 @@ use sha2::{Digest, Sha256};
 use std::path::{Path, PathBuf};
 use crate::errors::*;
 use std::fs;
+use std::sync::OnceLock;
 
-/// Find the vault root by looking for .gitnu directory
+/// Environment variable naming the vault root to use instead of searching
+/// from the current directory
+pub const VAULT_DIR_ENV: &str = "GITNU_DIR";
+
+/// Vault root given by `gnu --vault`, which takes precedence over `GITNU_DIR`
+static VAULT_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// Use `root` as the vault for the rest of the process (`gnu --vault`).
+/// Only the first call has any effect.
+pub fn set_vault_override(root: PathBuf) {
+    let _ = VAULT_DIR.set(root);
+}
+
+/// The vault root given by `--vault` or `GITNU_DIR`, if set
+pub fn vault_override() -> Option<PathBuf> {
+    if let Some(root) = VAULT_DIR.get() {
+        return Some(root.clone());
+    }
+    std::env::var_os(VAULT_DIR_ENV)
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+}
+
+/// Find the vault root: `--vault` or `GITNU_DIR` when set, otherwise the nearest
+/// directory at or above the current one with a .gitnu directory
 pub fn find_vault_root() -> Result<PathBuf> {
+    if let Some(root) = vault_override() {
+        if !root.join(".gitnu").is_dir() {
+            return Err(GitnuError::VaultNotFoundAt(root));
+        }
+        return Ok(fs::canonicalize(&root)?);
+    }
+
     let mut current = std::env::current_dir()?;
     loop {
         let gitnu_dir = current.join(".gitnu");
@@ -24,6 +56,73 @@ pub fn vault_exists(path: &Path) -> bool {
     path.join(".gitnu").exists()
 }
 
+/// Resolve a path argument to a path inside the vault. Paths are taken
+/// relative to the current directory when that names something in the vault,
+/// so `gnu load spec.md` works from inside `domains/myproject/`; otherwise
+/// they are vault-relative.
+pub fn resolve_path_arg(vault_root: &Path, arg: &str) -> PathBuf {
+    std::env::current_dir()
+        .ok()
+        .and_then(|cwd| in_vault(vault_root, &cwd.join(arg)))
+        .unwrap_or_else(|| vault_root.join(arg))
+}
+
+/// `resolve_path_arg` for a path that may not exist yet, such as the target of
+/// a move: it is taken relative to the current directory when its parent is.
+pub fn resolve_new_path_arg(vault_root: &Path, arg: &str) -> PathBuf {
+    let cwd = match std::env::current_dir() {
+        Ok(cwd) => cwd,
+        Err(_) => return vault_root.join(arg),
+    };
+    let candidate = cwd.join(arg);
+    if let Some(path) = in_vault(vault_root, &candidate) {
+        return path;
+    }
+    match (candidate.parent(), candidate.file_name()) {
+        (Some(parent), Some(name)) => in_vault(vault_root, parent)
+            .map(|parent| parent.join(name))
+            .unwrap_or_else(|| vault_root.join(arg)),
+        _ => vault_root.join(arg),
+    }
+}
+
+/// `path` re-rooted at `vault_root`, or an error if it resolves (through
+/// `..`, an absolute path or a symlink) to somewhere outside the vault. The
+/// path need not exist yet: `..` is resolved lexically and its deepest
+/// existing ancestor is canonicalized.
+pub fn ensure_in_vault(vault_root: &Path, path: &Path) -> Result<PathBuf> {
+    let outside = || GitnuError::Other(format!("'{}' is outside the vault", path.display()));
+
+    let mut normalized = PathBuf::new();
+    for component in vault_root.join(path).components() {
+        match component {
+            std::path::Component::CurDir => {}
+            std::path::Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+
+    let mut existing = normalized.as_path();
+    while !existing.exists() {
+        existing = existing.parent().ok_or_else(outside)?;
+    }
+    let rest = normalized.strip_prefix(existing).map_err(|_| outside())?;
+    let resolved = fs::canonicalize(existing)?.join(rest);
+    let root = fs::canonicalize(vault_root)?;
+    resolved.strip_prefix(&root)
+        .map(|rel| vault_root.join(rel))
+        .map_err(|_| outside())
+}
+
+/// `path` re-rooted at `vault_root` if it exists and lies inside the vault
+fn in_vault(vault_root: &Path, path: &Path) -> Option<PathBuf> {
+    let path = fs::canonicalize(path).ok()?;
+    let root = fs::canonicalize(vault_root).ok()?;
+    path.strip_prefix(&root).ok().map(|rel| vault_root.join(rel))
+}
+
 /// Compute SHA256 hash of content
 pub fn compute_hash(content: &[u8]) -> String {
     let mut hasher = Sha256::new();
@@ -37,6 +136,42 @@ pub fn hash_file(path: &Path) -> Result<String> {
     Ok(compute_hash(&content))
 }
 
+/// A file's modification time in nanoseconds since the epoch, where the
+/// platform reports one
+pub fn mtime_nanos(metadata: &fs::Metadata) -> Option<i64> {
+    let modified = metadata.modified().ok()?;
+    let since_epoch = modified.duration_since(std::time::UNIX_EPOCH).ok()?;
+    i64::try_from(since_epoch.as_nanos()).ok()
+}
+
+/// Whether file content should be treated as binary: a null byte near the
+/// start, or content that isn't valid UTF-8
+pub fn is_binary(content: &[u8]) -> bool {
+    content.iter().take(8000).any(|&b| b == 0) || std::str::from_utf8(content).is_err()
+}
+
+/// Lines inserted and deleted going from `old` to `new`
+pub fn line_changes(old: &str, new: &str) -> (usize, usize) {
+    use similar::{ChangeTag, TextDiff};
+    let (mut inserted, mut deleted) = (0, 0);
+    for change in TextDiff::from_lines(old, new).iter_all_changes() {
+        match change.tag() {
+            ChangeTag::Insert => inserted += 1,
+            ChangeTag::Delete => deleted += 1,
+            ChangeTag::Equal => {}
+        }
+    }
+    (inserted, deleted)
+}
+
+/// Abbreviate a commit hash to at most 7 characters for display
+pub fn short_hash(hash: &str) -> &str {
+    match hash.char_indices().nth(7) {
+        Some((idx, _)) => &hash[..idx],
+        None => hash,
+    }
+}
+
 /// Format file size in human-readable form
 pub fn format_size(size: u64) -> String {
     const UNITS: &[&str] = &["B", "KB", "MB", "GB"];
@@ -64,6 +199,85 @@ pub fn parse_commit_ref(reference: &str) -> Result<String> {
     Ok(reference.to_string())
 }
 
+/// Parse a `YYYY-MM-DD` date into a UTC timestamp at the start of that day
+pub fn parse_date(date: &str) -> Result<chrono::DateTime<chrono::Utc>> {
+    let day = chrono::NaiveDate::parse_from_str(date.trim(), "%Y-%m-%d").map_err(|_| {
+        GitnuError::Other(format!("Invalid date '{}'. Expected format: YYYY-MM-DD", date))
+    })?;
+    Ok(day.and_hms_opt(0, 0, 0).unwrap().and_utc())
+}
+
+/// Parse `YYYY-MM-DD` or `YYYY-MM-DDTHH:MM` (UTC) into the moment just after
+/// it ends: the next midnight for a day, the next minute for a time
+pub fn parse_date_until(date: &str) -> Result<chrono::DateTime<chrono::Utc>> {
+    let date = date.trim();
+    for format in ["%Y-%m-%dT%H:%M", "%Y-%m-%d %H:%M"] {
+        if let Ok(time) = chrono::NaiveDateTime::parse_from_str(date, format) {
+            return Ok(time.and_utc() + chrono::Duration::minutes(1));
+        }
+    }
+    parse_date(date)
+        .map(|day| day + chrono::Duration::days(1))
+        .map_err(|_| GitnuError::Other(format!(
+            "Invalid date '{}'. Expected format: YYYY-MM-DD or YYYY-MM-DDTHH:MM",
+            date
+        )))
+}
+
+/// Parse an RFC 3339 timestamp, `YYYY-MM-DDTHH:MM` (UTC) or a bare
+/// `YYYY-MM-DD` (midnight UTC)
+pub fn parse_timestamp(date: &str) -> Result<chrono::DateTime<chrono::Utc>> {
+    let date = date.trim();
+    if let Ok(time) = chrono::DateTime::parse_from_rfc3339(date) {
+        return Ok(time.with_timezone(&chrono::Utc));
+    }
+    for format in ["%Y-%m-%dT%H:%M", "%Y-%m-%d %H:%M"] {
+        if let Ok(time) = chrono::NaiveDateTime::parse_from_str(date, format) {
+            return Ok(time.and_utc());
+        }
+    }
+    parse_date(date).map_err(|_| GitnuError::Other(format!(
+        "Invalid date '{}'. Expected RFC 3339 (2024-03-01T09:30:00Z) or YYYY-MM-DD",
+        date
+    )))
+}
+
+/// Parse a duration such as `30s`, `5m` or `1h`; a bare number means seconds
+pub fn parse_duration(duration: &str) -> Result<std::time::Duration> {
+    let duration = duration.trim();
+    let invalid = || GitnuError::Other(format!(
+        "Invalid duration '{}'. Expected e.g. 30s, 5m or 1h",
+        duration
+    ));
+
+    let split = duration.find(|c: char| !c.is_ascii_digit()).unwrap_or(duration.len());
+    let (amount, unit) = duration.split_at(split);
+    let amount: u64 = amount.parse().map_err(|_| invalid())?;
+    let seconds = match unit {
+        "" | "s" => Some(amount),
+        "m" => amount.checked_mul(60),
+        "h" => amount.checked_mul(3600),
+        _ => return Err(invalid()),
+    };
+    Ok(std::time::Duration::from_secs(seconds.ok_or_else(invalid)?))
+}
+
+/// Whether a pin/exclude argument is a glob pattern rather than a path
+pub fn is_glob_pattern(s: &str) -> bool {
+    s.contains('*')
+}
+
+/// Compile glob patterns, reporting the first invalid one
+pub fn compile_patterns(patterns: &[String]) -> Result<Vec<glob::Pattern>> {
+    patterns
+        .iter()
+        .map(|p| {
+            glob::Pattern::new(p)
+                .map_err(|e| GitnuError::Other(format!("Invalid glob pattern '{}': {}", p, e)))
+        })
+        .collect()
+}
+
 /// Get relative path from base
 pub fn relative_path(base: &Path, target: &Path) -> PathBuf {
     target.strip_prefix(base)
@@ -73,24 +287,264 @@ pub fn relative_path(base: &Path, target: &Path) -> PathBuf {
 
 /// Format timestamp as relative time
 pub fn relative_time(timestamp: &chrono::DateTime<chrono::Utc>) -> String {
-    let now = chrono::Utc::now();
+    relative_time_from(timestamp, &chrono::Utc::now())
+}
+
+/// Format `timestamp` relative to `now`
+fn relative_time_from(
+    timestamp: &chrono::DateTime<chrono::Utc>,
+    now: &chrono::DateTime<chrono::Utc>,
+) -> String {
+    use chrono::Datelike;
+
     let duration = now.signed_duration_since(*timestamp);
-    
+    let plural = |n: i64, unit: &str| {
+        if n == 1 {
+            format!("1 {} ago", unit)
+        } else {
+            format!("{} {}s ago", n, unit)
+        }
+    };
+
+    // Allow for small clock skew before calling a timestamp "in the future"
+    if duration.num_seconds() < -60 {
+        return "in the future".to_string();
+    }
+    if duration.num_seconds() < 10 {
+        return "just now".to_string();
+    }
     if duration.num_seconds() < 60 {
-        format!("{} seconds ago", duration.num_seconds())
-    } else if duration.num_minutes() < 60 {
-        format!("{} minutes ago", duration.num_minutes())
-    } else if duration.num_hours() < 24 {
-        format!("{} hours ago", duration.num_hours())
-    } else if duration.num_days() < 7 {
-        format!("{} days ago", duration.num_days())
-    } else if duration.num_weeks() < 4 {
-        format!("{} weeks ago", duration.num_weeks())
+        return plural(duration.num_seconds(), "second");
+    }
+    if duration.num_minutes() < 60 {
+        return plural(duration.num_minutes(), "minute");
+    }
+    if duration.num_hours() < 24 {
+        return plural(duration.num_hours(), "hour");
+    }
+    if duration.num_days() < 7 {
+        return plural(duration.num_days(), "day");
+    }
+
+    // Calendar months, not counting a month that hasn't fully elapsed
+    let mut months = (now.year() - timestamp.year()) as i64 * 12
+        + now.month() as i64
+        - timestamp.month() as i64;
+    if (now.day(), now.time()) < (timestamp.day(), timestamp.time()) {
+        months -= 1;
+    }
+
+    if months < 1 {
+        plural(duration.num_weeks(), "week")
+    } else if months < 12 {
+        plural(months, "month")
     } else {
-        format!("{} months ago", duration.num_days() / 30)
+        plural(months / 12, "year")
     }
 }
 
+/// Write a file atomically: write a temp file alongside it, then rename into place
+pub fn atomic_write(path: &Path, contents: &[u8]) -> Result<()> {
+    use std::io::Write;
+
+    atomic_write_with(path, |file| Ok(file.write_all(contents)?))
+}
+
+/// Like `atomic_write`, but `write` streams the contents into the temp file,
+/// so large files never have to be held in memory
+pub fn atomic_write_with<F>(path: &Path, write: F) -> Result<()>
+where
+    F: FnOnce(&mut fs::File) -> Result<()>,
+{
+    let dir = path.parent().unwrap_or_else(|| Path::new("."));
+    let file_name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let tmp_path = dir.join(format!(".{}.tmp.{}", file_name, std::process::id()));
+
+    let result = (|| -> Result<()> {
+        let mut file = fs::File::create(&tmp_path)?;
+        write(&mut file)?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, path)?;
+        Ok(())
+    })();
+
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+    result
+}
+
+/// Ask a yes/no question on stdin; anything but "y"/"yes" means no
+pub fn confirm(prompt: &str) -> Result<bool> {
+    use std::io::Write;
+
+    print!("{} [y/N] ", prompt);
+    std::io::stdout().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+/// Open `$VISUAL`/`$EDITOR` (falling back to `vi`) on a file pre-filled with
+/// `initial` and return what the user saved
+pub fn edit_in_editor(path: &Path, initial: &str) -> Result<String> {
+    let hint = "\n# Write the commit message above. Lines starting with '#' are ignored\n# and an empty message aborts the commit.\n";
+    fs::write(path, format!("{}{}", initial, hint))?;
+
+    open_in_editor(path, None)?;
+
+    let content = fs::read_to_string(path)?;
+    let _ = fs::remove_file(path);
+    Ok(content)
+}
+
+/// Open a file in `program`, or `$VISUAL`/`$EDITOR` (falling back to `vi`),
+/// and wait for it to exit
+pub fn open_in_editor(path: &Path, program: Option<&str>) -> Result<()> {
+    let editor = match program {
+        Some(p) => p.to_string(),
+        None => std::env::var("VISUAL")
+            .or_else(|_| std::env::var("EDITOR"))
+            .unwrap_or_else(|_| "vi".to_string()),
+    };
+
+    // The editor may carry arguments, e.g. "code --wait"
+    let mut parts = editor.split_whitespace();
+    let program = parts.next().unwrap_or("vi");
+    let status = std::process::Command::new(program)
+        .args(parts)
+        .arg(path)
+        .status()
+        .map_err(|e| GitnuError::Other(format!("Failed to launch editor '{}': {}", editor, e)))?;
+    if !status.success() {
+        return Err(GitnuError::Other(format!("Editor '{}' exited with {}", editor, status)));
+    }
+    Ok(())
+}
+
+/// Whether text still contains `<<<<<<<` ... `>>>>>>>` conflict markers
+pub fn has_conflict_markers(content: &str) -> bool {
+    let mut open = false;
+    for line in content.lines() {
+        if line.starts_with("<<<<<<<") {
+            open = true;
+        } else if open && line.starts_with(">>>>>>>") {
+            return true;
+        }
+    }
+    false
+}
+
+/// Three-way merge of text by lines: the changes `ours` and `theirs` each
+/// made to `base` are combined, and where both changed the same (or
+/// adjacent) lines differently, both versions are kept between conflict
+/// markers labelled with `labels`. Returns the merged text and whether it
+/// has conflicts.
+pub fn merge_lines(base: &str, ours: &str, theirs: &str, labels: (&str, &str)) -> (String, bool) {
+    // A changed stretch of base lines and what one side replaced it with
+    struct Hunk<'a> {
+        start: usize,
+        end: usize,
+        lines: Vec<&'a str>,
+        ours: bool,
+    }
+
+    fn hunks<'a>(base: &[&str], other: &[&'a str], ours: bool) -> Vec<Hunk<'a>> {
+        let mut hunks: Vec<Hunk<'a>> = Vec::new();
+        for op in similar::capture_diff_slices(similar::Algorithm::Myers, base, other) {
+            if op.tag() == similar::DiffTag::Equal {
+                continue;
+            }
+            let (old, new) = (op.old_range(), op.new_range());
+            // Deletes and inserts at the same spot form one hunk
+            match hunks.last_mut() {
+                Some(last) if last.end == old.start => {
+                    last.end = old.end;
+                    last.lines.extend(&other[new]);
+                }
+                _ => hunks.push(Hunk { start: old.start, end: old.end, lines: other[new].to_vec(), ours }),
+            }
+        }
+        hunks
+    }
+
+    fn apply<'a>(base: &[&'a str], hunks: &[&Hunk<'a>], start: usize, end: usize) -> String {
+        let mut text = String::new();
+        let mut pos = start;
+        for hunk in hunks {
+            text.extend(base[pos..hunk.start].iter().copied());
+            text.extend(hunk.lines.iter().copied());
+            pos = hunk.end;
+        }
+        text.extend(base[pos..end].iter().copied());
+        text
+    }
+
+    let base_lines: Vec<&str> = base.split_inclusive('\n').collect();
+    let ours_lines: Vec<&str> = ours.split_inclusive('\n').collect();
+    let theirs_lines: Vec<&str> = theirs.split_inclusive('\n').collect();
+
+    let mut all = hunks(&base_lines, &ours_lines, true);
+    all.extend(hunks(&base_lines, &theirs_lines, false));
+    all.sort_by_key(|h| (h.start, h.end));
+
+    let mut merged = String::new();
+    let mut conflicted = false;
+    let mut pos = 0;
+    let mut i = 0;
+    while i < all.len() {
+        // Group hunks that overlap or touch; only groups with both sides can conflict
+        let start = all[i].start;
+        let mut end = all[i].end;
+        let mut j = i + 1;
+        while j < all.len() && all[j].start <= end {
+            end = end.max(all[j].end);
+            j += 1;
+        }
+        let group = &all[i..j];
+        let ours_hunks: Vec<&Hunk> = group.iter().filter(|h| h.ours).collect();
+        let theirs_hunks: Vec<&Hunk> = group.iter().filter(|h| !h.ours).collect();
+
+        merged.extend(base_lines[pos..start].iter().copied());
+        let ours_text = apply(&base_lines, &ours_hunks, start, end);
+        let theirs_text = apply(&base_lines, &theirs_hunks, start, end);
+        if theirs_hunks.is_empty() || ours_text == theirs_text {
+            merged.push_str(&ours_text);
+        } else if ours_hunks.is_empty() {
+            merged.push_str(&theirs_text);
+        } else {
+            conflicted = true;
+            let terminated = |text: String| {
+                if text.is_empty() || text.ends_with('\n') { text } else { text + "\n" }
+            };
+            merged.push_str(&format!(
+                "<<<<<<< {}\n{}=======\n{}>>>>>>> {}\n",
+                labels.0,
+                terminated(ours_text),
+                terminated(theirs_text),
+                labels.1
+            ));
+        }
+        pos = end;
+        i = j;
+    }
+    merged.extend(base_lines[pos..].iter().copied());
+    (merged, conflicted)
+}
+
+/// "1 file", "2 files"
+pub fn plural(count: usize, word: &str) -> String {
+    format!("{} {}{}", count, word, if count == 1 { "" } else { "s" })
+}
+
+/// Whether a file or directory name marks it hidden (starts with `.`)
+pub fn is_hidden(name: &std::ffi::OsStr) -> bool {
+    name.to_string_lossy().starts_with('.')
+}
+
 /// Ensure directory exists
 pub fn ensure_dir(path: &Path) -> Result<()> {
     if !path.exists() {
@@ -98,3 +552,101 @@ pub fn ensure_dir(path: &Path) -> Result<()> {
     }
     Ok(())
 }
+
+/// Total size of every file under a directory
+pub fn dir_size(path: &Path) -> u64 {
+    walkdir::WalkDir::new(path)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .filter_map(|e| e.metadata().ok())
+        .map(|m| m.len())
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_atomic_write_leaves_no_temp_file() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("HEAD");
+
+        atomic_write(&path, b"ref: refs/heads/main").unwrap();
+        atomic_write(&path, b"ref: refs/heads/dev").unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "ref: refs/heads/dev");
+        let entries: Vec<_> = fs::read_dir(temp_dir.path()).unwrap().collect();
+        assert_eq!(entries.len(), 1);
+    }
+
+    #[test]
+    fn test_relative_time() {
+        use chrono::{Duration, TimeZone, Utc};
+
+        let now = Utc.with_ymd_and_hms(2024, 6, 15, 12, 0, 0).unwrap();
+        let ago = |d: Duration| relative_time_from(&(now - d), &now);
+
+        assert_eq!(ago(Duration::zero()), "just now");
+        assert_eq!(ago(Duration::minutes(1)), "1 minute ago");
+        assert_eq!(ago(Duration::minutes(90)), "1 hour ago");
+        assert_eq!(ago(Duration::days(2)), "2 days ago");
+        assert_eq!(ago(Duration::weeks(3)), "3 weeks ago");
+        assert_eq!(ago(Duration::days(65)), "2 months ago");
+        assert_eq!(ago(Duration::days(800)), "2 years ago");
+        assert_eq!(ago(Duration::hours(-2)), "in the future");
+    }
+
+    #[test]
+    fn test_short_hash() {
+        assert_eq!(short_hash("abcdef0123456789"), "abcdef0");
+        assert_eq!(short_hash("abcd"), "abcd");
+        assert_eq!(short_hash(""), "");
+    }
+
+    #[test]
+    fn test_merge_lines() {
+        let base = "a\nb\nc\nd\ne\n";
+        let (merged, conflicted) = merge_lines(base, "A\nb\nc\nd\ne\n", "a\nb\nc\nd\nE\n", ("ours", "theirs"));
+        assert_eq!(merged, "A\nb\nc\nd\nE\n");
+        assert!(!conflicted);
+
+        // Both sides made the same change
+        let (merged, conflicted) = merge_lines(base, "a\nx\nc\nd\ne\n", "a\nx\nc\nd\ne\n", ("ours", "theirs"));
+        assert_eq!(merged, "a\nx\nc\nd\ne\n");
+        assert!(!conflicted);
+
+        let (merged, conflicted) = merge_lines(base, "a\nours\nc\nd\ne\n", "a\nc\nd\ne\n", ("main", "revert 1234567"));
+        assert_eq!(merged, "a\n<<<<<<< main\nours\n=======\n>>>>>>> revert 1234567\nc\nd\ne\n");
+        assert!(conflicted);
+        assert!(has_conflict_markers(&merged));
+    }
+
+    #[test]
+    fn test_is_binary() {
+        assert!(!is_binary(b"# Notes\n"));
+        assert!(!is_binary("caf\u{e9}".as_bytes()));
+        assert!(is_binary(b"PNG\0\x01"));
+        assert!(is_binary(&[0xff, 0xfe, b'a']));
+    }
+
+    #[test]
+    fn test_parse_timestamp() {
+        assert_eq!(parse_timestamp("2024-03-01T09:30:00+02:00").unwrap().to_rfc3339(), "2024-03-01T07:30:00+00:00");
+        assert_eq!(parse_timestamp("2024-03-01T09:30").unwrap().to_rfc3339(), "2024-03-01T09:30:00+00:00");
+        assert_eq!(parse_timestamp("2024-03-01").unwrap().to_rfc3339(), "2024-03-01T00:00:00+00:00");
+        assert!(parse_timestamp("March 1st").is_err());
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("30s").unwrap().as_secs(), 30);
+        assert_eq!(parse_duration("45").unwrap().as_secs(), 45);
+        assert_eq!(parse_duration("5m").unwrap().as_secs(), 300);
+        assert_eq!(parse_duration("1h").unwrap().as_secs(), 3600);
+        assert!(parse_duration("soon").is_err());
+        assert!(parse_duration("10d").is_err());
+        assert!(parse_duration("18446744073709551615h").is_err());
+    }
+}
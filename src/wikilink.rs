@@ -1,9 +1,24 @@
 use crate::errors::*;
+use crate::models::WikilinkTarget;
+use crate::storage::Storage;
+use crate::utils::relative_path;
+use regex::{Captures, Regex};
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
 use walkdir::WalkDir;
 
+/// Furthest edit distance a fuzzy match may be from the wikilink
+const MAX_FUZZY_DISTANCE: usize = 2;
+
 /// Resolve wikilink to full path
 pub fn resolve_wikilink(vault_root: &Path, link: &str) -> Result<PathBuf> {
+    resolve_wikilink_with(vault_root, link, false)
+}
+
+/// Resolve a wikilink: exact stem matches first, then matches ignoring case.
+/// Failing both, a single stem within `MAX_FUZZY_DISTANCE` edits is returned
+/// when `fuzzy` is set and suggested in the error otherwise.
+pub fn resolve_wikilink_with(vault_root: &Path, link: &str, fuzzy: bool) -> Result<PathBuf> {
     // Remove [[ and ]] brackets
     let name = link.trim_start_matches("[[").trim_end_matches("]]");
     
@@ -24,7 +39,7 @@ pub fn resolve_wikilink(vault_root: &Path, link: &str) -> Result<PathBuf> {
     }
     
     // Search all domains for matching filename
-    let mut matches = Vec::new();
+    let mut files = Vec::new();
     if domains_dir.exists() {
         for entry in WalkDir::new(&domains_dir) {
             let entry = match entry {
@@ -34,21 +49,129 @@ pub fn resolve_wikilink(vault_root: &Path, link: &str) -> Result<PathBuf> {
             
             if entry.file_type().is_file() {
                 if let Some(stem) = entry.path().file_stem() {
-                    if stem == name {
-                        matches.push(entry.path().to_path_buf());
-                    }
+                    files.push((stem.to_string_lossy().to_string(), entry.path().to_path_buf()));
                 }
             }
         }
     }
-    
+
+    // Exact matches are authoritative; case is only ignored when there are none
+    let mut matches: Vec<PathBuf> = files.iter()
+        .filter(|(stem, _)| stem == name)
+        .map(|(_, path)| path.clone())
+        .collect();
+    if matches.is_empty() {
+        let lower = name.to_lowercase();
+        matches = files.iter()
+            .filter(|(stem, _)| stem.to_lowercase() == lower)
+            .map(|(_, path)| path.clone())
+            .collect();
+    }
+
     match matches.len() {
-        0 => Err(GitnuError::WikilinkNotFound(name.to_string())),
+        0 => match closest_stem(&files, name) {
+            Some((_, path)) if fuzzy => Ok(path.clone()),
+            Some((stem, _)) => Err(GitnuError::WikilinkDidYouMean(name.to_string(), stem.clone())),
+            None => Err(GitnuError::WikilinkNotFound(name.to_string())),
+        },
         1 => Ok(matches[0].clone()),
         _ => Err(GitnuError::WikilinkAmbiguous(name.to_string(), matches)),
     }
 }
 
+/// The one file whose stem is nearest `name` within `MAX_FUZZY_DISTANCE`
+/// edits, ignoring case. Ties yield nothing rather than a guess.
+fn closest_stem<'a>(files: &'a [(String, PathBuf)], name: &str) -> Option<&'a (String, PathBuf)> {
+    let lower = name.to_lowercase();
+    let mut best: Vec<(usize, &(String, PathBuf))> = files.iter()
+        .map(|file| (edit_distance(&file.0.to_lowercase(), &lower), file))
+        .filter(|(distance, _)| *distance <= MAX_FUZZY_DISTANCE)
+        .collect();
+    best.sort_by_key(|(distance, _)| *distance);
+    match best.as_slice() {
+        [(_, only)] => Some(*only),
+        [(first, file), (second, _), ..] if first < second => Some(*file),
+        _ => None,
+    }
+}
+
+/// Levenshtein distance between two strings, by character
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitute = previous[j] + usize::from(ca != *cb);
+            current.push(substitute.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Every name a wikilink can resolve to: each markdown file's stem plus its
+/// `domain/stem` path form, sorted by name. Stems shared by several files are
+/// reported with their candidates instead of a path.
+pub fn wikilink_targets(storage: &Storage) -> Result<Vec<WikilinkTarget>> {
+    let vault_root = &storage.vault_root;
+    let domains_dir = storage.domains_dir();
+    let mut by_stem: BTreeMap<String, Vec<PathBuf>> = BTreeMap::new();
+    let mut targets = Vec::new();
+
+    for path in storage.domain_files()? {
+        if path.extension().is_none_or(|e| e != "md") {
+            continue;
+        }
+        let rel_path = relative_path(vault_root, &path);
+        if let Some(stem) = path.file_stem() {
+            by_stem.entry(stem.to_string_lossy().to_string()).or_default().push(rel_path.clone());
+        }
+        if let Ok(in_domains) = path.with_extension("").strip_prefix(&domains_dir) {
+            targets.push(WikilinkTarget {
+                name: in_domains.to_string_lossy().replace('\\', "/"),
+                path: Some(rel_path),
+                candidates: Vec::new(),
+            });
+        }
+    }
+
+    for (name, mut paths) in by_stem {
+        paths.sort();
+        targets.push(if paths.len() == 1 {
+            WikilinkTarget { name, path: paths.pop(), candidates: Vec::new() }
+        } else {
+            WikilinkTarget { name, path: None, candidates: paths }
+        });
+    }
+
+    targets.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(targets)
+}
+
+/// Rewrite the target of every `[[target]]`, `[[target|alias]]` or `[[target#heading]]`
+/// link for which `rewrite` returns a replacement. Returns the new content and
+/// the number of links rewritten.
+pub fn rewrite_wikilinks<F>(content: &str, mut rewrite: F) -> (String, usize)
+where
+    F: FnMut(&str) -> Option<String>,
+{
+    let link_re = Regex::new(r"\[\[([^\]|#]+)([^\]]*)\]\]").unwrap();
+    let mut count = 0;
+
+    let rewritten = link_re.replace_all(content, |caps: &Captures| {
+        match rewrite(&caps[1]) {
+            Some(target) => {
+                count += 1;
+                format!("[[{}{}]]", target, &caps[2])
+            }
+            None => caps[0].to_string(),
+        }
+    });
+
+    (rewritten.into_owned(), count)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -69,6 +192,34 @@ mod tests {
         assert_eq!(result, test_file);
     }
 
+    #[test]
+    fn test_resolve_ignores_case_then_suggests_close_matches() {
+        let temp_dir = TempDir::new().unwrap();
+        let vault_root = temp_dir.path();
+        let domains_dir = vault_root.join("domains/test");
+        fs::create_dir_all(&domains_dir).unwrap();
+        let spec = domains_dir.join("spec.md");
+        fs::write(&spec, "# Spec").unwrap();
+        fs::write(domains_dir.join("Notes.md"), "# Notes").unwrap();
+        fs::write(domains_dir.join("notes.md"), "# notes").unwrap();
+
+        assert_eq!(resolve_wikilink(vault_root, "[[Spec]]").unwrap(), spec);
+        // An exact match wins over ones differing only in case
+        assert_eq!(resolve_wikilink(vault_root, "[[Notes]]").unwrap(), domains_dir.join("Notes.md"));
+
+        match resolve_wikilink(vault_root, "[[spce]]") {
+            Err(GitnuError::WikilinkDidYouMean(name, suggestion)) => {
+                assert_eq!((name.as_str(), suggestion.as_str()), ("spce", "spec"));
+            }
+            other => panic!("expected a suggestion, got {:?}", other),
+        }
+        assert_eq!(resolve_wikilink_with(vault_root, "[[spce]]", true).unwrap(), spec);
+        assert!(matches!(
+            resolve_wikilink_with(vault_root, "[[architecture]]", true),
+            Err(GitnuError::WikilinkNotFound(_))
+        ));
+    }
+
     #[test]
     fn test_resolve_path_wikilink() {
         let temp_dir = TempDir::new().unwrap();
@@ -82,4 +233,42 @@ mod tests {
         let result = resolve_wikilink(vault_root, "[[auth/patterns]]").unwrap();
         assert_eq!(result, test_file);
     }
+
+    #[test]
+    fn test_wikilink_targets_marks_ambiguous_stems() {
+        let temp_dir = TempDir::new().unwrap();
+        let vault_root = temp_dir.path();
+        for file in ["auth/spec.md", "billing/spec.md", "auth/patterns.md"] {
+            let path = vault_root.join("domains").join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "# Notes").unwrap();
+        }
+
+        let storage = Storage::new(vault_root.to_path_buf());
+        fs::create_dir_all(storage.gitnu_dir()).unwrap();
+        let config = toml::to_string(&crate::models::Config::default()).unwrap();
+        fs::write(storage.gitnu_dir().join("config.toml"), config).unwrap();
+        fs::write(vault_root.join("domains/auth/.draft.md"), "# Hidden").unwrap();
+
+        let targets = wikilink_targets(&storage).unwrap();
+        let names: Vec<&str> = targets.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, ["auth/patterns", "auth/spec", "billing/spec", "patterns", "spec"]);
+        assert_eq!(targets[3].path, Some(PathBuf::from("domains/auth/patterns.md")));
+        assert_eq!(targets[4].path, None);
+        assert_eq!(targets[4].candidates.len(), 2);
+    }
+
+    #[test]
+    fn test_rewrite_wikilinks_keeps_alias_and_heading() {
+        let content = "See [[spec]], [[spec|the spec]] and [[spec#Goals]], not [[other]].";
+        let (rewritten, count) = rewrite_wikilinks(content, |target| {
+            (target == "spec").then(|| "design".to_string())
+        });
+
+        assert_eq!(count, 3);
+        assert_eq!(
+            rewritten,
+            "See [[design]], [[design|the spec]] and [[design#Goals]], not [[other]]."
+        );
+    }
 }
@@ -0,0 +1,49 @@
+// Driving gitnu as a library against a temporary vault
+
+use gitnu::commands::{log_report, make_commit, status_report, CommitOptions, LogOptions};
+use gitnu::storage::Storage;
+use std::fs;
+use std::process::Command;
+use tempfile::TempDir;
+
+fn init_vault() -> (TempDir, Storage) {
+    let temp_dir = TempDir::new().unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_gnu"))
+        .args(["init", "--name", "proj"])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("failed to run gnu");
+    assert!(output.status.success(), "init failed: {:?}", output);
+    let storage = Storage::new(temp_dir.path().to_path_buf());
+    (temp_dir, storage)
+}
+
+#[test]
+fn test_commit_status_and_log_reports() {
+    let (vault, storage) = init_vault();
+    fs::write(vault.path().join("domains/proj/notes.md"), "# Notes\n").unwrap();
+
+    let status = status_report(&storage).unwrap();
+    assert_eq!(status.branch, "main");
+    assert!(status.modified.iter().any(|f| f.path.ends_with("notes.md")));
+
+    let outcome = make_commit(&storage, &CommitOptions {
+        message: "Add notes".to_string(),
+        author: Some("human".to_string()),
+        ..Default::default()
+    }).unwrap();
+    let commit = outcome.commit.expect("a commit should be created");
+    assert_eq!(outcome.branch, "main");
+
+    let again = make_commit(&storage, &CommitOptions {
+        message: "Nothing new".to_string(),
+        ..Default::default()
+    }).unwrap();
+    assert!(again.commit.is_none());
+
+    let log = log_report(&storage, &LogOptions::default()).unwrap();
+    assert_eq!(log.total_commits, 2);
+    assert_eq!(log.commits[0].hash, commit.hash);
+    assert_eq!(log.head.as_deref(), Some(commit.hash.as_str()));
+    assert!(status_report(&storage).unwrap().modified.is_empty());
+}
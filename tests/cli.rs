@@ -0,0 +1,2470 @@
+// End-to-end tests driving the `gnu` binary against a temporary vault
+
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+use tempfile::TempDir;
+
+fn gnu(dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_gnu"))
+        .args(args)
+        .current_dir(dir)
+        .env_remove("GITNU_DIR")
+        .output()
+        .expect("failed to run gnu")
+}
+
+fn init_vault() -> TempDir {
+    let temp_dir = TempDir::new().unwrap();
+    let output = gnu(temp_dir.path(), &["init", "--name", "proj"]);
+    assert!(output.status.success(), "init failed: {:?}", output);
+    temp_dir
+}
+
+fn set_config(vault: &Path, from: &str, to: &str) {
+    let config_path = vault.join(".gitnu/config.toml");
+    let config = fs::read_to_string(&config_path).unwrap();
+    assert!(config.contains(from), "config missing '{}'", from);
+    fs::write(&config_path, config.replace(from, to)).unwrap();
+}
+
+fn read_log(vault: &Path, branch: &str) -> String {
+    fs::read_to_string(vault.join(".gitnu/commits").join(format!("{}.jsonl", branch))).unwrap()
+}
+
+#[test]
+fn test_checkout_auto_commits_when_enabled() {
+    let vault = init_vault();
+    let root = vault.path();
+    assert!(gnu(root, &["branch", "explore"]).status.success());
+
+    set_config(root, "auto_commit = false", "auto_commit = true");
+    fs::write(root.join("domains/proj/learnings.md"), "# Learned something\n").unwrap();
+
+    let output = gnu(root, &["checkout", "explore"]);
+    assert!(output.status.success(), "checkout failed: {:?}", output);
+
+    let main_log = read_log(root, "main");
+    assert!(main_log.contains("Auto-commit before checkout to explore"));
+    assert_eq!(main_log.lines().count(), 2);
+}
+
+#[test]
+fn test_auto_commit_is_held_to_commit_checks() {
+    let vault = init_vault();
+    let root = vault.path();
+    assert!(gnu(root, &["branch", "explore"]).status.success());
+    set_config(root, "auto_commit = false", "auto_commit = true");
+    set_config(root, "enforce = false", "enforce = true");
+
+    // Auto-commits are agent commits, so a human-owned file stops them
+    fs::write(root.join("domains/proj/spec.md"), "# Rewritten by agent\n").unwrap();
+    let output = gnu(root, &["checkout", "explore"]);
+    assert_eq!(output.status.code(), Some(8), "{:?}", output);
+    assert_eq!(read_log(root, "main").lines().count(), 1);
+    assert!(fs::read_to_string(root.join(".gitnu/HEAD")).unwrap().contains("main"));
+
+    // So do conflict markers
+    set_config(root, "enforce = true", "enforce = false");
+    fs::write(root.join("domains/proj/spec.md"), "<<<<<<< ours\na\n=======\nb\n>>>>>>> theirs\n").unwrap();
+    let output = gnu(root, &["checkout", "explore"]);
+    assert_eq!(output.status.code(), Some(4), "{:?}", output);
+    assert_eq!(read_log(root, "main").lines().count(), 1);
+}
+
+#[test]
+fn test_checkout_refuses_uncommitted_changes_by_default() {
+    let vault = init_vault();
+    let root = vault.path();
+    assert!(gnu(root, &["branch", "explore"]).status.success());
+
+    fs::write(root.join("domains/proj/learnings.md"), "# Learned something\n").unwrap();
+
+    let output = gnu(root, &["checkout", "explore"]);
+    assert!(!output.status.success());
+    assert_eq!(read_log(root, "main").lines().count(), 1);
+}
+
+#[test]
+fn test_agent_commit_to_human_owned_file_blocked_when_enforced() {
+    let vault = init_vault();
+    let root = vault.path();
+    set_config(root, "enforce = false", "enforce = true");
+
+    fs::write(root.join("domains/proj/spec.md"), "# Rewritten by agent\n").unwrap();
+
+    let output = gnu(root, &["commit", "Edit spec", "--author", "agent"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("domains/proj/spec.md"));
+
+    let output = gnu(root, &["commit", "Edit spec", "--author", "human"]);
+    assert!(output.status.success(), "human commit failed: {:?}", output);
+}
+
+#[test]
+fn test_context_renders_only_loaded_files_and_pins() {
+    let vault = init_vault();
+    let root = vault.path();
+    assert!(gnu(root, &["load", "[[spec]]"]).status.success());
+
+    let output = gnu(root, &["context"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("# File: domains/proj/spec.md"));
+    assert!(stdout.contains("# File: domains/_global/agent.md"));
+    assert!(!stdout.contains("# File: domains/proj/todos.md"));
+
+    let output = gnu(root, &["context", "--all"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("# File: domains/proj/todos.md"));
+}
+
+#[test]
+fn test_deleted_file_stays_deleted_across_checkouts() {
+    let vault = init_vault();
+    let root = vault.path();
+    let notes = root.join("domains/proj/notes.md");
+
+    fs::write(&notes, "# Notes\n").unwrap();
+    assert!(gnu(root, &["commit", "Add notes"]).status.success());
+    fs::remove_file(&notes).unwrap();
+
+    let status = gnu(root, &["status"]);
+    assert!(String::from_utf8_lossy(&status.stdout).contains("Deleted since last commit"));
+    assert!(gnu(root, &["commit", "Remove notes"]).status.success());
+
+    let storage = gitnu::storage::Storage::new(root.to_path_buf());
+    let commits = storage.read_commits("main").unwrap();
+    let child = &commits[commits.len() - 1];
+    assert_eq!(child.context_summary.files_removed.len(), 1);
+    let parent = child.parent.clone().unwrap();
+
+    assert!(gnu(root, &["checkout", &parent]).status.success());
+    assert!(notes.exists());
+
+    // On the detached parent, deletions are measured against the parent itself
+    let status = gnu(root, &["status"]);
+    assert!(!String::from_utf8_lossy(&status.stdout).contains("Deleted since last commit"));
+    fs::remove_file(&notes).unwrap();
+    let status = gnu(root, &["status"]);
+    assert!(String::from_utf8_lossy(&status.stdout).contains("Deleted since last commit"));
+    assert!(gnu(root, &["checkout", &parent, "--force"]).status.success());
+
+    assert!(gnu(root, &["checkout", "main"]).status.success());
+    assert!(!notes.exists());
+}
+
+#[test]
+fn test_concurrent_commits_do_not_corrupt_history() {
+    let vault = init_vault();
+    let root = vault.path().to_path_buf();
+
+    let handles: Vec<_> = (0..2)
+        .map(|worker| {
+            let root = root.clone();
+            std::thread::spawn(move || {
+                for round in 0..5 {
+                    let file = root.join(format!("domains/proj/worker-{}.md", worker));
+                    fs::write(&file, format!("# Round {}\n", round)).unwrap();
+                    let message = format!("Worker {} round {}", worker, round);
+                    let output = gnu(&root, &["commit", &message]);
+                    // The other worker's commit may already have picked up this file
+                    assert!(
+                        output.status.success() || output.status.code() == Some(12),
+                        "commit failed: {:?}",
+                        output
+                    );
+                }
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+
+    let storage = gitnu::storage::Storage::new(root.clone());
+    let commits = storage.read_commits("main").unwrap();
+    for pair in commits.windows(2) {
+        assert_eq!(pair[1].parent.as_deref(), Some(pair[0].hash.as_str()));
+    }
+    let head = storage.read_branch_ref("main").unwrap().unwrap();
+    assert_eq!(head, commits.last().unwrap().hash);
+}
+
+#[test]
+fn test_commit_blocked_on_detached_head() {
+    let vault = init_vault();
+    let root = vault.path();
+    fs::write(root.join("domains/proj/notes.md"), "# Notes\n").unwrap();
+    assert!(gnu(root, &["commit", "Add notes"]).status.success());
+
+    let storage = gitnu::storage::Storage::new(root.to_path_buf());
+    let first = storage.read_commits("main").unwrap()[0].hash.clone();
+    assert!(gnu(root, &["checkout", &first]).status.success());
+
+    let status = gnu(root, &["status"]);
+    assert!(String::from_utf8_lossy(&status.stdout).contains("HEAD detached at"));
+
+    fs::write(root.join("domains/proj/other.md"), "# Other\n").unwrap();
+    let output = gnu(root, &["commit", "On detached HEAD"]);
+    assert!(!output.status.success());
+    assert!(!root.join(format!(".gitnu/commits/{}.jsonl", first)).exists());
+
+    // Creating a branch and switching to it keeps the uncommitted file
+    assert!(gnu(root, &["branch", "rescue"]).status.success());
+    assert!(gnu(root, &["checkout", "rescue"]).status.success());
+    assert!(root.join("domains/proj/other.md").exists());
+    assert!(gnu(root, &["commit", "Rescued"]).status.success());
+}
+
+#[test]
+fn test_commit_allow_empty_records_marker() {
+    let vault = init_vault();
+    let root = vault.path();
+
+    assert_eq!(gnu(root, &["commit", "Nothing here"]).status.code(), Some(12));
+    assert_eq!(read_log(root, "main").lines().count(), 1);
+
+    let output = gnu(root, &["commit", "Session start", "--allow-empty"]);
+    assert!(output.status.success(), "commit failed: {:?}", output);
+
+    let log = read_log(root, "main");
+    assert_eq!(log.lines().count(), 2);
+    assert!(log.contains("Session start"));
+}
+
+#[test]
+fn test_mv_rewrites_wikilinks_to_moved_file() {
+    let vault = init_vault();
+    let root = vault.path();
+    fs::create_dir_all(root.join("domains/auth")).unwrap();
+    fs::write(root.join("domains/proj/notes.md"), "# Notes\n").unwrap();
+    fs::write(root.join("domains/auth/notes.md"), "# Auth notes\n").unwrap();
+    fs::write(root.join("domains/proj/design.md"), "# Design\n").unwrap();
+    fs::write(
+        root.join("domains/proj/index.md"),
+        "[[design|Design doc]] [[proj/notes]] [[notes]] [[design#Goals]]\n",
+    )
+    .unwrap();
+
+    let output = gnu(root, &["mv", "domains/proj/design.md", "domains/proj/architecture.md"]);
+    assert!(output.status.success(), "mv failed: {:?}", output);
+    assert!(root.join("domains/proj/architecture.md").exists());
+    assert!(!root.join("domains/proj/design.md").exists());
+
+    let output = gnu(root, &["mv", "domains/proj/notes.md", "domains/auth/old-notes.md"]);
+    assert!(output.status.success(), "mv failed: {:?}", output);
+
+    // The ambiguous [[notes]] link never resolved to the moved file, so it stays
+    let index = fs::read_to_string(root.join("domains/proj/index.md")).unwrap();
+    assert_eq!(
+        index,
+        "[[architecture|Design doc]] [[auth/old-notes]] [[notes]] [[architecture#Goals]]\n"
+    );
+}
+
+#[test]
+fn test_json_flag_reports_errors_as_json() {
+    let temp_dir = TempDir::new().unwrap();
+
+    let output = gnu(temp_dir.path(), &["status", "--json"]);
+    assert!(!output.status.success());
+
+    let error: serde_json::Value = serde_json::from_slice(&output.stderr).unwrap();
+    assert_eq!(error["error"], "no_vault_found");
+    assert!(error["message"].as_str().unwrap().contains("gnu init"));
+}
+
+#[test]
+fn test_log_orders_by_timestamp_not_log_position() {
+    let vault = init_vault();
+    let root = vault.path();
+    fs::write(root.join("domains/proj/a.md"), "a\n").unwrap();
+    assert!(gnu(root, &["commit", "Second"]).status.success());
+    fs::write(root.join("domains/proj/b.md"), "b\n").unwrap();
+    assert!(gnu(root, &["commit", "Third"]).status.success());
+
+    // Shuffle the log so append order no longer matches chronology
+    let log_path = root.join(".gitnu/commits/main.jsonl");
+    let log = read_log(root, "main");
+    let lines: Vec<&str> = log.lines().collect();
+    fs::write(&log_path, format!("{}\n{}\n{}\n", lines[2], lines[0], lines[1])).unwrap();
+
+    let messages = |args: &[&str]| -> Vec<String> {
+        let output = gnu(root, args);
+        assert!(output.status.success(), "log failed: {:?}", output);
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(|l| l.split_once(' ').unwrap().1.to_string())
+            .collect()
+    };
+
+    let newest_first = messages(&["log", "--oneline"]);
+    assert!(newest_first[0].ends_with("Third"));
+    assert!(newest_first[1].ends_with("Second"));
+
+    let oldest_first = messages(&["log", "--oneline", "--reverse", "--limit", "2"]);
+    assert_eq!(oldest_first.len(), 2);
+    assert!(oldest_first[0].ends_with("Second"));
+    assert!(oldest_first[1].ends_with("Third"));
+}
+
+#[test]
+fn test_commit_rejects_large_files_when_asked() {
+    let vault = init_vault();
+    let root = vault.path();
+    set_config(root, "max_file_size = 1048576", "max_file_size = 16");
+    fs::write(root.join("domains/proj/dump.md"), "x".repeat(64)).unwrap();
+
+    let output = gnu(root, &["commit", "Add dump", "--no-large-files"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("domains/proj/dump.md"));
+    assert_eq!(read_log(root, "main").lines().count(), 1);
+
+    // Without the flag the commit goes through with a warning
+    let output = gnu(root, &["commit", "Add dump"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("larger than"), "{}", stdout);
+    assert!(stdout.contains(".gnignore"), "{}", stdout);
+
+    // Files matching .gnignore are left out of snapshots and status
+    fs::write(root.join(".gnignore"), "# generated\n*.bin\ndomains/proj/cache\n").unwrap();
+    fs::write(root.join("domains/proj/model.bin"), "x".repeat(64)).unwrap();
+    fs::create_dir_all(root.join("domains/proj/cache")).unwrap();
+    fs::write(root.join("domains/proj/cache/page.md"), "cached\n").unwrap();
+    let output = gnu(root, &["status"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(!stdout.contains("model.bin") && !stdout.contains("page.md"), "{}", stdout);
+    assert_eq!(gnu(root, &["commit", "Add ignored files"]).status.code(), Some(12));
+}
+
+#[test]
+fn test_context_from_other_branch_leaves_working_tree_alone() {
+    let vault = init_vault();
+    let root = vault.path();
+    assert!(gnu(root, &["branch", "explore"]).status.success());
+    assert!(gnu(root, &["checkout", "explore"]).status.success());
+    fs::write(root.join("domains/proj/findings.md"), "GraphQL looks promising\n").unwrap();
+    assert!(gnu(root, &["commit", "Explore GraphQL"]).status.success());
+    assert!(gnu(root, &["checkout", "main"]).status.success());
+
+    let output = gnu(root, &["context", "--branch", "explore"]);
+    assert!(output.status.success(), "context failed: {:?}", output);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("GraphQL looks promising"));
+
+    assert!(!root.join("domains/proj/findings.md").exists());
+    assert!(fs::read_to_string(root.join(".gitnu/HEAD")).unwrap().contains("main"));
+}
+
+#[test]
+fn test_show_prints_commit_diff_against_parent() {
+    let vault = init_vault();
+    let root = vault.path();
+    fs::write(root.join("domains/proj/notes.md"), "first line\n").unwrap();
+    assert!(gnu(root, &["commit", "Add notes"]).status.success());
+
+    let output = gnu(root, &["show"]);
+    assert!(output.status.success(), "show failed: {:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Add notes"));
+    assert!(stdout.contains("+++ b/domains/proj/notes.md"));
+    assert!(stdout.contains("+first line"));
+
+    let output = gnu(root, &["show", "--name-only"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout.lines().last(), Some("domains/proj/notes.md"));
+}
+
+#[test]
+fn test_commit_enforces_required_prefix() {
+    let vault = init_vault();
+    let root = vault.path();
+    set_config(root, "require_prefix = []", "require_prefix = [\"Add\", \"Learned\"]");
+    fs::write(root.join("domains/proj/notes.md"), "# Notes\n").unwrap();
+
+    let output = gnu(root, &["commit", "notes"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Add, Learned"));
+
+    // Prefixes match whole words only
+    let output = gnu(root, &["commit", "Addendum to notes"]);
+    assert!(!output.status.success());
+
+    assert!(gnu(root, &["commit", "Learned where notes go"]).status.success());
+}
+
+#[test]
+fn test_backup_and_restore_round_trip() {
+    let vault = init_vault();
+    let root = vault.path();
+    fs::write(root.join("domains/proj/notes.md"), "# Notes\n").unwrap();
+    assert!(gnu(root, &["commit", "Add notes"]).status.success());
+
+    let archive = root.join("vault.tar.gz");
+    let output = gnu(root, &["backup", "--output", archive.to_str().unwrap()]);
+    assert!(output.status.success(), "backup failed: {:?}", output);
+
+    let target = TempDir::new().unwrap();
+    let restored = target.path().join("restored");
+    let output = gnu(
+        target.path(),
+        &["backup", "--restore", archive.to_str().unwrap(), "--into", restored.to_str().unwrap()],
+    );
+    assert!(output.status.success(), "restore failed: {:?}", output);
+
+    assert_eq!(read_log(&restored, "main"), read_log(root, "main"));
+    assert_eq!(fs::read_to_string(restored.join("domains/proj/notes.md")).unwrap(), "# Notes\n");
+
+    // Restoring over an existing vault is refused
+    let output = gnu(root, &["backup", "--restore", archive.to_str().unwrap()]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn test_undo_walks_back_through_operations() {
+    let vault = init_vault();
+    let root = vault.path();
+    let initial = fs::read_to_string(root.join(".gitnu/refs/heads/main")).unwrap();
+
+    fs::write(root.join("domains/proj/a.md"), "a\n").unwrap();
+    assert!(gnu(root, &["commit", "Add a"]).status.success());
+    let after_a = fs::read_to_string(root.join(".gitnu/refs/heads/main")).unwrap();
+    fs::write(root.join("domains/proj/b.md"), "b\n").unwrap();
+    assert!(gnu(root, &["commit", "Add b"]).status.success());
+
+    let output = gnu(root, &["undo"]);
+    assert!(output.status.success(), "undo failed: {:?}", output);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Add b"));
+    assert_eq!(fs::read_to_string(root.join(".gitnu/refs/heads/main")).unwrap(), after_a);
+    assert!(!root.join("domains/proj/b.md").exists());
+
+    assert!(gnu(root, &["undo"]).status.success());
+    assert_eq!(fs::read_to_string(root.join(".gitnu/refs/heads/main")).unwrap(), initial);
+    assert!(!root.join("domains/proj/a.md").exists());
+
+    // The initial commit created the branch, so there is nothing before it
+    assert!(!gnu(root, &["undo"]).status.success());
+}
+
+#[test]
+fn test_log_shortstat_groups_by_author() {
+    let vault = init_vault();
+    let root = vault.path();
+    fs::write(root.join("domains/proj/a.md"), "a\n").unwrap();
+    assert!(gnu(root, &["commit", "Add a", "--author", "agent", "--model", "test-model"]).status.success());
+    fs::write(root.join("domains/proj/b.md"), "b\n").unwrap();
+    assert!(gnu(root, &["commit", "Add b", "--author", "agent", "--model", "test-model"]).status.success());
+
+    let output = gnu(root, &["log", "--oneline", "--shortstat"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Agent (test-model)  2 commits, 2 files changed, 2 insertions (+), 0 deletions (-)"), "{}", stdout);
+    assert!(stdout.contains("Human"));
+
+    let output = gnu(root, &["summary"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Agent (test-model) 2 commits (2 files, +2/-0 lines)"), "{}", stdout);
+}
+
+#[test]
+fn test_merge_squash_applies_net_changes_as_one_commit() {
+    let vault = init_vault();
+    let root = vault.path();
+    let commit = |message: &str| {
+        let output = gnu(root, &["commit", message]);
+        assert!(output.status.success(), "commit failed: {:?}", output);
+    };
+
+    assert!(gnu(root, &["branch", "explore"]).status.success());
+    assert!(gnu(root, &["checkout", "explore"]).status.success());
+    fs::write(root.join("domains/proj/a.md"), "first\n").unwrap();
+    commit("Add a");
+    fs::write(root.join("domains/proj/b.md"), "b\n").unwrap();
+    commit("Add b");
+    fs::write(root.join("domains/proj/a.md"), "second\n").unwrap();
+    commit("Revise a");
+
+    assert!(gnu(root, &["checkout", "main"]).status.success());
+    fs::write(root.join("domains/proj/main-only.md"), "kept\n").unwrap();
+    commit("Work on main");
+    let main_commits = read_log(root, "main").lines().count();
+
+    let output = gnu(root, &["merge", "explore", "--squash"]);
+    assert!(output.status.success(), "merge failed: {:?}", output);
+
+    // Source changes land without wiping files only main has
+    assert_eq!(fs::read_to_string(root.join("domains/proj/a.md")).unwrap(), "second\n");
+    assert_eq!(fs::read_to_string(root.join("domains/proj/b.md")).unwrap(), "b\n");
+    assert_eq!(fs::read_to_string(root.join("domains/proj/main-only.md")).unwrap(), "kept\n");
+
+    let main_log = read_log(root, "main");
+    assert_eq!(main_log.lines().count(), main_commits + 1);
+    let squash: serde_json::Value = serde_json::from_str(main_log.lines().last().unwrap()).unwrap();
+    assert!(squash.get("merge_parent").is_none());
+    let message = squash["message"].as_str().unwrap();
+    assert!(message.starts_with("Squash merge explore (3 commits)"), "{}", message);
+    for subject in ["- Add a", "- Add b", "- Revise a"] {
+        assert!(message.contains(subject), "{}", message);
+    }
+}
+
+#[test]
+fn test_merge_squash_combines_changes_both_branches_made() {
+    let vault = init_vault();
+    let root = vault.path();
+    let file = root.join("domains/proj/f.md");
+    fs::write(&file, "one\ntwo\nthree\n").unwrap();
+    assert!(gnu(root, &["commit", "Add f"]).status.success());
+
+    assert!(gnu(root, &["branch", "feat"]).status.success());
+    assert!(gnu(root, &["checkout", "feat"]).status.success());
+    fs::write(&file, "one\ntwo\nTHREE\n").unwrap();
+    assert!(gnu(root, &["commit", "Edit line 3"]).status.success());
+    assert!(gnu(root, &["checkout", "main"]).status.success());
+    fs::write(&file, "ONE\ntwo\nthree\n").unwrap();
+    assert!(gnu(root, &["commit", "Edit line 1"]).status.success());
+
+    let output = gnu(root, &["merge", "feat", "--squash"]);
+    assert!(output.status.success(), "merge failed: {:?}", output);
+    assert_eq!(fs::read_to_string(&file).unwrap(), "ONE\ntwo\nTHREE\n");
+
+    // Lines both branches changed stop the squash until resolved
+    assert!(gnu(root, &["checkout", "feat"]).status.success());
+    fs::write(&file, "one\ntwo\nfeat\n").unwrap();
+    assert!(gnu(root, &["commit", "Feat line 3"]).status.success());
+    assert!(gnu(root, &["checkout", "main"]).status.success());
+    fs::write(&file, "ONE\ntwo\nmain\n").unwrap();
+    assert!(gnu(root, &["commit", "Main line 3"]).status.success());
+
+    let output = gnu(root, &["merge", "feat", "--squash"]);
+    assert_eq!(output.status.code(), Some(4), "{:?}", output);
+    assert!(fs::read_to_string(&file).unwrap().contains("<<<<<<< main"));
+    assert!(!root.join(".gitnu/MERGE_HEAD").exists());
+    let message = fs::read_to_string(root.join(".gitnu/MERGE_MSG")).unwrap();
+    assert!(message.starts_with("Squash merge feat"), "{}", message);
+    assert!(gnu(root, &["mergetool", "--theirs"]).status.success());
+    assert_eq!(fs::read_to_string(&file).unwrap(), "ONE\ntwo\nfeat\n");
+    assert!(gnu(root, &["commit", "Squash merge feat"]).status.success());
+
+    let main_log = read_log(root, "main");
+    let squash: serde_json::Value = serde_json::from_str(main_log.lines().last().unwrap()).unwrap();
+    assert!(squash.get("merge_parent").is_none(), "{}", squash);
+}
+
+#[test]
+fn test_checkout_dry_run_reports_changes_without_touching_files() {
+    let vault = init_vault();
+    let root = vault.path();
+    assert!(gnu(root, &["branch", "explore"]).status.success());
+    fs::write(root.join("domains/proj/draft.md"), "unsaved\n").unwrap();
+
+    let output = gnu(root, &["checkout", "explore", "--force", "--dry-run"]);
+    assert!(output.status.success(), "dry run failed: {:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("- Would remove: domains/proj/draft.md"), "{}", stdout);
+
+    assert!(root.join("domains/proj/draft.md").exists());
+    assert_eq!(fs::read_to_string(root.join(".gitnu/HEAD")).unwrap().trim(), "ref: refs/heads/main");
+
+    // Without --force the dry run refuses just like the real checkout
+    let output = gnu(root, &["checkout", "explore", "--dry-run"]);
+    assert_eq!(output.status.code(), Some(3), "{:?}", output);
+}
+
+#[test]
+fn test_merge_dry_run_into_previews_against_the_target() {
+    let vault = init_vault();
+    let root = vault.path();
+    let notes = root.join("domains/proj/notes.md");
+    fs::write(&notes, "base\n").unwrap();
+    assert!(gnu(root, &["commit", "Add notes"]).status.success());
+    assert!(gnu(root, &["branch", "explore"]).status.success());
+    assert!(gnu(root, &["branch", "feature"]).status.success());
+    assert!(gnu(root, &["checkout", "feature"]).status.success());
+    fs::write(&notes, "feature\n").unwrap();
+    assert!(gnu(root, &["commit", "Edit on feature"]).status.success());
+    assert!(gnu(root, &["checkout", "main"]).status.success());
+    fs::write(&notes, "feature\n").unwrap();
+    assert!(gnu(root, &["commit", "Same edit on main"]).status.success());
+
+    // main already has the change, explore doesn't
+    let output = gnu(root, &["merge", "feature", "--into", "explore", "--dry-run"]);
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Would switch to branch 'explore' first"), "{}", stdout);
+    assert!(stdout.contains("~ Would modify: domains/proj/notes.md"), "{}", stdout);
+    assert_eq!(fs::read_to_string(root.join(".gitnu/HEAD")).unwrap().trim(), "ref: refs/heads/main");
+}
+
+#[test]
+fn test_log_files_lists_changed_files() {
+    let vault = init_vault();
+    let root = vault.path();
+    fs::create_dir_all(root.join("domains/proj/notes")).unwrap();
+    fs::write(root.join("domains/proj/notes/spec.md"), "spec\n").unwrap();
+    assert!(gnu(root, &["commit", "Add spec"]).status.success());
+
+    let output = gnu(root, &["log", "--oneline", "--files", "--limit", "1"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.trim_end().ends_with("Add spec [spec.md]"), "{}", stdout);
+
+    let output = gnu(root, &["log", "--files", "--limit", "1"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Added: domains/proj/notes/spec.md"), "{}", stdout);
+}
+
+#[test]
+fn test_serve_mcp_answers_tool_calls() {
+    use std::io::Write;
+    use std::process::Stdio;
+
+    let vault = init_vault();
+    let root = vault.path();
+    fs::write(root.join("domains/proj/notes.md"), "# Notes\n").unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_gnu"))
+        .args(["serve", "--mcp"])
+        .current_dir(root)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("failed to run gnu");
+    let requests = [
+        r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{}}"#,
+        r#"{"jsonrpc":"2.0","method":"notifications/initialized"}"#,
+        r#"{"jsonrpc":"2.0","id":2,"method":"tools/call","params":{"name":"commit","arguments":{"message":"Add notes"}}}"#,
+        r#"{"jsonrpc":"2.0","id":3,"method":"tools/call","params":{"name":"status","arguments":{}}}"#,
+    ];
+    child.stdin.take().unwrap().write_all(requests.join("\n").as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+
+    let responses: Vec<serde_json::Value> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(responses.len(), 3, "notifications get no response");
+    assert_eq!(responses[0]["result"]["serverInfo"]["name"], "gitnu");
+
+    let commit = &responses[1]["result"];
+    assert_eq!(commit["isError"], false);
+    assert_eq!(commit["structuredContent"]["commit"]["message"], "Add notes");
+
+    let status = &responses[2]["result"]["structuredContent"];
+    assert_eq!(status["branch"], "main");
+    assert_eq!(status["modified"].as_array().unwrap().len(), 0);
+}
+
+#[test]
+fn test_annotate_tags_commits_without_changing_hashes() {
+    let vault = init_vault();
+    let root = vault.path();
+    fs::write(root.join("domains/proj/plan.md"), "plan\n").unwrap();
+    assert!(gnu(root, &["commit", "Settle the plan"]).status.success());
+    let log_before = read_log(root, "main");
+
+    let output = gnu(root, &["annotate", "HEAD", "--tag", "milestone", "--add", "Agreed in review"]);
+    assert!(output.status.success(), "annotate failed: {:?}", output);
+    assert_eq!(read_log(root, "main"), log_before);
+
+    let output = gnu(root, &["log", "--oneline", "--tagged", "milestone", "--notes"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Settle the plan"), "{}", stdout);
+    assert!(!stdout.contains("Initial commit"), "{}", stdout);
+    assert!(stdout.contains("Tags: milestone"), "{}", stdout);
+    assert!(stdout.contains("Note: Agreed in review"), "{}", stdout);
+}
+
+#[test]
+fn test_doctor_resets_dangling_branch_ref() {
+    let vault = init_vault();
+    let root = vault.path();
+    let head = fs::read_to_string(root.join(".gitnu/refs/heads/main")).unwrap();
+    fs::write(root.join(".gitnu/refs/heads/main"), "deadbeef").unwrap();
+
+    let output = gnu(root, &["doctor"]);
+    assert!(!output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("error: branch 'main' points to missing commit deadbee"), "{}", stdout);
+
+    let output = gnu(root, &["doctor", "--fix"]);
+    assert!(output.status.success(), "doctor --fix failed: {:?}", output);
+    assert_eq!(fs::read_to_string(root.join(".gitnu/refs/heads/main")).unwrap().trim(), head.trim());
+    assert!(gnu(root, &["doctor"]).status.success());
+}
+
+#[test]
+fn test_doctor_uses_default_branch_and_keeps_every_backup() {
+    let vault = init_vault();
+    let root = vault.path();
+    assert!(gnu(root, &["branch", "trunk"]).status.success());
+    set_config(root, "default_branch = \"main\"", "default_branch = \"trunk\"");
+    fs::remove_file(root.join(".gitnu/HEAD")).unwrap();
+    let output = gnu(root, &["doctor", "--fix"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("pointed HEAD at 'trunk'"), "{:?}", output);
+    assert_eq!(fs::read_to_string(root.join(".gitnu/HEAD")).unwrap().trim(), "ref: refs/heads/trunk");
+
+    let log_path = root.join(".gitnu/commits/main.jsonl");
+    for garbage in ["{broken", "{also broken"] {
+        let mut log = fs::read_to_string(&log_path).unwrap();
+        log.push_str(garbage);
+        log.push('\n');
+        fs::write(&log_path, log).unwrap();
+        assert!(gnu(root, &["doctor", "--fix"]).status.success());
+    }
+    let backups: Vec<String> = fs::read_dir(root.join(".gitnu/commits")).unwrap()
+        .map(|e| fs::read_to_string(e.unwrap().path()).unwrap())
+        .filter(|content| content.starts_with('{') && !content.starts_with("{\""))
+        .collect();
+    assert_eq!(backups.len(), 2, "{:?}", backups);
+}
+
+#[test]
+fn test_load_glob_loads_every_match() {
+    let vault = init_vault();
+    let root = vault.path();
+    fs::create_dir_all(root.join("domains/other")).unwrap();
+    fs::write(root.join("domains/other/decisions.md"), "# Decisions\n").unwrap();
+
+    let output = gnu(root, &["load", "domains/**/decisions.md", "--pin"]);
+    assert!(output.status.success(), "load failed: {:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("2 files"), "{}", stdout);
+
+    let index: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(root.join(".gitnu/index.json")).unwrap()).unwrap();
+    for key in ["loaded", "pinned"] {
+        let entries: Vec<&str> = index[key].as_array().unwrap().iter().map(|v| v.as_str().unwrap()).collect();
+        assert!(entries.contains(&"domains/proj/decisions.md"), "{:?}", entries);
+        assert!(entries.contains(&"domains/other/decisions.md"), "{:?}", entries);
+    }
+}
+
+#[test]
+fn test_diff_three_dot_shows_only_branch_changes() {
+    let vault = init_vault();
+    let root = vault.path();
+    assert!(gnu(root, &["branch", "explore"]).status.success());
+    assert!(gnu(root, &["checkout", "explore"]).status.success());
+    fs::write(root.join("domains/proj/idea.md"), "idea\n").unwrap();
+    assert!(gnu(root, &["commit", "Explore an idea"]).status.success());
+    assert!(gnu(root, &["checkout", "main"]).status.success());
+    fs::write(root.join("domains/proj/drift.md"), "drift\n").unwrap();
+    assert!(gnu(root, &["commit", "Unrelated work"]).status.success());
+
+    let output = gnu(root, &["diff", "main...explore"]);
+    assert!(output.status.success(), "diff failed: {:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("idea.md"), "{}", stdout);
+    assert!(!stdout.contains("drift.md"), "{}", stdout);
+
+    let output = gnu(root, &["diff", "main..explore"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("idea.md"), "{}", stdout);
+    assert!(stdout.contains("drift.md"), "{}", stdout);
+}
+
+#[test]
+fn test_status_warns_when_over_token_budget() {
+    let vault = init_vault();
+    let root = vault.path();
+
+    let output = gnu(root, &["status"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("of 100000 tokens"), "{}", stdout);
+    assert!(!stdout.contains("Over the context.max_tokens budget"), "{}", stdout);
+
+    set_config(root, "max_tokens = 100000", "max_tokens = 1");
+    let output = gnu(root, &["status"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Over the context.max_tokens budget"), "{}", stdout);
+}
+
+#[test]
+fn test_init_bare_skips_agent_scaffolding() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    let output = gnu(root, &["init", "--name", "proj", "--bare", "--no-project-domain"]);
+    assert!(output.status.success(), "init failed: {:?}", output);
+
+    assert!(root.join(".gitnu/HEAD").exists());
+    assert!(root.join("domains/_global").is_dir());
+    assert!(!root.join("domains/proj").exists());
+    assert!(!root.join("AGENTS.md").exists());
+    assert!(!root.join(".claude").exists());
+    assert!(!root.join("opencode.json").exists());
+    assert_eq!(read_log(root, "main").lines().count(), 1);
+}
+
+#[test]
+fn test_checkout_restores_loaded_context_per_branch() {
+    let vault = init_vault();
+    let root = vault.path();
+    let loaded = |root: &Path| -> Vec<String> {
+        let index: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(root.join(".gitnu/index.json")).unwrap()).unwrap();
+        index["loaded"].as_array().unwrap().iter().map(|v| v.as_str().unwrap().to_string()).collect()
+    };
+    let on_main = loaded(root);
+
+    assert!(gnu(root, &["branch", "explore"]).status.success());
+    assert!(gnu(root, &["checkout", "explore"]).status.success());
+    fs::write(root.join("domains/proj/idea.md"), "idea\n").unwrap();
+    assert!(gnu(root, &["load", "domains/proj/idea.md"]).status.success());
+    assert!(gnu(root, &["commit", "Explore an idea"]).status.success());
+
+    assert!(gnu(root, &["checkout", "main"]).status.success());
+    assert_eq!(loaded(root), on_main);
+
+    assert!(gnu(root, &["checkout", "explore"]).status.success());
+    assert!(loaded(root).contains(&"domains/proj/idea.md".to_string()));
+
+    // An unrecorded pin counts as uncommitted work, and can be committed alone
+    assert!(gnu(root, &["pin", "domains/proj/idea.md"]).status.success());
+    let output = gnu(root, &["status"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Loaded context changed since last commit"), "{:?}", output);
+    assert_eq!(gnu(root, &["checkout", "main"]).status.code(), Some(3));
+    assert!(gnu(root, &["commit", "Pin the idea"]).status.success());
+    assert!(gnu(root, &["checkout", "main"]).status.success());
+}
+
+#[test]
+fn test_checkout_leaves_restored_files_untouched() {
+    let vault = init_vault();
+    let root = vault.path();
+    assert!(gnu(root, &["branch", "explore"]).status.success());
+    fs::write(root.join("domains/proj/notes.md"), "on main\n").unwrap();
+    assert!(gnu(root, &["commit", "Add notes"]).status.success());
+
+    assert!(gnu(root, &["checkout", "explore"]).status.success());
+    assert!(gnu(root, &["checkout", "main"]).status.success());
+
+    let output = gnu(root, &["status", "--include-unchanged"]);
+    assert!(output.status.success(), "status failed: {:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(!stdout.contains("Saved but unchanged"), "{}", stdout);
+}
+
+#[test]
+fn test_squash_collapses_linear_commits() {
+    let vault = init_vault();
+    let root = vault.path();
+    for (file, message) in [("a.md", "Add a"), ("b.md", "Add b"), ("c.md", "Add c")] {
+        fs::write(root.join("domains/proj").join(file), "note\n").unwrap();
+        assert!(gnu(root, &["commit", message]).status.success());
+    }
+    let before = read_log(root, "main");
+    let init: serde_json::Value = serde_json::from_str(before.lines().next().unwrap()).unwrap();
+
+    let output = gnu(root, &["squash", "3"]);
+    assert!(output.status.success(), "squash failed: {:?}", output);
+
+    let after = read_log(root, "main");
+    assert_eq!(after.lines().count(), 2);
+    let squashed: serde_json::Value = serde_json::from_str(after.lines().last().unwrap()).unwrap();
+    assert_eq!(squashed["parent"], init["hash"]);
+    assert_eq!(squashed["message"], "Add a\n\nAdd b\n\nAdd c");
+    assert_eq!(squashed["context_summary"]["files_added"].as_array().unwrap().len(), 3);
+    let head = fs::read_to_string(root.join(".gitnu/refs/heads/main")).unwrap();
+    assert_eq!(head.trim(), squashed["hash"].as_str().unwrap());
+
+    assert!(!gnu(root, &["squash", "5"]).status.success());
+}
+
+#[test]
+fn test_load_list_shows_token_costs() {
+    let vault = init_vault();
+    let root = vault.path();
+    fs::write(root.join("domains/proj/big.md"), "x".repeat(400)).unwrap();
+    assert!(gnu(root, &["load", "domains/proj/big.md"]).status.success());
+
+    let output = gnu(root, &["load", "--list"]);
+    assert!(output.status.success(), "load --list failed: {:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("~100 tokens"), "{}", stdout);
+    assert!(stdout.contains("of 100000 tokens"), "{}", stdout);
+
+    // A directory entry overlapping a loaded file counts the file once in the total
+    fs::write(root.join("domains/proj/small.md"), "y".repeat(40)).unwrap();
+    assert!(gnu(root, &["load", "domains/proj"]).status.success());
+    let output = gnu(root, &["load", "--list"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let dir_tokens = stdout.lines()
+        .find(|line| line.trim_start().starts_with("- domains/proj "))
+        .and_then(|line| line.split('~').nth(1))
+        .and_then(|rest| rest.split(' ').next())
+        .unwrap();
+    assert!(stdout.contains(&format!("Total: ~{} of", dir_tokens)), "{}", stdout);
+}
+
+#[test]
+fn test_binary_files_get_a_placeholder_in_context() {
+    let vault = init_vault();
+    let root = vault.path();
+    fs::write(root.join("domains/proj/logo.png"), [0x89, b'P', b'N', b'G', 0, 0, 1, 2]).unwrap();
+
+    let output = gnu(root, &["context", "--all"]);
+    assert!(output.status.success(), "context failed: {:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("[binary file: domains/proj/logo.png, 8.0 B]"), "{}", stdout);
+
+    set_config(root, "warn_binary_files = false", "warn_binary_files = true");
+    let output = gnu(root, &["commit", "Add logo"]);
+    assert!(output.status.success(), "commit failed: {:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Warning: committing binary files"), "{}", stdout);
+}
+
+#[test]
+fn test_auto_gc_prunes_expired_unreachable_snapshots() {
+    let vault = init_vault();
+    let root = vault.path();
+    let hash_of = |line: &str| -> String {
+        serde_json::from_str::<serde_json::Value>(line).unwrap()["hash"].as_str().unwrap().to_string()
+    };
+    let initial = hash_of(read_log(root, "main").lines().next().unwrap());
+
+    // A commit only a deleted branch knew about (its reflog goes with it)
+    assert!(gnu(root, &["branch", "scratch"]).status.success());
+    assert!(gnu(root, &["checkout", "scratch"]).status.success());
+    fs::write(root.join("domains/proj/a.md"), "a\n").unwrap();
+    assert!(gnu(root, &["commit", "Add a"]).status.success());
+    let abandoned = hash_of(read_log(root, "scratch").lines().last().unwrap());
+    assert!(gnu(root, &["checkout", "main"]).status.success());
+    assert!(gnu(root, &["branch", "-d", "scratch"]).status.success());
+
+    set_config(root, "keep_last = 10", "keep_last = 0");
+    set_config(root, "keep_days = 30", "keep_days = 0");
+    set_config(root, "auto_gc = false", "auto_gc = true");
+    fs::write(root.join("domains/proj/b.md"), "b\n").unwrap();
+    let output = gnu(root, &["commit", "Add b"]);
+    assert!(output.status.success(), "commit failed: {:?}", output);
+
+    let objects = root.join(".gitnu/objects");
+    assert!(!objects.join(&abandoned).exists());
+    assert!(objects.join(&initial).exists());
+    let head = hash_of(read_log(root, "main").lines().last().unwrap());
+    assert!(objects.join(&head).exists());
+    assert!(gnu(root, &["doctor"]).status.success());
+}
+
+#[test]
+fn test_branch_description_is_stored_and_updated() {
+    let vault = init_vault();
+    let root = vault.path();
+    assert!(gnu(root, &["branch", "explore", "--describe", "Try a cache"]).status.success());
+
+    let output = gnu(root, &["branch"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Try a cache"));
+
+    // The branch name is never taken as part of the description
+    assert!(gnu(root, &["branch", "--describe", "Cache with TTLs", "explore"]).status.success());
+    let output = gnu(root, &["--json", "summary"]);
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let explore = report["branches"].as_array().unwrap().iter().find(|b| b["name"] == "explore").unwrap();
+    assert_eq!(explore["description"], "Cache with TTLs");
+
+    assert_eq!(gnu(root, &["branch", "--describe", "Too", "many", "words"]).status.code(), Some(64));
+
+    assert!(gnu(root, &["branch", "--describe", "Mainline"]).status.success());
+    let output = gnu(root, &["branch"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Mainline"), "{:?}", output);
+}
+
+#[test]
+fn test_branch_age_and_prune_older_than() {
+    let vault = init_vault();
+    let root = vault.path();
+    assert!(gnu(root, &["branch", "explore-old"]).status.success());
+    assert!(gnu(root, &["branch", "explore-new"]).status.success());
+
+    let output = gnu(root, &["branch", "-v"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("(created just now)"));
+
+    let meta_path = root.join(".gitnu/refs/meta/explore-old.json");
+    let mut meta: serde_json::Value = serde_json::from_str(&fs::read_to_string(&meta_path).unwrap()).unwrap();
+    meta["created_at"] = "2020-01-01T00:00:00Z".into();
+    fs::write(&meta_path, meta.to_string()).unwrap();
+
+    let output = gnu(root, &["prune-branches", "--older-than", "30", "--yes"]);
+    assert!(output.status.success(), "prune-branches failed: {:?}", output);
+    assert!(!root.join(".gitnu/refs/heads/explore-old").exists());
+    assert!(root.join(".gitnu/refs/heads/explore-new").exists());
+}
+
+#[test]
+fn test_prune_branches_deletes_only_merged_matches() {
+    let vault = init_vault();
+    let root = vault.path();
+    for branch in ["explore-merged", "explore-open", "other"] {
+        assert!(gnu(root, &["branch", branch]).status.success());
+    }
+    for (branch, file) in [("explore-merged", "merged.md"), ("explore-open", "open.md")] {
+        assert!(gnu(root, &["checkout", branch]).status.success());
+        fs::write(root.join("domains/proj").join(file), "note\n").unwrap();
+        assert!(gnu(root, &["commit", "Explore"]).status.success());
+    }
+    assert!(gnu(root, &["checkout", "main"]).status.success());
+    assert!(gnu(root, &["merge", "explore-merged"]).status.success());
+
+    let output = gnu(root, &["prune-branches", "--dry-run"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("explore-merged") && stdout.contains("other"), "{}", stdout);
+    assert!(!stdout.contains("explore-open"), "{}", stdout);
+    assert!(root.join(".gitnu/refs/heads/explore-merged").exists());
+
+    let output = gnu(root, &["prune-branches", "--pattern", "explore-*", "--yes"]);
+    assert!(output.status.success(), "prune-branches failed: {:?}", output);
+    assert!(!root.join(".gitnu/refs/heads/explore-merged").exists());
+    assert!(root.join(".gitnu/refs/heads/explore-open").exists());
+    assert!(root.join(".gitnu/refs/heads/other").exists());
+}
+
+#[test]
+fn test_rewind_cleans_stale_index_entries() {
+    let vault = init_vault();
+    let root = vault.path();
+    let initial: serde_json::Value = serde_json::from_str(read_log(root, "main").lines().next().unwrap()).unwrap();
+    let initial = initial["hash"].as_str().unwrap().to_string();
+
+    fs::write(root.join("domains/proj/later.md"), "later\n").unwrap();
+    assert!(gnu(root, &["commit", "Add later"]).status.success());
+    assert!(gnu(root, &["load", "domains/proj/later.md", "--pin"]).status.success());
+
+    // Simulate a commit from before indexes were recorded
+    let log_path = root.join(".gitnu/commits/main.jsonl");
+    let log: Vec<String> = read_log(root, "main").lines().map(|line| {
+        let mut commit: serde_json::Value = serde_json::from_str(line).unwrap();
+        commit.as_object_mut().unwrap().remove("index");
+        commit.to_string()
+    }).collect();
+    fs::write(&log_path, log.join("\n") + "\n").unwrap();
+
+    let output = gnu(root, &["rewind", &initial]);
+    assert!(output.status.success(), "rewind failed: {:?}", output);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Cleaned 2 stale index entries"));
+    let index = fs::read_to_string(root.join(".gitnu/index.json")).unwrap();
+    assert!(!index.contains("later.md"), "{}", index);
+}
+
+#[test]
+fn test_patch_context_lines() {
+    let vault = init_vault();
+    let root = vault.path();
+    let lines: Vec<String> = (1..=9).map(|n| format!("line {}", n)).collect();
+    fs::write(root.join("domains/proj/notes.md"), lines.join("\n") + "\n").unwrap();
+    assert!(gnu(root, &["commit", "Add notes"]).status.success());
+    let edited = lines.join("\n").replace("line 5", "line five") + "\n";
+    fs::write(root.join("domains/proj/notes.md"), edited).unwrap();
+
+    let output = gnu(root, &["diff", "--format", "patch", "--context-lines", "0"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("@@ -5 +5 @@"), "{}", stdout);
+    assert!(!stdout.contains(" line 4"), "{}", stdout);
+
+    let output = gnu(root, &["diff", "--format", "patch", "--context-lines", "1"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains(" line 4\n-line 5\n+line five\n line 6"), "{}", stdout);
+}
+
+#[test]
+fn test_mergetool_tracks_resolution_until_commit() {
+    let vault = init_vault();
+    let root = vault.path();
+    let notes = root.join("domains/proj/notes.md");
+    fs::write(&notes, "# Notes\n<<<<<<< main\nuse redis\n=======\nuse memcached\n>>>>>>> explore\n").unwrap();
+
+    // No answer on stdin skips the file, leaving the merge unfinished
+    assert!(gnu(root, &["mergetool"]).status.success());
+    assert!(root.join(".gitnu/MERGE_STATE.json").exists());
+    let output = gnu(root, &["commit", "Finish merge"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("gnu mergetool"), "{:?}", output);
+
+    assert!(gnu(root, &["mergetool", "--theirs"]).status.success());
+    assert_eq!(fs::read_to_string(&notes).unwrap(), "# Notes\nuse memcached\n");
+    assert!(gnu(root, &["commit", "Finish merge"]).status.success());
+    assert!(!root.join(".gitnu/MERGE_STATE.json").exists());
+}
+
+#[test]
+fn test_conflicting_merge_is_finished_by_commit_or_aborted() {
+    let vault = init_vault();
+    let root = vault.path();
+    let notes = root.join("domains/proj/notes.md");
+    let commit = |message: &str| {
+        let output = gnu(root, &["commit", message]);
+        assert!(output.status.success(), "commit failed: {:?}", output);
+    };
+
+    fs::write(&notes, "cache: none\n").unwrap();
+    commit("Add notes");
+    assert!(gnu(root, &["branch", "explore"]).status.success());
+    assert!(gnu(root, &["checkout", "explore"]).status.success());
+    fs::write(&notes, "cache: memcached\n").unwrap();
+    fs::write(root.join("domains/proj/extra.md"), "extra\n").unwrap();
+    commit("Try memcached");
+    let explore_head = fs::read_to_string(root.join(".gitnu/refs/heads/explore")).unwrap();
+
+    assert!(gnu(root, &["checkout", "main"]).status.success());
+    fs::write(&notes, "cache: redis\n").unwrap();
+    commit("Use redis");
+
+    let output = gnu(root, &["merge", "explore"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("gnu merge --abort"), "{:?}", output);
+    assert!(root.join(".gitnu/MERGE_HEAD").exists());
+    assert!(fs::read_to_string(&notes).unwrap().contains("<<<<<<< main\ncache: redis\n=======\ncache: memcached\n>>>>>>> explore"));
+    assert_eq!(fs::read_to_string(root.join("domains/proj/extra.md")).unwrap(), "extra\n");
+    assert!(!gnu(root, &["merge", "explore"]).status.success());
+
+    assert!(gnu(root, &["merge", "--abort"]).status.success());
+    assert_eq!(fs::read_to_string(&notes).unwrap(), "cache: redis\n");
+    assert!(!root.join("domains/proj/extra.md").exists());
+    assert!(!root.join(".gitnu/MERGE_HEAD").exists());
+
+    assert!(!gnu(root, &["merge", "explore"]).status.success());
+    assert!(gnu(root, &["mergetool", "--theirs"]).status.success());
+    commit("Finish merge");
+    let merge: serde_json::Value = serde_json::from_str(read_log(root, "main").lines().last().unwrap()).unwrap();
+    assert_eq!(merge["merge_parent"].as_str().unwrap(), explore_head.trim());
+    assert_eq!(fs::read_to_string(&notes).unwrap(), "cache: memcached\n");
+    for file in ["MERGE_HEAD", "MERGE_MSG", "MERGE_STATE.json"] {
+        assert!(!root.join(".gitnu").join(file).exists(), "{} left behind", file);
+    }
+}
+
+#[test]
+fn test_merge_combines_lines_and_leaves_binary_conflicts_intact() {
+    let vault = init_vault();
+    let root = vault.path();
+    let notes = root.join("domains/proj/notes.md");
+    let image = root.join("domains/proj/diagram.png");
+    fs::write(&notes, "one\ntwo\nthree\nfour\n").unwrap();
+    fs::write(&image, [0x89, b'P', b'N', b'G', 0, 1]).unwrap();
+    assert!(gnu(root, &["commit", "Add notes"]).status.success());
+    assert!(gnu(root, &["branch", "explore"]).status.success());
+    assert!(gnu(root, &["checkout", "explore"]).status.success());
+    fs::write(&notes, "one\ntwo\nthree\nFOUR\n").unwrap();
+    fs::write(&image, [0x89, b'P', b'N', b'G', 0, 2, 0xff]).unwrap();
+    assert!(gnu(root, &["commit", "Edit on explore"]).status.success());
+    assert!(gnu(root, &["checkout", "main"]).status.success());
+    fs::write(&notes, "ONE\ntwo\nthree\nfour\n").unwrap();
+    fs::write(&image, [0x89, b'P', b'N', b'G', 0, 3]).unwrap();
+    assert!(gnu(root, &["commit", "Edit on main"]).status.success());
+
+    let output = gnu(root, &["merge", "explore"]);
+    assert_eq!(output.status.code(), Some(4), "{:?}", output);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Merge stopped with 1 conflicted file"), "{:?}", output);
+    assert_eq!(fs::read_to_string(&notes).unwrap(), "ONE\ntwo\nthree\nFOUR\n");
+    assert_eq!(fs::read(&image).unwrap(), [0x89, b'P', b'N', b'G', 0, 3]);
+
+    assert!(gnu(root, &["mergetool", "--theirs"]).status.success());
+    assert_eq!(fs::read(&image).unwrap(), [0x89, b'P', b'N', b'G', 0, 2, 0xff]);
+    assert!(gnu(root, &["commit", "Finish merge"]).status.success());
+}
+
+#[test]
+fn test_fsck_reports_unreachable_and_missing_objects() {
+    let vault = init_vault();
+    let root = vault.path();
+    fs::write(root.join("domains/proj/a.md"), "a\n").unwrap();
+    assert!(gnu(root, &["commit", "Add a"]).status.success());
+
+    let output = gnu(root, &["fsck", "--unreachable"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("no unreachable objects"));
+
+    fs::create_dir_all(root.join(".gitnu/objects/deadbeefcafe")).unwrap();
+    fs::write(root.join(".gitnu/objects/deadbeefcafe/snapshot.tar.gz"), "junk").unwrap();
+    let output = gnu(root, &["fsck", "--unreachable", "--verbose"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("1 object (4.0 B) unreachable"), "{}", stdout);
+    assert!(stdout.contains("deadbee"), "{}", stdout);
+    // Read-only: the object is still there
+    assert!(root.join(".gitnu/objects/deadbeefcafe").exists());
+
+    let head = fs::read_to_string(root.join(".gitnu/refs/heads/main")).unwrap();
+    fs::remove_dir_all(root.join(".gitnu/objects").join(head.trim())).unwrap();
+    let output = gnu(root, &["fsck", "--verbose"]);
+    assert!(!output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("1 ref with a missing snapshot"), "{}", stdout);
+    assert!(stdout.contains("1 log entry without a snapshot"), "{}", stdout);
+}
+
+#[test]
+fn test_vault_override_from_flag_and_env() {
+    let vault = init_vault();
+    let root = vault.path();
+    let elsewhere = TempDir::new().unwrap();
+    let vault_arg = root.to_str().unwrap();
+    fs::write(root.join("domains/proj/a.md"), "a\n").unwrap();
+
+    let output = gnu(elsewhere.path(), &["--vault", vault_arg, "commit", "Add a"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(read_log(root, "main").lines().count(), 2);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_gnu"))
+        .args(["log", "--oneline"])
+        .current_dir(elsewhere.path())
+        .env("GITNU_DIR", root)
+        .output()
+        .unwrap();
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Add a"), "{:?}", output);
+
+    // A directory without .gitnu is rejected rather than searched upward from
+    let output = gnu(root, &["--vault", elsewhere.path().to_str().unwrap(), "status"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("No gitnu vault at"), "{:?}", output);
+
+    let output = gnu(root, &["--vault", elsewhere.path().to_str().unwrap(), "init", "--name", "other"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(elsewhere.path().join(".gitnu").is_dir());
+
+    // --vault wins over GITNU_DIR
+    let output = Command::new(env!("CARGO_BIN_EXE_gnu"))
+        .args(["--vault", vault_arg, "log", "--oneline"])
+        .env("GITNU_DIR", elsewhere.path())
+        .output()
+        .unwrap();
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Add a"), "{:?}", output);
+}
+
+#[test]
+fn test_context_template_fills_placeholders() {
+    let vault = init_vault();
+    let root = vault.path();
+    fs::write(root.join("domains/proj/notes.md"), "Use redis for caching\n").unwrap();
+    assert!(gnu(root, &["load", "domains/proj/notes.md"]).status.success());
+    assert!(gnu(root, &["commit", "Add notes"]).status.success());
+
+    let template = root.join("prompt.md");
+    fs::write(&template, "You are on {{branch}} at {{commit}}.\n{{summary}}\n<context>\n{{context}}\n</context>\n").unwrap();
+    let output = gnu(root, &["context", "--template", "prompt.md"]);
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.starts_with("You are on main at "), "{}", stdout);
+    assert!(stdout.contains("- Last commit: "), "{}", stdout);
+    assert!(stdout.contains("<context>\n"), "{}", stdout);
+    assert!(stdout.contains("Use redis for caching"), "{}", stdout);
+    assert!(!stdout.contains("{{"), "{}", stdout);
+
+    fs::write(&template, "No placeholder here\n").unwrap();
+    let output = gnu(root, &["context", "--template", "prompt.md"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("{{context}}"), "{:?}", output);
+}
+
+#[test]
+fn test_commits_record_line_stats() {
+    let vault = init_vault();
+    let root = vault.path();
+    fs::write(root.join("domains/proj/a.md"), "one\ntwo\nthree\n").unwrap();
+    assert!(gnu(root, &["commit", "Add a"]).status.success());
+    fs::write(root.join("domains/proj/a.md"), "one\n2\nthree\nfour\n").unwrap();
+    assert!(gnu(root, &["commit", "Edit a"]).status.success());
+
+    let last: serde_json::Value = serde_json::from_str(read_log(root, "main").lines().last().unwrap()).unwrap();
+    let stats = &last["context_summary"]["file_stats"];
+    assert_eq!(stats[0]["path"], "domains/proj/a.md");
+    assert_eq!(stats[0]["added"], 2);
+    assert_eq!(stats[0]["removed"], 1);
+
+    let output = gnu(root, &["show", "HEAD", "--stat"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("1 file changed, 2 insertions(+), 1 deletion(-)"), "{}", stdout);
+
+    let output = gnu(root, &["log", "--shortstat"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Lines: +2 -1"), "{}", stdout);
+    assert!(stdout.contains("insertions (+)"), "{}", stdout);
+}
+
+#[test]
+fn test_rewind_to_date_picks_last_commit_before_it() {
+    let vault = init_vault();
+    let root = vault.path();
+    let notes = root.join("domains/proj/notes.md");
+    for (i, text) in ["monday\n", "wednesday\n", "friday\n"].iter().enumerate() {
+        fs::write(&notes, text).unwrap();
+        assert!(gnu(root, &["commit", &format!("Day {}", i)]).status.success());
+    }
+
+    // Backdate the initial commit and the three days
+    let log_path = root.join(".gitnu/commits/main.jsonl");
+    let mut lines: Vec<serde_json::Value> = read_log(root, "main")
+        .lines()
+        .map(|l| serde_json::from_str(l).unwrap())
+        .collect();
+    let dates = ["2023-12-31", "2024-01-01", "2024-01-03", "2024-01-05"];
+    assert_eq!(lines.len(), dates.len());
+    for (commit, date) in lines.iter_mut().zip(dates) {
+        commit["timestamp"] = serde_json::json!(format!("{}T10:00:00Z", date));
+    }
+    let rewritten: Vec<String> = lines.iter().map(|c| c.to_string()).collect();
+    fs::write(&log_path, rewritten.join("\n") + "\n").unwrap();
+
+    let output = gnu(root, &["rewind", "--to-date", "2024-01-04"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("\"Day 1\" from 2024-01-03 10:00"), "{:?}", output);
+    assert_eq!(fs::read_to_string(&notes).unwrap(), "wednesday\n");
+
+    let output = gnu(root, &["rewind", "--to-date", "2024-01-03T09:59"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(fs::read_to_string(&notes).unwrap(), "monday\n");
+
+    let output = gnu(root, &["checkout", "--to-date", "2023-12-01"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("No commit on 'main' at or before 2023-12-01"), "{:?}", output);
+}
+
+#[test]
+fn test_summary_reports_pending_change_size() {
+    let vault = init_vault();
+    let root = vault.path();
+    let output = gnu(root, &["summary"]);
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("Pending:"));
+
+    fs::write(root.join("domains/proj/a.md"), "one\ntwo\n").unwrap();
+    let output = gnu(root, &["summary"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("- Pending: 0 modified, 1 added, 0 removed (+2/-0 lines), +2 tokens since last commit"), "{}", stdout);
+
+    let output = gnu(root, &["summary", "--json"]);
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["pending"]["added"], 1);
+    assert_eq!(report["pending"]["lines_added"], 2);
+}
+
+#[test]
+fn test_context_outline_lists_headings_only() {
+    let vault = init_vault();
+    let root = vault.path();
+    fs::write(
+        root.join("domains/proj/spec.md"),
+        "# Spec\nIntro text\n## API\nDetails\n```sh\n# not a heading\n```\n### Errors\n#hashtag\n",
+    )
+    .unwrap();
+    fs::write(root.join("domains/proj/other.md"), "# Other\n").unwrap();
+    assert!(gnu(root, &["load", "domains/proj/spec.md"]).status.success());
+
+    let output = gnu(root, &["context", "--outline"]);
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("## domains/proj/spec.md\n- Spec\n  - API\n    - Errors\n"), "{}", stdout);
+    assert!(!stdout.contains("Intro text"), "{}", stdout);
+    assert!(!stdout.contains("not a heading"), "{}", stdout);
+    assert!(!stdout.contains("hashtag"), "{}", stdout);
+    // Only the loaded file
+    assert!(!stdout.contains("other.md"), "{}", stdout);
+}
+
+#[test]
+fn test_log_branches_interleaves_history() {
+    let vault = init_vault();
+    let root = vault.path();
+    let commit = |message: &str| {
+        let output = gnu(root, &["commit", message]);
+        assert!(output.status.success(), "commit failed: {:?}", output);
+    };
+
+    assert!(gnu(root, &["branch", "explore"]).status.success());
+    assert!(gnu(root, &["checkout", "explore"]).status.success());
+    fs::write(root.join("domains/proj/a.md"), "a\n").unwrap();
+    commit("Explore idea");
+    assert!(gnu(root, &["checkout", "main"]).status.success());
+    fs::write(root.join("domains/proj/b.md"), "b\n").unwrap();
+    commit("Main work");
+
+    let output = gnu(root, &["log", "--branches", "--oneline"]);
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert!(lines[0].contains(" main    (HEAD -> main) Main work"), "{}", stdout);
+    assert!(lines[1].contains(" explore Explore idea"), "{}", stdout);
+
+    // Deleted branches only show up with --all
+    assert!(gnu(root, &["branch", "-d", "explore"]).status.success());
+    let stdout = String::from_utf8_lossy(&gnu(root, &["log", "--branches", "--oneline"]).stdout).to_string();
+    assert!(!stdout.contains("Explore idea"), "{}", stdout);
+    let stdout = String::from_utf8_lossy(&gnu(root, &["log", "--all", "--oneline"]).stdout).to_string();
+    assert!(stdout.contains("Explore idea"), "{}", stdout);
+}
+
+#[test]
+fn test_config_user_identity_is_used_for_human_commits() {
+    let vault = init_vault();
+    let root = vault.path();
+    assert!(gnu(root, &["config", "set", "user.name", "Ada Lovelace"]).status.success());
+    assert!(gnu(root, &["config", "set", "user.email", "ada@example.com"]).status.success());
+    let output = gnu(root, &["config", "get", "user.name"]);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "Ada Lovelace\n");
+
+    // Typos and wrongly typed values are rejected
+    assert!(!gnu(root, &["config", "set", "user.nmae", "x"]).status.success());
+    assert!(!gnu(root, &["config", "set", "context.max_tokens", "lots"]).status.success());
+    assert!(gnu(root, &["config", "set", "context.max_tokens", "5000"]).status.success());
+    assert!(fs::read_to_string(root.join(".gitnu/config.toml")).unwrap().contains("max_tokens = 5000"));
+
+    fs::write(root.join("domains/proj/a.md"), "a\n").unwrap();
+    assert!(gnu(root, &["commit", "Add a", "--author", "human"]).status.success());
+    let last: serde_json::Value = serde_json::from_str(read_log(root, "main").lines().last().unwrap()).unwrap();
+    assert_eq!(last["author"]["name"], "Ada Lovelace");
+    assert_eq!(last["author"]["email"], "ada@example.com");
+
+    let output = gnu(root, &["log", "--limit", "1"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Human (Ada Lovelace <ada@example.com>)"));
+}
+
+#[test]
+fn test_diff_staged_previews_context_growth() {
+    let vault = init_vault();
+    let root = vault.path();
+    fs::write(root.join("domains/proj/loaded.md"), "x".repeat(400)).unwrap();
+    fs::write(root.join("domains/proj/queued.md"), "y".repeat(200)).unwrap();
+    assert!(gnu(root, &["load", "domains/proj/loaded.md"]).status.success());
+    fs::create_dir_all(root.join("domains/pack")).unwrap();
+    fs::write(root.join("domains/pack/a.md"), "a".repeat(400)).unwrap();
+    fs::write(root.join("domains/pack/b.md"), "b".repeat(200)).unwrap();
+    assert!(gnu(root, &["load", "domains/pack/a.md"]).status.success());
+
+    let index_path = root.join(".gitnu/index.json");
+    let mut index: serde_json::Value = serde_json::from_str(&fs::read_to_string(&index_path).unwrap()).unwrap();
+    index["staged"] = serde_json::json!([
+        { "path": "domains/proj/queued.md", "reason": "API notes", "priority": "high" },
+        { "path": "domains/proj/loaded.md", "reason": "spec", "priority": "low" },
+        { "path": "domains/proj/gone.md", "reason": "old", "priority": "medium" },
+        { "path": "domains/pack", "reason": "all of it", "priority": "low" },
+        { "path": "domains/pack/b.md", "reason": "again", "priority": "low" },
+    ]);
+    fs::write(&index_path, index.to_string()).unwrap();
+
+    let output = gnu(root, &["diff", "--staged"]);
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("+ domains/proj/queued.md  [High] \"API notes\"  ~50 tokens"), "{}", stdout);
+    assert!(stdout.contains("= domains/proj/loaded.md  [Low] \"spec\"  ~100 tokens, already in context"), "{}", stdout);
+    assert!(stdout.contains("! domains/proj/gone.md    [Medium] \"old\"  missing"), "{}", stdout);
+    // Only the part of a directory not yet loaded counts, and only once
+    assert!(stdout.contains("\"all of it\"  ~50 tokens (1 file already in context)"), "{}", stdout);
+    assert!(stdout.contains("(+100) of"), "{}", stdout);
+}
+
+#[test]
+fn test_commit_trusts_mtime_unless_full() {
+    let vault = init_vault();
+    let root = vault.path();
+    let notes = root.join("domains/proj/notes.md");
+    fs::write(&notes, "alpha\n").unwrap();
+    assert!(gnu(root, &["commit", "Add notes"]).status.success());
+
+    // Same size and mtime: only a full re-hash notices the edit
+    let modified = fs::metadata(&notes).unwrap().modified().unwrap();
+    fs::write(&notes, "omega\n").unwrap();
+    fs::File::options().write(true).open(&notes).unwrap().set_modified(modified).unwrap();
+
+    let before = read_log(root, "main").lines().count();
+    gnu(root, &["commit", "Quick"]);
+    assert_eq!(read_log(root, "main").lines().count(), before);
+
+    assert!(gnu(root, &["commit", "--full", "Full"]).status.success());
+    let last: serde_json::Value = serde_json::from_str(read_log(root, "main").lines().last().unwrap()).unwrap();
+    assert_eq!(last["context_summary"]["files_modified"][0], "domains/proj/notes.md");
+}
+
+#[test]
+fn test_alias_expands_before_parsing() {
+    let vault = init_vault();
+    let root = vault.path();
+    assert!(gnu(root, &["alias", "ci", "commit --author human"]).status.success());
+    assert!(gnu(root, &["alias", "save", "ci", "--allow-empty"]).status.success());
+    assert!(!gnu(root, &["alias", "log", "status"]).status.success());
+
+    assert!(gnu(root, &["save", "Checkpoint"]).status.success());
+    let last: serde_json::Value = serde_json::from_str(read_log(root, "main").lines().last().unwrap()).unwrap();
+    assert_eq!(last["message"], "Checkpoint");
+    assert_eq!(last["author"]["type"], "human");
+
+    let output = gnu(root, &["alias"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("ci = commit --author human"), "{}", stdout);
+
+    // Loops are reported instead of expanding forever
+    set_config(root, "[alias]\n", "[alias]\nping = \"pong\"\npong = \"ping\"\n");
+    let output = gnu(root, &["ping"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("ping -> pong -> ping"));
+
+    assert!(gnu(root, &["alias", "--unset", "save"]).status.success());
+    assert!(!gnu(root, &["save", "Again"]).status.success());
+}
+
+#[test]
+fn test_exit_codes_distinguish_failures() {
+    let empty = TempDir::new().unwrap();
+    let output = gnu(empty.path(), &["status"]);
+    assert_eq!(output.status.code(), Some(2));
+
+    let vault = init_vault();
+    let root = vault.path();
+    assert_eq!(gnu(root, &["checkout", "nowhere"]).status.code(), Some(5));
+    assert_eq!(gnu(root, &["branch", "main"]).status.code(), Some(6));
+    let output = gnu(root, &["commit", "Nothing new"]);
+    assert_eq!(output.status.code(), Some(12));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("No changes to commit"), "{:?}", output);
+    assert_eq!(gnu(root, &["log", "--no-such-flag"]).status.code(), Some(64));
+    assert_eq!(gnu(root, &["--version"]).status.code(), Some(0));
+
+    let output = gnu(root, &["--json", "show", "deadbeef"]);
+    let error: serde_json::Value = serde_json::from_slice(&output.stderr).unwrap();
+    assert_eq!(error["exit_code"], output.status.code().unwrap());
+}
+
+#[test]
+fn test_json_errors_follow_the_parsed_flag() {
+    let vault = init_vault();
+    let root = vault.path();
+
+    // An argument that merely reads "--json" doesn't switch the format
+    let output = gnu(root, &["show", "--", "--json"]);
+    assert_eq!(output.status.code(), Some(5));
+    assert!(serde_json::from_slice::<serde_json::Value>(&output.stderr).is_err(), "{:?}", output);
+
+    let output = gnu(root, &["--json", "log", "--no-such-flag"]);
+    assert_eq!(output.status.code(), Some(64));
+    let error: serde_json::Value = serde_json::from_slice(&output.stderr).unwrap();
+    assert_eq!(error["error"], "usage");
+}
+
+#[test]
+fn test_context_budget_packs_by_strategy() {
+    let vault = init_vault();
+    let root = vault.path();
+    let proj = root.join("domains/pack");
+    fs::create_dir_all(&proj).unwrap();
+    let write_dated = |name: &str, bytes: usize, year: i32| {
+        let path = proj.join(name);
+        fs::write(&path, "x".repeat(bytes)).unwrap();
+        if year > 0 {
+            let secs = (year as u64 - 1970) * 365 * 24 * 3600;
+            let time = std::time::UNIX_EPOCH + std::time::Duration::from_secs(secs);
+            fs::File::options().write(true).open(&path).unwrap().set_modified(time).unwrap();
+        }
+    };
+    write_dated("old.md", 200, 2020);
+    write_dated("big.md", 2000, 2021);
+    write_dated("new.md", 200, 0);
+
+    let packed = |budget: &str, strategy: &str| -> serde_json::Value {
+        let args = ["--json", "context", "--include", "domains/pack/**", "--budget", budget, "--strategy", strategy];
+        let output = gnu(root, &args);
+        assert!(output.status.success(), "{:?}", output);
+        serde_json::from_slice(&output.stdout).unwrap()
+    };
+    let names = |list: &serde_json::Value| -> Vec<String> {
+        list.as_array().unwrap().iter().map(|p| p.as_str().unwrap().trim_start_matches("domains/").to_string()).collect()
+    };
+
+    // Recent skips what doesn't fit but keeps filling with smaller, older files
+    let report = packed("150", "recent");
+    assert_eq!(names(&report["files"]), ["pack/new.md", "pack/old.md"]);
+    assert!(names(&report["dropped"]).contains(&"pack/big.md".to_string()));
+    assert!(report["token_estimate"].as_u64().unwrap() <= 150);
+    assert_eq!(names(&packed("60", "recent")["files"]), ["pack/new.md"]);
+
+    // Staged priorities win over recency
+    let index_path = root.join(".gitnu/index.json");
+    let mut index: serde_json::Value = serde_json::from_str(&fs::read_to_string(&index_path).unwrap()).unwrap();
+    index["staged"] = serde_json::json!([{ "path": "domains/pack/old.md", "reason": "spec", "priority": "high" }]);
+    fs::write(&index_path, index.to_string()).unwrap();
+    assert_eq!(names(&packed("60", "priority")["files"]), ["pack/old.md"]);
+
+    // Pins are kept even past the budget
+    assert!(gnu(root, &["pin", "domains/pack/big.md"]).status.success());
+    let files = names(&packed("60", "pinned-first")["files"]);
+    assert!(files.contains(&"pack/big.md".to_string()), "{:?}", files);
+
+    let output = gnu(root, &["context", "--budget", "60"]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Packed"));
+    assert!(!gnu(root, &["context", "--budget", "60", "--strategy", "newest"]).status.success());
+}
+
+#[test]
+fn test_path_arguments_resolve_from_subdirectory() {
+    let vault = init_vault();
+    let root = vault.path();
+    let proj = root.join("domains/proj");
+    fs::write(proj.join("spec.md"), "# Spec\n").unwrap();
+    fs::write(proj.join("other.md"), "# Other\n").unwrap();
+
+    assert!(gnu(&proj, &["commit", "Add spec"]).status.success());
+    let output = gnu(&proj, &["load", "spec.md"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("domains/proj/spec.md"));
+    // Vault-relative paths still work when nothing matches from here
+    assert!(gnu(&proj, &["load", "domains/proj/other.md"]).status.success());
+
+    let index = fs::read_to_string(root.join(".gitnu/index.json")).unwrap();
+    assert!(index.contains("domains/proj/spec.md") && index.contains("domains/proj/other.md"), "{}", index);
+
+    let output = gnu(&proj, &["log", "--oneline", "spec.md"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Add spec"));
+
+    assert!(gnu(&proj, &["mv", "other.md", "renamed.md"]).status.success());
+    assert!(proj.join("renamed.md").exists());
+}
+
+#[test]
+fn test_log_format_template() {
+    let vault = init_vault();
+    let root = vault.path();
+    fs::write(root.join("domains/proj/notes.md"), "hello\n").unwrap();
+    assert!(gnu(root, &["commit", "--author", "human", "Add notes"]).status.success());
+
+    let output = gnu(root, &["log", "-l", "1", "--format", "%h|%an|%s|%t tokens|%d|100%%|%x"]);
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let last: serde_json::Value = serde_json::from_str(read_log(root, "main").lines().last().unwrap()).unwrap();
+    let expected = format!(
+        "{}|{}|Add notes|{} tokens|{}|100%|%x\n",
+        &last["hash"].as_str().unwrap()[..7],
+        last["author"]["name"].as_str().unwrap(),
+        last["context_summary"]["token_estimate"],
+        last["context_summary"]["domains_loaded"].as_array().unwrap().len()
+    );
+    assert_eq!(stdout, expected);
+
+    let output = gnu(root, &["log", "--format", "%H%n%tr"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout.lines().nth(1), Some("just now"), "{}", stdout);
+}
+
+#[test]
+fn test_context_diff_only_emits_changes() {
+    let vault = init_vault();
+    let root = vault.path();
+    let proj = root.join("domains/proj");
+    fs::write(proj.join("notes.md"), "one\ntwo\nthree\n").unwrap();
+    fs::write(proj.join("old.md"), "gone soon\n").unwrap();
+    assert!(gnu(root, &["commit", "Add notes"]).status.success());
+
+    fs::write(proj.join("notes.md"), "one\n2\nthree\n").unwrap();
+    fs::write(proj.join("fresh.md"), "brand new\n").unwrap();
+    fs::remove_file(proj.join("old.md")).unwrap();
+
+    let output = gnu(root, &["context", "--diff-only"]);
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("# Changes since"), "{}", stdout);
+    assert!(stdout.contains("## Modified: domains/proj/notes.md"), "{}", stdout);
+    assert!(stdout.contains("-two\n+2\n"), "{}", stdout);
+    assert!(stdout.contains("## Added: domains/proj/fresh.md"), "{}", stdout);
+    assert!(stdout.contains("+brand new"), "{}", stdout);
+    assert!(stdout.contains("## Removed: domains/proj/old.md"), "{}", stdout);
+    // Unchanged files aren't rendered at all
+    assert!(!stdout.contains("# File:"), "{}", stdout);
+
+    let output = gnu(root, &["--json", "context", "--diff-only", "--exclude", "**/old.md"]);
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["files"].as_array().unwrap().len(), 2);
+
+    let template = root.join("prompt.txt");
+    fs::write(&template, "Review on {{branch}}:\n{{context}}").unwrap();
+    let output = gnu(root, &["context", "--diff-only", "--template", template.to_str().unwrap()]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.starts_with("Review on main:\n# Changes since"), "{}", stdout);
+    assert_eq!(gnu(root, &["context", "--diff-only", "--compress"]).status.code(), Some(64));
+}
+
+#[test]
+fn test_read_only_vault_refuses_changes() {
+    let vault = init_vault();
+    let root = vault.path();
+    assert!(gnu(root, &["branch", "explore"]).status.success());
+    fs::write(root.join("domains/proj/notes.md"), "hello\n").unwrap();
+    assert!(gnu(root, &["config", "set", "core.read_only", "true"]).status.success());
+
+    for args in [
+        vec!["commit", "Blocked"],
+        vec!["branch", "explore"],
+        vec!["load", "domains/proj/notes.md"],
+        vec!["pin", "domains/proj/notes.md"],
+    ] {
+        let output = gnu(root, &args);
+        assert_eq!(output.status.code(), Some(11), "{:?}", args);
+        assert!(String::from_utf8_lossy(&output.stderr).contains("read-only"));
+    }
+    assert_eq!(read_log(root, "main").lines().count(), 1);
+
+    for args in [
+        vec!["log"],
+        vec!["status"],
+        vec!["context"],
+        vec!["summary"],
+        vec!["diff"],
+        vec!["unload", "--list"],
+        vec!["checkout", "explore", "--force", "--dry-run"],
+        vec!["merge", "explore", "--dry-run"],
+        vec!["rewind", "HEAD", "--dry-run"],
+    ] {
+        let output = gnu(root, &args);
+        assert!(output.status.success(), "{:?}: {:?}", args, output);
+    }
+
+    assert!(gnu(root, &["config", "set", "core.read_only", "false"]).status.success());
+    assert!(gnu(root, &["commit", "Allowed"]).status.success());
+}
+
+#[test]
+fn test_diff_detects_renames() {
+    let vault = init_vault();
+    let root = vault.path();
+    let notes = root.join("domains/notes");
+    fs::create_dir_all(&notes).unwrap();
+    fs::write(notes.join("a.md"), "alpha\nbeta\ngamma\ndelta\n").unwrap();
+    fs::write(notes.join("b.md"), "one\ntwo\nthree\nfour\n").unwrap();
+    assert!(gnu(root, &["commit", "Add notes"]).status.success());
+
+    fs::rename(notes.join("a.md"), notes.join("moved.md")).unwrap();
+    fs::remove_file(notes.join("b.md")).unwrap();
+    fs::write(notes.join("edited.md"), "one\ntwo\nthree\n4\n").unwrap();
+
+    let output = gnu(root, &["diff"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("R Renamed: domains/notes/a.md -> domains/notes/moved.md\n"), "{}", stdout);
+    assert!(stdout.contains("R Renamed: domains/notes/b.md -> domains/notes/edited.md (75% similar)"), "{}", stdout);
+    assert!(!stdout.contains("Removed"), "{}", stdout);
+
+    // Exact-only matching leaves the edited file as a remove and an add
+    let output = gnu(root, &["diff", "--rename-threshold", "100"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("- Removed: domains/notes/b.md"), "{}", stdout);
+    assert!(stdout.contains("+ Added file: domains/notes/edited.md"), "{}", stdout);
+
+    assert!(gnu(root, &["commit", "Move notes"]).status.success());
+    let log = read_log(root, "main");
+    let hashes: Vec<String> = log.lines()
+        .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap()["hash"].as_str().unwrap().to_string())
+        .collect();
+    let (before, after) = (&hashes[hashes.len() - 2], &hashes[hashes.len() - 1]);
+    let output = gnu(root, &["diff", before, after, "--format", "patch"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("similarity index 100%\nrename from domains/notes/a.md\nrename to domains/notes/moved.md\n"), "{}", stdout);
+    assert!(stdout.contains("-four\n+4\n"), "{}", stdout);
+}
+
+#[test]
+fn test_diff_output_captures_everything_printed() {
+    let vault = init_vault();
+    let root = vault.path();
+    fs::write(root.join("domains/proj/notes.md"), "one\n").unwrap();
+    assert!(gnu(root, &["commit", "Add notes"]).status.success());
+    fs::write(root.join("domains/proj/notes.md"), "one\ntwo\n").unwrap();
+
+    let patch = root.join("notes.patch");
+    let output = gnu(root, &["diff", "--format", "patch", "--output", patch.to_str().unwrap()]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(output.stdout.is_empty(), "{:?}", output);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Wrote diff to"), "{:?}", output);
+    let written = fs::read_to_string(&patch).unwrap();
+    assert!(written.contains("+++ b/domains/proj/notes.md\n"), "{}", written);
+    assert!(written.contains("+two\n"), "{}", written);
+
+    // Staged previews go to the file too
+    let index_path = root.join(".gitnu/index.json");
+    let mut index: serde_json::Value = serde_json::from_str(&fs::read_to_string(&index_path).unwrap()).unwrap();
+    index["staged"] = serde_json::json!([{ "path": "domains/proj/notes.md", "reason": "review", "priority": "high" }]);
+    fs::write(&index_path, index.to_string()).unwrap();
+    let staged = root.join("staged.txt");
+    let output = gnu(root, &["diff", "--staged", "--output", staged.to_str().unwrap()]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(output.stdout.is_empty(), "{:?}", output);
+    assert!(fs::read_to_string(&staged).unwrap().contains("Staged (1):"));
+}
+
+#[test]
+fn test_diff_caps_inexact_rename_detection() {
+    let vault = init_vault();
+    let root = vault.path();
+    let notes = root.join("domains/notes");
+    fs::create_dir_all(&notes).unwrap();
+    for i in 0..102 {
+        fs::write(notes.join(format!("old{}.md", i)), format!("note {}\nline\n", i)).unwrap();
+    }
+    assert!(gnu(root, &["commit", "Add notes"]).status.success());
+
+    for i in 0..102 {
+        fs::remove_file(notes.join(format!("old{}.md", i))).unwrap();
+        fs::write(notes.join(format!("new{}.md", i)), format!("note {}\nline\nmore\n", i)).unwrap();
+    }
+    fs::write(notes.join("new0.md"), "note 0\nline\n").unwrap();
+
+    let output = gnu(root, &["diff"]);
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Skipped inexact rename detection"), "{}", stderr);
+    // Exact renames are still found past the limit
+    assert!(stdout.contains("R Renamed: domains/notes/old0.md -> domains/notes/new0.md\n"), "{}", stdout);
+    assert!(stdout.contains("- Removed: domains/notes/old1.md"), "{}", stdout);
+}
+
+#[test]
+fn test_info_reports_vault_metadata() {
+    let vault = init_vault();
+    let root = vault.path();
+    fs::create_dir_all(root.join("domains/research")).unwrap();
+
+    let output = gnu(root, &["--json", "info"]);
+    assert!(output.status.success(), "{:?}", output);
+    let info: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(info["branch"], "main");
+    assert_eq!(info["total_commits"], 1);
+    assert_eq!(info["domains"], 2, "_global isn't counted");
+    assert_eq!(info["head"].as_str().unwrap().len(), 64);
+    assert!(info["max_tokens"].as_u64().unwrap() > 0);
+
+    // Works from anywhere inside the vault
+    let output = gnu(&root.join("domains/research"), &["info"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Branch:         main"), "{}", stdout);
+    assert!(stdout.contains("Commits:        1"), "{}", stdout);
+}
+
+#[test]
+fn test_objects_dir_config_moves_snapshot_store() {
+    let vault = init_vault();
+    let root = vault.path();
+    let store = tempfile::tempdir().unwrap();
+    let objects = store.path().join("objects");
+
+    let output = gnu(root, &["config", "set", "core.objects_dir", objects.to_str().unwrap()]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(objects.is_dir());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Existing snapshots are still in"), "{}", stdout);
+    fs::remove_dir(&objects).unwrap();
+    fs::rename(root.join(".gitnu/objects"), &objects).unwrap();
+
+    fs::write(root.join("domains/proj/notes.md"), "stored elsewhere\n").unwrap();
+    assert!(gnu(root, &["commit", "Add notes"]).status.success());
+    assert!(objects.join("trees").is_dir());
+    let log = read_log(root, "main");
+    let head: serde_json::Value = serde_json::from_str(log.lines().last().unwrap()).unwrap();
+    assert!(objects.join(head["hash"].as_str().unwrap()).join("manifest.json").exists());
+
+    let output = gnu(root, &["show", head["hash"].as_str().unwrap()]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("+stored elsewhere"));
+
+    // A store that can't be created is rejected before the config changes
+    fs::write(store.path().join("file"), "").unwrap();
+    let output = gnu(root, &["config", "set", "core.objects_dir", store.path().join("file/objects").to_str().unwrap()]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("not writable"));
+}
+
+#[test]
+fn test_commit_date_backfills_history() {
+    let vault = init_vault();
+    let root = vault.path();
+    let proj = root.join("domains/proj");
+    fs::write(proj.join("journal.md"), "day one\n").unwrap();
+    assert!(gnu(root, &["commit", "--date", "2021-05-01", "First entry"]).status.success());
+    fs::write(proj.join("journal.md"), "day one\nday two\n").unwrap();
+    let output = gnu(root, &["commit", "--author-date", "2021-05-02T08:15:00+02:00", "Second entry"]);
+    assert!(output.status.success(), "{:?}", output);
+
+    let last: serde_json::Value = serde_json::from_str(read_log(root, "main").lines().last().unwrap()).unwrap();
+    assert_eq!(last["timestamp"], "2021-05-02T06:15:00Z");
+
+    let output = gnu(root, &["log", "--until", "2021-12-31", "--format", "%s"]);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "Second entry\nFirst entry\n");
+
+    let output = gnu(root, &["commit", "--allow-empty", "--date", "yesterday", "Bad"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Invalid date"));
+}
+
+#[test]
+fn test_branch_merged_filters() {
+    let vault = init_vault();
+    let root = vault.path();
+    let notes = root.join("domains/proj/notes.md");
+    assert!(gnu(root, &["branch", "stale"]).status.success());
+    assert!(gnu(root, &["branch", "explore"]).status.success());
+    assert!(gnu(root, &["checkout", "explore"]).status.success());
+    fs::write(&notes, "explored\n").unwrap();
+    assert!(gnu(root, &["commit", "Explore"]).status.success());
+    assert!(gnu(root, &["checkout", "main"]).status.success());
+
+    let names = |args: &[&str]| -> Vec<String> {
+        let output = gnu(root, args);
+        assert!(output.status.success(), "{:?}", output);
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter_map(|line| line.trim_start_matches(['*', ' ']).split_whitespace().next().map(str::to_string))
+            .collect()
+    };
+    assert_eq!(names(&["branch", "--merged"]), ["main", "stale"]);
+    assert_eq!(names(&["branch", "--no-merged"]), ["explore"]);
+    assert_eq!(names(&["branch", "--merged", "explore"]), ["explore", "main", "stale"]);
+
+    fs::write(&notes, "main moved on\n").unwrap();
+    assert!(gnu(root, &["commit", "Main work"]).status.success());
+    let output = gnu(root, &["merge", "explore"]);
+    assert!(!output.status.success());
+    assert!(gnu(root, &["mergetool", "--theirs"]).status.success());
+    assert!(gnu(root, &["commit", "Merge explore"]).status.success());
+    // The merge's second parent makes explore reachable
+    assert_eq!(names(&["branch", "--merged"]), ["explore", "main", "stale"]);
+    assert_eq!(names(&["branch", "--no-merged"]), ["No"]);
+}
+
+#[test]
+fn test_commit_refuses_conflict_markers() {
+    let vault = init_vault();
+    let root = vault.path();
+    let notes = root.join("domains/proj/notes.md");
+    fs::write(&notes, "intro\n<<<<<<< main\nours\n=======\ntheirs\n>>>>>>> explore\n").unwrap();
+
+    let output = gnu(root, &["commit", "Half resolved"]);
+    assert_eq!(output.status.code(), Some(4));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Conflict markers left in: domains/proj/notes.md"), "{}", stderr);
+    assert_eq!(read_log(root, "main").lines().count(), 1);
+
+    let output = gnu(root, &["commit", "--allow-conflict-markers", "Keep both on purpose"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(read_log(root, "main").lines().count(), 2);
+}
+
+#[test]
+fn test_context_group_by_domain() {
+    let vault = init_vault();
+    let root = vault.path();
+    for (domain, file) in [("alpha", "a.md"), ("beta", "b.md"), ("zeta", "z.md")] {
+        fs::create_dir_all(root.join("domains").join(domain)).unwrap();
+        fs::write(root.join("domains").join(domain).join(file), format!("# {}\n", domain)).unwrap();
+    }
+    let config_path = root.join(".gitnu/config.toml");
+    let config = fs::read_to_string(&config_path).unwrap();
+    let always_load = config.lines().find(|l| l.starts_with("always_load")).unwrap();
+    fs::write(&config_path, config.replace(always_load, r#"always_load = ["domains/zeta/**"]"#)).unwrap();
+
+    let output = gnu(root, &["context", "--group-by", "domain", "--include", "domains/[abz]*/**"]);
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let headers: Vec<&str> = stdout.lines().filter(|l| l.starts_with("## Domain:")).collect();
+    assert_eq!(headers, ["## Domain: zeta", "## Domain: alpha", "## Domain: beta"], "{}", stdout);
+    assert!(stdout.contains("## Domain: alpha\n\n# File: domains/alpha/a.md"), "{}", stdout);
+
+    let output = gnu(root, &["context", "--group-by", "domain", "--outline", "--include", "domains/alpha/**"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.starts_with("# Outline\n"), "{}", stdout);
+    assert_eq!(stdout.matches("# Outline").count(), 1, "{}", stdout);
+    assert!(stdout.contains("## Domain: alpha\n\n## domains/alpha/a.md\n- alpha"), "{}", stdout);
+
+    assert!(!gnu(root, &["context", "--group-by", "project"]).status.success());
+}
+
+#[test]
+fn test_stats_tokens_shows_trend() {
+    let vault = init_vault();
+    let root = vault.path();
+    let notes = root.join("domains/proj/notes.md");
+    fs::write(&notes, "x".repeat(400)).unwrap();
+    assert!(gnu(root, &["commit", "Grow"]).status.success());
+    fs::write(&notes, "x".repeat(40)).unwrap();
+    assert!(gnu(root, &["commit", "Shrink"]).status.success());
+
+    let output = gnu(root, &["--json", "stats", "--tokens"]);
+    assert!(output.status.success(), "{:?}", output);
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let commits = report["commits"].as_array().unwrap();
+    assert_eq!(commits.len(), 3);
+    assert_eq!(commits[1]["subject"], "Grow");
+    assert_eq!(commits[0]["delta"], commits[0]["tokens"]);
+    let tokens: Vec<i64> = commits.iter().map(|c| c["tokens"].as_i64().unwrap()).collect();
+    assert_eq!(commits[1]["delta"].as_i64().unwrap(), tokens[1] - tokens[0]);
+    assert_eq!(commits[2]["delta"].as_i64().unwrap(), -90);
+
+    let output = gnu(root, &["stats", "--tokens"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.lines().nth(1).unwrap().contains('█'), "{}", stdout);
+    assert!(stdout.contains("-90  Shrink"), "{}", stdout);
+}
+
+#[test]
+fn test_status_separates_touched_from_modified() {
+    let vault = init_vault();
+    let root = vault.path();
+    let notes = root.join("domains/proj/notes.md");
+    let plan = root.join("domains/proj/plan.md");
+    fs::write(&notes, "alpha\n").unwrap();
+    fs::write(&plan, "first\n").unwrap();
+    assert!(gnu(root, &["commit", "Add notes"]).status.success());
+
+    // Re-saving with the same content only moves the mtime
+    let later = fs::metadata(&notes).unwrap().modified().unwrap() + std::time::Duration::from_secs(60);
+    fs::write(&notes, "alpha\n").unwrap();
+    fs::File::options().write(true).open(&notes).unwrap().set_modified(later).unwrap();
+    fs::write(&plan, "second\n").unwrap();
+
+    let output = gnu(root, &["status"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let modified = stdout.split("Modified since last commit:").nth(1).unwrap();
+    assert!(modified.contains("plan.md"), "{}", stdout);
+    assert!(!modified.contains("notes.md"), "{}", stdout);
+    assert!(stdout.contains("1 file saved since the last commit with identical content"), "{}", stdout);
+
+    let output = gnu(root, &["status", "--include-unchanged"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Saved but unchanged (not modified):"), "{}", stdout);
+    assert!(stdout.contains("domains/proj/notes.md"), "{}", stdout);
+}
+
+#[test]
+fn test_revert_undoes_one_commit() {
+    let vault = init_vault();
+    let root = vault.path();
+    let notes = root.join("domains/proj/notes.md");
+    fs::write(&notes, "one\ntwo\nthree\nfour\nfive\n").unwrap();
+    assert!(gnu(root, &["commit", "Add notes"]).status.success());
+    fs::write(&notes, "one\nTWO\nthree\nfour\nfive\n").unwrap();
+    assert!(gnu(root, &["commit", "Shout two"]).status.success());
+    let shout = serde_json::from_str::<serde_json::Value>(read_log(root, "main").lines().last().unwrap()).unwrap();
+    let shout = shout["hash"].as_str().unwrap().to_string();
+    fs::write(&notes, "one\nTWO\nthree\nfour\nFIVE\n").unwrap();
+    assert!(gnu(root, &["commit", "Shout five"]).status.success());
+
+    // Later edits to other lines survive the revert
+    let output = gnu(root, &["revert", &shout]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(fs::read_to_string(&notes).unwrap(), "one\ntwo\nthree\nfour\nFIVE\n");
+    let last: serde_json::Value = serde_json::from_str(read_log(root, "main").lines().last().unwrap()).unwrap();
+    assert_eq!(last["message"], format!("Revert \"Shout two\"\n\nThis reverts commit {}.", shout));
+    assert_eq!(read_log(root, "main").lines().count(), 5);
+
+    // The same lines changed again: stop with conflict markers
+    fs::write(&notes, "one\ntwo\nthree\nfour\nfinal\n").unwrap();
+    assert!(gnu(root, &["commit", "Settle five"]).status.success());
+    let five = serde_json::from_str::<serde_json::Value>(read_log(root, "main").lines().nth(3).unwrap()).unwrap();
+    assert_eq!(five["message"], "Shout five");
+    let output = gnu(root, &["revert", "HEAD~2"]);
+    assert_eq!(output.status.code(), Some(4), "{:?}", output);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Revert stopped with 1 conflicted file"), "{}", stderr);
+    let content = fs::read_to_string(&notes).unwrap();
+    assert!(content.contains("<<<<<<< main\nfinal\n=======\nfive\n>>>>>>> revert "), "{}", content);
+
+    assert!(gnu(root, &["mergetool", "--theirs"]).status.success());
+    assert!(gnu(root, &["commit", "Revert five"]).status.success());
+    assert_eq!(fs::read_to_string(&notes).unwrap(), "one\ntwo\nthree\nfour\nfive\n");
+    assert_eq!(read_log(root, "main").lines().count(), 7);
+
+    assert!(gnu(root, &["revert", "HEAD"]).status.success());
+    assert_eq!(fs::read_to_string(&notes).unwrap(), "one\ntwo\nthree\nfour\nfinal\n");
+}
+
+#[test]
+fn test_is_vault_exit_status() {
+    let vault = init_vault();
+    let root = vault.path();
+    fs::create_dir_all(root.join("domains/proj/deep")).unwrap();
+
+    let output = gnu(&root.join("domains/proj/deep"), &["is-vault"]);
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty() && output.stderr.is_empty(), "{:?}", output);
+
+    let output = gnu(&root.join("domains/proj/deep"), &["is-vault", "--print"]);
+    let printed = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(fs::canonicalize(printed.trim()).unwrap(), fs::canonicalize(root).unwrap());
+
+    let outside = TempDir::new().unwrap();
+    let output = gnu(outside.path(), &["is-vault", "--print"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(output.stdout.is_empty() && output.stderr.is_empty(), "{:?}", output);
+}
+
+#[test]
+fn test_commit_date_on_two_branches_keeps_content_apart() {
+    let vault = init_vault();
+    let root = vault.path();
+    let notes = root.join("domains/proj/notes.md");
+    assert!(gnu(root, &["branch", "other"]).status.success());
+
+    // Same parent, message and date; only the content differs
+    fs::write(&notes, "main\n").unwrap();
+    assert!(gnu(root, &["commit", "--date", "2021-05-01", "Import"]).status.success());
+    assert!(gnu(root, &["checkout", "other"]).status.success());
+    fs::write(&notes, "other\n").unwrap();
+    assert!(gnu(root, &["commit", "--date", "2021-05-01", "Import"]).status.success());
+
+    let head = |branch: &str| fs::read_to_string(root.join(".gitnu/refs/heads").join(branch)).unwrap();
+    assert_ne!(head("main"), head("other"));
+
+    assert!(gnu(root, &["checkout", "main"]).status.success());
+    assert_eq!(fs::read_to_string(&notes).unwrap(), "main\n");
+    assert!(gnu(root, &["checkout", "other"]).status.success());
+    assert_eq!(fs::read_to_string(&notes).unwrap(), "other\n");
+}
+
+#[test]
+fn test_undo_after_gc_still_has_its_snapshot() {
+    let vault = init_vault();
+    let root = vault.path();
+    let hash_of = |line: &str| -> String {
+        serde_json::from_str::<serde_json::Value>(line).unwrap()["hash"].as_str().unwrap().to_string()
+    };
+    let initial = hash_of(read_log(root, "main").lines().next().unwrap());
+    let notes = root.join("domains/proj/notes.md");
+    fs::write(&notes, "kept\n").unwrap();
+    assert!(gnu(root, &["commit", "Add notes"]).status.success());
+
+    // The rewound commit is only reachable through the reflog
+    assert!(gnu(root, &["rewind", &initial]).status.success());
+    set_config(root, "keep_last = 10", "keep_last = 0");
+    set_config(root, "keep_days = 30", "keep_days = 0");
+    assert!(gnu(root, &["gc"]).status.success());
+
+    let output = gnu(root, &["undo"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(fs::read_to_string(&notes).unwrap(), "kept\n");
+
+    let output = gnu(root, &["fsck", "--unreachable"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("no unreachable objects"), "{:?}", output);
+}
+
+#[test]
+fn test_undo_with_missing_snapshot_leaves_branch_alone() {
+    let vault = init_vault();
+    let root = vault.path();
+    let initial = serde_json::from_str::<serde_json::Value>(read_log(root, "main").lines().next().unwrap()).unwrap();
+    let initial = initial["hash"].as_str().unwrap().to_string();
+    fs::write(root.join("domains/proj/notes.md"), "notes\n").unwrap();
+    assert!(gnu(root, &["commit", "Add notes"]).status.success());
+    let head = fs::read_to_string(root.join(".gitnu/refs/heads/main")).unwrap();
+
+    fs::remove_dir_all(root.join(".gitnu/objects").join(&initial)).unwrap();
+    let output = gnu(root, &["undo"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("is missing"), "{:?}", output);
+    assert_eq!(fs::read_to_string(root.join(".gitnu/refs/heads/main")).unwrap(), head);
+    assert!(root.join("domains/proj/notes.md").exists());
+}
+
+#[test]
+fn test_auto_commit_waits_for_a_valid_target() {
+    let vault = init_vault();
+    let root = vault.path();
+    set_config(root, "auto_commit = false", "auto_commit = true");
+    fs::write(root.join("domains/proj/notes.md"), "notes\n").unwrap();
+
+    assert!(!gnu(root, &["rewind", "nosuchcommit"]).status.success());
+    assert!(!gnu(root, &["merge", "nosuchbranch"]).status.success());
+    assert_eq!(read_log(root, "main").lines().count(), 1);
+}
+
+#[test]
+fn test_import_round_trip_and_into_traversal() {
+    let vault = init_vault();
+    let root = vault.path();
+    fs::write(root.join("domains/proj/notes.md"), "notes\n").unwrap();
+    fs::write(root.join("domains/proj/diagram.png"), [0x89, b'P', b'N', b'G', 0, 0xff]).unwrap();
+    assert!(gnu(root, &["commit", "Add notes"]).status.success());
+    let bundle = root.join("bundle.json");
+    let output = gnu(root, &["export", "--format", "json", "--output", bundle.to_str().unwrap()]);
+    assert!(output.status.success(), "{:?}", output);
+
+    let output = gnu(root, &["import", bundle.to_str().unwrap(), "--into", "copy"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(fs::read_to_string(root.join("domains/copy/notes.md")).unwrap(), "notes\n");
+    assert_eq!(fs::read(root.join("domains/copy/diagram.png")).unwrap(), [0x89, b'P', b'N', b'G', 0, 0xff]);
+
+    for into in ["../../escaped", "/tmp/escaped", "copy/../../escaped"] {
+        let output = gnu(root, &["import", bundle.to_str().unwrap(), "--into", into]);
+        assert!(!output.status.success(), "{} was accepted", into);
+        assert!(String::from_utf8_lossy(&output.stderr).contains("Invalid --into"), "{:?}", output);
+    }
+    assert!(!root.join("escaped").exists());
+    assert!(!root.parent().unwrap().join("escaped").exists());
+}
+
+#[test]
+fn test_serve_mcp_load_stays_inside_the_vault() {
+    use std::io::Write;
+    use std::process::Stdio;
+
+    let outer = TempDir::new().unwrap();
+    let root = outer.path().join("vault");
+    fs::create_dir(&root).unwrap();
+    assert!(gnu(&root, &["init", "--name", "proj"]).status.success());
+    fs::write(outer.path().join("secret.md"), "secret\n").unwrap();
+    let absolute = outer.path().join("secret.md");
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_gnu"))
+        .args(["serve", "--mcp"])
+        .current_dir(&root)
+        .env_remove("GITNU_DIR")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("failed to run gnu");
+    let requests = [
+        r#"{"jsonrpc":"2.0","id":1,"method":"tools/call","params":{"name":"load","arguments":{"path":"../secret.md"}}}"#.to_string(),
+        format!(
+            r#"{{"jsonrpc":"2.0","id":2,"method":"tools/call","params":{{"name":"load","arguments":{{"path":{}}}}}}}"#,
+            serde_json::to_string(absolute.to_str().unwrap()).unwrap()
+        ),
+        r#"{"jsonrpc":"2.0","id":3,"method":"tools/call","params":{"name":"load","arguments":{"path":"domains/proj/spec.md"}}}"#.to_string(),
+    ];
+    child.stdin.take().unwrap().write_all(requests.join("\n").as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+
+    let responses: Vec<serde_json::Value> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    for response in &responses[..2] {
+        assert_eq!(response["result"]["isError"], true, "{}", response);
+        assert!(response["result"]["content"][0]["text"].as_str().unwrap().contains("outside the vault"));
+    }
+    assert_eq!(responses[2]["result"]["isError"], false, "{}", responses[2]);
+    let index = fs::read_to_string(root.join(".gitnu/index.json")).unwrap();
+    assert!(!index.contains("secret"), "{}", index);
+}
+
+#[test]
+fn test_mv_with_vault_flag_stays_inside_domains() {
+    let vault = init_vault();
+    let root = vault.path();
+    let elsewhere = TempDir::new().unwrap();
+    let vault_arg = root.to_str().unwrap();
+    fs::write(root.join("domains/proj/notes.md"), "notes\n").unwrap();
+
+    let output = gnu(elsewhere.path(), &["--vault", vault_arg, "mv", "domains/proj/notes.md", "domains/../../escaped.md"]);
+    assert!(!output.status.success(), "{:?}", output);
+    assert!(root.join("domains/proj/notes.md").exists());
+    assert!(!root.parent().unwrap().join("escaped.md").exists());
+
+    let output = gnu(elsewhere.path(), &["--vault", vault_arg, "mv", "domains/../.gitnu/HEAD", "domains/proj/head.md"]);
+    assert!(!output.status.success(), "{:?}", output);
+    assert!(root.join(".gitnu/HEAD").exists());
+
+    let output = gnu(elsewhere.path(), &["--vault", vault_arg, "mv", "domains/proj/notes.md", "domains/proj/renamed.md"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(root.join("domains/proj/renamed.md").exists());
+}
+
+#[test]
+fn test_log_filters_by_author_and_grep() {
+    let vault = init_vault();
+    let root = vault.path();
+    fs::write(root.join("domains/proj/a.md"), "a\n").unwrap();
+    assert!(gnu(root, &["commit", "Add a", "--author", "agent", "--model", "model-one"]).status.success());
+    fs::write(root.join("domains/proj/b.md"), "b\n").unwrap();
+    assert!(gnu(root, &["commit", "Fix b", "--author", "human"]).status.success());
+    fs::write(root.join("domains/proj/c.md"), "c\n").unwrap();
+    assert!(gnu(root, &["commit", "Add c", "--author", "agent", "--model", "model-two"]).status.success());
+
+    let subjects = |args: &[&str]| -> Vec<String> {
+        let mut args = args.to_vec();
+        args.extend(["--format", "%s"]);
+        String::from_utf8_lossy(&gnu(root, &args).stdout).lines().map(String::from).collect()
+    };
+    assert_eq!(subjects(&["log", "--author", "agent"]), ["Add c", "Add a"]);
+    assert_eq!(subjects(&["log", "--author", "model-one"]), ["Add a"]);
+    assert_eq!(subjects(&["log", "--grep", "^Add"]), ["Add c", "Add a"]);
+    // Filters compose with each other and with --limit
+    assert_eq!(subjects(&["log", "--author", "agent", "--grep", "^Add", "--limit", "1"]), ["Add c"]);
+    assert_eq!(subjects(&["log", "--author", "human", "--grep", "^Add"]), ["No matching commits"]);
+    assert!(!gnu(root, &["log", "--grep", "("]).status.success());
+}
+
+#[test]
+fn test_log_path_lists_only_commits_touching_the_file() {
+    let vault = init_vault();
+    let root = vault.path();
+    let design = root.join("domains/proj/design.md");
+    fs::write(&design, "# Design\n").unwrap();
+    assert!(gnu(root, &["commit", "Add design"]).status.success());
+    fs::write(root.join("domains/proj/other.md"), "# Other\n").unwrap();
+    assert!(gnu(root, &["commit", "Add other"]).status.success());
+    fs::write(&design, "# Design\n\nMore\n").unwrap();
+    assert!(gnu(root, &["commit", "Expand design"]).status.success());
+    fs::remove_file(&design).unwrap();
+    assert!(gnu(root, &["commit", "Drop design"]).status.success());
+
+    let output = gnu(root, &["log", "domains/proj/design.md", "--format", "%s"]);
+    assert!(output.status.success(), "{:?}", output);
+    let subjects: Vec<&str> = std::str::from_utf8(&output.stdout).unwrap().lines().collect();
+    assert_eq!(subjects, ["Drop design", "Expand design", "Add design"]);
+}
+
+#[test]
+fn test_log_path_compares_each_commit_with_its_parent() {
+    let vault = init_vault();
+    let root = vault.path();
+    let design = root.join("domains/proj/design.md");
+    fs::write(&design, "# Design\n").unwrap();
+    assert!(gnu(root, &["commit", "Add design"]).status.success());
+
+    // The first commit on a fresh branch leaves the file alone
+    assert!(gnu(root, &["branch", "explore"]).status.success());
+    assert!(gnu(root, &["checkout", "explore"]).status.success());
+    fs::write(root.join("domains/proj/idea.md"), "# Idea\n").unwrap();
+    assert!(gnu(root, &["commit", "Explore idea"]).status.success());
+    fs::write(&design, "# Design\n\nExplored\n").unwrap();
+    assert!(gnu(root, &["commit", "Explore design"]).status.success());
+
+    assert!(gnu(root, &["checkout", "main"]).status.success());
+    fs::write(&design, "# Design\n\nMain\n").unwrap();
+    assert!(gnu(root, &["commit", "Main design"]).status.success());
+
+    let subjects = |args: &[&str]| -> Vec<String> {
+        let output = gnu(root, args);
+        assert!(output.status.success(), "{:?}", output);
+        String::from_utf8_lossy(&output.stdout).lines().map(str::to_string).collect()
+    };
+    let log = ["log", "--branch", "explore", "domains/proj/design.md", "--format", "%s"];
+    assert_eq!(subjects(&log), ["Explore design"]);
+    assert_eq!(
+        subjects(&["log", "--branches", "domains/proj/design.md", "--format", "%s"]),
+        ["Main design", "Explore design", "Add design"]
+    );
+}
+
+#[test]
+fn test_summary_json_has_stable_schema() {
+    let vault = init_vault();
+    let root = vault.path();
+    fs::write(root.join("domains/proj/notes.md"), "# Notes\n").unwrap();
+    assert!(gnu(root, &["commit", "Add notes", "--author", "agent", "--model", "test-model"]).status.success());
+    fs::write(root.join("domains/proj/draft.md"), "# Draft\n").unwrap();
+
+    let output = gnu(root, &["--json", "summary"]);
+    assert!(output.status.success(), "{:?}", output);
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["schema_version"], 1);
+    assert_eq!(report["branch"], "main");
+    assert_eq!(report["detached"], false);
+
+    let head = fs::read_to_string(root.join(".gitnu/refs/heads/main")).unwrap();
+    assert_eq!(report["head"]["hash"], head.trim());
+    assert_eq!(report["head"]["message"], "Add notes");
+    assert_eq!(report["head"]["author"]["type"], "agent");
+    assert_eq!(report["head"]["author"]["model"], "test-model");
+    assert!(report["head"]["token_estimate"].as_u64().unwrap() > 0);
+
+    let proj = report["domains"].as_array().unwrap().iter().find(|d| d["name"] == "proj").unwrap();
+    assert!(proj["file_count"].as_u64().unwrap() >= 2);
+    let uncommitted: Vec<&str> = report["uncommitted_files"].as_array().unwrap()
+        .iter()
+        .map(|f| f.as_str().unwrap())
+        .collect();
+    assert_eq!(uncommitted, ["domains/proj/draft.md"]);
+    assert_eq!(report["branches"][0]["name"], "main");
+    assert_eq!(report["branches"][0]["current"], true);
+}
+
+#[test]
+fn test_init_template_selects_scaffold() {
+    let builtin = TempDir::new().unwrap();
+    let output = gnu(builtin.path(), &["init", "--name", "lab", "--template", "research"]);
+    assert!(output.status.success(), "{:?}", output);
+    for file in ["sources.md", "hypotheses.md", "learnings.md", "todos.md"] {
+        assert!(builtin.path().join("domains/lab").join(file).is_file(), "missing {}", file);
+    }
+    assert!(!builtin.path().join("domains/lab/spec.md").exists());
+
+    let unknown = TempDir::new().unwrap();
+    let output = gnu(unknown.path(), &["init", "--template", "nope"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Unknown template 'nope'"), "{:?}", output);
+    assert!(!unknown.path().join(".gitnu").exists());
+
+    // User templates come from the config directory
+    let config = TempDir::new().unwrap();
+    let template = config.path().join("gitnu/templates/meetings");
+    fs::create_dir_all(template.join("notes")).unwrap();
+    fs::write(template.join("agenda.md"), "# Agenda\n").unwrap();
+    fs::write(template.join("notes/first.md"), "# First\n").unwrap();
+    let user = TempDir::new().unwrap();
+    let run = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_gnu"))
+            .args(args)
+            .current_dir(user.path())
+            .env_remove("GITNU_DIR")
+            .env("XDG_CONFIG_HOME", config.path())
+            .output()
+            .unwrap()
+    };
+    let output = run(&["init", "--list-templates"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("meetings"), "{:?}", output);
+    let output = run(&["init", "--name", "team", "--template", "meetings"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(user.path().join("domains/team/agenda.md").is_file());
+    assert!(user.path().join("domains/team/notes/first.md").is_file());
+}
+
+#[test]
+fn test_branch_contains_lists_reachable_branches() {
+    let vault = init_vault();
+    let root = vault.path();
+    let head = |branch: &str| fs::read_to_string(root.join(".gitnu/refs/heads").join(branch)).unwrap().trim().to_string();
+    let base = head("main");
+    assert!(gnu(root, &["branch", "side"]).status.success());
+    assert!(gnu(root, &["checkout", "side"]).status.success());
+    fs::write(root.join("domains/proj/side.md"), "side\n").unwrap();
+    assert!(gnu(root, &["commit", "Side work"]).status.success());
+    assert!(gnu(root, &["checkout", "main"]).status.success());
+    fs::write(root.join("domains/proj/main.md"), "main\n").unwrap();
+    assert!(gnu(root, &["commit", "Main work"]).status.success());
+
+    let branches = |reference: &str| -> Vec<String> {
+        let output = gnu(root, &["branch", "--contains", reference]);
+        assert!(output.status.success(), "{:?}", output);
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(|line| line.trim_start_matches(['*', ' ']).to_string())
+            .collect()
+    };
+    assert_eq!(branches(&base), ["main", "side"]);
+    assert_eq!(branches(&head("side")), ["side"]);
+    assert_eq!(branches("main"), ["main"]);
+    assert_eq!(gnu(root, &["branch", "--contains", "deadbeef"]).status.code(), Some(5));
+}
+
+#[test]
+fn test_stats_reports_vault_metrics() {
+    let vault = init_vault();
+    let root = vault.path();
+    let notes = root.join("domains/proj/notes.md");
+    fs::write(&notes, "# Notes\n").unwrap();
+    assert!(gnu(root, &["commit", "Add notes"]).status.success());
+    fs::write(&notes, "# Notes\n\nMore\n").unwrap();
+    assert!(gnu(root, &["commit", "Expand notes"]).status.success());
+    fs::write(root.join("domains/proj/big.md"), "x".repeat(5000)).unwrap();
+    assert!(gnu(root, &["commit", "Add big"]).status.success());
+    assert!(gnu(root, &["branch", "side"]).status.success());
+
+    let output = gnu(root, &["--json", "stats"]);
+    assert!(output.status.success(), "{:?}", output);
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["total_commits"], 4);
+    assert_eq!(report["snapshots"], 4);
+    // A new branch contains the commits it was created from
+    assert_eq!(report["commits_per_branch"]["main"], 4);
+    assert_eq!(report["commits_per_branch"]["side"], 4);
+    assert_eq!(report["largest_file"]["path"], "domains/proj/big.md");
+    assert_eq!(report["largest_file"]["size"], 5000);
+    assert_eq!(report["most_edited_file"]["path"], "domains/proj/notes.md");
+    assert!(report["files_tracked"].as_u64().unwrap() >= 2);
+    assert!(report["objects_size"].as_u64().unwrap() > 0);
+
+    let output = gnu(root, &["stats"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Largest file:      domains/proj/big.md (4.9 KB)"), "{}", stdout);
+    assert!(stdout.contains("Most edited:       domains/proj/notes.md (1 edit)"), "{}", stdout);
+}